@@ -1,5 +1,3 @@
-mod wal;
-
 fn main() {
     println!("Hello, world!");
 }
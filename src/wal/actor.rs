@@ -0,0 +1,181 @@
+use std::error::Error;
+use std::sync::mpsc::{channel, Receiver, RecvTimeoutError, Sender};
+use std::thread::JoinHandle;
+use std::time::Duration;
+
+use super::core::{WALEntry, WALManager};
+use super::lsn::Lsn;
+
+/// [`WALManager`] 를 자기 스레드에서 돌리고 채널로 요청을 받는 writer actor
+///
+/// [`WALActorHandle::append`] 는 엔트리를 채널에 넣고 바로 [`Receipt`] 를 돌려준다. actor 는 채널에 쌓인 요청을
+/// 한꺼번에 꺼내 [`WALManager::append_logs`] 로 쓰고 fsync 한 뒤에 영수증을 채우므로, 동시에 들어온 요청은 fsync 한 번을 나눠 쓴다.
+/// 락을 나눠 쓰는 [`GroupCommitter`](super::group_commit::GroupCommitter) 와 달리 호출자는 `WALManager` 를 직접 만지지 않는다.
+pub struct WALActor {
+    sender: Sender<Command>,
+    thread: Option<JoinHandle<WALManager>>,
+}
+
+/// [`WALActor`] 에 요청을 보내는 핸들. 채널의 `Sender` 하나라서 복제가 싸다.
+#[derive(Clone)]
+pub struct WALActorHandle {
+    sender: Sender<Command>,
+}
+
+/// 엔트리가 fsync 된 뒤에 그 LSN 으로 채워지는 영수증
+pub struct Receipt {
+    receiver: Receiver<Result<Lsn, String>>,
+}
+
+enum Command {
+    Append(WALEntry, Sender<Result<Lsn, String>>),
+    Stop,
+}
+
+impl WALActor {
+    pub fn spawn(mut manager: WALManager) -> Self {
+        let (sender, receiver) = channel();
+        let thread = std::thread::spawn(move || {
+            run(&mut manager, receiver);
+            manager
+        });
+
+        Self { sender, thread: Some(thread) }
+    }
+
+    pub fn handle(&self) -> WALActorHandle {
+        WALActorHandle { sender: self.sender.clone() }
+    }
+
+    /// 앞서 보낸 요청까지 처리하고 actor 를 멈춘 뒤 [`WALManager`] 를 돌려준다.
+    /// 그 뒤로 남은 핸들이 받은 영수증은 오류로 끝난다.
+    pub fn into_inner(mut self) -> WALManager {
+        self.stop().expect("actor thread is running")
+    }
+
+    fn stop(&mut self) -> Option<WALManager> {
+        let thread = self.thread.take()?;
+        let _ = self.sender.send(Command::Stop);
+
+        Some(thread.join().expect("WAL actor thread panicked"))
+    }
+}
+
+impl Drop for WALActor {
+    fn drop(&mut self) {
+        self.stop();
+    }
+}
+
+impl WALActorHandle {
+    /// actor 가 멈췄다면 영수증이 바로 오류로 끝난다
+    pub fn append(&self, entry: WALEntry) -> Receipt {
+        let (sender, receiver) = channel();
+        // 보내지 못한 요청은 영수증의 송신 쪽과 함께 버려진다
+        let _ = self.sender.send(Command::Append(entry, sender));
+
+        Receipt { receiver }
+    }
+}
+
+impl Receipt {
+    /// 엔트리가 durable 해질 때까지 기다린다
+    pub fn wait(self) -> Result<Lsn, Box<dyn Error>> {
+        match self.receiver.recv() {
+            Ok(result) => result.map_err(Into::into),
+            Err(_) => Err("WAL actor is stopped".into()),
+        }
+    }
+
+    /// `timeout` 안에 durable 해지지 않았다면 `None`
+    pub fn wait_timeout(&self, timeout: Duration) -> Option<Result<Lsn, Box<dyn Error>>> {
+        match self.receiver.recv_timeout(timeout) {
+            Ok(result) => Some(result.map_err(Into::into)),
+            Err(RecvTimeoutError::Timeout) => None,
+            Err(RecvTimeoutError::Disconnected) => Some(Err("WAL actor is stopped".into())),
+        }
+    }
+}
+
+fn run(manager: &mut WALManager, receiver: Receiver<Command>) {
+    let mut stopped = false;
+    while !stopped {
+        let Ok(command) = receiver.recv() else {
+            break;
+        };
+
+        // 기다리는 동안 쌓인 요청을 모두 모아 한 번에 쓴다
+        let mut batch = Vec::new();
+        let mut next = Some(command);
+        while let Some(command) = next.take() {
+            match command {
+                Command::Append(entry, receipt) => batch.push((entry, receipt)),
+                Command::Stop => {
+                    stopped = true;
+                    break;
+                },
+            }
+            next = receiver.try_recv().ok();
+        }
+
+        if !batch.is_empty() {
+            write(manager, batch);
+        }
+    }
+}
+
+fn write(manager: &mut WALManager, batch: Vec<(WALEntry, Sender<Result<Lsn, String>>)>) {
+    let (entries, receipts): (Vec<_>, Vec<_>) = batch.into_iter().unzip();
+    let count = entries.len() as u64;
+
+    let result = manager.append_logs(entries)
+        .and_then(|last| Ok(manager.sync().map(|_| last)?))
+        .map_err(|e| e.to_string());
+
+    for (offset, receipt) in (0..count).rev().zip(receipts) {
+        let _ = receipt.send(result.clone().map(|last| Lsn(last.0 - offset)));
+    }
+}
+
+#[cfg(test)]
+mod actor_tests {
+    use std::time::Duration;
+
+    use super::WALActor;
+    use crate::wal::core::{WALEntry, WALManager};
+    use crate::wal::lsn::Lsn;
+    use crate::wal::sync::SyncPolicy;
+    use crate::wal::test_utils::{insert_entry, temp_directory};
+
+    fn entry(value: u8) -> WALEntry {
+        insert_entry(vec![value; 16])
+    }
+
+    #[test]
+    fn test_receipts_resolve_when_durable() {
+        let wal_manager = WALManager::builder()
+            .set_directory(temp_directory("actor"))
+            .set_sync_policy(SyncPolicy::Never)
+            .build().expect("Cannot create WALManager");
+        let actor = WALActor::spawn(wal_manager);
+
+        let threads = (0..4).map(|thread| {
+            let handle = actor.handle();
+            std::thread::spawn(move || {
+                let receipts = (0..25).map(|_| handle.append(entry(thread))).collect::<Vec<_>>();
+                receipts.into_iter().map(|receipt| receipt.wait().unwrap()).collect::<Vec<_>>()
+            })
+        }).collect::<Vec<_>>();
+        let mut lsns = threads.into_iter().flat_map(|thread| thread.join().unwrap()).collect::<Vec<_>>();
+        lsns.sort();
+        assert_eq!(lsns, (1..=100).map(Lsn).collect::<Vec<_>>());
+
+        let receipt = actor.handle().append(entry(9));
+        assert_eq!(receipt.wait_timeout(Duration::from_secs(5)).unwrap().unwrap(), Lsn(101));
+
+        let handle = actor.handle();
+        let wal_manager = actor.into_inner();
+        assert_eq!(wal_manager.durable_lsn(), Lsn(101));
+        assert!(handle.append(entry(0)).wait().is_err());
+    }
+}
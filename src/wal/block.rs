@@ -0,0 +1,239 @@
+use std::io::{Error, ErrorKind};
+
+use super::checksum::ChecksumAlgorithm;
+use super::compression::{compress, decompress};
+use super::frame::{encode_frame_with, FrameLayout};
+
+/// `[compression u8][entries u32 LE][raw_length u32 LE]`
+pub const BLOCK_HEADER_SIZE: usize = 9;
+
+/// 블록을 압축하는 방식. 블록마다 헤더에 남기므로 압축해도 줄지 않는 블록은 그대로 둔다.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum BlockCompression {
+    None,
+    /// [`compress`] (LZ77)
+    #[default]
+    Lz,
+}
+
+impl BlockCompression {
+    fn id(self) -> u8 {
+        match self {
+            BlockCompression::None => 0,
+            BlockCompression::Lz => 1,
+        }
+    }
+
+    fn from_id(id: u8) -> Option<Self> {
+        match id {
+            0 => Some(BlockCompression::None),
+            1 => Some(BlockCompression::Lz),
+            _ => None,
+        }
+    }
+}
+
+/// 엔트리 프레임을 모아 블록 단위로 기록할 조건 ([`WALBuilder::set_block_framing`](super::core::WALBuilder::set_block_framing))
+///
+/// 블록은 한 번에 쓰는 프레임들로만 채우므로 fsync 할 때마다 블록이 끝난다.
+/// 버퍼링([`WriteBufferOptions`](super::writer::WriteBufferOptions))이나 `append_logs` 와 함께 써야 블록이 찬다.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct BlockOptions {
+    /// 블록에 담을 프레임 크기의 합(압축 전). 이보다 큰 엔트리는 혼자 블록 하나가 된다.
+    pub size: usize,
+    pub compression: BlockCompression,
+}
+
+impl Default for BlockOptions {
+    fn default() -> Self {
+        Self { size: 32 * 1024, compression: BlockCompression::default() }
+    }
+}
+
+/// 쓴 블록 하나. `offset` 은 `encode_blocks` 에 넘긴 `out` 안에서의 시작 위치다.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Block {
+    pub offset: u64,
+    pub entries: u64,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct BlockHeader {
+    pub compression: BlockCompression,
+    pub entries: u64,
+    pub raw_length: usize,
+}
+
+impl BlockHeader {
+    pub fn decode(payload: &[u8]) -> Result<Self, Error> {
+        let bytes = payload.get(..BLOCK_HEADER_SIZE)
+            .ok_or_else(|| Error::new(ErrorKind::InvalidData, "block header is too short"))?;
+        let compression = BlockCompression::from_id(bytes[0])
+            .ok_or_else(|| Error::new(ErrorKind::InvalidData, format!("unknown block compression {}", bytes[0])))?;
+
+        Ok(Self {
+            compression,
+            entries: u32::from_le_bytes(bytes[1..5].try_into().unwrap()) as u64,
+            raw_length: u32::from_le_bytes(bytes[5..9].try_into().unwrap()) as usize,
+        })
+    }
+}
+
+/// 이어붙인 엔트리 프레임들을 `options.size` 단위의 블록으로 나눠 `out` 끝에 붙인다.
+/// 블록은 엔트리 프레임과 같은 `[길이][체크섬]` 프레임으로 감싸므로 패딩과 찢어진 쓰기는 그대로 걸러진다.
+/// 안쪽 프레임과 블록 프레임은 모두 세그먼트 포맷의 `layout` 을 따른다.
+pub fn encode_blocks(frames: &[u8], layout: FrameLayout, salt: u64, checksum: ChecksumAlgorithm, options: &BlockOptions, out: &mut Vec<u8>) -> Vec<Block> {
+    let base = out.len();
+    let mut blocks = Vec::new();
+    let mut start = 0;
+
+    while start < frames.len() {
+        // 블록 크기보다 큰 프레임이라도 하나는 담는다
+        let mut end = start + frame_length(&frames[start..], layout);
+        let mut entries = 1;
+        while end < frames.len() {
+            let next = end + frame_length(&frames[end..], layout);
+            if next - start > options.size {
+                break;
+            }
+            (end, entries) = (next, entries + 1);
+        }
+
+        blocks.push(Block { offset: (out.len() - base) as u64, entries });
+        encode_block(&frames[start..end], entries, layout, salt, checksum, options.compression, out);
+        start = end;
+    }
+
+    blocks
+}
+
+fn encode_block(raw: &[u8], entries: u64, layout: FrameLayout, salt: u64, checksum: ChecksumAlgorithm, compression: BlockCompression, out: &mut Vec<u8>) {
+    let _ = encode_frame_with::<()>(layout, salt, checksum, out, |out| {
+        let header = out.len();
+        out.push(compression.id());
+        out.extend_from_slice(&(entries as u32).to_le_bytes());
+        out.extend_from_slice(&(raw.len() as u32).to_le_bytes());
+
+        let body = out.len();
+        if compression == BlockCompression::Lz {
+            compress(raw, out);
+            if out.len() - body < raw.len() {
+                return Ok(());
+            }
+            out.truncate(body);
+            out[header] = BlockCompression::None.id();
+        }
+        out.extend_from_slice(raw);
+
+        Ok(())
+    });
+}
+
+/// 블록 프레임의 payload 를 풀어서 안에 든 엔트리 프레임들을 `out` 에 채운다
+pub fn decode_block(payload: &[u8], out: &mut Vec<u8>) -> Result<BlockHeader, Error> {
+    let header = BlockHeader::decode(payload)?;
+    let body = &payload[BLOCK_HEADER_SIZE..];
+
+    out.clear();
+    match header.compression {
+        BlockCompression::None if body.len() == header.raw_length => out.extend_from_slice(body),
+        BlockCompression::None => return Err(Error::new(ErrorKind::InvalidData, "block length mismatch")),
+        BlockCompression::Lz => decompress(body, header.raw_length, out)?,
+    }
+
+    Ok(header)
+}
+
+/// 풀어둔 블록의 `cursor` 에서 엔트리 프레임 하나의 payload 를 꺼내고 `cursor` 를 다음 프레임으로 옮긴다.
+/// 블록 전체의 체크섬을 이미 검증했으므로 안쪽 프레임의 체크섬은 다시 보지 않는다.
+pub fn next_frame<'a>(block: &'a [u8], layout: FrameLayout, cursor: &mut usize) -> Result<&'a [u8], Error> {
+    let truncated = || Error::new(ErrorKind::InvalidData, "truncated frame inside a block");
+    let frame = &block[*cursor..];
+    let (header, length) = layout.decode_header(frame).ok_or_else(truncated)?;
+    let payload = frame.get(header..header + length).ok_or_else(truncated)?;
+    *cursor += header + length;
+
+    Ok(payload)
+}
+
+/// 헤더까지 포함한 프레임 길이 (`frames` 는 프레임 경계에서 시작한다)
+fn frame_length(frames: &[u8], layout: FrameLayout) -> usize {
+    match layout.decode_header(frames) {
+        Some((header, length)) => header + length,
+        None => frames.len(),
+    }
+}
+
+#[cfg(test)]
+mod block_tests {
+    use std::path::Path;
+    use std::time::Duration;
+
+    use super::BlockOptions;
+    use crate::wal::core::{WALEntry, WALManager};
+    use crate::wal::lsn::Lsn;
+    use crate::wal::reader::WALReader;
+    use crate::wal::repair::RecoveryTarget;
+    use crate::wal::segment::segment_path;
+    use crate::wal::sync::SyncPolicy;
+    use crate::wal::test_utils::{insert_entry, temp_directory};
+    use crate::wal::writer::WriteBufferOptions;
+
+    fn entry(value: u64) -> WALEntry {
+        insert_entry(format!("INSERT INTO users VALUES ({}, 'alice');", value).into_bytes())
+    }
+
+    fn replay(directory: &Path, lsn: Lsn) -> Vec<(Lsn, Option<Vec<u8>>)> {
+        let reader = WALReader::open(directory).unwrap().read_from(lsn).unwrap();
+        reader.map(|item| item.map(|(lsn, entry)| (lsn, entry.data))).collect::<Result<_, _>>().unwrap()
+    }
+
+    #[test]
+    fn test_block_framing() {
+        let mut sizes = Vec::new();
+        let directories = [temp_directory("block_entries"), temp_directory("block_framing")];
+        for (directory, blocks) in directories.iter().zip([None, Some(BlockOptions { size: 1024, ..Default::default() })]) {
+            let builder = WALManager::builder()
+                .set_directory(directory.clone())
+                .set_sync_policy(SyncPolicy::Never)
+                .set_write_buffer(WriteBufferOptions { max_delay: Duration::from_secs(60), ..Default::default() });
+            let mut wal_manager = match blocks {
+                Some(options) => builder.set_block_framing(options),
+                None => builder,
+            }.build().expect("Cannot create WALManager");
+
+            wal_manager.append_logs((0..300).map(entry).collect()).unwrap();
+            wal_manager.checkpoint().unwrap();
+            for value in 300..600 {
+                wal_manager.append_log(entry(value)).unwrap();
+            }
+            drop(wal_manager);
+
+            sizes.push((1..=2).map(|sequence| std::fs::metadata(segment_path(directory, sequence)).unwrap().len()).sum::<u64>());
+        }
+        let [entries, blocked] = &directories;
+
+        // 비슷한 엔트리가 모인 블록은 압축되어 훨씬 작다
+        assert!(sizes[1] < sizes[0] / 2, "{:?}", sizes);
+        let expected = replay(entries, Lsn::ZERO);
+        assert_eq!(expected.len(), 601);
+        assert_eq!(replay(blocked, Lsn::ZERO), expected);
+
+        // 봉인된 세그먼트와 활성 세그먼트에서 앞선 블록을 건너뛰고 읽는다
+        assert_eq!(replay(blocked, Lsn(200)), expected[199..]);
+        assert_eq!(replay(blocked, Lsn(450)), expected[449..]);
+
+        // 다시 열면 세그먼트 헤더를 따라 블록으로 이어 쓴다
+        let mut wal_manager = WALManager::builder().set_directory(blocked.clone()).build().expect("Cannot reopen WALManager");
+        assert_eq!(wal_manager.last_lsn(), Lsn(601));
+        wal_manager.append_log(entry(600)).unwrap();
+        drop(wal_manager);
+        assert_eq!(replay(blocked, Lsn(602)), vec![(Lsn(602), entry(600).data)]);
+
+        // 블록 중간으로 되돌리면 블록의 앞부분을 다시 써서 남긴다
+        WALManager::recover_to(blocked, RecoveryTarget::Lsn(Lsn(500))).unwrap();
+        assert_eq!(replay(blocked, Lsn::ZERO), expected[..500]);
+        let wal_manager = WALManager::builder().set_directory(blocked.clone()).build().expect("Cannot reopen WALManager");
+        assert_eq!(wal_manager.last_lsn(), Lsn(500));
+    }
+}
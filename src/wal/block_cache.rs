@@ -0,0 +1,151 @@
+use std::collections::{BTreeMap, HashMap};
+use std::sync::{Arc, Mutex};
+
+/// 풀어둔 블록을 (세그먼트 순번, 블록 프레임 위치) 로 기억하는 LRU 캐시
+///
+/// 최근 구간을 여러 소비자가 거듭 읽을 때 블록을 다시 풀지 않도록 `Arc` 로 감싸서 리더들이 함께 쓴다
+/// ([`WALReader::with_block_cache`](super::reader::WALReader::with_block_cache), [`WALBuilder::set_block_cache`](super::core::WALBuilder::set_block_cache)).
+/// 블록 프레임은 여전히 디스크에서 읽고 체크섬을 검증하며, 캐시에 남은 체크섬과 다르다면
+/// (잘라낸 뒤 같은 위치에 다시 쓴 블록) 새로 푼다. 풀어둔 크기의 합이 `budget` 을 넘으면 가장 오래 쓰지 않은 블록부터 버린다.
+#[derive(Debug)]
+pub struct BlockCache {
+    budget: usize,
+    state: Mutex<CacheState>,
+}
+
+#[derive(Debug, Default)]
+struct CacheState {
+    blocks: HashMap<(u64, u64), CachedBlock>,
+    /// 마지막으로 쓴 순서 → 블록
+    recency: BTreeMap<u64, (u64, u64)>,
+    tick: u64,
+    used: usize,
+    hits: u64,
+    misses: u64,
+}
+
+#[derive(Debug)]
+struct CachedBlock {
+    checksum: u32,
+    block: Arc<Vec<u8>>,
+    tick: u64,
+}
+
+impl BlockCache {
+    /// 풀어둔 블록을 `budget` 바이트까지 기억한다
+    pub fn new(budget: usize) -> Self {
+        Self { budget, state: Mutex::new(CacheState::default()) }
+    }
+
+    pub(crate) fn get(&self, sequence: u64, offset: u64, checksum: u32) -> Option<Arc<Vec<u8>>> {
+        let mut state = self.state.lock().unwrap();
+        state.tick += 1;
+        let tick = state.tick;
+
+        let Some(cached) = state.blocks.get_mut(&(sequence, offset)).filter(|cached| cached.checksum == checksum) else {
+            state.misses += 1;
+            return None;
+        };
+        let (previous, block) = (std::mem::replace(&mut cached.tick, tick), cached.block.clone());
+        state.recency.remove(&previous);
+        state.recency.insert(tick, (sequence, offset));
+        state.hits += 1;
+
+        Some(block)
+    }
+
+    /// 예산보다 큰 블록은 기억하지 않는다
+    pub(crate) fn insert(&self, sequence: u64, offset: u64, checksum: u32, block: Arc<Vec<u8>>) {
+        if block.len() > self.budget {
+            return;
+        }
+
+        let mut state = self.state.lock().unwrap();
+        state.tick += 1;
+        let tick = state.tick;
+        state.used += block.len();
+        if let Some(previous) = state.blocks.insert((sequence, offset), CachedBlock { checksum, block, tick }) {
+            state.recency.remove(&previous.tick);
+            state.used -= previous.block.len();
+        }
+        state.recency.insert(tick, (sequence, offset));
+
+        while state.used > self.budget {
+            let Some((_, key)) = state.recency.pop_first() else {
+                break;
+            };
+            if let Some(evicted) = state.blocks.remove(&key) {
+                state.used -= evicted.block.len();
+            }
+        }
+    }
+
+    /// 기억하고 있는 블록 크기의 합
+    pub fn used_bytes(&self) -> usize {
+        self.state.lock().unwrap().used
+    }
+
+    pub fn hits(&self) -> u64 {
+        self.state.lock().unwrap().hits
+    }
+
+    pub fn misses(&self) -> u64 {
+        self.state.lock().unwrap().misses
+    }
+}
+
+#[cfg(test)]
+mod block_cache_tests {
+    use std::sync::Arc;
+    use std::time::Duration;
+
+    use super::BlockCache;
+    use crate::wal::block::BlockOptions;
+    use crate::wal::core::WALManager;
+    use crate::wal::sync::SyncPolicy;
+    use crate::wal::test_utils::{insert_entry, temp_directory};
+    use crate::wal::writer::WriteBufferOptions;
+
+    #[test]
+    fn test_evict_least_recently_used() {
+        let cache = BlockCache::new(100);
+        cache.insert(1, 10, 7, Arc::new(vec![1; 40]));
+        cache.insert(1, 20, 7, Arc::new(vec![2; 40]));
+        assert!(cache.get(1, 10, 7).is_some());
+
+        // 가장 오래 쓰지 않은 (1, 20) 을 버린다
+        cache.insert(2, 10, 7, Arc::new(vec![3; 40]));
+        assert_eq!(cache.used_bytes(), 80);
+        assert!(cache.get(1, 20, 7).is_none());
+        assert_eq!(cache.get(1, 10, 7).unwrap()[0], 1);
+
+        // 체크섬이 다르면 같은 위치에 새로 쓴 블록이다
+        assert!(cache.get(2, 10, 8).is_none());
+        cache.insert(3, 10, 7, Arc::new(vec![4; 200]));
+        assert_eq!((cache.hits(), cache.misses(), cache.used_bytes()), (2, 2, 80));
+    }
+
+    #[test]
+    fn test_share_decoded_blocks_between_readers() {
+        let mut wal_manager = WALManager::builder()
+            .set_directory(temp_directory("block_cache"))
+            .set_sync_policy(SyncPolicy::Never)
+            .set_write_buffer(WriteBufferOptions { max_entries: 8, max_delay: Duration::from_secs(60), ..Default::default() })
+            .set_block_framing(BlockOptions::default())
+            .set_block_cache(1024 * 1024)
+            .build().expect("Cannot create WALManager");
+        for value in 0..32u8 {
+            wal_manager.append_log(insert_entry(vec![value; 64])).unwrap();
+        }
+
+        let first = wal_manager.recover().unwrap().collect::<Result<Vec<_>, _>>().unwrap();
+        let cache = wal_manager.block_cache().unwrap().clone();
+        assert_eq!((cache.hits(), cache.misses()), (0, 4));
+
+        // 두 번째 소비자는 풀어둔 블록을 그대로 쓴다
+        let second = wal_manager.tail().unwrap().take(32).collect::<Result<Vec<_>, _>>().unwrap();
+        assert_eq!(cache.hits(), 4);
+        assert_eq!(first.iter().map(|(lsn, entry)| (*lsn, &entry.data)).collect::<Vec<_>>(),
+            second.iter().map(|(lsn, entry)| (*lsn, &entry.data)).collect::<Vec<_>>());
+    }
+}
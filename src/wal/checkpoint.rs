@@ -0,0 +1,126 @@
+use std::error::Error;
+use std::time::{Duration, Instant};
+
+use super::lsn::Lsn;
+
+/// 세그먼트 크기(`segment_max_bytes`) 말고도 언제 저절로 체크포인트할지 결정하는 정책.
+/// 다음 append 직전에 따지며, 활성 세그먼트에 엔트리가 없다면 체크포인트하지 않는다.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum CheckpointPolicy {
+    /// 세그먼트 크기로만 봉인
+    #[default]
+    SegmentSize,
+    /// 활성 세그먼트에 n개의 엔트리가 쌓일 때마다
+    EveryNEntries(u64),
+    /// 활성 세그먼트에 쓴 바이트 수가 n 바이트에 이를 때마다 (`segment_max_bytes` 와 같은 기준)
+    EveryNBytes(usize),
+    /// 마지막 체크포인트 이후 주어진 시간이 지났다면 다음 append 에서. 복구할 때 읽을 분량을 시간으로 묶어둔다.
+    Interval(Duration),
+}
+
+impl CheckpointPolicy {
+    pub(crate) fn should_checkpoint(&self, segment_entries: u64, segment_bytes: usize, last_checkpoint: Instant) -> bool {
+        if segment_entries == 0 {
+            return false;
+        }
+
+        match *self {
+            CheckpointPolicy::SegmentSize => false,
+            CheckpointPolicy::EveryNEntries(n) => segment_entries >= n.max(1),
+            CheckpointPolicy::EveryNBytes(n) => segment_bytes >= n.max(1),
+            CheckpointPolicy::Interval(interval) => last_checkpoint.elapsed() >= interval,
+        }
+    }
+}
+
+/// 체크포인트 훅이 불리는 시점. 세그먼트가 커져서 저절로 남기는 체크포인트에서도 불린다.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum CheckpointEvent {
+    /// 체크포인트 엔트리를 기록하기 직전. 여기서 memtable 이나 스냅샷을 내려쓰면 `lsn` 앞의 엔트리가 모두 반영된 상태가 된다.
+    Sealing { lsn: Lsn, sequence: u64 },
+    /// 세그먼트가 봉인되고 매니페스트까지 fsync 된 뒤
+    Durable { lsn: Lsn, sequence: u64 },
+}
+
+/// [`WALBuilder::set_checkpoint_hook`](super::core::WALBuilder::set_checkpoint_hook) 로 등록하는 콜백
+///
+/// `Sealing` 에서 오류를 돌려주면 체크포인트를 남기지 않고 그 오류를 돌려준다.
+/// `Durable` 에서 돌려준 오류도 그대로 전하지만 체크포인트는 이미 끝난 뒤다.
+pub type CheckpointHook = Box<dyn FnMut(CheckpointEvent) -> Result<(), Box<dyn Error>> + Send>;
+
+#[cfg(test)]
+mod checkpoint_tests {
+    use std::sync::{Arc, Mutex};
+    use std::time::Duration;
+
+    use super::{CheckpointEvent, CheckpointPolicy};
+    use crate::wal::core::{EntryType, WALEntry, WALManager};
+    use crate::wal::lsn::Lsn;
+    use crate::wal::test_utils::{insert_entry, temp_directory};
+
+    #[test]
+    fn test_checkpoint_hook() {
+        let directory = temp_directory("checkpoint_hook");
+        let events = Arc::new(Mutex::new(Vec::new()));
+        let recorded = events.clone();
+        let mut wal_manager = WALManager::builder()
+            .set_directory(directory.clone())
+            .set_checkpoint_hook(move |event| {
+                recorded.lock().unwrap().push(event);
+                match event {
+                    CheckpointEvent::Sealing { lsn: Lsn(4), .. } => Err("memtable flush failed".into()),
+                    _ => Ok(()),
+                }
+            })
+            .build().expect("Cannot create WALManager");
+
+        wal_manager.append_log(insert_entry(vec![1; 16])).unwrap();
+        assert_eq!(wal_manager.checkpoint().unwrap(), Lsn(2));
+        assert_eq!(wal_manager.checkpoint().unwrap(), Lsn(3));
+
+        // 기록하기 전에 실패하면 체크포인트를 남기지 않는다
+        assert!(wal_manager.checkpoint().is_err());
+        assert_eq!(wal_manager.last_lsn(), Lsn(3));
+
+        assert_eq!(*events.lock().unwrap(), vec![
+            CheckpointEvent::Sealing { lsn: Lsn(2), sequence: 1 },
+            CheckpointEvent::Durable { lsn: Lsn(2), sequence: 1 },
+            CheckpointEvent::Sealing { lsn: Lsn(3), sequence: 2 },
+            CheckpointEvent::Durable { lsn: Lsn(3), sequence: 2 },
+            CheckpointEvent::Sealing { lsn: Lsn(4), sequence: 3 },
+        ]);
+        assert_eq!(wal_manager.recover().unwrap().count(), 3);
+    }
+
+    #[test]
+    fn test_checkpoint_policy() {
+        // 타임스탬프는 세그먼트 시각과의 차이로 남으므로 멀리 떨어진 고정 시각을 써서 프레임 크기를 맞춘다
+        let entry = |value| WALEntry { timestamp: 0.0, ..insert_entry(vec![value; 16]) };
+        let checkpoints = |policy, directory| {
+            let mut wal_manager = WALManager::builder()
+                .set_directory(temp_directory(directory))
+                .set_checkpoint_policy(policy)
+                .build().expect("Cannot create WALManager");
+            for value in 0..7 {
+                wal_manager.append_log(entry(value)).unwrap();
+            }
+
+            wal_manager.recover().unwrap()
+                .filter_entry_types([EntryType::Checkpoint])
+                .map(|entry| entry.map(|(lsn, _)| lsn.0))
+                .collect::<Result<Vec<_>, _>>().unwrap()
+        };
+
+        assert_eq!(checkpoints(CheckpointPolicy::SegmentSize, "checkpoint_policy_size"), vec![]);
+        assert_eq!(checkpoints(CheckpointPolicy::EveryNEntries(3), "checkpoint_policy_entries"), vec![4, 8]);
+        // 엔트리 크기는 인코딩한 프레임의 길이로 센다
+        let size = {
+            let mut wal_manager = WALManager::builder().set_directory(temp_directory("checkpoint_policy_frame")).build().unwrap();
+            wal_manager.append_log(entry(0)).unwrap();
+            wal_manager.metrics().bytes_written as usize
+        };
+        assert_eq!(checkpoints(CheckpointPolicy::EveryNBytes(size * 2), "checkpoint_policy_bytes"), vec![3, 6, 9]);
+        // 체크포인트 직후의 빈 세그먼트는 봉인하지 않는다
+        assert_eq!(checkpoints(CheckpointPolicy::Interval(Duration::ZERO), "checkpoint_policy_interval"), vec![2, 4, 6, 8, 10, 12]);
+    }
+}
@@ -0,0 +1,325 @@
+const CRC32_POLYNOMIAL: u32 = 0xEDB8_8320;
+const CRC32C_POLYNOMIAL: u32 = 0x82F6_3B78;
+
+const CRC32_TABLE: [u32; 256] = crc_table(CRC32_POLYNOMIAL);
+const CRC32C_TABLE: [u32; 256] = crc_table(CRC32C_POLYNOMIAL);
+
+const fn crc_table(polynomial: u32) -> [u32; 256] {
+    let mut table = [0u32; 256];
+    let mut i = 0;
+
+    while i < 256 {
+        let mut crc = i as u32;
+        let mut bit = 0;
+
+        while bit < 8 {
+            crc = if crc & 1 == 1 { (crc >> 1) ^ polynomial } else { crc >> 1 };
+            bit += 1;
+        }
+
+        table[i] = crc;
+        i += 1;
+    }
+
+    table
+}
+
+fn crc_update(table: &[u32; 256], mut state: u32, bytes: &[u8]) -> u32 {
+    for &byte in bytes {
+        state = table[((state ^ byte as u32) & 0xFF) as usize] ^ (state >> 8);
+    }
+
+    state
+}
+
+/// 프레임 체크섬 알고리즘. 세그먼트 헤더에 기록되어 읽는 쪽이 같은 알고리즘으로 검증한다.
+///
+/// 프레임 헤더의 체크섬 칸은 4 바이트이므로 xxHash64 는 하위 32 비트만 쓴다.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum ChecksumAlgorithm {
+    /// IEEE CRC32 (테이블 방식)
+    #[default]
+    Crc32,
+    /// Castagnoli CRC32. SSE4.2 / ARMv8 CRC 명령어가 있으면 사용한다.
+    Crc32c,
+    XxHash64,
+}
+
+impl ChecksumAlgorithm {
+    /// 세그먼트 헤더에 기록되는 값
+    pub fn id(self) -> u16 {
+        match self {
+            ChecksumAlgorithm::Crc32 => 0,
+            ChecksumAlgorithm::Crc32c => 1,
+            ChecksumAlgorithm::XxHash64 => 2,
+        }
+    }
+
+    pub fn from_id(id: u16) -> Option<Self> {
+        match id {
+            0 => Some(ChecksumAlgorithm::Crc32),
+            1 => Some(ChecksumAlgorithm::Crc32c),
+            2 => Some(ChecksumAlgorithm::XxHash64),
+            _ => None,
+        }
+    }
+
+    /// `salt` 와 `bytes` 를 이어서 계산한 체크섬. xxHash64 는 `salt` 를 seed 로 쓴다.
+    pub fn checksum(self, salt: u64, bytes: &[u8]) -> u32 {
+        match self {
+            ChecksumAlgorithm::Crc32 => {
+                let mut hasher = Crc32::new();
+                hasher.update(&salt.to_le_bytes());
+                hasher.update(bytes);
+                hasher.finish()
+            },
+            ChecksumAlgorithm::Crc32c => {
+                let mut hasher = Crc32c::new();
+                hasher.update(&salt.to_le_bytes());
+                hasher.update(bytes);
+                hasher.finish()
+            },
+            ChecksumAlgorithm::XxHash64 => xxhash64(bytes, salt) as u32,
+        }
+    }
+}
+
+/// IEEE CRC32
+pub fn crc32(bytes: &[u8]) -> u32 {
+    let mut hasher = Crc32::new();
+    hasher.update(bytes);
+    hasher.finish()
+}
+
+/// 여러 조각에 걸쳐 CRC32 를 계산
+#[derive(Clone, Copy, Debug)]
+pub struct Crc32 {
+    state: u32,
+}
+
+impl Default for Crc32 {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Crc32 {
+    pub fn new() -> Self {
+        Self { state: !0u32 }
+    }
+
+    pub fn update(&mut self, bytes: &[u8]) {
+        self.state = crc_update(&CRC32_TABLE, self.state, bytes);
+    }
+
+    pub fn finish(&self) -> u32 {
+        !self.state
+    }
+}
+
+/// Castagnoli CRC32
+pub fn crc32c(bytes: &[u8]) -> u32 {
+    let mut hasher = Crc32c::new();
+    hasher.update(bytes);
+    hasher.finish()
+}
+
+/// 여러 조각에 걸쳐 CRC32C 를 계산
+#[derive(Clone, Copy, Debug)]
+pub struct Crc32c {
+    state: u32,
+}
+
+impl Default for Crc32c {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Crc32c {
+    pub fn new() -> Self {
+        Self { state: !0u32 }
+    }
+
+    pub fn update(&mut self, bytes: &[u8]) {
+        #[cfg(target_arch = "x86_64")]
+        if std::arch::is_x86_feature_detected!("sse4.2") {
+            self.state = unsafe { crc32c_sse42(self.state, bytes) };
+            return;
+        }
+
+        #[cfg(target_arch = "aarch64")]
+        if std::arch::is_aarch64_feature_detected!("crc") {
+            self.state = unsafe { crc32c_armv8(self.state, bytes) };
+            return;
+        }
+
+        self.state = crc_update(&CRC32C_TABLE, self.state, bytes);
+    }
+
+    pub fn finish(&self) -> u32 {
+        !self.state
+    }
+}
+
+#[cfg(target_arch = "x86_64")]
+#[target_feature(enable = "sse4.2")]
+unsafe fn crc32c_sse42(state: u32, bytes: &[u8]) -> u32 {
+    use std::arch::x86_64::{_mm_crc32_u64, _mm_crc32_u8};
+
+    let mut chunks = bytes.chunks_exact(8);
+    let mut wide = state as u64;
+    for chunk in &mut chunks {
+        wide = _mm_crc32_u64(wide, u64::from_le_bytes(chunk.try_into().unwrap()));
+    }
+
+    let mut state = wide as u32;
+    for &byte in chunks.remainder() {
+        state = _mm_crc32_u8(state, byte);
+    }
+
+    state
+}
+
+#[cfg(target_arch = "aarch64")]
+#[target_feature(enable = "crc")]
+unsafe fn crc32c_armv8(mut state: u32, bytes: &[u8]) -> u32 {
+    use std::arch::aarch64::{__crc32cb, __crc32cd};
+
+    let mut chunks = bytes.chunks_exact(8);
+    for chunk in &mut chunks {
+        state = __crc32cd(state, u64::from_le_bytes(chunk.try_into().unwrap()));
+    }
+
+    for &byte in chunks.remainder() {
+        state = __crc32cb(state, byte);
+    }
+
+    state
+}
+
+const XXH_PRIME64_1: u64 = 0x9E37_79B1_85EB_CA87;
+const XXH_PRIME64_2: u64 = 0xC2B2_AE3D_27D4_EB4F;
+const XXH_PRIME64_3: u64 = 0x1656_67B1_9E37_79F9;
+const XXH_PRIME64_4: u64 = 0x85EB_CA77_C2B2_AE63;
+const XXH_PRIME64_5: u64 = 0x27D4_EB2F_1656_67C5;
+
+fn xxh64_round(accumulator: u64, input: u64) -> u64 {
+    accumulator.wrapping_add(input.wrapping_mul(XXH_PRIME64_2))
+        .rotate_left(31)
+        .wrapping_mul(XXH_PRIME64_1)
+}
+
+fn xxh64_merge(hash: u64, accumulator: u64) -> u64 {
+    (hash ^ xxh64_round(0, accumulator))
+        .wrapping_mul(XXH_PRIME64_1)
+        .wrapping_add(XXH_PRIME64_4)
+}
+
+fn read_u64(bytes: &[u8]) -> u64 {
+    u64::from_le_bytes(bytes[..8].try_into().unwrap())
+}
+
+/// xxHash64 (XXH64)
+pub fn xxhash64(bytes: &[u8], seed: u64) -> u64 {
+    let mut rest = bytes;
+
+    let mut hash = if bytes.len() >= 32 {
+        let mut accumulators = [
+            seed.wrapping_add(XXH_PRIME64_1).wrapping_add(XXH_PRIME64_2),
+            seed.wrapping_add(XXH_PRIME64_2),
+            seed,
+            seed.wrapping_sub(XXH_PRIME64_1),
+        ];
+
+        while rest.len() >= 32 {
+            for (i, accumulator) in accumulators.iter_mut().enumerate() {
+                *accumulator = xxh64_round(*accumulator, read_u64(&rest[i * 8..]));
+            }
+            rest = &rest[32..];
+        }
+
+        let [v1, v2, v3, v4] = accumulators;
+        let mut hash = v1.rotate_left(1)
+            .wrapping_add(v2.rotate_left(7))
+            .wrapping_add(v3.rotate_left(12))
+            .wrapping_add(v4.rotate_left(18));
+        for accumulator in accumulators {
+            hash = xxh64_merge(hash, accumulator);
+        }
+
+        hash
+    } else {
+        seed.wrapping_add(XXH_PRIME64_5)
+    };
+
+    hash = hash.wrapping_add(bytes.len() as u64);
+
+    while rest.len() >= 8 {
+        hash ^= xxh64_round(0, read_u64(rest));
+        hash = hash.rotate_left(27).wrapping_mul(XXH_PRIME64_1).wrapping_add(XXH_PRIME64_4);
+        rest = &rest[8..];
+    }
+
+    if rest.len() >= 4 {
+        let word = u32::from_le_bytes(rest[..4].try_into().unwrap()) as u64;
+        hash ^= word.wrapping_mul(XXH_PRIME64_1);
+        hash = hash.rotate_left(23).wrapping_mul(XXH_PRIME64_2).wrapping_add(XXH_PRIME64_3);
+        rest = &rest[4..];
+    }
+
+    for &byte in rest {
+        hash ^= (byte as u64).wrapping_mul(XXH_PRIME64_5);
+        hash = hash.rotate_left(11).wrapping_mul(XXH_PRIME64_1);
+    }
+
+    hash ^= hash >> 33;
+    hash = hash.wrapping_mul(XXH_PRIME64_2);
+    hash ^= hash >> 29;
+    hash = hash.wrapping_mul(XXH_PRIME64_3);
+    hash ^ (hash >> 32)
+}
+
+#[cfg(test)]
+mod checksum_tests {
+    use super::{crc32, crc32c, crc_update, xxhash64, ChecksumAlgorithm, Crc32, Crc32c, CRC32C_TABLE};
+
+    #[test]
+    fn test_crc32_check_value() {
+        assert_eq!(crc32(b""), 0);
+        assert_eq!(crc32(b"123456789"), 0xCBF4_3926);
+
+        let mut hasher = Crc32::new();
+        hasher.update(b"1234");
+        hasher.update(b"56789");
+        assert_eq!(hasher.finish(), 0xCBF4_3926);
+    }
+
+    #[test]
+    fn test_crc32c_check_value() {
+        assert_eq!(crc32c(b""), 0);
+        assert_eq!(crc32c(b"123456789"), 0xE306_9283);
+
+        // 하드웨어 경로와 테이블 경로가 같은 값을 내는지
+        let bytes = (0..=255u8).cycle().take(1000).collect::<Vec<_>>();
+        let mut hasher = Crc32c::new();
+        hasher.update(&bytes[..13]);
+        hasher.update(&bytes[13..]);
+        assert_eq!(hasher.finish(), !crc_update(&CRC32C_TABLE, !0, &bytes));
+    }
+
+    #[test]
+    fn test_xxhash64_check_value() {
+        assert_eq!(xxhash64(b"", 0), 0xEF46_DB37_51D8_E999);
+        assert_eq!(xxhash64(b"abc", 0), 0x44BC_2CF5_AD77_0999);
+        assert_eq!(xxhash64(b"Nobody inspects the spammish repetition", 0), 0xFBCE_A83C_8A37_8BF1);
+    }
+
+    #[test]
+    fn test_algorithm_id_roundtrip() {
+        for algorithm in [ChecksumAlgorithm::Crc32, ChecksumAlgorithm::Crc32c, ChecksumAlgorithm::XxHash64] {
+            assert_eq!(ChecksumAlgorithm::from_id(algorithm.id()), Some(algorithm));
+        }
+        assert_eq!(ChecksumAlgorithm::from_id(99), None);
+    }
+}
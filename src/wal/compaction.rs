@@ -0,0 +1,240 @@
+use std::collections::{HashMap, HashSet};
+use std::fs::File;
+use std::io::{Error, ErrorKind, Read, Write};
+use std::path::Path;
+use std::time::Duration;
+
+use super::block::{encode_blocks, BlockOptions};
+use super::core::{EntryType, WALManager};
+use super::flags::EntryFlags;
+use super::index::SegmentIndex;
+use super::lsn::Lsn;
+use super::manifest::Manifest;
+use super::reader::{RecoveryMode, SegmentReader, WALReader};
+use super::schema::EntryFormat;
+use super::segment::{segment_path, temp_segment_path, ChecksumWriter, SegmentFooter, SegmentHeader, FLAGS_FORMAT_VERSION, SEGMENT_HEADER_SIZE};
+use super::sync::sync_directory;
+
+/// 툼스톤이 이 기간보다 오래되면 그 앞의 값을 치운다
+pub const DEFAULT_TOMBSTONE_GRACE_PERIOD: Duration = Duration::from_secs(60 * 60);
+
+/// `compact` 가 치운 내용
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct CompactionReport {
+    /// data 를 비운 `Insert`/`Set` 엔트리 수
+    pub compacted_entries: u64,
+    /// 다시 쓴 봉인된 세그먼트
+    pub rewritten_segments: Vec<u64>,
+    /// 줄어든 바이트 수
+    pub reclaimed_bytes: u64,
+}
+
+/// 키/값 엔트리([`KeyValue`](super::kv::KeyValue))를 적용되는 순서대로 따라가면서, `grace_period` 보다 오래된
+/// `Delete` 툼스톤 앞에 적용된 같은 키의 `Insert`/`Set` 엔트리를 봉인된 세그먼트에서 치운다.
+/// 툼스톤 자체는 남긴다.
+///
+/// 엔트리를 빼면 LSN 과 세그먼트 안의 위치가 어긋나므로 지우지 않고, data 를 비우고 [`EntryFlags::COMPACTED`] 를
+/// 표시한 빈 엔트리로 바꿔서 다시 쓴다. 읽는 쪽은 이 엔트리를 건너뛰지만 LSN 은 그대로 차지한다.
+/// 플래그를 남기지 못하는 포맷 4 전의 세그먼트와 활성 세그먼트는 건드리지 않는다.
+/// 디렉토리 잠금은 호출하는 쪽에서 잡아야 한다 ([`WALManager::compact`]).
+pub fn compact_directory(directory: &Path, manifest: &Manifest, grace_period: Duration) -> Result<CompactionReport, Error> {
+    let superseded = superseded_entries(directory, grace_period)?;
+    let mut report = CompactionReport::default();
+    if superseded.is_empty() {
+        return Ok(report);
+    }
+
+    for &sequence in &manifest.sealed_segments {
+        let (compacted, reclaimed) = compact_segment(directory, sequence, &superseded)?;
+        if compacted > 0 {
+            report.compacted_entries += compacted;
+            report.rewritten_segments.push(sequence);
+            report.reclaimed_bytes += reclaimed;
+        }
+    }
+
+    if !report.rewritten_segments.is_empty() {
+        sync_directory(directory)?;
+    }
+
+    Ok(report)
+}
+
+/// 오래된 툼스톤에 가려진 엔트리의 LSN. 트랜잭션 안의 엔트리는 커밋되어 적용되는 시점을 기준으로 한다.
+fn superseded_entries(directory: &Path, grace_period: Duration) -> Result<HashSet<Lsn>, Error> {
+    let expired = WALManager::get_current_secs() - grace_period.as_secs_f64();
+    let mut values: HashMap<Vec<u8>, Vec<Lsn>> = HashMap::new();
+    let mut superseded = HashSet::new();
+
+    WALReader::open(directory)?.with_recovery_mode(RecoveryMode::TruncateAtError).replay_committed(|lsn, entry| {
+        let Some(key_value) = entry.key_value_ref() else {
+            return;
+        };
+
+        match entry.entry_type {
+            EntryType::Insert | EntryType::Set => values.entry(key_value.key.to_vec()).or_default().push(lsn),
+            EntryType::Delete => {
+                let earlier = values.remove(key_value.key).unwrap_or_default();
+                // 더 뒤의 툼스톤은 더 최근이므로, 아직 기간이 지나지 않았다면 다음 번에 다시 본다
+                if entry.timestamp <= expired {
+                    superseded.extend(earlier);
+                }
+            },
+            _ => {},
+        }
+    })?;
+
+    Ok(superseded)
+}
+
+/// `superseded` 에 속한 엔트리가 있다면 세그먼트를 다시 쓰고 비운 엔트리 수와 줄어든 바이트 수를 반환
+fn compact_segment(directory: &Path, sequence: u64, superseded: &HashSet<Lsn>) -> Result<(u64, u64), Error> {
+    let path = segment_path(directory, sequence as usize);
+    let mut header = [0u8; SEGMENT_HEADER_SIZE];
+    File::open(&path)?.read_exact(&mut header)?;
+    let segment_header = SegmentHeader::decode(&header)?;
+    if segment_header.version < FLAGS_FORMAT_VERSION {
+        return Ok((0, 0));
+    }
+
+    let Some(footer) = SegmentFooter::read(&path, sequence)? else {
+        return Err(Error::new(ErrorKind::InvalidData, format!("sealed segment {} has no footer", sequence)));
+    };
+
+    let mut segment = SegmentReader::open(&path, sequence)?
+        .with_recovery_mode(RecoveryMode::Strict)
+        .with_compacted(true);
+    let mut body = Vec::new();
+    // 블록으로 묶은 세그먼트라면 다시 인코딩한 프레임을 모았다가 새로 묶는다
+    let mut frames = Vec::new();
+    let mut index = SegmentIndex::new(sequence);
+    let mut compacted = 0;
+
+    while let Some(entry_header) = segment.advance()? {
+        let mut entry = segment.take_entry();
+        if superseded.contains(&entry_header.lsn) && !entry.flags.contains(EntryFlags::COMPACTED) {
+            entry.data = None;
+            entry.flags |= EntryFlags::COMPACTED;
+            compacted += 1;
+        }
+
+        let frame = entry.encode_frame(sequence as usize, segment_header.checksum, EntryFormat::of(&segment_header), entry_header.lsn)
+            .map_err(|e| Error::new(ErrorKind::InvalidData, e))?;
+        if segment_header.blocks {
+            frames.extend_from_slice(&frame);
+        } else {
+            index.record(segment.frames_read() - 1, (SEGMENT_HEADER_SIZE + body.len()) as u64);
+            body.extend_from_slice(&frame);
+        }
+    }
+
+    // 원래의 블록 크기와 압축 방식은 헤더에 남지 않으므로 기본값으로 묶는다
+    let mut position = 0;
+    for block in encode_blocks(&frames, EntryFormat::of(&segment_header).frame_layout(), sequence, segment_header.checksum, &BlockOptions::default(), &mut body) {
+        index.record_block(position, block.entries, SEGMENT_HEADER_SIZE as u64 + block.offset);
+        position += block.entries;
+    }
+
+    if compacted == 0 {
+        return Ok((0, 0));
+    }
+
+    let temp_path = temp_segment_path(directory, sequence as usize);
+    let mut temp_file = File::create(&temp_path)?;
+    temp_file.write_all(&header)?;
+
+    let mut writer = ChecksumWriter::new(temp_file);
+    writer.write_all(&body)?;
+    let footer = SegmentFooter { body_length: writer.written(), body_checksum: writer.checksum(), ..footer };
+    let mut temp_file = writer.into_inner();
+    temp_file.write_all(&footer.encode())?;
+    temp_file.sync_all()?;
+    drop(temp_file);
+
+    let before = std::fs::metadata(&path)?.len();
+    std::fs::rename(&temp_path, &path)?;
+    index.save(directory)?;
+
+    Ok((compacted, before.saturating_sub(std::fs::metadata(&path)?.len())))
+}
+
+#[cfg(test)]
+mod compaction_tests {
+    use std::time::Duration;
+
+    use crate::wal::core::{EntryType, Metadata, WALEntry, WALManager};
+    use crate::wal::flags::EntryFlags;
+    use crate::wal::lsn::Lsn;
+    use crate::wal::test_utils::temp_directory;
+
+    #[test]
+    fn test_compact_values_behind_tombstones() {
+        let directory = temp_directory("compaction");
+        let mut wal_manager = WALManager::builder()
+            .set_directory(directory.clone())
+            .set_tombstone_grace_period(Duration::ZERO)
+            .build().expect("Cannot create WALManager");
+
+        wal_manager.append_log(WALEntry::put(b"a", &[1; 64])).unwrap();
+        wal_manager.append_log(WALEntry::put(b"b", &[2; 64])).unwrap();
+        wal_manager.append_log(WALEntry::put(b"a", &[3; 64])).unwrap();
+
+        // 툼스톤보다 늦게 커밋된 값은 툼스톤에 가려지지 않는다
+        let marker = |entry_type| WALEntry {
+            entry_type,
+            data: None,
+            timestamp: WALManager::get_current_secs(),
+            transaction_id: 1,
+            metadata: Metadata::new(),
+            flags: EntryFlags::NONE
+        };
+        wal_manager.append_log(marker(EntryType::TransactionBegin)).unwrap();
+        wal_manager.append_log(WALEntry { transaction_id: 1, ..WALEntry::put(b"b", &[4; 64]) }).unwrap();
+        wal_manager.append_log(WALEntry::delete(b"b")).unwrap();
+        wal_manager.append_log(marker(EntryType::TransactionCommit)).unwrap();
+        wal_manager.checkpoint().unwrap();
+        wal_manager.append_log(WALEntry::delete(b"a")).unwrap();
+
+        let report = wal_manager.compact().unwrap();
+        assert_eq!(report.compacted_entries, 3);
+        assert_eq!(report.rewritten_segments, vec![1]);
+        assert!(report.reclaimed_bytes >= 3 * 64);
+        assert_eq!(wal_manager.compact().unwrap().compacted_entries, 0);
+        wal_manager.verify_all().unwrap();
+
+        // 비운 엔트리는 건너뛰지만 남은 엔트리의 LSN 은 그대로다
+        let remaining = wal_manager.recover().unwrap()
+            .map(|entry| entry.map(|(lsn, entry)| (lsn.0, entry.entry_type)))
+            .collect::<Result<Vec<_>, _>>().unwrap();
+        assert_eq!(remaining, vec![
+            (4, EntryType::TransactionBegin),
+            (5, EntryType::Set),
+            (6, EntryType::Delete),
+            (7, EntryType::TransactionCommit),
+            (8, EntryType::Checkpoint),
+            (9, EntryType::Delete),
+        ]);
+
+        drop(wal_manager);
+        let wal_manager = WALManager::builder()
+            .set_directory(directory.clone())
+            .build().expect("Cannot reopen WALManager");
+        assert_eq!(wal_manager.last_lsn().0, 9);
+        assert_eq!(wal_manager.read_from(Lsn(5)).unwrap().count(), 5);
+    }
+
+    #[test]
+    fn test_keep_values_within_grace_period() {
+        let directory = temp_directory("compaction_grace");
+        let mut wal_manager = WALManager::builder()
+            .set_directory(directory.clone())
+            .build().expect("Cannot create WALManager");
+
+        wal_manager.append_log(WALEntry::put(b"a", &[1; 64])).unwrap();
+        wal_manager.append_log(WALEntry::delete(b"a")).unwrap();
+        wal_manager.checkpoint().unwrap();
+
+        assert_eq!(wal_manager.compact().unwrap(), Default::default());
+        assert_eq!(wal_manager.recover().unwrap().count(), 3);
+    }
+}
@@ -0,0 +1,165 @@
+use std::io::{Error, ErrorKind};
+
+/// 이보다 짧은 일치는 기록하지 않는다. offset 과 토큰이 차지하는 3 바이트보다는 길어야 이득이다.
+const MIN_MATCH: usize = 4;
+const MAX_OFFSET: usize = u16::MAX as usize;
+const HASH_BITS: u32 = 12;
+
+/// LZ77 계열의 간단한 블록 압축 (LZ4 블록 포맷과 같은 모양)
+///
+/// `[token][literal 길이 확장][literal][offset u16 LE][match 길이 확장]` 의 반복이고 마지막은 literal 만 남긴다.
+/// token 의 상위 4 비트는 literal 길이, 하위 4 비트는 `match 길이 - MIN_MATCH` 이며, 15 면 255 단위의 확장 바이트가 이어진다.
+/// 블록 하나를 통째로 압축하고 풀기 때문에 스트리밍이나 사전은 지원하지 않는다.
+pub fn compress(input: &[u8], out: &mut Vec<u8>) {
+    // 위치 + 1 을 기록해서 0 을 빈 칸으로 쓴다
+    let mut table = vec![0u32; 1 << HASH_BITS];
+    let (mut anchor, mut i) = (0, 0);
+
+    while i + MIN_MATCH <= input.len() {
+        let sequence = u32::from_le_bytes(input[i..i + MIN_MATCH].try_into().unwrap());
+        let hash = (sequence.wrapping_mul(2_654_435_761) >> (32 - HASH_BITS)) as usize;
+        let candidate = std::mem::replace(&mut table[hash], i as u32 + 1) as usize;
+
+        if let Some(candidate) = candidate.checked_sub(1) {
+            if i - candidate <= MAX_OFFSET && input[candidate..candidate + MIN_MATCH] == input[i..i + MIN_MATCH] {
+                let mut length = MIN_MATCH;
+                while i + length < input.len() && input[candidate + length] == input[i + length] {
+                    length += 1;
+                }
+
+                emit_sequence(out, &input[anchor..i], Some((i - candidate, length)));
+                i += length;
+                anchor = i;
+                continue;
+            }
+        }
+
+        i += 1;
+    }
+
+    emit_sequence(out, &input[anchor..], None);
+}
+
+/// `compress` 로 압축한 `input` 을 풀어서 `out` 끝에 붙인다. 풀린 길이가 `raw_length` 와 다르면 실패한다.
+pub fn decompress(input: &[u8], raw_length: usize, out: &mut Vec<u8>) -> Result<(), Error> {
+    let corrupt = || Error::new(ErrorKind::InvalidData, "corrupt compressed block");
+    let start = out.len();
+    out.reserve(raw_length);
+    let mut i = 0;
+
+    loop {
+        let token = *input.get(i).ok_or_else(corrupt)?;
+        i += 1;
+
+        let mut literals = (token >> 4) as usize;
+        if literals == 15 {
+            literals += read_length(input, &mut i).ok_or_else(corrupt)?;
+        }
+        let bytes = input.get(i..i + literals).ok_or_else(corrupt)?;
+        if out.len() - start + literals > raw_length {
+            return Err(corrupt());
+        }
+        out.extend_from_slice(bytes);
+        i += literals;
+
+        if i == input.len() {
+            break;
+        }
+
+        let offset = u16::from_le_bytes(input.get(i..i + 2).ok_or_else(corrupt)?.try_into().unwrap()) as usize;
+        i += 2;
+        let mut length = (token & 0x0F) as usize;
+        if length == 15 {
+            length += read_length(input, &mut i).ok_or_else(corrupt)?;
+        }
+        length += MIN_MATCH;
+
+        let produced = out.len() - start;
+        if offset == 0 || offset > produced || produced + length > raw_length {
+            return Err(corrupt());
+        }
+
+        // 겹치는 일치(offset < length)는 앞에서 방금 복사한 바이트를 다시 읽는다
+        let from = out.len() - offset;
+        for k in 0..length {
+            out.push(out[from + k]);
+        }
+    }
+
+    if out.len() - start != raw_length {
+        return Err(corrupt());
+    }
+
+    Ok(())
+}
+
+fn emit_sequence(out: &mut Vec<u8>, literals: &[u8], matched: Option<(usize, usize)>) {
+    let match_length = matched.map_or(0, |(_, length)| length - MIN_MATCH);
+    out.push(((literals.len().min(15) as u8) << 4) | match_length.min(15) as u8);
+    if literals.len() >= 15 {
+        write_length(out, literals.len() - 15);
+    }
+    out.extend_from_slice(literals);
+
+    if let Some((offset, _)) = matched {
+        out.extend_from_slice(&(offset as u16).to_le_bytes());
+        if match_length >= 15 {
+            write_length(out, match_length - 15);
+        }
+    }
+}
+
+fn write_length(out: &mut Vec<u8>, mut length: usize) {
+    while length >= 255 {
+        out.push(255);
+        length -= 255;
+    }
+    out.push(length as u8);
+}
+
+fn read_length(input: &[u8], i: &mut usize) -> Option<usize> {
+    let mut length = 0;
+    loop {
+        let byte = *input.get(*i)?;
+        *i += 1;
+        length += byte as usize;
+        if byte != 255 {
+            return Some(length);
+        }
+    }
+}
+
+#[cfg(test)]
+mod compression_tests {
+    use super::{compress, decompress};
+
+    #[test]
+    fn test_compress_roundtrip() {
+        let repetitive = b"INSERT INTO users VALUES (1, 'alice'); ".repeat(100);
+        let mut noise = Vec::new();
+        let mut state = 0x2545_f491u32;
+        for _ in 0..1000 {
+            state ^= state << 13;
+            state ^= state >> 17;
+            state ^= state << 5;
+            noise.push(state as u8);
+        }
+
+        for input in [Vec::new(), b"abc".to_vec(), vec![7; 5000], repetitive.clone(), noise] {
+            let mut compressed = Vec::new();
+            compress(&input, &mut compressed);
+
+            let mut output = vec![42];
+            decompress(&compressed, input.len(), &mut output).unwrap();
+            assert_eq!(output[1..], input[..]);
+        }
+
+        let mut compressed = Vec::new();
+        compress(&repetitive, &mut compressed);
+        assert!(compressed.len() < repetitive.len() / 10);
+
+        // 길이가 맞지 않거나 잘린 입력은 거부한다
+        assert!(decompress(&compressed, repetitive.len() - 1, &mut Vec::new()).is_err());
+        assert!(decompress(&compressed[..compressed.len() / 2], repetitive.len(), &mut Vec::new()).is_err());
+    }
+}
@@ -1,8 +1,55 @@
+// bitcode 0.4 derive 매크로가 생성하는 코드에서 발생하는 lint
+#![allow(unused_must_use, clippy::assign_op_pattern)]
+
 use bitcode::{Encode, Decode};
-use std::path::{Path, PathBuf};
+use std::collections::BTreeMap;
+use std::ops::Range;
+use std::path::PathBuf;
 use std::error::Error;
-use std::fs::{self};
-use std::time::SystemTime;
+use std::fs::{File, OpenOptions};
+use std::io::{Read, Write};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::mpsc::Receiver;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant, SystemTime};
+
+use super::block::{Block, BlockOptions};
+use super::block_cache::BlockCache;
+use super::checkpoint::{CheckpointEvent, CheckpointHook, CheckpointPolicy};
+use super::checksum::ChecksumAlgorithm;
+use super::compaction::{self, CompactionReport, DEFAULT_TOMBSTONE_GRACE_PERIOD};
+use super::double_write::DoubleWriteBuffer;
+use super::error::WALError;
+use super::flags::EntryFlags;
+use super::flusher::FlushBatch;
+use super::frame::encode_frame_with;
+use super::index::{index_path, SegmentIndex};
+use super::lock::DirectoryLock;
+use super::lsn::Lsn;
+use super::manifest::Manifest;
+use super::metrics::WALMetrics;
+#[cfg(feature = "metrics-prometheus")]
+use super::metrics::PrometheusMetrics;
+use super::quarantine::quarantine;
+use super::quota::{DiskQuota, QuotaPolicy};
+use super::rate_limit::{RateLimit, RateLimiter};
+use super::reader::{RecoveryMode, RecoveryReport, SegmentReader, WALReader, WALVisitor};
+use super::repair::{self, RecoveryTarget, RepairReport};
+use super::retry::RetryPolicy;
+use super::schema::{self, EntryFormat, LegacyEntry};
+use super::segment::{recycled_segment_path, segment_path, temp_segment_path, ChecksumWriter, SegmentFooter, SegmentHeader, LEGACY_FORMAT_VERSION, METADATA_FORMAT_VERSION, SEGMENT_FOOTER_SIZE, SEGMENT_HEADER_SIZE, VARINT_FORMAT_VERSION};
+use super::stall::{StallEvent, StallHook, StallMonitor, StallReason};
+use super::sync::{sync_directory, Durability, SyncMethod, SyncPolicy};
+use super::tail::WALTail;
+use super::trace::{Operation, OperationSpan};
+use super::transaction::Transaction;
+use super::varint::{encode_varint, read_varint, write_varint};
+use super::verify::{self, IntegrityProblem};
+use super::watch::{DurableRange, Watchers};
+use super::writer::{SegmentWriter, SyncHandle, WriteBufferOptions, WriterOptions, DIRECT_IO_ALIGNMENT, MIN_DIRECT_IO_BLOCK_SIZE};
+
+/// 엔트리에 붙이는 작은 키/값 메타데이터 (요청을 보낸 노드, 테넌트 id, 요청 id 등)
+pub type Metadata = BTreeMap<String, Vec<u8>>;
 
 #[derive(Clone, Debug, Encode, Decode)]
 pub struct WALEntry {
@@ -10,32 +57,314 @@ pub struct WALEntry {
     pub data: Option<Vec<u8>>,
     pub timestamp: f64,
     pub transaction_id: u64,
+    /// 포맷 3 부터 기록한다. 이전 포맷의 활성 세그먼트에 이어 쓰는 동안에는 남지 않는다.
+    pub metadata: Metadata,
+    /// 포맷 4 부터 기록한다 ([`EntryFlags`])
+    pub flags: EntryFlags,
 }
 
 impl WALEntry {
-    fn size(&self) -> usize {
-        let data_size = self.data.as_ref().map_or(0, |data| data.len());
+    pub fn with_metadata(mut self, key: &str, value: &[u8]) -> Self {
+        self.metadata.insert(key.to_string(), value.to_vec());
+        self
+    }
+
+    /// 중첩 트랜잭션을 시작하는 `TransactionBegin` 엔트리라면 부모 트랜잭션 id (data 에 u64 LE 로 담는다)
+    pub fn parent_transaction_id(&self) -> Option<u64> {
+        parent_transaction_id(self.entry_type, self.data.as_deref())
+    }
+
+    /// 빌려온 엔트리로 ([`WALEntryRef`])
+    pub fn as_entry_ref(&self) -> WALEntryRef<'_> {
+        WALEntryRef {
+            entry_type: self.entry_type,
+            data: self.data.as_deref(),
+            timestamp: self.timestamp,
+            transaction_id: self.transaction_id,
+            metadata: &self.metadata,
+            flags: self.flags,
+        }
+    }
+
+    /// [`WALEntryRef::encode_payload`]
+    pub fn encode_payload(&self, format: EntryFormat, lsn: Lsn) -> Result<Vec<u8>, bitcode::Error> {
+        self.as_entry_ref().encode_payload(format, lsn)
+    }
 
-        size_of::<EntryType>() + size_of::<f64>() + size_of::<u64>() + data_size
+    pub(crate) fn encode_frame(&self, salt: usize, checksum: ChecksumAlgorithm, format: EntryFormat, lsn: Lsn) -> Result<Vec<u8>, bitcode::Error> {
+        self.as_entry_ref().encode_frame(salt, checksum, format, lsn)
+    }
+
+    /// 끊기거나 체크섬이 맞지 않는 프레임, 혹은 데이터의 끝을 뜻하는 패딩을 만나면 그 앞까지의 엔트리와
+    /// 마지막으로 온전한 프레임의 끝 위치를 함께 반환
+    #[cfg(test)]
+    fn decode_frames<R: Read>(reader: super::frame::FrameReader<R>, mode: RecoveryMode) -> Result<(Vec<WALEntry>, u64), std::io::Error> {
+        let mut segment = SegmentReader::new(reader).with_recovery_mode(mode);
+        let entries = segment.by_ref().collect::<Result<_, _>>()?;
+
+        Ok((entries, segment.offset()))
     }
 }
 
-#[derive(Clone, Debug, Encode, Decode)]
+/// 읽는 쪽에서 data 를 새 `Vec<u8>` 으로 복사하지 않고 프레임 버퍼에서 빌려온 엔트리.
+/// 다음 엔트리를 읽기 전까지만 유효하므로 남겨둘 엔트리는 [`WALEntryRef::into_owned`] 로 옮긴다.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct WALEntryRef<'a> {
+    pub entry_type: EntryType,
+    pub data: Option<&'a [u8]>,
+    pub timestamp: f64,
+    pub transaction_id: u64,
+    pub metadata: &'a Metadata,
+    pub flags: EntryFlags,
+}
+
+impl WALEntryRef<'_> {
+    /// [`WALEntry::parent_transaction_id`]
+    pub fn parent_transaction_id(&self) -> Option<u64> {
+        parent_transaction_id(self.entry_type, self.data)
+    }
+
+    /// 포맷 2 부터는 `[u16 LE 헤더 길이][bitcode 로 인코딩한 EntryHeader][data]` 로 기록해서, 읽는 쪽이 data 를
+    /// 프레임 버퍼에서 그대로 빌려갈 수 있다 ([`WALEntryRef`]). 포맷 3 은 헤더 뒤에 `[u32 LE 길이][bitcode 로 인코딩한 메타데이터]` 를
+    /// 끼운다 (메타데이터가 없다면 길이 0). 포맷 1 은 엔트리 전체를 bitcode 로 인코딩하고 `lsn` 과 메타데이터를 남기지 않는다.
+    /// 포맷 6 은 타임스탬프를 `format.epoch` 와의 마이크로초 차이로 남기므로 마이크로초 아래는 버려진다 ([`schema::encode_header`]).
+    pub fn encode_payload(&self, format: EntryFormat, lsn: Lsn) -> Result<Vec<u8>, bitcode::Error> {
+        let mut payload = Vec::new();
+        self.encode_payload_into(format, lsn, &mut bitcode::Buffer::new(), &mut payload)?;
+
+        Ok(payload)
+    }
+
+    /// `encode_payload` 와 같지만 `buffer` 를 다시 써서 `out` 끝에 이어붙이므로, 버퍼가 충분히 커진 뒤로는 할당하지 않는다
+    pub(crate) fn encode_payload_into(&self, format: EntryFormat, lsn: Lsn, buffer: &mut bitcode::Buffer, out: &mut Vec<u8>) -> Result<(), bitcode::Error> {
+        if format.version == LEGACY_FORMAT_VERSION {
+            out.extend_from_slice(buffer.encode(&LegacyEntry::of(*self))?);
+            return Ok(());
+        }
+
+        // 포맷 7 부터는 헤더와 메타데이터의 길이를 u16/u32 대신 varint 로 남긴다
+        let varint = format.version >= VARINT_FORMAT_VERSION;
+        let start = out.len();
+        let reserved = if varint { 1 } else { 2 };
+        out.resize(start + reserved, 0);
+        schema::encode_header(&EntryHeader::of(*self, lsn), format, buffer, out)?;
+        let length = out.len() - start - reserved;
+        match varint {
+            // 헤더는 거의 항상 128 바이트보다 짧아서 자리를 옮길 일이 드물다
+            true => {
+                let (bytes, size) = encode_varint(length as u64);
+                out.splice(start..start + reserved, bytes[..size].iter().copied());
+            }
+            false => out[start..start + reserved].copy_from_slice(&(length as u16).to_le_bytes()),
+        }
+        if format.version >= METADATA_FORMAT_VERSION {
+            let metadata = match self.metadata.is_empty() {
+                true => &[][..],
+                false => buffer.encode(&self.metadata)?,
+            };
+            match varint {
+                true => write_varint(metadata.len() as u64, out),
+                false => out.extend_from_slice(&(metadata.len() as u32).to_le_bytes()),
+            }
+            out.extend_from_slice(metadata);
+        }
+        out.extend_from_slice(self.data.unwrap_or_default());
+
+        Ok(())
+    }
+
+    /// `salt` 는 엔트리가 기록될 세그먼트의 순번
+    pub(crate) fn encode_frame(&self, salt: usize, checksum: ChecksumAlgorithm, format: EntryFormat, lsn: Lsn) -> Result<Vec<u8>, bitcode::Error> {
+        let mut frame = Vec::new();
+        self.encode_frame_into(salt, checksum, format, lsn, &mut bitcode::Buffer::new(), &mut frame)?;
+
+        Ok(frame)
+    }
+
+    /// payload 를 따로 모으지 않고 `out` 끝에 프레임을 바로 인코딩한다 ([`WALManager`] 의 재사용 버퍼용)
+    pub(crate) fn encode_frame_into(
+        &self,
+        salt: usize,
+        checksum: ChecksumAlgorithm,
+        format: EntryFormat,
+        lsn: Lsn,
+        buffer: &mut bitcode::Buffer,
+        out: &mut Vec<u8>,
+    ) -> Result<(), bitcode::Error> {
+        encode_frame_with(format.frame_layout(), salt as u64, checksum, out, |out| self.encode_payload_into(format, lsn, buffer, out))
+    }
+
+    pub fn into_owned(self) -> WALEntry {
+        WALEntry {
+            entry_type: self.entry_type,
+            data: self.data.map(<[u8]>::to_vec),
+            timestamp: self.timestamp,
+            transaction_id: self.transaction_id,
+            metadata: self.metadata.clone(),
+            flags: self.flags,
+        }
+    }
+}
+
+/// 포맷 2 이상 페이로드에서 data 앞에 오는 나머지 필드. 디스크에 남기는 모양은 포맷마다 다르다 ([`schema`]).
+#[derive(Clone, Copy, Debug)]
+pub(crate) struct EntryHeader {
+    /// 기록할 때 매긴 LSN. 매니페스트를 다시 만들면서 앞선 세그먼트를 셀 수 없게 되더라도 엔트리의 LSN 은 바뀌지 않는다.
+    /// `lsn` 을 남기지 않는 포맷 1 엔트리는 0 이다.
+    pub(crate) lsn: Lsn,
+    pub(crate) entry_type: EntryType,
+    pub(crate) has_data: bool,
+    pub(crate) timestamp: f64,
+    pub(crate) transaction_id: u64,
+    pub(crate) flags: EntryFlags,
+}
+
+impl EntryHeader {
+    pub(crate) fn of(entry: WALEntryRef<'_>, lsn: Lsn) -> Self {
+        Self {
+            lsn,
+            entry_type: entry.entry_type,
+            has_data: entry.data.is_some(),
+            timestamp: entry.timestamp,
+            transaction_id: entry.transaction_id,
+            flags: entry.flags,
+        }
+    }
+
+    /// 포맷 2 이상 페이로드의 헤더, 메타데이터가 있는 범위(포맷 2 는 비어 있다), data 가 시작하는 위치
+    pub(crate) fn decode_payload(payload: &[u8], format: EntryFormat, buffer: &mut bitcode::Buffer) -> Result<(Self, Range<usize>, usize), std::io::Error> {
+        let invalid = || std::io::Error::new(std::io::ErrorKind::InvalidData, "entry payload is too short");
+
+        let varint = format.version >= VARINT_FORMAT_VERSION;
+
+        let (length, size) = match varint {
+            true => read_varint(payload).map(|(length, size)| (length as usize, size)),
+            false => payload.first_chunk::<2>().map(|length| (u16::from_le_bytes(*length) as usize, 2)),
+        }.ok_or_else(invalid)?;
+        let header = payload.get(size..size + length).ok_or_else(invalid)?;
+        let header = schema::decode_header(header, format, buffer)?;
+
+        let mut data_start = size + length;
+        let mut metadata = data_start..data_start;
+        if format.version >= METADATA_FORMAT_VERSION {
+            let rest = payload.get(data_start..).ok_or_else(invalid)?;
+            let (length, size) = match varint {
+                true => read_varint(rest).map(|(length, size)| (length as usize, size)),
+                false => rest.first_chunk::<4>().map(|length| (u32::from_le_bytes(*length) as usize, size_of::<u32>())),
+            }.ok_or_else(invalid)?;
+            let start = data_start + size;
+            metadata = start..start.saturating_add(length);
+            if metadata.end > payload.len() {
+                return Err(invalid());
+            }
+            data_start = metadata.end;
+        }
+
+        Ok((header, metadata, data_start))
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Encode, Decode)]
 pub enum EntryType {
     Insert,
     Set,
+    /// 키를 지운 툼스톤. 키/값 엔트리라면 오래된 툼스톤 앞의 같은 키 값은 컴팩션으로 치울 수 있다 ([`WALManager::compact`])
     Delete,
     Checkpoint,
 
     TransactionBegin,
     TransactionCommit,
+    TransactionAbort,
+    /// 트랜잭션 안의 저장점. data 에 저장점 이름을 담는다.
+    Savepoint,
+    /// 같은 이름의 마지막 저장점 뒤로 기록한 트랜잭션 엔트리를 되돌린다. data 에 저장점 이름을 담는다.
+    RollbackToSavepoint,
+    /// 2PC 에서 커밋할 준비를 마쳤다는 표시. 뒤에 커밋이나 중단 엔트리로 결정을 남긴다.
+    TransactionPrepare,
+    /// `append_batch` 로 한 번에 기록한 엔트리들의 시작과 끝
+    BatchBegin,
+    BatchEnd,
+    /// 어플리케이션이 정의한 엔트리 종류. 복구할 때는 `Insert`/`Set`/`Delete` 처럼 데이터 엔트리로 다루므로
+    /// 트랜잭션 안에 있다면 커밋된 경우에만 적용된다. 번호의 뜻은 어플리케이션이 정한다.
+    Custom(u16),
 }
 
+fn parent_transaction_id(entry_type: EntryType, data: Option<&[u8]>) -> Option<u64> {
+    match (entry_type, data) {
+        (EntryType::TransactionBegin, Some(data)) => data.try_into().ok().map(u64::from_le_bytes),
+        _ => None,
+    }
+}
+
+/// `next_transaction_id` 가 매니페스트에 한 번에 예약해두는 트랜잭션 id 수
+pub const TRANSACTION_ID_BLOCK: u64 = 1024;
+
 pub struct WALManager {
     sequence: usize,
-    page_size: usize,
-    buffered: Vec<WALEntry>,
+    manifest: Manifest,
+    segment_max_bytes: usize,
+    checkpoint_policy: CheckpointPolicy,
+    last_checkpoint: Instant,
+    /// 활성 세그먼트에 쓴 바이트 수 (헤더 제외). 버퍼에 모아두고 아직 쓰지 않은 프레임은 인코딩한 크기로 센다.
+    /// 다시 열었을 때도 이미 있던 프레임부터 센다.
+    segment_bytes: usize,
+    /// 활성 세그먼트를 봉인할 때 더 붙는 체크포인트 프레임과 footer 의 크기. 쓰기 핸들을 열 때 잰다.
+    seal_bytes: usize,
     directory: PathBuf,
+    writer: Option<SegmentWriter>,
+    writer_options: WriterOptions,
+    /// 활성 세그먼트에 기록된 엔트리 수 (봉인할 때 footer 에 남긴다)
+    segment_entries: u64,
+    /// 활성 세그먼트의 sparse 위치 색인 (봉인할 때 `walN.idx` 로 남긴다)
+    segment_index: SegmentIndex,
+    recycle_segments: usize,
+    recycled: Vec<PathBuf>,
+    sync_policy: SyncPolicy,
+    unsynced_entries: usize,
+    last_synced: Instant,
+    recovery_mode: RecoveryMode,
+    mmap_reads: bool,
+    read_ahead: bool,
+    recovery_threads: usize,
+    block_cache: Option<Arc<BlockCache>>,
+    tombstone_grace_period: Duration,
+    checkpoint_hook: Option<CheckpointHook>,
+    archive_directory: Option<PathBuf>,
+    retain_applied_segments: bool,
+    sync_on_drop: bool,
+    /// 마지막으로 기록한 엔트리의 LSN
+    last_lsn: Lsn,
+    /// fsync 로 내구성이 보장된 마지막 LSN
+    durable_lsn: Lsn,
+    /// 다음에 나눠줄 트랜잭션 id. 매니페스트에 예약된 범위를 넘으면 다시 예약한다.
+    next_transaction_id: u64,
+    watchers: Watchers,
+    /// append 마다 새로 할당하지 않도록 헤더 인코딩과 프레임에 다시 쓰는 버퍼
+    encode_buffer: bitcode::Buffer,
+    /// `append_record` 가 레코드를 인코딩해두는 버퍼. 프레임을 인코딩하는 동안 빌려 쓰므로 `encode_buffer` 와 따로 둔다.
+    record_buffer: bitcode::Buffer,
+    /// 버퍼링한다면 아직 세그먼트에 쓰지 않은 프레임들
+    frame_buffer: Vec<u8>,
+    write_buffer: Option<WriteBufferOptions>,
+    rate_limiter: Option<RateLimiter>,
+    disk_quota: Option<DiskQuota>,
+    /// 봉인된 세그먼트 파일 크기의 합 (`disk_quota` 용)
+    sealed_bytes: u64,
+    stall_monitor: Option<StallMonitor>,
+    metrics: WALMetrics,
+    #[cfg(feature = "metrics-prometheus")]
+    prometheus: Option<PrometheusMetrics>,
+    buffered_entries: usize,
+    buffer_started: Option<Instant>,
+    /// 백그라운드 flusher 가 앞의 배치를 쓰는 동안 `frame_buffer` 와 자리를 바꿔 쓰는 두 번째 버퍼
+    spare_buffer: Vec<u8>,
+    flushing: bool,
+    /// `pause` 로 멈춰 있다면 쓰기를 거절한다
+    paused: bool,
+    /// 읽기 전용으로 열었다면 어떤 파일도 고치지 않는다
+    read_only: bool,
+    /// 읽기 전용으로 열었다면 잠금을 잡지 않는다
+    _lock: Option<DirectoryLock>,
 }
 
 // TODO: gz 압축 구현
@@ -45,155 +374,2999 @@ impl WALManager {
         WALBuilder::default()
     }
 
-    fn check_and_mark(&mut self, entry: &WALEntry) -> Result<(), Box<dyn Error>> {
-        let size = self.buffered.iter().map(|entry| entry.size()).sum::<usize>();
+    /// 멈춰 있다면 거절하고, 세그먼트를 봉인할 때가 되었다면 체크포인트를 남긴다
+    fn check_and_mark(&mut self) -> Result<(), Box<dyn Error>> {
+        if self.paused {
+            return Err(WALError::Paused.into());
+        }
 
-        if size > self.page_size {
+        if self.checkpoint_due() {
             self.checkpoint()?;
         }
 
         Ok(())
     }
 
-    fn append(&mut self, entry: WALEntry) -> Result<(), Box<dyn Error>>{
-        self.buffered.push(entry);
-        let path = Path::join(&self.directory, format!("wal{}.log", self.sequence));
-        let bytes = bitcode::encode(&self.buffered)?;
+    /// `incoming` 바이트를 더 쓰면 [`DiskQuota`] 를 넘는다면 정책에 따라 반영된 오래된 세그먼트를 지우거나 거절한다
+    fn enforce_quota(&mut self, incoming: u64) -> Result<(), Box<dyn Error>> {
+        let Some(quota) = self.disk_quota else {
+            return Ok(());
+        };
+
+        let mut total_bytes = self.disk_usage() + incoming;
+        if total_bytes > quota.max_total_bytes && quota.policy == QuotaPolicy::EvictOldest {
+            // `truncate_before` 와 같이 모든 엔트리가 `applied_lsn` 이하인 세그먼트만 지운다
+            let mut evicted = Vec::new();
+            let mut remaining = total_bytes;
+            for sequence in self.segments_before(self.manifest.applied_lsn + 1)? {
+                if remaining <= quota.max_total_bytes {
+                    break;
+                }
+                remaining = remaining.saturating_sub(std::fs::metadata(segment_path(&self.directory, sequence as usize))?.len());
+                evicted.push(sequence);
+            }
+
+            if !evicted.is_empty() {
+                self.remove_segments(evicted)?;
+                total_bytes = self.disk_usage() + incoming;
+            }
+        }
 
-        fs::write(path, bytes)?;
+        if total_bytes > quota.max_total_bytes {
+            return Err(WALError::QuotaExceeded { total_bytes, max_total_bytes: quota.max_total_bytes }.into());
+        }
 
         Ok(())
     }
 
-    pub fn append_log(&mut self, entry: WALEntry) -> Result<(), Box<dyn Error>>{
-        self.check_and_mark(&entry)?;
+    /// 봉인된 세그먼트와 활성 세그먼트 파일 크기의 합. 버퍼에 모아두고 아직 쓰지 않은 프레임도 센다.
+    /// 활성 세그먼트를 아직 열지 않았다면 열 때 쓸 헤더와 복구한 길이로 센다.
+    pub fn disk_usage(&self) -> u64 {
+        let active = match self.writer.as_ref() {
+            Some(writer) => writer.length(),
+            None => (SEGMENT_HEADER_SIZE + self.segment_bytes) as u64,
+        };
+
+        self.sealed_bytes + active + self.frame_buffer.len() as u64
+    }
+
+    /// 읽기 전용이면 `PermissionDenied`, 멈춰 있다면 [`WALError::Paused`] 를 담은 오류
+    fn check_writable(&self) -> Result<(), std::io::Error> {
+        if self.read_only {
+            return Err(std::io::Error::new(std::io::ErrorKind::PermissionDenied, "WAL is opened read-only"));
+        }
 
-        self.append(entry)?;
+        if self.paused {
+            return Err(std::io::Error::other(WALError::Paused));
+        }
 
         Ok(())
     }
 
-    pub fn checkpoint(&mut self) -> Result<(), Box<dyn Error>> {
-        self.append(WALEntry {
-            data: None,
-            entry_type: EntryType::Checkpoint,
-            timestamp: WALManager::get_current_secs(),
-            transaction_id: 0
-        })?;
+    /// 지금까지 기록한 엔트리를 fsync 하고, `resume` 할 때까지 디렉토리를 바꾸는 작업(append, 체크포인트, 컴팩션,
+    /// 잘라내기)을 모두 거절한다. 프로세스를 내리지 않고 핫 백업이나 디렉토리 이전을 할 때 쓴다.
+    /// 거절된 append 는 [`WALError::Paused`] 를 돌려준다. 여러 스레드에서 쓴다면 [`GroupCommitter::pause`](super::group_commit::GroupCommitter::pause) 는
+    /// 오류 대신 `resume` 까지 기다린다. fsync 된 마지막 LSN 을 반환한다.
+    pub fn pause(&mut self) -> Result<Lsn, std::io::Error> {
+        let lsn = self.sync()?;
+        self.paused = true;
 
-        self.buffered.clear();
-        self.sequence += 1;
+        Ok(lsn)
+    }
+
+    pub fn resume(&mut self) {
+        self.paused = false;
+    }
+
+    pub fn is_paused(&self) -> bool {
+        self.paused
+    }
+
+    /// 쓰기 버퍼가 꺼져 있다면 기본값으로 켠다 ([`SharedWAL`](super::shared::SharedWAL) 용)
+    pub(crate) fn ensure_write_buffer(&mut self) {
+        self.write_buffer.get_or_insert_with(WriteBufferOptions::default);
+    }
+
+    /// 현재 세그먼트의 쓰기 핸들을 반환하고, 열려있지 않다면 연다.
+    /// 새 세그먼트가 필요할 때 재활용 대기 중인 파일이 있으면 그 파일을 rename 하고 비워서 다시 쓴다.
+    ///
+    /// 새로 만들거나 rename 한 `walN.log` 는 디렉토리를 fsync 하기 전까지는 크래시 후에 사라질 수 있다.
+    /// 재활용한 파일은 예전 이름이 남지 않도록 rename 직후 항상 디렉토리를 동기화하고, 새로 만든 파일은
+    /// fsync 하지 않는 정책(`Never`)이 아니라면 첫 쓰기 전에 동기화한다.
+    fn segment_writer(&mut self) -> Result<&mut SegmentWriter, std::io::Error> {
+        self.check_writable()?;
+
+        if self.writer.is_none() {
+            let path = segment_path(&self.directory, self.sequence);
+            let created = !path.exists();
+
+            let writer = match self.recycled.pop() {
+                Some(recycled) if created => {
+                    std::fs::rename(&recycled, &path)?;
+                    self.sync_directory()?;
+                    SegmentWriter::open_recycled(&path, self.sequence, &self.writer_options)?
+                },
+                recycled => {
+                    self.recycled.extend(recycled);
+                    let writer = SegmentWriter::open(&path, self.sequence, &self.writer_options)?;
+                    if created && self.sync_policy != SyncPolicy::Never {
+                        self.sync_directory()?;
+                    }
+                    writer
+                },
+            };
+
+            // 체크포인트 프레임은 LSN 이 가장 길 때로 어림한다
+            let checkpoint = Self::checkpoint_entry(None)
+                .encode_frame(self.sequence, writer.checksum(), writer.format(), Lsn(u64::MAX))
+                .map_err(std::io::Error::other)?;
+            let mut block = Vec::new();
+            let checkpoint = writer.encode_frames(&checkpoint, &mut block).map_or(checkpoint.len(), |_| block.len());
+            self.seal_bytes = checkpoint + SEGMENT_FOOTER_SIZE;
+            self.writer = Some(writer);
+        }
+
+        Ok(self.writer.as_mut().unwrap())
+    }
+
+    /// 세그먼트 파일을 만들거나 옮기거나 지운 뒤 WAL 디렉토리를 fsync 한다
+    fn sync_directory(&mut self) -> Result<(), std::io::Error> {
+        sync_directory(&self.directory)?;
+        self.metrics.directory_syncs += 1;
 
         Ok(())
     }
 
-    pub fn get_current_secs() -> f64 {
-        SystemTime::now()
-            .duration_since(SystemTime::UNIX_EPOCH)
-            .expect("Cannot getting since time")
-            .as_secs_f64()
+    /// 엔트리에 붙인 LSN 과 기록한 프레임 크기를 반환
+    fn write_entry(&mut self, entry: WALEntryRef<'_>) -> Result<(Lsn, usize), Box<dyn Error>> {
+        let (lsn, offset, start, length, blocks) = loop {
+            let (sequence, lsn) = (self.sequence, self.last_lsn + 1);
+            let writer = self.segment_writer().map_err(append_error)?;
+            let (checksum, format, blocks) = (writer.checksum(), writer.format(), writer.blocks());
+            let offset = writer.length() + self.frame_buffer.len() as u64;
+
+            // 버퍼링하지 않는다면 비어 있고, 버퍼링한다면 아직 쓰지 않은 프레임이 앞에 남아 있다
+            let mut frames = std::mem::take(&mut self.frame_buffer);
+            let start = frames.len();
+            let encoded = entry.encode_frame_into(sequence, checksum, format, lsn, &mut self.encode_buffer, &mut frames);
+            let length = frames.len() - start;
+            self.frame_buffer = frames;
+            encoded?;
+
+            // 봉인할 자리가 모자라면 세그먼트를 먼저 교체하고 새 세그먼트의 순번과 LSN 으로 다시 인코딩한다.
+            // 백그라운드 flush 중에는 교체할 수 없으므로 그대로 모아두고, 다음 append 가 flush 를 기다렸다가 교체한다 (`checkpoint_due`).
+            // 방금 버퍼에 넣은 프레임은 `disk_usage` 가 센다.
+            let rotate = !self.flushing && self.segment_overflows(length);
+            if let Err(e) = self.enforce_quota(self.rotation_bytes(rotate)) {
+                self.frame_buffer.truncate(start);
+                return Err(e);
+            }
+            if !rotate {
+                break (lsn, offset, start, length, blocks);
+            }
+            self.frame_buffer.truncate(start);
+            self.checkpoint()?;
+        };
+        self.throttle(1, length);
+
+        // 블록으로 묶는 세그먼트는 블록을 쓸 때 그 위치를 색인에 남긴다 (`write_pending`)
+        if !blocks {
+            self.segment_index.record(self.segment_entries, offset);
+        }
+        self.segment_entries += 1;
+        self.last_lsn += 1;
+        self.unsynced_entries += 1;
+
+        self.buffered_entries += 1;
+        self.buffer_started.get_or_insert_with(Instant::now);
+        if self.should_flush() {
+            if let Err(e) = self.flush_buffer() {
+                self.discard_last_entry(start, blocks);
+                return Err(append_error(e));
+            }
+        }
+        self.record_appends(1, length as u64);
+        self.measure_segment();
+
+        Ok((lsn, length))
     }
 
-}
+    /// 쓰지 못한 마지막 엔트리를 버퍼에서 빼고 기록하기 전으로 되돌린다.
+    /// 앞서 모아둔 엔트리는 LSN 을 이미 돌려줬으므로 남겨두고 다음 flush 에서 다시 쓴다.
+    fn discard_last_entry(&mut self, start: usize, blocks: bool) {
+        self.frame_buffer.truncate(start);
+        self.segment_entries -= 1;
+        if !blocks {
+            let position = self.segment_entries;
+            self.segment_index.entries.retain(|indexed| indexed.position < position);
+        }
+        self.last_lsn = Lsn(self.last_lsn.0 - 1);
+        self.unsynced_entries -= 1;
+        self.buffered_entries -= 1;
+        if self.buffered_entries == 0 {
+            self.buffer_started = None;
+        }
+        self.measure_segment();
+    }
 
-pub struct WALBuilder {
-    page_size: usize,
-    directory: PathBuf,
-}
+    /// 세그먼트 크기로 봉인할지 따질 수 있도록 `segment_bytes` 를 쓰기 핸들의 길이로 다시 센다.
+    /// 블록으로 묶는다면 파일에는 압축한 크기가 남고, 아직 묶지 않은 프레임만 압축하기 전의 크기로 센다.
+    fn measure_segment(&mut self) {
+        if let Some(writer) = &self.writer {
+            self.segment_bytes = writer.length() as usize - SEGMENT_HEADER_SIZE + self.frame_buffer.len();
+        }
+    }
 
-impl Default for WALBuilder {
-    fn default() -> Self {
-        Self { page_size: 4096, directory: PathBuf::from(".") }
+    fn throttle(&mut self, entries: usize, bytes: usize) {
+        if let Some(limiter) = &mut self.rate_limiter {
+            let waited = limiter.throttle(entries, bytes);
+            self.report_stall(StallReason::RateLimited, waited);
+        }
     }
-}
 
-impl WALBuilder {
-    pub fn set_page_size(mut self, page_size: usize) -> Self {
-        self.page_size = page_size;
-        self
+    /// [`StallHook`] 에 알린다. 임계값보다 짧았다면 부르지 않는다.
+    pub(crate) fn report_stall(&mut self, reason: StallReason, duration: Duration) {
+        if let Some(monitor) = &mut self.stall_monitor {
+            monitor.report(reason, duration);
+        }
     }
 
-    pub fn set_directory(mut self, directory: PathBuf) -> Self {
-        self.directory = directory;
-        self
+    /// `started` 부터 걸린 쓰기나 fsync 가 느렸거나 디스크가 가득 차서 실패했다면 알린다.
+    /// `synced` 라면 fsync 까지 한 것이므로 지표에 fsync 로 남긴다.
+    fn observe_disk<T>(&mut self, started: Instant, synced: bool, result: std::io::Result<T>) -> std::io::Result<T> {
+        if let Some(monitor) = &mut self.stall_monitor {
+            monitor.observe(started, &result);
+        }
+        if synced && result.is_ok() {
+            self.record_fsync(started.elapsed());
+        }
+
+        result
     }
 
-    fn load_data(&self) -> Result<(usize, Vec<WALEntry>), std::io::Error> {
-        let mut log_sequence = 1;
-        let log_files = std::fs::read_dir(&self.directory)?
-            .filter_map(|entry| entry.ok())
-            .filter(|entry| entry.path().extension() == Some(std::ffi::OsStr::new("log")))
-            .collect::<Vec<_>>();
+    /// 락 밖에서 끝낸 fsync 도 지표에 남긴다 (group commit 용)
+    pub(crate) fn record_fsync(&mut self, latency: Duration) {
+        self.metrics.fsyncs += 1;
+        self.metrics.fsync_latency.record(latency);
+        #[cfg(feature = "metrics-prometheus")]
+        if let Some(prometheus) = &self.prometheus {
+            prometheus.record_fsync(latency);
+        }
+    }
 
-        let mut entries = Vec::new();
-
-        if let Some(last_log) = log_files.last() {
-            log_sequence = log_files.len();
-            let file_content = std::fs::read(last_log.path())?;
-            let saved_entries: Vec<WALEntry> = bitcode::decode(&file_content)
-                .map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidData, e))?;
-
-            
-            match saved_entries.last() {
-                Some(last_entry) => {
-                    match last_entry.entry_type {
-                        EntryType::Checkpoint => log_sequence += 1,
-                        _ => entries = saved_entries.clone(),
-                        
-                    }
+    /// 기록한 엔트리를 지표에 남긴다. 체크포인트 엔트리는 세지 않는다.
+    fn record_appends(&mut self, entries: u64, bytes: u64) {
+        self.metrics.entries_appended += entries;
+        self.metrics.bytes_written += bytes;
+        #[cfg(feature = "metrics-prometheus")]
+        if let Some(prometheus) = &self.prometheus {
+            prometheus.record_appends(entries, bytes);
+        }
+    }
+
+    /// 봉인된 세그먼트 목록이 바뀌면 레지스트리의 세그먼트 수를 맞춘다
+    fn publish_segment_count(&self) {
+        #[cfg(feature = "metrics-prometheus")]
+        if let Some(prometheus) = &self.prometheus {
+            prometheus.set_segment_count(self.manifest.sealed_segments.len() as u64 + 1);
+        }
+    }
+
+    /// 연 뒤로 누적한 지표 ([`WALMetrics`])
+    pub fn metrics(&self) -> &WALMetrics {
+        &self.metrics
+    }
+
+    /// 지표를 Prometheus 텍스트 형식으로 ([`WALMetrics::encode_prometheus`])
+    #[cfg(feature = "metrics-prometheus")]
+    pub fn prometheus_metrics(&self) -> String {
+        let mut text = String::new();
+        self.metrics.encode_prometheus(self.manifest.sealed_segments.len() as u64 + 1, &mut text);
+
+        text
+    }
+
+    fn should_flush(&self) -> bool {
+        match self.write_buffer {
+            None => true,
+            Some(options) => self.frame_buffer.len() >= options.max_bytes
+                || self.buffered_entries >= options.max_entries
+                || self.buffer_started.is_some_and(|started| started.elapsed() >= options.max_delay),
+        }
+    }
+
+    /// 메모리에 모아둔 프레임을 활성 세그먼트에 쓴다 ([`WALBuilder::set_write_buffer`]).
+    /// 백그라운드 flusher 가 앞의 배치를 쓰는 중이라면 그 배치가 끝난 뒤 다음 배치로 쓰이도록 모아둔다.
+    fn flush_buffer(&mut self) -> Result<(), std::io::Error> {
+        self.write_pending(false)
+    }
+
+    /// `flush_buffer` 와 같되 `sync` 가 참이면 쓰면서 fsync 까지 한다 ([`SegmentWriter::write_and_sync`])
+    fn write_pending(&mut self, sync: bool) -> Result<(), std::io::Error> {
+        if self.flushing {
+            return Ok(());
+        }
+        if self.frame_buffer.is_empty() {
+            return match sync {
+                true => {
+                    let span = OperationSpan::enter(Operation::Flush);
+                    let started = Instant::now();
+                    let synced = self.segment_writer().and_then(|writer| writer.sync());
+                    self.observe_disk(started, true, synced)?;
+                    span.finish(self.last_lsn, 0);
+
+                    Ok(())
                 },
-                _ => {}
+                false => Ok(()),
+            };
+        }
+
+        // 버퍼링하지 않는다면 append 마다 바로 쓰는 것이므로 따로 span 을 열지 않는다
+        let span = (sync || self.write_buffer.is_some()).then(|| OperationSpan::enter(Operation::Flush));
+        let first = self.segment_entries - self.buffered_entries as u64;
+        let frames = std::mem::take(&mut self.frame_buffer);
+        let started = Instant::now();
+        let written = self.segment_writer().and_then(|writer| match sync {
+            true => writer.write_frames_and_sync(&frames),
+            false => writer.write_frames(&frames),
+        });
+        let written = self.observe_disk(started, sync, written);
+        self.frame_buffer = frames;
+        let blocks = written?;
+
+        self.record_blocks(first, &blocks);
+        self.frame_buffer.clear();
+        if let Some(span) = span {
+            span.finish(self.last_lsn, self.buffered_entries as u64);
+        }
+        self.buffered_entries = 0;
+        self.buffer_started = None;
+
+        Ok(())
+    }
+
+    /// 블록으로 묶어서 쓴 세그먼트라면 `first` 번째 엔트리부터 차례로 담긴 블록들의 위치를 색인에 남긴다
+    fn record_blocks(&mut self, first: u64, blocks: &[Block]) {
+        let mut position = first;
+        for block in blocks {
+            self.segment_index.record_block(position, block.entries, block.offset);
+            position += block.entries;
+        }
+    }
+
+    fn append(&mut self, entry: WALEntryRef<'_>) -> Result<Lsn, Box<dyn Error>>{
+        let (lsn, _) = self.write_entry(entry)?;
+        self.sync_by_policy().map_err(append_error)?;
+
+        Ok(lsn)
+    }
+
+    fn sync_by_policy(&mut self) -> Result<(), std::io::Error> {
+        if self.sync_policy.should_sync(self.unsynced_entries, self.last_synced) {
+            self.sync()?;
+        }
+
+        Ok(())
+    }
+
+    /// 백그라운드 flusher 용. 모아둔 프레임을 예비 버퍼와 바꿔서 꺼내고 그만큼 세그먼트에 자리를 잡아둔다.
+    /// 락 밖에서 [`FlushBatch::write`] 로 쓰고 fsync 하는 동안 다음 엔트리는 다른 버퍼에 인코딩된다.
+    /// 끝나면 `finish_flush` 로 버퍼를 돌려줘야 하며, 그 전까지는 파일을 직접 건드리는 작업(`sync`, 체크포인트,
+    /// `append_logs` 등)이 `WouldBlock` 을 돌려준다.
+    pub(crate) fn begin_flush(&mut self) -> Result<FlushBatch, std::io::Error> {
+        self.check_not_flushing()?;
+        let (target, first) = (self.last_lsn, self.segment_entries - self.buffered_entries as u64);
+
+        // 블록으로 묶는 세그먼트라면 자리를 잡기 전에 먼저 묶어서 쓸 길이를 정한다
+        let mut encoded = Vec::new();
+        let frames = std::mem::take(&mut self.frame_buffer);
+        let blocks = self.segment_writer().map(|writer| writer.encode_frames(&frames, &mut encoded));
+        self.frame_buffer = frames;
+        let blocks = blocks?;
+        let length = match blocks {
+            Some(_) => encoded.len(),
+            None => self.frame_buffer.len(),
+        };
+
+        let offset = self.segment_writer()?.reserve(length as u64)?;
+        if offset.is_none() {
+            self.flush_buffer()?;
+        }
+        let handle = self.segment_writer()?.sync_handle()?;
+
+        let mut frames = std::mem::replace(&mut self.frame_buffer, std::mem::take(&mut self.spare_buffer));
+        let mut unencoded = None;
+        if let (Some(_), Some(blocks)) = (offset, blocks) {
+            self.record_blocks(first, &blocks);
+            unencoded = Some(std::mem::replace(&mut frames, encoded));
+        }
+        // 자리를 잡지 못했다면 이미 `flush_buffer` 로 썼으므로 fsync 만 남는다
+        let entries = if offset.is_some() { self.buffered_entries } else { 0 };
+        let offset = offset.unwrap_or(0);
+        self.buffered_entries = 0;
+        self.buffer_started = None;
+        self.flushing = true;
+
+        let span = OperationSpan::enter(Operation::Flush);
+        Ok(FlushBatch { handle, offset, frames, unencoded, first, entries, target, started: Instant::now(), span })
+    }
+
+    /// `begin_flush` 로 꺼낸 배치를 쓰고 fsync 한 결과를 받아서 버퍼를 돌려받는다.
+    /// 쓰지 못했다면 배치의 프레임을 버퍼 앞에 되돌리고 잡아둔 자리를 놓아서, 다음 flush 가 같은 위치부터 다시 쓴다.
+    /// 그때까지 `durable_lsn` 은 배치 앞에 머문다.
+    pub(crate) fn finish_flush(&mut self, batch: FlushBatch, result: Result<(), std::io::Error>) -> Result<(), std::io::Error> {
+        let FlushBatch { mut frames, unencoded, offset, first, entries, target, started, span, .. } = batch;
+        self.flushing = false;
+        if let Err(e) = self.observe_disk(started, true, result) {
+            if entries > 0 {
+                let mut pending = unencoded.unwrap_or(frames);
+                pending.extend_from_slice(&self.frame_buffer);
+                self.frame_buffer.clear();
+                self.spare_buffer = std::mem::replace(&mut self.frame_buffer, pending);
+                // 블록은 다시 쓸 때 새로 묶어서 색인에 남긴다
+                if self.segment_writer()?.blocks() {
+                    self.segment_index.entries.retain(|indexed| indexed.position < first);
+                }
+                self.segment_writer()?.release(offset)?;
+                self.buffered_entries += entries;
+                self.buffer_started.get_or_insert_with(Instant::now);
+                self.measure_segment();
             }
+            return Err(e);
         }
 
-        Ok((log_sequence, entries))
+        frames.clear();
+        self.spare_buffer = unencoded.unwrap_or(frames);
+        self.spare_buffer.clear();
+
+        let entries = target.0.saturating_sub(self.durable_lsn.0);
+        self.advance_durable(target);
+        span.finish(self.last_lsn, entries);
+
+        Ok(())
     }
 
-    pub fn build(self) -> Result<WALManager, std::io::Error> {
-        let (sequence, buffered) = self.load_data()?;
+    fn check_not_flushing(&self) -> Result<(), std::io::Error> {
+        if self.flushing {
+            return Err(std::io::Error::new(std::io::ErrorKind::WouldBlock, "a background flush is in progress"));
+        }
 
-        Ok(WALManager {
-            sequence: sequence,
-            page_size: self.page_size,
-            directory: self.directory,
-            buffered: Vec::new(),
-        })
+        Ok(())
     }
-}
 
-#[cfg(test)]
-mod io_tests {
-    use super::{WALEntry, WALManager, EntryType};
+    /// 백그라운드 flusher 가 배치를 쓰는 중인지
+    pub(crate) fn is_flushing(&self) -> bool {
+        self.flushing
+    }
 
-    #[test]
-    fn test_create() {
-        let builder = WALManager::builder()
-            .build();
-        assert!(builder.is_ok());
+    /// 앞의 배치를 쓰는 동안 모인 프레임이 `max_pending_bytes` 를 넘었는지
+    pub(crate) fn buffer_full(&self) -> bool {
+        self.flushing && self.write_buffer.is_some_and(|options| self.frame_buffer.len() >= options.max_pending_bytes)
+    }
 
-        let builder = builder.unwrap();
-        assert_eq!(builder.sequence, 1);
+    /// append 가 쓰는 중인 배치를 기다려야 하는지. 체크포인트는 파일을 직접 쓰고, 가득 찬 버퍼는 더 모으지 않는다.
+    pub(crate) fn must_wait_for_flush(&self) -> bool {
+        (self.flushing && self.checkpoint_due()) || self.buffer_full()
     }
 
-    #[test]
-    fn test_append_wal() {
-        let mut wal_manager = WALManager::builder()
-            .build().expect("Cannot create WALManager");
+    /// 다음 append 가 체크포인트를 남길지 (`check_and_mark` 와 같은 조건)
+    pub(crate) fn checkpoint_due(&self) -> bool {
+        self.segment_overflows(0)
+            || self.checkpoint_policy.should_checkpoint(self.segment_entries, self.segment_bytes, self.last_checkpoint)
+    }
 
-        let start = WALManager::get_current_secs();
-        for _ in 0..100 {
-            let entry = WALEntry {
-                entry_type: EntryType::Insert,
-                data: Some(Vec::from([10u8;100])),
-                timestamp: WALManager::get_current_secs(),
-                transaction_id: 0
-            };
+    /// 세그먼트를 교체한다면 봉인하면서 붙는 체크포인트 프레임과 footer, 새 세그먼트의 헤더만큼 디스크를 더 쓴다
+    fn rotation_bytes(&self, rotate: bool) -> u64 {
+        match rotate {
+            true => (self.seal_bytes + SEGMENT_HEADER_SIZE) as u64,
+            false => 0,
+        }
+    }
 
-            let result = wal_manager.append_log(entry);
-            assert!(result.is_ok());
+    /// `incoming` 바이트를 더 쓴 뒤 체크포인트 프레임과 footer 를 붙여 봉인하면 세그먼트 파일이 `segment_max_bytes` 를 넘는지.
+    /// 빈 세그먼트는 교체해도 자리가 나지 않으므로 넘지 않는다고 본다.
+    fn segment_overflows(&self, incoming: usize) -> bool {
+        self.segment_entries > 0 && SEGMENT_HEADER_SIZE + self.segment_bytes + incoming + self.seal_bytes > self.segment_max_bytes
+    }
+
+    /// 동기화 정책과 상관없이 지금까지 기록한 엔트리를 fsync 하고, 내구성이 보장된 가장 큰 LSN 을 반환
+    pub fn sync(&mut self) -> Result<Lsn, std::io::Error> {
+        if self.durable_lsn < self.last_lsn {
+            self.check_not_flushing()?;
+            self.write_pending(true)?;
+            self.mark_synced();
         }
-        let end = WALManager::get_current_secs();
 
-        println!("elapsed: {}s", end - start);
+        Ok(self.durable_lsn)
     }
-}
\ No newline at end of file
+
+    fn mark_synced(&mut self) {
+        self.unsynced_entries = 0;
+        self.last_synced = Instant::now();
+        self.advance_durable(self.last_lsn);
+    }
+
+    pub fn last_lsn(&self) -> Lsn {
+        self.last_lsn
+    }
+
+    pub fn durable_lsn(&self) -> Lsn {
+        self.durable_lsn
+    }
+
+    /// 복제 핸들로 락 밖에서 fsync 를 끝낸 뒤 호출 (group commit 용)
+    pub(crate) fn mark_durable(&mut self, lsn: Lsn) {
+        self.advance_durable(lsn);
+    }
+
+    /// 엔트리가 fsync 될 때마다 새로 durable 해진 LSN 범위를 받는 채널을 연다.
+    /// 디렉토리를 주기적으로 훑지 않고 새 엔트리를 따라가려는 색인기 같은 소비자용.
+    pub fn subscribe(&mut self) -> Receiver<DurableRange> {
+        self.watchers.subscribe()
+    }
+
+    fn advance_durable(&mut self, lsn: Lsn) {
+        if lsn > self.durable_lsn {
+            self.watchers.notify(self.durable_lsn + 1..=lsn);
+            self.durable_lsn = lsn;
+        }
+    }
+
+    /// 여러 엔트리를 프레임 단위 iovec 으로 한 번에 기록하고, 동기화 정책은 배치 끝에서 한 번만 따진다.
+    /// 마지막 엔트리의 LSN 을 반환하며, 엔트리가 없다면 `last_lsn` 그대로다.
+    pub fn append_logs(&mut self, entries: Vec<WALEntry>) -> Result<Lsn, Box<dyn Error>> {
+        self.append_entry_refs(entries.iter().map(WALEntry::as_entry_ref))
+    }
+
+    /// `append_logs` 와 같지만 빌려온 엔트리를 기록한다 ([`Transaction`] 이 모아둔 엔트리 용).
+    /// 세그먼트를 교체하면 다시 인코딩하므로 `entries` 는 여러 번 훑을 수 있어야 한다.
+    pub(crate) fn append_entry_refs<'e>(&mut self, entries: impl Iterator<Item = WALEntryRef<'e>> + Clone) -> Result<Lsn, Box<dyn Error>> {
+        let count = entries.clone().count();
+        if count == 0 {
+            return Ok(self.last_lsn);
+        }
+        let span = OperationSpan::enter(Operation::Append);
+        self.check_and_mark()?;
+        self.check_not_flushing()?;
+        self.flush_buffer()?;
+
+        let mut bounds = Vec::with_capacity(count + 1);
+        let (offset, mut frames) = self.encode_batch(entries, &mut bounds)?;
+        self.throttle(count, frames.len());
+        let written = if self.segment_writer()?.blocks() {
+            let started = Instant::now();
+            let written = self.segment_writer().and_then(|writer| writer.write_frames(&frames));
+            self.observe_disk(started, false, written).map(Some)
+        } else {
+            let slices = bounds.windows(2).map(|bound| &frames[bound[0]..bound[1]]).collect::<Vec<_>>();
+            let started = Instant::now();
+            let written = self.segment_writer().and_then(|writer| writer.write_vectored(&slices));
+            self.observe_disk(started, false, written).map(|_| None)
+        };
+        let length = frames.len() as u64;
+        frames.clear();
+        self.frame_buffer = frames;
+        match written.map_err(append_error)? {
+            Some(blocks) => self.record_blocks(self.segment_entries, &blocks),
+            None => {
+                for (position, start) in (self.segment_entries..).zip(&bounds[..count]) {
+                    self.segment_index.record(position, offset + *start as u64);
+                }
+            },
+        }
+        self.record_appends(count as u64, length);
+        self.segment_entries += count as u64;
+        self.last_lsn += count as u64;
+        self.unsynced_entries += count;
+        self.measure_segment();
+        self.sync_by_policy().map_err(append_error)?;
+        span.finish(self.last_lsn, count as u64);
+
+        Ok(self.last_lsn)
+    }
+
+    /// 대량 적재용. 엔트리를 재사용하는 프레임 버퍼 하나에 이어서 인코딩한 뒤 한 번에 쓰고, 동기화 정책과 상관없이
+    /// 한 번 fsync 한 뒤에 반환한다. 세그먼트를 교체하면 다시 인코딩해야 하므로 엔트리를 먼저 모두 모은다.
+    /// 마지막 엔트리의 LSN 을 반환하며, 엔트리가 없다면 `last_lsn` 그대로다.
+    pub fn append_many(&mut self, entries: impl IntoIterator<Item = WALEntry>) -> Result<Lsn, Box<dyn Error>> {
+        let entries = entries.into_iter().collect::<Vec<_>>();
+        if entries.is_empty() {
+            return Ok(self.last_lsn);
+        }
+        let span = OperationSpan::enter(Operation::Append);
+        self.check_and_mark()?;
+        self.check_not_flushing()?;
+        self.flush_buffer()?;
+
+        let mut bounds = Vec::with_capacity(entries.len() + 1);
+        let (offset, mut frames) = self.encode_batch(entries.iter().map(WALEntry::as_entry_ref), &mut bounds)?;
+        self.throttle(entries.len(), frames.len());
+        let started = Instant::now();
+        let written = self.segment_writer().and_then(|writer| writer.write_frames_and_sync(&frames));
+        let blocks = match self.observe_disk(started, true, written) {
+            Ok(blocks) => blocks,
+            Err(e) => {
+                frames.clear();
+                self.frame_buffer = frames;
+                return Err(append_error(e));
+            },
+        };
+        self.record_appends(entries.len() as u64, frames.len() as u64);
+        frames.clear();
+        self.frame_buffer = frames;
+
+        if blocks.is_empty() {
+            for (position, start) in (self.segment_entries..).zip(&bounds[..entries.len()]) {
+                self.segment_index.record(position, offset + *start as u64);
+            }
+        }
+        self.record_blocks(self.segment_entries, &blocks);
+        self.segment_entries += entries.len() as u64;
+        self.last_lsn += entries.len() as u64;
+        self.measure_segment();
+        self.mark_synced();
+        span.finish(self.last_lsn, entries.len() as u64);
+
+        Ok(self.last_lsn)
+    }
+
+    /// 프레임마다 할당하지 않도록 꺼낸 프레임 버퍼 하나에 배치를 이어서 인코딩하고, 첫 프레임을 쓸 위치와 그 버퍼를 반환한다.
+    /// i 번째 프레임은 `bounds[i]..bounds[i + 1]` 이다. 봉인할 자리가 모자라면 세그먼트를 먼저 교체하고 새 세그먼트의
+    /// 순번과 LSN 으로 다시 인코딩하며, 배치 전체가 용량 상한을 넘는다면 거절한다. 실패하면 프레임 버퍼를 비워서 되돌려 둔다.
+    fn encode_batch<'e>(&mut self, entries: impl Iterator<Item = WALEntryRef<'e>> + Clone, bounds: &mut Vec<usize>) -> Result<(u64, Vec<u8>), Box<dyn Error>> {
+        loop {
+            let (sequence, first_lsn) = (self.sequence, self.last_lsn + 1);
+            let writer = self.segment_writer()?;
+            let (checksum, format, offset) = (writer.checksum(), writer.format(), writer.length());
+            let mut frames = std::mem::take(&mut self.frame_buffer);
+            frames.clear();
+            bounds.clear();
+            bounds.push(0);
+            let encoded = entries.clone().zip((0..).map(|i| first_lsn + i)).try_for_each(|(entry, lsn)| {
+                entry.encode_frame_into(sequence, checksum, format, lsn, &mut self.encode_buffer, &mut frames)?;
+                bounds.push(frames.len());
+                Ok::<_, bitcode::Error>(())
+            });
+            let fits = encoded.map_err(Box::from).and_then(|_| {
+                let rotate = self.segment_overflows(frames.len());
+                self.enforce_quota(frames.len() as u64 + self.rotation_bytes(rotate)).map(|_| !rotate)
+            });
+            if let Ok(true) = fits {
+                return Ok((offset, frames));
+            }
+
+            frames.clear();
+            self.frame_buffer = frames;
+            fits?;
+            self.checkpoint()?;
+        }
+    }
+
+    /// `append_logs` 처럼 한 번에 기록하되 `BatchBegin` 과 `BatchEnd` 사이에 끼운다. 끝 표시까지 기록되기 전에 죽었다면
+    /// 다시 열 때 배치 전체를 잘라내므로, 배치의 엔트리는 모두 남거나 모두 사라진다.
+    pub fn append_batch(&mut self, entries: Vec<WALEntry>) -> Result<Lsn, Box<dyn Error>> {
+        if entries.is_empty() {
+            return Ok(self.last_lsn);
+        }
+
+        let marker = |entry_type| WALEntry {
+            entry_type,
+            data: None,
+            timestamp: Self::get_current_secs(),
+            transaction_id: 0,
+            metadata: Metadata::new(),
+            flags: EntryFlags::NONE,
+        };
+        let mut batch = Vec::with_capacity(entries.len() + 2);
+        batch.push(marker(EntryType::BatchBegin));
+        batch.extend(entries);
+        batch.push(marker(EntryType::BatchEnd));
+
+        self.append_logs(batch)
+    }
+
+    /// 동기화 정책과 상관없이 쓰기만 하고, LSN 과 기록한 프레임 크기를 반환 (group commit 용)
+    pub(crate) fn append_unsynced(&mut self, entry: WALEntry) -> Result<(Lsn, usize), Box<dyn Error>> {
+        self.check_and_mark()?;
+
+        self.write_entry(entry.as_entry_ref())
+    }
+
+    /// 락을 잡지 않고 fsync 할 수 있도록 활성 세그먼트의 복제 핸들을 반환
+    pub(crate) fn sync_handle(&mut self) -> Result<SyncHandle, std::io::Error> {
+        self.check_not_flushing()?;
+        self.flush_buffer()?;
+        self.segment_writer()?.sync_handle()
+    }
+
+    /// 엔트리를 기록하고 붙인 LSN 을 반환한다. 동기화 정책에 따라 아직 durable 하지 않을 수 있으므로
+    /// `durable_lsn` 이나 `subscribe` 로 이 LSN 까지 fsync 되었는지 확인한다.
+    pub fn append_log(&mut self, entry: WALEntry) -> Result<Lsn, Box<dyn Error>>{
+        let span = OperationSpan::enter(Operation::Append);
+        self.check_and_mark()?;
+        let lsn = self.append(entry.as_entry_ref())?;
+        span.finish(self.last_lsn, 1);
+
+        Ok(lsn)
+    }
+
+    /// `record` 를 재사용하는 버퍼에 bitcode 로 인코딩하고, 그 바이트를 data 로 빌려서 `append_log` 처럼 기록한다
+    /// ([`append_record`](Self::append_record))
+    pub(crate) fn append_encoded<T: Encode + ?Sized>(&mut self, entry_type: EntryType, record: &T) -> Result<Lsn, Box<dyn Error>> {
+        let span = OperationSpan::enter(Operation::Append);
+        self.check_and_mark()?;
+
+        let mut buffer = std::mem::take(&mut self.record_buffer);
+        let metadata = Metadata::new();
+        let appended = match buffer.encode(record) {
+            Ok(data) => self.append(WALEntryRef {
+                entry_type,
+                data: Some(data),
+                timestamp: Self::get_current_secs(),
+                transaction_id: 0,
+                metadata: &metadata,
+                flags: EntryFlags::NONE,
+            }),
+            Err(e) => Err(e.into()),
+        };
+        self.record_buffer = buffer;
+        let lsn = appended?;
+        span.finish(self.last_lsn, 1);
+
+        Ok(lsn)
+    }
+
+    /// 엔트리를 모았다가 커밋할 때 한 번에 기록하는 트랜잭션을 시작한다 ([`Transaction`])
+    pub fn begin_transaction(&mut self) -> Result<Transaction<'_>, std::io::Error> {
+        let transaction_id = self.next_transaction_id()?;
+
+        Ok(Transaction::new(self, transaction_id))
+    }
+
+    /// 다시 열어도 겹치지 않는 트랜잭션 id 를 나눠준다. 0 은 트랜잭션에 속하지 않은 엔트리를 뜻하므로 1 부터 시작한다.
+    ///
+    /// id 마다 매니페스트를 쓰지 않도록 [`TRANSACTION_ID_BLOCK`] 개씩 미리 예약해두므로,
+    /// 죽었다가 다시 열면 예약만 하고 쓰지 않은 id 는 건너뛴다.
+    pub fn next_transaction_id(&mut self) -> Result<u64, std::io::Error> {
+        self.check_writable()?;
+
+        if self.next_transaction_id >= self.manifest.transaction_id_limit {
+            self.manifest.transaction_id_limit = self.next_transaction_id + TRANSACTION_ID_BLOCK;
+            self.manifest.save(&self.directory)?;
+        }
+
+        let transaction_id = self.next_transaction_id;
+        self.next_transaction_id += 1;
+
+        Ok(transaction_id)
+    }
+
+    /// `parent_transaction_id` 안에서 중첩 트랜잭션을 시작하는 엔트리를 남긴다. 자식이 커밋했더라도 부모가 중단되면
+    /// 복구할 때 자식의 엔트리도 버려진다 ([`WALReader::replay_committed`])
+    pub fn begin_nested_transaction(&mut self, transaction_id: u64, parent_transaction_id: u64) -> Result<Lsn, Box<dyn Error>> {
+        self.append_log(WALEntry {
+            entry_type: EntryType::TransactionBegin,
+            data: Some(parent_transaction_id.to_le_bytes().to_vec()),
+            timestamp: Self::get_current_secs(),
+            transaction_id,
+            metadata: Metadata::new(),
+            flags: EntryFlags::NONE,
+        })
+    }
+
+    /// 트랜잭션을 중단했다는 엔트리를 남긴다. 복구할 때 그 트랜잭션의 엔트리는 모두 버려진다 ([`WALReader::replay_committed`])
+    pub fn abort_transaction(&mut self, transaction_id: u64) -> Result<Lsn, Box<dyn Error>> {
+        self.append_log(WALEntry {
+            entry_type: EntryType::TransactionAbort,
+            data: None,
+            timestamp: Self::get_current_secs(),
+            transaction_id,
+            metadata: Metadata::new(),
+            flags: EntryFlags::NONE,
+        })
+    }
+
+    /// 2PC 참여자로서 트랜잭션을 준비했다는 엔트리를 남긴다. 코디네이터에 응답하기 전에 디스크에 있어야 하므로
+    /// 동기화 정책과 상관없이 fsync 한다. 결정은 `TransactionCommit` 엔트리나 `abort_transaction` 으로 남긴다.
+    pub fn prepare_transaction(&mut self, transaction_id: u64) -> Result<Lsn, Box<dyn Error>> {
+        self.append_log_with(WALEntry {
+            entry_type: EntryType::TransactionPrepare,
+            data: None,
+            timestamp: Self::get_current_secs(),
+            transaction_id,
+            metadata: Metadata::new(),
+            flags: EntryFlags::NONE,
+        }, Durability::Durable)
+    }
+
+    /// 트랜잭션 안에 `name` 저장점을 남긴다
+    pub fn savepoint(&mut self, transaction_id: u64, name: &str) -> Result<Lsn, Box<dyn Error>> {
+        self.append_log(WALEntry {
+            entry_type: EntryType::Savepoint,
+            data: Some(name.as_bytes().to_vec()),
+            timestamp: Self::get_current_secs(),
+            transaction_id,
+            metadata: Metadata::new(),
+            flags: EntryFlags::NONE,
+        })
+    }
+
+    /// `name` 저장점 뒤로 기록한 트랜잭션 엔트리를 되돌렸다는 엔트리를 남긴다. 저장점은 그대로 남아서 다시 되돌릴 수 있다.
+    pub fn rollback_to_savepoint(&mut self, transaction_id: u64, name: &str) -> Result<Lsn, Box<dyn Error>> {
+        self.append_log(WALEntry {
+            entry_type: EntryType::RollbackToSavepoint,
+            data: Some(name.as_bytes().to_vec()),
+            timestamp: Self::get_current_secs(),
+            transaction_id,
+            metadata: Metadata::new(),
+            flags: EntryFlags::NONE,
+        })
+    }
+
+    /// 동기화 정책 대신 엔트리마다 지정한 내구성 수준을 따른다
+    pub fn append_log_with(&mut self, entry: WALEntry, durability: Durability) -> Result<Lsn, Box<dyn Error>> {
+        let span = OperationSpan::enter(Operation::Append);
+        self.check_and_mark()?;
+        let (lsn, _) = self.write_entry(entry.as_entry_ref())?;
+
+        if durability == Durability::Durable {
+            self.sync().map_err(append_error)?;
+        }
+        span.finish(self.last_lsn, 1);
+
+        Ok(lsn)
+    }
+
+    /// 기록한 엔트리를 fsync 하고, 활성 세그먼트에 엔트리가 있다면 체크포인트로 봉인해서 매니페스트에 남긴 뒤
+    /// 디렉토리 잠금을 놓는다. 다시 열 때 활성 세그먼트를 훑지 않아도 된다. 마지막 LSN 을 반환한다.
+    /// 그냥 drop 해도 기록된 엔트리는 남지만, 오류를 받아볼 수 있고 잠금이 풀리는 시점이 분명해진다.
+    pub fn close(mut self) -> Result<Lsn, Box<dyn Error>> {
+        if self.read_only {
+            return Ok(self.last_lsn);
+        }
+
+        self.sync()?;
+        if self.segment_entries > 0 {
+            self.checkpoint()?;
+        }
+
+        Ok(self.last_lsn)
+    }
+
+    /// 체크포인트 엔트리로 현재 세그먼트를 봉인하고 다음 세그먼트로 넘어간다
+    ///
+    /// 봉인된 세그먼트는 `walN.log.tmp` 에 먼저 만들어진 뒤 rename 되므로,
+    /// 복구 시에 반쯤 봉인된 세그먼트를 볼 일이 없다. 파일 끝에는 엔트리 수와 body 체크섬을 담은 footer 가 붙는다.
+    /// 재활용이 켜져 있으면 쓰던 활성 파일은 지우지 않고 재활용 대기열로 옮긴다.
+    /// 체크포인트 엔트리의 LSN 을 반환한다.
+    pub fn checkpoint(&mut self) -> Result<Lsn, Box<dyn Error>> {
+        self.seal(None)
+    }
+
+    /// `checkpoint` 와 같지만 체크포인트 엔트리의 data 에 `payload` (스냅샷 파일 이름이나 어플리케이션의 LSN 등)를 남긴다.
+    /// 다시 열었을 때 [`last_checkpoint_payload`](Self::last_checkpoint_payload) 로 어떤 스냅샷을 읽어야 하는지 알 수 있다.
+    pub fn checkpoint_with_payload(&mut self, payload: Vec<u8>) -> Result<Lsn, Box<dyn Error>> {
+        self.seal(Some(payload))
+    }
+
+    fn checkpoint_entry(payload: Option<Vec<u8>>) -> WALEntry {
+        WALEntry {
+            data: payload,
+            entry_type: EntryType::Checkpoint,
+            timestamp: WALManager::get_current_secs(),
+            transaction_id: 0,
+            metadata: Metadata::new(),
+            flags: EntryFlags::NONE
+        }
+    }
+
+    fn seal(&mut self, payload: Option<Vec<u8>>) -> Result<Lsn, Box<dyn Error>> {
+        if self.paused {
+            return Err(WALError::Paused.into());
+        }
+        let span = OperationSpan::enter(Operation::Checkpoint);
+        self.check_not_flushing()?;
+        self.flush_buffer()?;
+
+        let entry = Self::checkpoint_entry(payload);
+        let (sequence, lsn) = (self.sequence, self.last_lsn + 1);
+        let writer = self.segment_writer()?;
+        let mut frame = entry.encode_frame(sequence, writer.checksum(), writer.format(), lsn)?;
+        let mut block = Vec::new();
+        if writer.encode_frames(&frame, &mut block).is_some() {
+            frame = block;
+        }
+        let length = writer.length();
+        self.notify_checkpoint(CheckpointEvent::Sealing { lsn, sequence: sequence as u64 })?;
+        let (rotating, sealed) = (OperationSpan::enter(Operation::Rotate), self.segment_entries + 1);
+        self.writer = None;
+
+        let path = segment_path(&self.directory, self.sequence);
+        let temp_path = temp_segment_path(&self.directory, self.sequence);
+
+        // direct I/O 모드의 패딩은 빼고 실제 데이터만 옮긴다
+        let mut source = File::open(&path)?.take(length);
+        let mut header = [0u8; SEGMENT_HEADER_SIZE];
+        source.read_exact(&mut header)?;
+
+        let mut temp_file = File::create(&temp_path)?;
+        temp_file.write_all(&header)?;
+
+        let mut body = ChecksumWriter::new(temp_file);
+        std::io::copy(&mut source, &mut body)?;
+        body.write_all(&frame)?;
+
+        let footer = SegmentFooter {
+            sequence: sequence as u64,
+            entry_count: sealed,
+            body_length: body.written(),
+            body_checksum: body.checksum(),
+        };
+        let mut temp_file = body.into_inner();
+        temp_file.write_all(&footer.encode())?;
+        temp_file.sync_all()?;
+        drop(temp_file);
+
+        self.segment_index.record(self.segment_entries, length);
+        self.segment_index.save(&self.directory)?;
+
+        if self.recycled.len() < self.recycle_segments {
+            let recycled = recycled_segment_path(&self.directory, self.sequence);
+            std::fs::rename(&path, &recycled)?;
+            self.recycled.push(recycled);
+        }
+        std::fs::rename(&temp_path, &path)?;
+        self.sync_directory()?;
+        DoubleWriteBuffer::clear(&self.directory)?;
+        self.sealed_bytes += std::fs::metadata(&path)?.len();
+
+        self.segment_bytes = 0;
+        self.segment_entries = 0;
+        self.unsynced_entries = 0;
+        self.last_synced = Instant::now();
+        self.last_checkpoint = Instant::now();
+        self.last_lsn += 1;
+        self.advance_durable(self.last_lsn);
+
+        self.manifest.sealed_segments.push(self.sequence as u64);
+        self.manifest.last_checkpoint = Some(self.sequence as u64);
+        self.manifest.sealed_lsn = self.last_lsn;
+        self.sequence += 1;
+        self.manifest.sequence = self.sequence as u64;
+        self.manifest.save(&self.directory)?;
+        self.segment_index = SegmentIndex::new(self.sequence as u64);
+        self.metrics.rotations += 1;
+        #[cfg(feature = "metrics-prometheus")]
+        if let Some(prometheus) = &self.prometheus {
+            prometheus.record_rotation();
+        }
+        self.publish_segment_count();
+        rotating.finish(self.last_lsn, sealed);
+        self.notify_checkpoint(CheckpointEvent::Durable { lsn, sequence: sequence as u64 })?;
+        span.finish(self.last_lsn, sealed);
+
+        Ok(self.last_lsn)
+    }
+
+    fn notify_checkpoint(&mut self, event: CheckpointEvent) -> Result<(), Box<dyn Error>> {
+        match &mut self.checkpoint_hook {
+            Some(hook) => hook(event),
+            None => Ok(()),
+        }
+    }
+
+    /// payload 를 남긴 마지막 체크포인트의 LSN 과 payload. 어플리케이션은 그 스냅샷을 읽은 뒤
+    /// [`replay_after`](Self::replay_after) 로 체크포인트 뒤의 엔트리만 다시 적용하면 된다.
+    /// 세그먼트가 커져서 저절로 남긴 체크포인트처럼 payload 가 없는 체크포인트는 건너뛴다.
+    pub fn last_checkpoint_payload(&self) -> Result<Option<(Lsn, Vec<u8>)>, std::io::Error> {
+        let mut last = None;
+        for checkpoint in self.recover()?.filter_entry_types([EntryType::Checkpoint]) {
+            let (lsn, entry) = checkpoint?;
+            if let Some(payload) = entry.data {
+                last = Some((lsn, payload));
+            }
+        }
+
+        Ok(last)
+    }
+
+    /// 빌더에 설정했다면 리더들이 함께 쓰는 블록 캐시. 직접 연 [`WALReader`] 에 넘기거나 적중률을 볼 때 쓴다.
+    pub fn block_cache(&self) -> Option<&Arc<BlockCache>> {
+        self.block_cache.as_ref()
+    }
+
+    /// 모든 세그먼트의 엔트리를 처음부터 LSN 과 함께 다시 읽는다 (크래시 후 상태 재구성용)
+    pub fn recover(&self) -> Result<WALReader, std::io::Error> {
+        Ok(WALReader::open(&self.directory)?.with_recovery_mode(self.recovery_mode).with_mmap(self.mmap_reads).with_read_ahead(self.read_ahead)
+            .with_block_cache(self.block_cache.clone()).with_parallelism(self.recovery_threads))
+    }
+
+    /// 모든 세그먼트를 처음부터 읽으면서 `visitor` 에 넘긴다 ([`WALReader::visit`])
+    pub fn replay<V: WALVisitor>(&self, visitor: &mut V) -> Result<RecoveryReport, std::io::Error> {
+        self.recover()?.visit(visitor)
+    }
+
+    /// LSN 이 `lsn` 이상인 엔트리부터 읽는다. 이미 적용한 앞부분을 다시 훑지 않으려는 소비자용.
+    pub fn read_from(&self, lsn: Lsn) -> Result<WALReader, std::io::Error> {
+        self.recover()?.read_from(lsn)
+    }
+
+    /// LSN 이 `applied` 이하인 엔트리는 건너뛰고 재생한다 ([`WALReader::replay_after`])
+    pub fn replay_after<F: FnMut(Lsn, WALEntry)>(&self, applied: Lsn, apply: F) -> Result<RecoveryReport, std::io::Error> {
+        self.recover()?.replay_after(applied, apply)
+    }
+
+    /// 타임스탬프가 `start` 이상 `end` 미만인 엔트리만 읽는다 (디버깅, 시점 조회용)
+    pub fn read_between(&self, start: f64, end: f64) -> Result<WALReader, std::io::Error> {
+        Ok(self.recover()?.read_between(start, end))
+    }
+
+    /// 기록된 엔트리를 모두 돌려준 뒤 새로 기록되는 엔트리를 기다리는 iterator ([`WALTail`]).
+    /// 다른 프로세스에서 따라갈 때는 읽기 전용으로 열어서 쓴다.
+    pub fn tail(&self) -> Result<WALTail, std::io::Error> {
+        Ok(WALTail::open(&self.directory)?.with_mmap(self.mmap_reads).with_block_cache(self.block_cache.clone()))
+    }
+
+    /// 첫 번째 손상 지점에서 로그를 잘라내서 다시 열 수 있게 만든다 ([`repair::repair_directory`]).
+    /// 열 수 없는 디렉토리에 쓰는 것이므로 `WALManager` 없이 디렉토리 잠금만 잡고 고친다.
+    pub fn repair(directory: &std::path::Path, save_removed: bool) -> Result<RepairReport, WALError> {
+        let _lock = DirectoryLock::acquire(directory)?;
+
+        Ok(repair::repair_directory(directory, save_removed)?)
+    }
+
+    /// `target` 이후의 엔트리를 잘라내서 로그를 그 시점으로 되돌린다 ([`repair::recover_to`]).
+    /// 잘라낸 바이트는 `quarantine/` 에 남는다.
+    pub fn recover_to(directory: &std::path::Path, target: RecoveryTarget) -> Result<RepairReport, WALError> {
+        let _lock = DirectoryLock::acquire(directory)?;
+
+        Ok(repair::recover_to(directory, target)?)
+    }
+
+    /// 어플리케이션이 다른 곳(memtable 을 내려쓴 SSTable, 복제본 등)에 `lsn` 까지 내구성 있게 반영했다고 알린다.
+    /// 매니페스트에 남겨서 다시 열었을 때 [`applied_lsn`](Self::applied_lsn) 으로 돌려주고,
+    /// 마지막 엔트리까지 모두 반영된 봉인된 세그먼트는 앞에서부터 지운다 ([`WALBuilder::set_retain_applied_segments`] 로 끌 수 있다).
+    /// 반영 지점은 뒤로 물러나지 않는다. 지운 세그먼트의 순번을 반환한다.
+    pub fn set_applied_lsn(&mut self, lsn: Lsn) -> Result<Vec<u64>, std::io::Error> {
+        self.check_writable()?;
+
+        if lsn > self.last_lsn {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::InvalidInput,
+                format!("applied LSN {} is beyond the last LSN {}", lsn.0, self.last_lsn.0),
+            ));
+        }
+
+        if lsn <= self.manifest.applied_lsn {
+            return Ok(Vec::new());
+        }
+
+        self.manifest.applied_lsn = lsn;
+        if self.retain_applied_segments {
+            self.manifest.save(&self.directory)?;
+            return Ok(Vec::new());
+        }
+
+        let removed = self.segments_before(lsn + 1)?;
+        self.remove_segments(removed)
+    }
+
+    /// 어플리케이션이 반영했다고 알린 마지막 LSN. 다시 열었을 때 이 뒤부터 재생하면 된다 ([`replay_after`](Self::replay_after))
+    pub fn applied_lsn(&self) -> Lsn {
+        self.manifest.applied_lsn
+    }
+
+    /// LSN 이 `lsn` 미만인 엔트리만 담은 봉인된 세그먼트를 앞에서부터 지우고 그 순번을 반환한다.
+    /// `lsn` 이 걸친 세그먼트와 활성 세그먼트는 남긴다. 반영되지 않은 엔트리까지 지우려 하면
+    /// ([`applied_lsn`](Self::applied_lsn) 보다 뒤) `InvalidInput` 을 돌려주고 아무것도 지우지 않는다.
+    /// 빌더에 보관 디렉토리를 설정했다면 지우기 전에 그곳으로 옮긴다 ([`WALBuilder::set_archive_directory`]).
+    pub fn truncate_before(&mut self, lsn: Lsn) -> Result<Vec<u64>, std::io::Error> {
+        self.check_writable()?;
+
+        if lsn > self.manifest.applied_lsn + 1 {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::InvalidInput,
+                format!("cannot truncate before LSN {}: only up to LSN {} is applied", lsn.0, self.manifest.applied_lsn.0),
+            ));
+        }
+
+        let removed = self.segments_before(lsn)?;
+        if removed.is_empty() {
+            return Ok(removed);
+        }
+
+        self.remove_segments(removed)
+    }
+
+    /// 모든 엔트리의 LSN 이 `lsn` 미만인 봉인된 세그먼트들 (앞에서부터). footer 가 없는 예전 세그먼트에서 멈춘다.
+    fn segments_before(&self, lsn: Lsn) -> Result<Vec<u64>, std::io::Error> {
+        let mut last_lsn = self.manifest.first_lsn(&self.directory)?;
+        let mut segments = Vec::new();
+        for &sequence in &self.manifest.sealed_segments {
+            let Some(footer) = SegmentFooter::read(&segment_path(&self.directory, sequence as usize), sequence)? else {
+                break;
+            };
+
+            last_lsn += footer.entry_count;
+            if last_lsn >= lsn {
+                break;
+            }
+            segments.push(sequence);
+        }
+
+        Ok(segments)
+    }
+
+    /// 매니페스트에서 앞쪽 세그먼트들을 빼고 파일을 지운다. 보관 디렉토리가 있다면 먼저 그곳에 복사해서 fsync 해둔다.
+    fn remove_segments(&mut self, removed: Vec<u64>) -> Result<Vec<u64>, std::io::Error> {
+        if let Some(archive) = &self.archive_directory {
+            std::fs::create_dir_all(archive)?;
+            for &sequence in &removed {
+                let path = segment_path(&self.directory, sequence as usize);
+                std::fs::copy(&path, archive.join(path.file_name().unwrap()))?;
+                File::open(archive.join(path.file_name().unwrap()))?.sync_all()?;
+            }
+            sync_directory(archive)?;
+        }
+
+        self.manifest.sealed_segments.drain(..removed.len());
+        self.manifest.save(&self.directory)?;
+        self.publish_segment_count();
+        self.sealed_bytes = sealed_segment_bytes(&self.directory, &self.manifest.sealed_segments)?;
+
+        // 매니페스트가 먼저 바뀌었으므로 지우다가 죽어도 남은 파일은 다시 읽히지 않는다
+        for &sequence in &removed {
+            remove_if_exists(&segment_path(&self.directory, sequence as usize))?;
+            remove_if_exists(&index_path(&self.directory, sequence as usize))?;
+        }
+        if !removed.is_empty() {
+            self.sync_directory()?;
+        }
+
+        Ok(removed)
+    }
+
+    /// 빌더에 설정한 기간보다 오래된 툼스톤에 가려진 값을 봉인된 세그먼트에서 치운다 ([`compaction::compact_directory`])
+    pub fn compact(&mut self) -> Result<CompactionReport, std::io::Error> {
+        self.check_writable()?;
+
+        let report = compaction::compact_directory(&self.directory, &self.manifest, self.tombstone_grace_period)?;
+        self.sealed_bytes = sealed_segment_bytes(&self.directory, &self.manifest.sealed_segments)?;
+
+        Ok(report)
+    }
+
+    /// 모든 세그먼트의 헤더, 프레임 체크섬, footer 를 검사해서 찾아낸 문제들을 반환한다. 아무것도 고치지 않는다.
+    pub fn verify(&self) -> Result<Vec<IntegrityProblem>, std::io::Error> {
+        verify::verify_directory(&self.directory)
+    }
+
+    /// 세그먼트 하나만 `verify` 와 같은 방식으로 검사한다
+    pub fn verify_segment(&self, sequence: u64) -> Result<Vec<IntegrityProblem>, std::io::Error> {
+        let sealed = sequence != self.sequence as u64;
+        verify::verify_segment(&segment_path(&self.directory, sequence as usize), sequence, sealed)
+    }
+
+    /// 봉인된 세그먼트들을 footer 의 체크섬으로 여러 스레드에서 나눠 검사하고, 처음 찾은 손상을 오류로 반환한다.
+    /// 로그가 클 때 시작 전 검증용. footer 가 없는 예전 세그먼트는 건너뛴다.
+    pub fn verify_all(&self) -> Result<(), std::io::Error> {
+        verify_sealed_segments(&self.directory, &self.manifest.sealed_segments)
+    }
+
+    /// 지금 시각(초). 디스크에 남는 정밀도에 맞춰 마이크로초 단위로 자른다.
+    pub fn get_current_secs() -> f64 {
+        SystemTime::now()
+            .duration_since(SystemTime::UNIX_EPOCH)
+            .expect("Cannot getting since time")
+            .as_micros() as f64 / 1_000_000.0
+    }
+
+}
+
+/// footer 가 있는 봉인 세그먼트의 body 체크섬이 맞지 않으면 `InvalidData`
+fn verify_sealed_segment(path: &std::path::Path, sequence: u64) -> Result<(), std::io::Error> {
+    let Some(footer) = SegmentFooter::read(path, sequence)? else {
+        return Ok(());
+    };
+
+    if !footer.verify(path)? {
+        return Err(std::io::Error::new(
+            std::io::ErrorKind::InvalidData,
+            format!("sealed segment {} is corrupted", path.display()),
+        ));
+    }
+
+    Ok(())
+}
+
+/// 디스크가 가득 차서 쓰지 못했다면 [`WALError::DiskFull`], 아니면 그대로
+pub(crate) fn append_error(e: std::io::Error) -> Box<dyn Error> {
+    match e.kind() {
+        std::io::ErrorKind::StorageFull => WALError::DiskFull.into(),
+        _ => e.into(),
+    }
+}
+
+/// 봉인된 세그먼트 파일 크기의 합. 없는 파일은 0 으로 센다.
+fn sealed_segment_bytes(directory: &std::path::Path, sequences: &[u64]) -> Result<u64, std::io::Error> {
+    sequences.iter().try_fold(0, |total, &sequence| match std::fs::metadata(segment_path(directory, sequence as usize)) {
+        Ok(metadata) => Ok(total + metadata.len()),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(total),
+        Err(e) => Err(e),
+    })
+}
+
+fn remove_if_exists(path: &std::path::Path) -> Result<(), std::io::Error> {
+    match std::fs::remove_file(path) {
+        Err(e) if e.kind() != std::io::ErrorKind::NotFound => Err(e),
+        _ => Ok(()),
+    }
+}
+
+/// 봉인 도중 죽어서 남은 임시 파일은 원본 세그먼트가 그대로 있다면 버린다.
+/// 원본이 없다면 재활용 대기열로 옮겨진 직후에 죽은 것이므로, 이미 fsync 된 임시 파일을 살린다.
+/// 그 뒤 double-write 버퍼에 온전한 기록이 남아있다면 찢어진 페이지를 되살린다.
+pub(crate) fn finish_interrupted_writes(directory: &std::path::Path) -> Result<(), std::io::Error> {
+    for file in std::fs::read_dir(directory)?.filter_map(|entry| entry.ok()) {
+        let path = file.path();
+        if path.to_str().is_some_and(|path| path.ends_with(".log.tmp")) {
+            let original = path.with_extension("");
+
+            if original.exists() {
+                std::fs::remove_file(&path)?;
+            } else {
+                std::fs::rename(&path, &original)?;
+            }
+        }
+    }
+
+    DoubleWriteBuffer::recover(directory)?;
+
+    Ok(())
+}
+
+/// 살아있는 세그먼트를 모두 읽어서 엔트리에 남은 가장 큰 트랜잭션 id 를 찾는다 (매니페스트를 다시 만들었을 때)
+fn max_transaction_id(directory: &std::path::Path, manifest: &Manifest) -> Result<u64, std::io::Error> {
+    let mut max = 0;
+    for sequence in manifest.live_segments() {
+        let path = segment_path(directory, sequence as usize);
+        if !path.exists() {
+            continue;
+        }
+
+        let mut segment = SegmentReader::open(&path, sequence)?.with_recovery_mode(RecoveryMode::TruncateAtError);
+        while let Some(header) = segment.advance()? {
+            max = max.max(header.transaction_id);
+        }
+    }
+
+    Ok(max)
+}
+
+/// 코어 수만큼의 스레드가 남은 세그먼트를 하나씩 가져가며 검사한다.
+/// 여러 세그먼트가 깨졌다면 순번이 가장 앞선 세그먼트의 오류를 반환한다.
+fn verify_sealed_segments(directory: &std::path::Path, sequences: &[u64]) -> Result<(), std::io::Error> {
+    let threads = std::thread::available_parallelism().map_or(1, |threads| threads.get()).min(sequences.len());
+    if threads <= 1 {
+        return sequences.iter()
+            .try_for_each(|&sequence| verify_sealed_segment(&segment_path(directory, sequence as usize), sequence));
+    }
+
+    let next = AtomicUsize::new(0);
+    let failure = Mutex::new(None::<(usize, std::io::Error)>);
+
+    std::thread::scope(|scope| {
+        for _ in 0..threads {
+            scope.spawn(|| loop {
+                let index = next.fetch_add(1, Ordering::Relaxed);
+                let Some(&sequence) = sequences.get(index) else {
+                    break;
+                };
+
+                if let Err(error) = verify_sealed_segment(&segment_path(directory, sequence as usize), sequence) {
+                    let mut failure = failure.lock().unwrap();
+                    if failure.as_ref().is_none_or(|(failed, _)| index < *failed) {
+                        *failure = Some((index, error));
+                    }
+                    // 더 뒤의 세그먼트는 검사할 필요가 없다
+                    next.store(sequences.len(), Ordering::Relaxed);
+                }
+            });
+        }
+    });
+
+    match failure.into_inner().unwrap() {
+        Some((_, error)) => Err(error),
+        None => Ok(()),
+    }
+}
+
+impl Drop for WALManager {
+    /// 오류를 돌려줄 곳이 없으므로 fsync 에 실패해도 무시한다. 봉인은 하지 않는다 ([`WALManager::close`]).
+    fn drop(&mut self) {
+        if self.sync_on_drop && !self.read_only {
+            let _ = self.sync();
+        }
+    }
+}
+
+pub struct WALBuilder {
+    segment_max_bytes: usize,
+    checkpoint_policy: CheckpointPolicy,
+    flush_block_size: usize,
+    directory: PathBuf,
+    preallocate: bool,
+    direct_io: bool,
+    double_write: bool,
+    pad_blocks: bool,
+    io_uring: bool,
+    checksum: ChecksumAlgorithm,
+    recycle_segments: usize,
+    sync_policy: SyncPolicy,
+    sync_method: SyncMethod,
+    recovery_mode: RecoveryMode,
+    mmap_reads: bool,
+    read_ahead: bool,
+    recovery_threads: usize,
+    /// 풀어둔 블록을 기억할 메모리 예산
+    block_cache: Option<usize>,
+    tombstone_grace_period: Duration,
+    checkpoint_hook: Option<CheckpointHook>,
+    archive_directory: Option<PathBuf>,
+    retain_applied_segments: bool,
+    sync_on_drop: bool,
+    write_buffer: Option<WriteBufferOptions>,
+    rate_limit: Option<RateLimit>,
+    disk_quota: Option<DiskQuota>,
+    retry_policy: RetryPolicy,
+    stall_hook: Option<(Duration, StallHook)>,
+    block_framing: Option<BlockOptions>,
+    read_only: bool,
+    #[cfg(feature = "metrics-prometheus")]
+    prometheus_registry: Option<prometheus::Registry>,
+}
+
+impl Default for WALBuilder {
+    fn default() -> Self {
+        Self {
+            segment_max_bytes: 64 * 1024 * 1024,
+            checkpoint_policy: CheckpointPolicy::default(),
+            flush_block_size: DIRECT_IO_ALIGNMENT,
+            directory: PathBuf::from("."),
+            preallocate: false,
+            direct_io: false,
+            double_write: false,
+            pad_blocks: false,
+            io_uring: false,
+            checksum: ChecksumAlgorithm::default(),
+            recycle_segments: 0,
+            sync_policy: SyncPolicy::default(),
+            sync_method: SyncMethod::default(),
+            recovery_mode: RecoveryMode::default(),
+            mmap_reads: false,
+            read_ahead: false,
+            recovery_threads: 1,
+            block_cache: None,
+            tombstone_grace_period: DEFAULT_TOMBSTONE_GRACE_PERIOD,
+            checkpoint_hook: None,
+            archive_directory: None,
+            retain_applied_segments: false,
+            sync_on_drop: true,
+            write_buffer: None,
+            rate_limit: None,
+            disk_quota: None,
+            retry_policy: RetryPolicy::default(),
+            stall_hook: None,
+            block_framing: None,
+            read_only: false,
+            #[cfg(feature = "metrics-prometheus")]
+            prometheus_registry: None,
+        }
+    }
+}
+
+impl WALBuilder {
+    /// 다음 엔트리나 배치를 쓰고 체크포인트로 봉인한 세그먼트 파일이 이 크기를 넘게 된다면, 쓰기 전에 봉인하고 다음 세그먼트로 넘어간다.
+    /// 빈 세그먼트에도 들어가지 않는 엔트리나 배치는 그대로 써서 넘친다.
+    pub fn set_segment_max_bytes(mut self, segment_max_bytes: usize) -> Self {
+        self.segment_max_bytes = segment_max_bytes;
+        self
+    }
+
+    /// 세그먼트 크기와 별개로 저절로 체크포인트할 조건 ([`CheckpointPolicy`])
+    pub fn set_checkpoint_policy(mut self, checkpoint_policy: CheckpointPolicy) -> Self {
+        self.checkpoint_policy = checkpoint_policy;
+        self
+    }
+
+    /// direct I/O 정렬 쓰기와 double write 의 블록 단위
+    pub fn set_flush_block_size(mut self, flush_block_size: usize) -> Self {
+        self.flush_block_size = flush_block_size;
+        self
+    }
+
+    pub fn set_directory(mut self, directory: PathBuf) -> Self {
+        self.directory = directory;
+        self
+    }
+
+    /// 새 세그먼트를 만들 때 최대 크기만큼 디스크 공간을 미리 할당
+    pub fn set_preallocate(mut self, preallocate: bool) -> Self {
+        self.preallocate = preallocate;
+        self
+    }
+
+    /// O_DIRECT 로 페이지 캐시를 우회해서 정렬된 블록 단위로 쓴다
+    pub fn set_direct_io(mut self, direct_io: bool) -> Self {
+        self.direct_io = direct_io;
+        self
+    }
+
+    /// 세그먼트에 쓰기 전에 대상 페이지를 스크래치 파일에 먼저 기록하고 fsync 해서
+    /// 섹터 단위로 찢어지는 쓰기로부터 이미 기록된 데이터를 보호한다
+    pub fn set_double_write(mut self, double_write: bool) -> Self {
+        self.double_write = double_write;
+        self
+    }
+
+    /// fsync 전에 flush 블록 경계까지 패딩해서 동기화되는 단위가 항상 블록에 정렬되도록 한다
+    pub fn set_pad_blocks(mut self, pad_blocks: bool) -> Self {
+        self.pad_blocks = pad_blocks;
+        self
+    }
+
+    /// Linux 에서 세그먼트 쓰기와 fsync 를 io_uring 으로 제출하고, 쓰고 바로 fsync 하는 경우(`sync()`, `append_logs`,
+    /// 백그라운드 flusher)는 링크된 write+fsync 로 묶는다. direct I/O, double write, `SyncMethod::FileRange` 와는 함께 쓰지 않고,
+    /// 커널이 지원하지 않으면 평소처럼 쓴다.
+    #[cfg(feature = "io-uring")]
+    pub fn set_io_uring(mut self, io_uring: bool) -> Self {
+        self.io_uring = io_uring;
+        self
+    }
+
+    /// 연 매니저의 지표를 `registry` 에 등록한다 (`wal_appends_total`, `wal_bytes_written_total`, `wal_rotations_total`,
+    /// `wal_segment_count`, `wal_fsync_seconds`). 같은 이름이 이미 등록되어 있다면 `build` 가 `InvalidConfig` 를 돌려주므로,
+    /// 한 레지스트리에 매니저를 여럿 등록하려면 `Registry::new_custom` 의 prefix 나 라벨로 나눈다.
+    #[cfg(feature = "metrics-prometheus")]
+    pub fn set_prometheus_registry(mut self, registry: &prometheus::Registry) -> Self {
+        self.prometheus_registry = Some(registry.clone());
+        self
+    }
+
+    /// 새로 만드는 세그먼트의 프레임 체크섬 알고리즘. 기존 세그먼트는 헤더에 기록된 알고리즘을 계속 쓴다.
+    pub fn set_checksum(mut self, checksum: ChecksumAlgorithm) -> Self {
+        self.checksum = checksum;
+        self
+    }
+
+    /// 봉인된 세그먼트의 활성 파일을 최대 `count` 개까지 남겨두었다가 다음 세그먼트로 재사용
+    pub fn set_recycle_segments(mut self, count: usize) -> Self {
+        self.recycle_segments = count;
+        self
+    }
+
+    pub fn set_sync_policy(mut self, sync_policy: SyncPolicy) -> Self {
+        self.sync_policy = sync_policy;
+        self
+    }
+
+    pub fn set_sync_method(mut self, sync_method: SyncMethod) -> Self {
+        self.sync_method = sync_method;
+        self
+    }
+
+    /// 열 때 활성 세그먼트와 `recover()` 에서 깨진 프레임을 어떻게 처리할지
+    pub fn set_recovery_mode(mut self, recovery_mode: RecoveryMode) -> Self {
+        self.recovery_mode = recovery_mode;
+        self
+    }
+
+    /// `recover()` 와 `tail()` 에서 봉인된 세그먼트를 매핑해서 읽는다
+    pub fn set_mmap_reads(mut self, mmap_reads: bool) -> Self {
+        self.mmap_reads = mmap_reads;
+        self
+    }
+
+    /// `recover()` 에서 다음 봉인된 세그먼트를 미리 읽어둔다 ([`WALReader::with_read_ahead`])
+    pub fn set_read_ahead(mut self, read_ahead: bool) -> Self {
+        self.read_ahead = read_ahead;
+        self
+    }
+
+    /// `recover()` 에서 봉인된 세그먼트를 `threads` 개의 스레드로 나눠서 풀어둔다 ([`WALReader::with_parallelism`])
+    pub fn set_recovery_threads(mut self, threads: usize) -> Self {
+        self.recovery_threads = threads;
+        self
+    }
+
+    /// `recover()`, `read_from()`, `tail()` 로 연 리더들이 풀어둔 블록을 `budget` 바이트까지 함께 기억한다 ([`BlockCache`]).
+    /// 블록으로 묶은 세그먼트에만 쓰인다 ([`set_block_framing`](Self::set_block_framing)).
+    pub fn set_block_cache(mut self, budget: usize) -> Self {
+        self.block_cache = Some(budget);
+        self
+    }
+
+    /// `compact()` 가 툼스톤 앞의 값을 치우기 전에 툼스톤이 지나야 하는 기간
+    pub fn set_tombstone_grace_period(mut self, grace_period: Duration) -> Self {
+        self.tombstone_grace_period = grace_period;
+        self
+    }
+
+    /// 체크포인트를 기록하기 직전과 내구성이 보장된 뒤에 부를 콜백 ([`CheckpointHook`])
+    pub fn set_checkpoint_hook<F>(mut self, hook: F) -> Self
+    where
+        F: FnMut(CheckpointEvent) -> Result<(), Box<dyn Error>> + Send + 'static,
+    {
+        self.checkpoint_hook = Some(Box::new(hook));
+        self
+    }
+
+    /// `truncate_before` 와 `set_applied_lsn` 이 세그먼트를 지우기 전에 복사해둘 디렉토리 (백업, 시점 복구용)
+    pub fn set_archive_directory(mut self, directory: PathBuf) -> Self {
+        self.archive_directory = Some(directory);
+        self
+    }
+
+    /// `set_applied_lsn` 이 반영된 세그먼트를 바로 지우지 않고 `truncate_before` 를 부를 때까지 남겨둔다
+    /// (뒤처진 복제본이 따라잡을 때까지 로그를 남겨야 할 때)
+    pub fn set_retain_applied_segments(mut self, retain: bool) -> Self {
+        self.retain_applied_segments = retain;
+        self
+    }
+
+    /// 엔트리를 append 할 때마다 쓰지 않고 메모리에 모았다가 조건을 넘으면 한 번에 쓴다 ([`WriteBufferOptions`]).
+    /// 잃어도 되는 만큼만 모으는 것이므로 fsync 하지 않는 동기화 정책(`Never`, `Interval`)과 함께 쓴다.
+    pub fn set_write_buffer(mut self, options: WriteBufferOptions) -> Self {
+        self.write_buffer = Some(options);
+        self
+    }
+
+    /// append 속도를 제한한다 ([`RateLimit`]). 벌크 적재나 한 테넌트가 데이터 파일과 같이 쓰는 디스크를 다 차지하지 않도록 한다.
+    pub fn set_rate_limit(mut self, limit: RateLimit) -> Self {
+        self.rate_limit = Some(limit);
+        self
+    }
+
+    /// 세그먼트가 디스크를 이만큼보다 더 쓰지 않도록 한다 ([`DiskQuota`])
+    pub fn set_disk_quota(mut self, quota: DiskQuota) -> Self {
+        self.disk_quota = Some(quota);
+        self
+    }
+
+    /// 세그먼트에 쓰다가 디스크가 가득 차는 것 같은 일시적인 오류가 나면 다시 시도한다 ([`RetryPolicy`]).
+    /// 기본값은 다시 시도하지 않는다.
+    pub fn set_retry_policy(mut self, policy: RetryPolicy) -> Self {
+        self.retry_policy = policy;
+        self
+    }
+
+    /// append 가 `threshold` 보다 오래 멈췄을 때 이유와 시간을 받을 콜백 ([`StallHook`]).
+    /// 디스크가 가득 찬 것은 시간과 상관없이 알린다.
+    pub fn set_stall_hook<F>(mut self, threshold: Duration, hook: F) -> Self
+    where
+        F: FnMut(StallEvent) + Send + 'static,
+    {
+        self.stall_hook = Some((threshold, Box::new(hook)));
+        self
+    }
+
+    /// 엔트리 프레임을 블록으로 묶고 블록마다 압축해서 기록한다 ([`BlockOptions`]).
+    /// 새로 만드는 세그먼트에만 적용되고, 이미 있는 세그먼트는 헤더에 남은 방식을 따른다.
+    pub fn set_block_framing(mut self, options: BlockOptions) -> Self {
+        self.block_framing = Some(options);
+        self
+    }
+
+    /// `close()` 를 부르지 않고 drop 할 때 아직 fsync 하지 않은 엔트리를 fsync 한다 (기본값 `true`)
+    pub fn set_sync_on_drop(mut self, sync_on_drop: bool) -> Self {
+        self.sync_on_drop = sync_on_drop;
+        self
+    }
+
+    /// 잠금을 잡지 않고 파일도 고치지 않는 읽기 전용 핸들로 연다. 쓰는 쪽이 열어둔 디렉토리를
+    /// 모니터링이나 백업 도구가 들여다볼 때 쓴다. 쓰거나 체크포인트하면 `PermissionDenied` 를 돌려준다.
+    pub fn set_read_only(mut self, read_only: bool) -> Self {
+        self.read_only = read_only;
+        self
+    }
+
+    /// 매니페스트(없으면 파일 이름으로 재구성)를 기준으로 활성 세그먼트를 읽어들이고,
+    /// 활성 세그먼트의 엔트리 수와 그 위치 색인을 반환
+    ///
+    /// 읽기 전용이라면 봉인이나 잘라내기는 메모리 위의 매니페스트에만 반영하고 파일은 그대로 둔다.
+    /// 쓰는 쪽이 아직 기록 중인 꼬리는 깨진 프레임처럼 보이므로 읽기만 하고 넘어간다.
+    fn load_data(&self) -> Result<(Manifest, u64, usize, SegmentIndex), std::io::Error> {
+        if !self.read_only {
+            finish_interrupted_writes(&self.directory)?;
+        }
+
+        let (mut manifest, mut changed) = match Manifest::load(&self.directory)? {
+            Some(manifest) => (manifest, false),
+            None => (Manifest::rebuild(&self.directory)?, true),
+        };
+        let rebuilt = changed;
+
+        let mut sealed = Vec::new();
+        for sequence in manifest.live_segments() {
+            let path = segment_path(&self.directory, sequence as usize);
+            if !path.exists() {
+                continue;
+            }
+
+            let mut header = [0u8; SEGMENT_HEADER_SIZE];
+            File::open(&path)?.read_exact(&mut header)?;
+            SegmentHeader::decode(&header)?;
+
+            if sequence != manifest.sequence {
+                sealed.push(sequence);
+            }
+        }
+
+        if self.recovery_mode != RecoveryMode::SkipCorrupt {
+            verify_sealed_segments(&self.directory, &sealed)?;
+        }
+
+        let mut active_entries = 0;
+        let mut active_bytes = 0;
+        let mut active_index = None;
+        let active_path = segment_path(&self.directory, manifest.sequence as usize);
+
+        // 봉인 직후 매니페스트를 갱신하기 전에 죽은 경우. footer 가 온전하다면 다시 읽을 필요가 없다.
+        if let Some(footer) = active_path.exists().then(|| SegmentFooter::read(&active_path, manifest.sequence)).transpose()?.flatten() {
+            verify_sealed_segment(&active_path, manifest.sequence)?;
+
+            manifest.sealed_lsn += footer.entry_count;
+            manifest.sealed_segments.push(manifest.sequence);
+            manifest.last_checkpoint = Some(manifest.sequence);
+            manifest.sequence += 1;
+            if !self.read_only {
+                manifest.save(&self.directory)?;
+            }
+        } else if active_path.exists() {
+            let file_length = std::fs::metadata(&active_path)?.len();
+            let mut segment = SegmentReader::open(&active_path, manifest.sequence)?
+                .with_recovery_mode(self.recovery_mode);
+
+            // 엔트리를 모아두지 않고 개수와 마지막 종류만 세면서, 이어 쓸 때 필요한 색인을 다시 만든다
+            let mut index = SegmentIndex::new(manifest.sequence);
+            let mut last_entry_type = None;
+            let mut sealed_lsn = None;
+            // 끝 표시를 아직 만나지 못한 배치의 시작 위치.
+            // 블록으로 묶은 세그먼트라면 배치가 든 블록의 시작에서 자르므로 같은 블록의 앞선 엔트리도 함께 버린다.
+            let mut batch = None;
+            loop {
+                let at = segment.resume_point();
+                let Some(header) = segment.advance()? else {
+                    break;
+                };
+                index.record_block(at.position, segment.frames_read() - at.position, at.offset);
+                if header.lsn != Lsn::ZERO {
+                    sealed_lsn = Some(header.lsn.saturating_sub(segment.frames_read()));
+                }
+                match header.entry_type {
+                    EntryType::BatchBegin => batch = Some(at),
+                    EntryType::BatchEnd => batch = None,
+                    _ => {},
+                }
+                last_entry_type = Some(header.entry_type);
+            }
+
+            // 배치 중간에서 끝났다면 배치의 시작부터 버린다
+            let (valid_length, entries) = match batch {
+                Some(at) => {
+                    index.entries.retain(|entry| entry.position <= at.position);
+                    (at.offset, at.position)
+                },
+                None => (segment.offset(), segment.frames_read()),
+            };
+
+            // 앞선 세그먼트가 사라진 뒤 매니페스트를 다시 만들었다면 봉인된 엔트리 수를 다 셀 수 없으므로 엔트리에 남은 LSN 을 따른다
+            if let Some(sealed_lsn) = sealed_lsn.filter(|&lsn| lsn != manifest.sealed_lsn) {
+                manifest.sealed_lsn = sealed_lsn;
+                changed = true;
+            }
+
+            // 깨진 프레임 때문에 버리는 바이트는 지우기 전에 격리해둔다
+            if (segment.damaged() || batch.is_some()) && valid_length < file_length && !self.read_only {
+                let reason = match batch {
+                    Some(_) => "incomplete batch",
+                    None if segment.take_corruptions().last().is_some_and(|frame| frame.truncated) => "truncated frame",
+                    None => "checksum mismatch",
+                };
+                quarantine(&self.directory, manifest.sequence, valid_length, reason)?;
+            }
+
+            // 쓰는 도중 죽어서 끊긴 꼬리나 패딩은 잘라내고, 이후 append 가 그 뒤에 이어지도록 한다.
+            // 이전 프로세스가 fsync 하지 못한 엔트리도 여기서 동기화해서 남은 엔트리는 모두 durable 로 본다.
+            if !self.read_only {
+                let file = OpenOptions::new().write(true).open(&active_path)?;
+                if valid_length < file_length {
+                    file.set_len(valid_length)?;
+                }
+                file.sync_all()?;
+            }
+
+            // footer 가 없던 예전 형식으로 봉인된 경우
+            if let Some(EntryType::Checkpoint) = last_entry_type {
+                manifest.sealed_lsn += entries;
+                manifest.sealed_segments.push(manifest.sequence);
+                manifest.last_checkpoint = Some(manifest.sequence);
+                manifest.sequence += 1;
+                if !self.read_only {
+                    index.save(&self.directory)?;
+                    manifest.save(&self.directory)?;
+                }
+            } else {
+                active_entries = entries;
+                active_bytes = valid_length as usize - SEGMENT_HEADER_SIZE;
+                active_index = Some(index);
+            }
+        }
+
+        // 예약해둔 트랜잭션 id 범위를 잃었으므로 로그에 남은 가장 큰 id 뒤부터 나눠준다
+        if rebuilt {
+            manifest.transaction_id_limit = max_transaction_id(&self.directory, &manifest)? + 1;
+        }
+
+        if changed && !self.read_only {
+            manifest.save(&self.directory)?;
+        }
+
+        let active_index = active_index.unwrap_or_else(|| SegmentIndex::new(manifest.sequence));
+
+        Ok((manifest, active_entries, active_bytes, active_index))
+    }
+
+    fn load_recycled(&self) -> Result<Vec<PathBuf>, std::io::Error> {
+        let recycled = std::fs::read_dir(&self.directory)?
+            .filter_map(|entry| entry.ok())
+            .map(|entry| entry.path())
+            .filter(|path| path.extension() == Some(std::ffi::OsStr::new("recycle")))
+            .collect();
+
+        Ok(recycled)
+    }
+
+    fn validate(&self) -> Result<(), WALError> {
+        if self.segment_max_bytes == 0 {
+            return Err(WALError::InvalidConfig("segment_max_bytes must be greater than zero".into()));
+        }
+
+        if self.flush_block_size == 0 {
+            return Err(WALError::InvalidConfig("flush_block_size must be greater than zero".into()));
+        }
+
+        if self.direct_io && !self.flush_block_size.is_multiple_of(MIN_DIRECT_IO_BLOCK_SIZE) {
+            return Err(WALError::InvalidConfig(format!(
+                "flush_block_size must be a multiple of {} with direct I/O", MIN_DIRECT_IO_BLOCK_SIZE
+            )));
+        }
+
+        if self.disk_quota.is_some_and(|quota| quota.max_total_bytes <= self.segment_max_bytes as u64) {
+            return Err(WALError::InvalidConfig("max_total_bytes must be greater than segment_max_bytes".into()));
+        }
+
+        Ok(())
+    }
+
+    pub fn build(self) -> Result<WALManager, WALError> {
+        self.validate()?;
+        let lock = (!self.read_only).then(|| DirectoryLock::acquire(&self.directory)).transpose()?;
+        let span = OperationSpan::enter(Operation::Recovery);
+        let started = Instant::now();
+        let (manifest, active_entries, active_bytes, segment_index) = self.load_data()?;
+        let metrics = WALMetrics { recovery_time: started.elapsed(), ..Default::default() };
+        let recycled = self.load_recycled()?;
+        let sealed_bytes = sealed_segment_bytes(&self.directory, &manifest.sealed_segments)?;
+        let last_lsn = manifest.sealed_lsn + active_entries;
+        let manifest_transaction_id = manifest.transaction_id_limit;
+
+        let manager = WALManager {
+            sequence: manifest.sequence as usize,
+            manifest,
+            segment_max_bytes: self.segment_max_bytes,
+            checkpoint_policy: self.checkpoint_policy,
+            last_checkpoint: Instant::now(),
+            directory: self.directory,
+            segment_bytes: active_bytes,
+            seal_bytes: 0,
+            writer: None,
+            segment_entries: active_entries,
+            segment_index,
+            writer_options: WriterOptions {
+                block_size: self.flush_block_size,
+                segment_max_bytes: self.segment_max_bytes,
+                preallocate: self.preallocate,
+                direct_io: self.direct_io,
+                double_write: self.double_write,
+                pad_blocks: self.pad_blocks,
+                checksum: self.checksum,
+                sync_method: self.sync_method,
+                io_uring: self.io_uring,
+                blocks: self.block_framing,
+                retry: self.retry_policy,
+            },
+            recycle_segments: self.recycle_segments,
+            recycled,
+            sync_policy: self.sync_policy,
+            unsynced_entries: 0,
+            last_synced: Instant::now(),
+            recovery_mode: self.recovery_mode,
+            mmap_reads: self.mmap_reads,
+            read_ahead: self.read_ahead,
+            recovery_threads: self.recovery_threads,
+            block_cache: self.block_cache.map(|budget| Arc::new(BlockCache::new(budget))),
+            tombstone_grace_period: self.tombstone_grace_period,
+            checkpoint_hook: self.checkpoint_hook,
+            archive_directory: self.archive_directory,
+            retain_applied_segments: self.retain_applied_segments,
+            sync_on_drop: self.sync_on_drop,
+            last_lsn,
+            durable_lsn: last_lsn,
+            next_transaction_id: manifest_transaction_id.max(1),
+            watchers: Watchers::default(),
+            encode_buffer: bitcode::Buffer::new(),
+            record_buffer: bitcode::Buffer::new(),
+            frame_buffer: Vec::new(),
+            write_buffer: self.write_buffer,
+            rate_limiter: self.rate_limit.map(RateLimiter::new),
+            disk_quota: self.disk_quota,
+            sealed_bytes,
+            stall_monitor: self.stall_hook.map(|(threshold, hook)| StallMonitor::new(threshold, hook)),
+            metrics,
+            #[cfg(feature = "metrics-prometheus")]
+            prometheus: self.prometheus_registry.as_ref()
+                .map(PrometheusMetrics::register).transpose()
+                .map_err(|e| WALError::InvalidConfig(format!("cannot register prometheus metrics: {}", e)))?,
+            buffered_entries: 0,
+            buffer_started: None,
+            spare_buffer: Vec::new(),
+            flushing: false,
+            paused: false,
+            read_only: self.read_only,
+            _lock: lock,
+        };
+        manager.publish_segment_count();
+        span.finish(manager.last_lsn, active_entries);
+
+        Ok(manager)
+    }
+}
+
+#[cfg(test)]
+mod io_tests {
+    use super::{Metadata, WALEntry, WALManager, EntryType, TRANSACTION_ID_BLOCK};
+    use crate::wal::checksum::ChecksumAlgorithm;
+    use crate::wal::error::WALError;
+    use crate::wal::flags::EntryFlags;
+    use crate::wal::lsn::Lsn;
+    use crate::wal::manifest::Manifest;
+    use crate::wal::quota::{DiskQuota, QuotaPolicy};
+    use crate::wal::reader::{RecoveryMode, WALReader};
+    use crate::wal::frame::{FrameReader, FRAME_HEADER_SIZE};
+    use crate::wal::schema::EntryFormat;
+    use crate::wal::segment::{SegmentFooter, SegmentHeader, FORMAT_VERSION, LEGACY_FORMAT_VERSION, SEGMENT_FOOTER_SIZE, SEGMENT_HEADER_SIZE};
+    use crate::wal::sync::{Durability, SyncPolicy};
+    use crate::wal::test_utils::{insert_entry, temp_directory};
+    use crate::wal::writer::WriteBufferOptions;
+    use std::time::Duration;
+
+    #[test]
+    fn test_create() {
+        let builder = WALManager::builder()
+            .set_directory(temp_directory("create"))
+            .build();
+        assert!(builder.is_ok());
+
+        let builder = builder.unwrap();
+        assert_eq!(builder.sequence, 1);
+    }
+
+    #[test]
+    fn test_append_wal() {
+        let mut wal_manager = WALManager::builder()
+            .set_directory(temp_directory("append_wal"))
+            .build().expect("Cannot create WALManager");
+
+        let start = WALManager::get_current_secs();
+        for _ in 0..100 {
+            let entry = insert_entry(Vec::from([10u8;100]));
+
+            let result = wal_manager.append_log(entry);
+            assert!(result.is_ok());
+        }
+        let end = WALManager::get_current_secs();
+
+        println!("elapsed: {}s", end - start);
+    }
+
+    #[test]
+    fn test_sync_every_n_entries() {
+        let mut wal_manager = WALManager::builder()
+            .set_directory(temp_directory("sync_every_n"))
+            .set_sync_policy(SyncPolicy::EveryNEntries(3))
+            .build().expect("Cannot create WALManager");
+
+        for i in 1..=4 {
+            let entry = insert_entry(vec![i as u8]);
+
+            wal_manager.append_log(entry).expect("Cannot append entry");
+        }
+
+        assert_eq!(wal_manager.unsynced_entries, 1);
+    }
+
+    #[test]
+    fn test_append_only_frames() {
+        let directory = temp_directory("append_only");
+        let mut wal_manager = WALManager::builder()
+            .set_directory(directory.clone())
+            .build().expect("Cannot create WALManager");
+
+        let mut appended = Vec::new();
+        for i in 0..10 {
+            let entry = insert_entry(vec![i as u8; 16]);
+            wal_manager.append_log(entry.clone()).expect("Cannot append entry");
+            appended.push(entry);
+        }
+
+        let bytes = std::fs::read(directory.join("wal1.log")).unwrap();
+        let format = EntryFormat::of(&SegmentHeader::decode(&bytes[..SEGMENT_HEADER_SIZE]).unwrap());
+        let expected_size = (1..).zip(&appended)
+            .map(|(lsn, entry)| entry.encode_frame(1, ChecksumAlgorithm::Crc32, format, Lsn(lsn)).unwrap().len())
+            .sum::<usize>();
+        assert_eq!(bytes.len(), SEGMENT_HEADER_SIZE + expected_size);
+
+        let (entries, valid_length) = WALEntry::decode_frames(FrameReader::new(&bytes[SEGMENT_HEADER_SIZE..], 0, 1), RecoveryMode::TruncateAtError).unwrap();
+        assert_eq!(valid_length, expected_size as u64);
+        assert_eq!(entries.len(), 10);
+        assert_eq!(entries[9].data, Some(vec![9u8; 16]));
+    }
+
+    #[test]
+    fn test_sync_returns_durable_lsn() {
+        let directory = temp_directory("durable_lsn");
+        let mut wal_manager = WALManager::builder()
+            .set_directory(directory.clone())
+            .set_sync_policy(SyncPolicy::Never)
+            .build().expect("Cannot create WALManager");
+        assert_eq!(wal_manager.sync().unwrap(), Lsn(0));
+
+        for i in 0..3 {
+            let entry = insert_entry(vec![i as u8; 16]);
+            wal_manager.append_log(entry).expect("Cannot append entry");
+        }
+        assert_eq!(wal_manager.last_lsn(), Lsn(3));
+        assert_eq!(wal_manager.durable_lsn(), Lsn(0));
+        assert_eq!(wal_manager.sync().unwrap(), Lsn(3));
+
+        // 체크포인트 엔트리도 LSN 을 하나 차지하고, 봉인된 세그먼트 너머로 이어진다
+        wal_manager.checkpoint().unwrap();
+        assert_eq!(wal_manager.durable_lsn(), Lsn(4));
+        drop(wal_manager);
+
+        let wal_manager = WALManager::builder().set_directory(directory).build().unwrap();
+        assert_eq!(wal_manager.last_lsn(), Lsn(4));
+        assert_eq!(wal_manager.durable_lsn(), Lsn(4));
+    }
+
+    #[test]
+    fn test_durability_per_entry() {
+        let directory = temp_directory("durability_class");
+        let mut wal_manager = WALManager::builder()
+            .set_directory(directory)
+            .build().expect("Cannot create WALManager");
+
+        for _ in 0..3 {
+            let entry = insert_entry(vec![1; 16]);
+            wal_manager.append_log_with(entry, Durability::Lazy).expect("Cannot append entry");
+        }
+        assert_eq!(wal_manager.durable_lsn(), Lsn(0));
+
+        let commit = WALEntry {
+            entry_type: EntryType::TransactionCommit,
+            data: None,
+            timestamp: WALManager::get_current_secs(),
+            transaction_id: 1,
+            metadata: Metadata::new(),
+            flags: EntryFlags::NONE
+        };
+        wal_manager.append_log_with(commit, Durability::Durable).expect("Cannot append entry");
+        assert_eq!(wal_manager.durable_lsn(), Lsn(4));
+    }
+
+    #[test]
+    fn test_checksum_recorded_in_header() {
+        let directory = temp_directory("checksum_header");
+        let mut wal_manager = WALManager::builder()
+            .set_directory(directory.clone())
+            .set_checksum(ChecksumAlgorithm::Crc32c)
+            .build().expect("Cannot create WALManager");
+
+        let entry = insert_entry(vec![1; 16]);
+        wal_manager.append_log(entry.clone()).expect("Cannot append entry");
+        drop(wal_manager);
+
+        // 다른 알고리즘으로 다시 열어도 활성 세그먼트는 헤더의 알고리즘을 따른다
+        let mut wal_manager = WALManager::builder()
+            .set_directory(directory.clone())
+            .set_checksum(ChecksumAlgorithm::XxHash64)
+            .build().expect("Cannot create WALManager");
+        wal_manager.append_log(entry).expect("Cannot append entry");
+        drop(wal_manager);
+
+        let bytes = std::fs::read(directory.join("wal1.log")).unwrap();
+        assert_eq!(SegmentHeader::decode(&bytes).unwrap().checksum, ChecksumAlgorithm::Crc32c);
+
+        let reader = FrameReader::new(&bytes[SEGMENT_HEADER_SIZE..], 0, 1).with_checksum(ChecksumAlgorithm::Crc32c);
+        let (entries, _) = WALEntry::decode_frames(reader, RecoveryMode::TruncateAtError).unwrap();
+        assert_eq!(entries.len(), 2);
+    }
+
+    #[test]
+    fn test_pad_blocks_on_sync() {
+        let directory = temp_directory("pad_blocks");
+        let mut wal_manager = WALManager::builder()
+            .set_directory(directory.clone())
+            .set_flush_block_size(512)
+            .set_pad_blocks(true)
+            .build().expect("Cannot create WALManager");
+
+        for i in 0..3 {
+            let entry = insert_entry(vec![i as u8; 16]);
+            wal_manager.append_log(entry).expect("Cannot append entry");
+        }
+        drop(wal_manager);
+
+        let bytes = std::fs::read(directory.join("wal1.log")).unwrap();
+        assert_eq!(bytes.len(), 3 * 512);
+
+        let reader = FrameReader::new(&bytes[SEGMENT_HEADER_SIZE..], SEGMENT_HEADER_SIZE as u64, 1).with_block_size(512);
+        let (entries, valid_length) = WALEntry::decode_frames(reader, RecoveryMode::TruncateAtError).unwrap();
+        assert_eq!(entries.len(), 3);
+        assert_eq!(valid_length, bytes.len() as u64);
+
+        // 다시 열어도 패딩을 잘라내지 않는다
+        WALManager::builder().set_directory(directory.clone()).build().unwrap();
+        assert_eq!(std::fs::metadata(directory.join("wal1.log")).unwrap().len(), 3 * 512);
+    }
+
+    #[test]
+    fn test_decode_stops_at_checksum_mismatch() {
+        let mut bytes = Vec::new();
+        for i in 0..3 {
+            let entry = WALEntry { entry_type: EntryType::Set, ..insert_entry(vec![i as u8; 8]) };
+            bytes.extend(entry.encode_frame(1, ChecksumAlgorithm::Crc32, EntryFormat::new(FORMAT_VERSION), Lsn(i + 1)).unwrap());
+        }
+
+        let frame_size = bytes.len() / 3;
+        bytes[frame_size + FRAME_HEADER_SIZE] ^= 0xFF;
+
+        let (entries, valid_length) = WALEntry::decode_frames(FrameReader::new(&bytes[..], 0, 1), RecoveryMode::TruncateAtError).unwrap();
+        assert_eq!(entries.len(), 1);
+        assert_eq!(valid_length, frame_size as u64);
+    }
+
+    #[test]
+    fn test_reject_foreign_log_file() {
+        let directory = temp_directory("foreign_log");
+        std::fs::write(directory.join("wal1.log"), b"this is not a wal segment").unwrap();
+
+        let result = WALManager::builder()
+            .set_directory(directory)
+            .build();
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn test_segment_handle_rotates_on_checkpoint() {
+        let directory = temp_directory("handle_rotation");
+        let mut wal_manager = WALManager::builder()
+            .set_directory(directory.clone())
+            .build().expect("Cannot create WALManager");
+
+        let entry = insert_entry(vec![1, 2, 3]);
+        wal_manager.append_log(entry.clone()).unwrap();
+        assert!(wal_manager.writer.is_some());
+
+        wal_manager.checkpoint().unwrap();
+        assert!(wal_manager.writer.is_none());
+
+        wal_manager.append_log(entry).unwrap();
+        assert!(directory.join("wal1.log").exists());
+        assert!(directory.join("wal2.log").exists());
+    }
+
+    #[test]
+    fn test_truncate_torn_tail_on_open() {
+        let directory = temp_directory("torn_tail");
+        let mut wal_manager = WALManager::builder()
+            .set_directory(directory.clone())
+            .build().expect("Cannot create WALManager");
+
+        // 타임스탬프는 세그먼트 시각과의 차이로 남으므로 같은 시각을 써서 프레임 크기를 맞춘다
+        let timestamp = WALManager::get_current_secs();
+        for i in 0..3 {
+            let entry = WALEntry {
+                entry_type: EntryType::Insert,
+                data: Some(vec![i as u8; 32]),
+                timestamp,
+                transaction_id: 0,
+                metadata: Metadata::new(),
+                flags: EntryFlags::NONE
+            };
+            wal_manager.append_log(entry).unwrap();
+        }
+        drop(wal_manager);
+
+        let path = directory.join("wal1.log");
+        let full_length = std::fs::metadata(&path).unwrap().len();
+        let file = std::fs::OpenOptions::new().write(true).open(&path).unwrap();
+        file.set_len(full_length - 5).unwrap();
+        drop(file);
+
+        // Strict 는 끊긴 꼬리도 손상으로 보고 파일을 건드리지 않는다
+        let strict = WALManager::builder().set_directory(directory.clone()).set_recovery_mode(RecoveryMode::Strict);
+        assert!(strict.load_data().is_err());
+        assert_eq!(std::fs::metadata(&path).unwrap().len(), full_length - 5);
+
+        let builder = WALManager::builder().set_directory(directory.clone());
+        let (_, active_entries, _, _) = builder.load_data().expect("Cannot recover torn segment");
+        assert_eq!(active_entries, 2);
+
+        let frame_size = (full_length - SEGMENT_HEADER_SIZE as u64) / 3;
+        assert_eq!(std::fs::metadata(&path).unwrap().len(), SEGMENT_HEADER_SIZE as u64 + frame_size * 2);
+
+        // 잘라낸 꼬리는 격리된다
+        let offset = SEGMENT_HEADER_SIZE as u64 + frame_size * 2;
+        let quarantined = directory.join("quarantine").join(format!("wal1.{}.bin", offset));
+        assert_eq!(std::fs::metadata(&quarantined).unwrap().len(), frame_size - 5);
+    }
+
+    #[test]
+    fn test_verify_detects_corrupted_sealed_segment() {
+        let directory = temp_directory("verify_footer");
+        let mut wal_manager = WALManager::builder()
+            .set_directory(directory.clone())
+            .build().expect("Cannot create WALManager");
+
+        let entry = insert_entry(vec![7; 10]);
+        wal_manager.append_log(entry).unwrap();
+        wal_manager.checkpoint().unwrap();
+        assert!(wal_manager.verify().unwrap().is_empty());
+        drop(wal_manager);
+
+        let path = directory.join("wal1.log");
+        let mut bytes = std::fs::read(&path).unwrap();
+        bytes[SEGMENT_HEADER_SIZE + FRAME_HEADER_SIZE] ^= 0xFF;
+        std::fs::write(&path, &bytes).unwrap();
+
+        let error = WALManager::builder().set_directory(directory).build().err().expect("Corruption should be detected");
+        assert!(matches!(error, WALError::Io(e) if e.kind() == std::io::ErrorKind::InvalidData));
+    }
+
+    #[test]
+    fn test_verify_all_in_parallel() {
+        let directory = temp_directory("verify_all");
+        let mut wal_manager = WALManager::builder()
+            .set_directory(directory.clone())
+            .build().expect("Cannot create WALManager");
+
+        for i in 0..12 {
+            let entry = insert_entry(vec![i; 64]);
+            wal_manager.append_log(entry).unwrap();
+            wal_manager.checkpoint().unwrap();
+        }
+        wal_manager.verify_all().expect("Sealed segments should be intact");
+
+        for sequence in [9, 4] {
+            let path = directory.join(format!("wal{}.log", sequence));
+            let mut bytes = std::fs::read(&path).unwrap();
+            bytes[SEGMENT_HEADER_SIZE + FRAME_HEADER_SIZE] ^= 0xFF;
+            std::fs::write(&path, &bytes).unwrap();
+        }
+
+        let error = wal_manager.verify_all().expect_err("Corruption should be detected");
+        assert_eq!(error.kind(), std::io::ErrorKind::InvalidData);
+        assert!(error.to_string().contains("wal4.log"));
+    }
+
+    #[test]
+    fn test_checkpoint_seals_via_rename() {
+        let directory = temp_directory("seal_rename");
+        let mut wal_manager = WALManager::builder()
+            .set_directory(directory.clone())
+            .build().expect("Cannot create WALManager");
+
+        let entry = insert_entry(vec![7; 10]);
+        wal_manager.append_log(entry).unwrap();
+        wal_manager.checkpoint().unwrap();
+
+        assert!(!directory.join("wal1.log.tmp").exists());
+
+        let bytes = std::fs::read(directory.join("wal1.log")).unwrap();
+        let (entries, valid_length) = WALEntry::decode_frames(FrameReader::new(&bytes[SEGMENT_HEADER_SIZE..], 0, 1), RecoveryMode::TruncateAtError).unwrap();
+        assert_eq!(valid_length as usize, bytes.len() - SEGMENT_HEADER_SIZE - SEGMENT_FOOTER_SIZE);
+        assert_eq!(entries.len(), 2);
+        assert!(matches!(entries[1].entry_type, EntryType::Checkpoint));
+
+        let footer = SegmentFooter::read(&directory.join("wal1.log"), 1).unwrap().expect("sealed segment has a footer");
+        assert_eq!(footer.entry_count, 2);
+        assert_eq!(footer.body_length, valid_length);
+        drop(wal_manager);
+
+        std::fs::write(directory.join("wal1.log.tmp"), b"half sealed").unwrap();
+        let _ = WALManager::builder().set_directory(directory.clone()).build().unwrap();
+        assert!(!directory.join("wal1.log.tmp").exists());
+        assert_eq!(std::fs::read(directory.join("wal1.log")).unwrap(), bytes);
+    }
+
+    #[test]
+    fn test_next_transaction_id_survives_reopen() {
+        let directory = temp_directory("transaction_ids");
+        let open = || WALManager::builder()
+            .set_directory(directory.clone())
+            .build().expect("Cannot create WALManager");
+
+        let mut wal_manager = open();
+        assert_eq!(wal_manager.next_transaction_id().unwrap(), 1);
+        assert_eq!(wal_manager.next_transaction_id().unwrap(), 2);
+        drop(wal_manager);
+
+        // 예약해두고 쓰지 않은 id 는 건너뛴다
+        let mut wal_manager = open();
+        let transaction_id = wal_manager.next_transaction_id().unwrap();
+        assert_eq!(transaction_id, TRANSACTION_ID_BLOCK + 1);
+        wal_manager.abort_transaction(transaction_id).unwrap();
+        drop(wal_manager);
+
+        // 매니페스트를 잃으면 로그에 남은 id 뒤부터 나눠준다
+        std::fs::remove_file(Manifest::path(&directory)).unwrap();
+        let mut wal_manager = open();
+        assert_eq!(wal_manager.next_transaction_id().unwrap(), TRANSACTION_ID_BLOCK + 2);
+    }
+
+    #[test]
+    fn test_append_returns_lsn() {
+        let entry = |value: u8| insert_entry(vec![value; 16]);
+        let mut wal_manager = WALManager::builder()
+            .set_directory(temp_directory("append_lsn"))
+            .set_sync_policy(SyncPolicy::Never)
+            .build().expect("Cannot create WALManager");
+
+        assert_eq!(wal_manager.append_log(entry(1)).unwrap(), Lsn(1));
+        assert_eq!(wal_manager.append_log_with(entry(2), Durability::Lazy).unwrap(), Lsn(2));
+        assert_eq!(wal_manager.append_logs(vec![entry(3), entry(4)]).unwrap(), Lsn(4));
+        assert_eq!(wal_manager.append_logs(Vec::new()).unwrap(), Lsn(4));
+        assert_eq!(wal_manager.append_batch(vec![entry(5)]).unwrap(), Lsn(7));
+        wal_manager.checkpoint().unwrap();
+        assert_eq!(wal_manager.append_log(entry(6)).unwrap(), Lsn(9));
+
+        assert_eq!(wal_manager.durable_lsn(), Lsn(8));
+        assert_eq!(wal_manager.sync().unwrap(), Lsn(9));
+    }
+
+    #[test]
+    fn test_checkpoint_payload() {
+        let directory = temp_directory("checkpoint_payload");
+        let entry = |value: u8| insert_entry(vec![value; 16]);
+        let open = || WALManager::builder()
+            .set_directory(directory.clone())
+            .build().expect("Cannot create WALManager");
+
+        let mut wal_manager = open();
+        assert_eq!(wal_manager.last_checkpoint_payload().unwrap(), None);
+        wal_manager.append_log(entry(1)).unwrap();
+        assert_eq!(wal_manager.checkpoint_with_payload(b"snapshot-1".to_vec()).unwrap(), Lsn(2));
+        wal_manager.append_log(entry(2)).unwrap();
+        // payload 가 없는 체크포인트는 건너뛴다
+        wal_manager.checkpoint().unwrap();
+        wal_manager.append_log(entry(3)).unwrap();
+        drop(wal_manager);
+
+        let wal_manager = open();
+        let (lsn, snapshot) = wal_manager.last_checkpoint_payload().unwrap().unwrap();
+        assert_eq!((lsn, snapshot.as_slice()), (Lsn(2), b"snapshot-1".as_slice()));
+
+        let mut tail = Vec::new();
+        wal_manager.replay_after(lsn, |lsn, _| tail.push(lsn.0)).unwrap();
+        assert_eq!(tail, vec![3, 4, 5]);
+    }
+
+    #[test]
+    fn test_discard_incomplete_batch() {
+        let directory = temp_directory("incomplete_batch");
+        let entry = |value: u8| insert_entry(vec![value; 16]);
+        let open = || WALManager::builder()
+            .set_directory(directory.clone())
+            .build().expect("Cannot create WALManager");
+
+        let mut wal_manager = open();
+        wal_manager.append_log(entry(1)).unwrap();
+        wal_manager.append_batch(vec![entry(2), entry(3)]).unwrap();
+        assert_eq!(wal_manager.last_lsn(), Lsn(5));
+        wal_manager.append_batch(vec![entry(4), entry(5)]).unwrap();
+        drop(wal_manager);
+
+        // 끝 표시가 끊기면 앞선 엔트리까지 온전하더라도 배치 전체를 버린다
+        let path = directory.join("wal1.log");
+        let length = std::fs::metadata(&path).unwrap().len();
+        std::fs::OpenOptions::new().write(true).open(&path).unwrap().set_len(length - 3).unwrap();
+
+        let mut wal_manager = open();
+        assert_eq!(wal_manager.last_lsn(), Lsn(5));
+        assert!(directory.join("quarantine").join(format!("wal1.{}.bin", wal_manager.segment_writer().unwrap().length())).exists());
+
+        wal_manager.append_log(entry(6)).unwrap();
+        let replayed = wal_manager.recover().unwrap()
+            .map(|entry| entry.unwrap())
+            .map(|(lsn, entry)| (lsn.0, entry.entry_type, entry.data.map(|data| data[0])))
+            .collect::<Vec<_>>();
+        assert_eq!(replayed, vec![
+            (1, EntryType::Insert, Some(1)),
+            (2, EntryType::BatchBegin, None),
+            (3, EntryType::Insert, Some(2)),
+            (4, EntryType::Insert, Some(3)),
+            (5, EntryType::BatchEnd, None),
+            (6, EntryType::Insert, Some(6)),
+        ]);
+    }
+
+    #[test]
+    fn test_resume_appending_after_reopen() {
+        let directory = temp_directory("resume_appending");
+        let entry = |value: u8| insert_entry(vec![value; 16]);
+        // 봉인 시점은 엔트리를 인코딩해서 쓴 프레임 크기에 헤더와 봉인할 때 붙는 체크포인트, footer 까지 더해서 정한다
+        let (frame, seal_bytes) = {
+            let mut wal_manager = WALManager::builder().set_directory(temp_directory("resume_appending_frame")).build().unwrap();
+            wal_manager.append_log(entry(0)).unwrap();
+            (wal_manager.segment_bytes, wal_manager.seal_bytes)
+        };
+        let segment_max_bytes = SEGMENT_HEADER_SIZE + frame * 4 + frame / 2 + seal_bytes;
+        let open = || WALManager::builder()
+            .set_directory(directory.clone())
+            .set_segment_max_bytes(segment_max_bytes)
+            .build().expect("Cannot create WALManager");
+
+        let mut wal_manager = open();
+        for value in 1..=3 {
+            wal_manager.append_log(entry(value)).unwrap();
+        }
+        let written = wal_manager.segment_bytes;
+        assert_eq!(written as u64, std::fs::metadata(directory.join("wal1.log")).unwrap().len() - SEGMENT_HEADER_SIZE as u64);
+        drop(wal_manager);
+
+        // 이미 있던 엔트리 뒤에 이어 쓰고, 봉인 시점도 그 엔트리들까지 세서 정한다
+        let mut wal_manager = open();
+        assert_eq!(wal_manager.segment_bytes, written);
+        wal_manager.append_log(entry(4)).unwrap();
+        assert_eq!(wal_manager.sequence, 1);
+        wal_manager.append_log(entry(5)).unwrap();
+        assert_eq!(wal_manager.sequence, 2);
+        // 다섯 번째 엔트리를 쓰기 전에 교체했으므로 봉인한 세그먼트도 상한을 넘지 않는다
+        assert!(std::fs::metadata(directory.join("wal1.log")).unwrap().len() <= segment_max_bytes as u64);
+
+        let replayed = wal_manager.recover().unwrap()
+            .map(|entry| entry.unwrap().1.data.map(|data| data[0]))
+            .collect::<Vec<_>>();
+        assert_eq!(replayed, vec![Some(1), Some(2), Some(3), Some(4), None, Some(5)]);
+    }
+
+    #[test]
+    fn test_rotate_before_batch_overflows() {
+        let directory = temp_directory("rotate_before_batch");
+        let mut wal_manager = WALManager::builder()
+            .set_directory(directory.clone())
+            .set_segment_max_bytes(1024)
+            .build().expect("Cannot create WALManager");
+        let entry = |value: u8| insert_entry(vec![value; 100]);
+
+        for value in 0..10 {
+            wal_manager.append_logs((0..3).map(|i| entry(value * 3 + i)).collect()).unwrap();
+            wal_manager.append_many((0..2).map(|i| entry(value * 2 + i))).unwrap();
+        }
+
+        // 배치가 들어가지 않으면 쓰기 전에 교체하므로 봉인한 세그먼트는 상한을 넘지 않는다
+        assert!(wal_manager.manifest.sealed_segments.len() > 2);
+        for &sequence in &wal_manager.manifest.sealed_segments {
+            assert!(std::fs::metadata(super::segment_path(&directory, sequence as usize)).unwrap().len() <= 1024);
+        }
+        let entries = wal_manager.recover().unwrap().filter_entry_types([EntryType::Insert]).count();
+        assert_eq!(entries, 50);
+    }
+
+    #[test]
+    fn test_append_to_legacy_format_segment() {
+        let directory = temp_directory("legacy_format");
+        let entry = |value: u8| insert_entry(vec![value; 16]);
+
+        // 엔트리 전체를 bitcode 로 인코딩하던 예전 포맷의 활성 세그먼트
+        let header = SegmentHeader { version: LEGACY_FORMAT_VERSION, ..SegmentHeader::new(4096, ChecksumAlgorithm::Crc32, 0.0, 1) };
+        let mut bytes = header.encode().to_vec();
+        for value in [1, 2] {
+            let payload = entry(value).encode_payload(EntryFormat::new(LEGACY_FORMAT_VERSION), Lsn::ZERO).unwrap();
+            crate::wal::frame::encode_frame(&payload, 1, ChecksumAlgorithm::Crc32, &mut bytes);
+        }
+        std::fs::write(directory.join("wal1.log"), &bytes).unwrap();
+
+        let mut wal_manager = WALManager::builder()
+            .set_directory(directory.clone())
+            .build().expect("Cannot create WALManager");
+        assert_eq!(wal_manager.last_lsn(), Lsn(2));
+
+        // 이어 쓰는 엔트리도 세그먼트의 포맷을 따른다
+        wal_manager.append_log(entry(3)).unwrap();
+        wal_manager.checkpoint().unwrap();
+        wal_manager.append_log(entry(4)).unwrap();
+
+        let data = wal_manager.recover().unwrap()
+            .map(|entry| entry.unwrap().1.data.map(|data| data[0]))
+            .collect::<Vec<_>>();
+        assert_eq!(data, vec![Some(1), Some(2), Some(3), None, Some(4)]);
+
+        let legacy = std::fs::read(directory.join("wal1.log")).unwrap();
+        assert_eq!(SegmentHeader::decode(&legacy).unwrap().version, LEGACY_FORMAT_VERSION);
+        let current = std::fs::read(directory.join("wal2.log")).unwrap();
+        assert_eq!(SegmentHeader::decode(&current).unwrap().version, FORMAT_VERSION);
+    }
+
+    #[test]
+    fn test_record_entry_metadata() {
+        let directory = temp_directory("entry_metadata");
+        let entry = |value: u8| insert_entry(vec![value; 16]);
+
+        // 메타데이터를 남기기 전 포맷의 활성 세그먼트
+        let header = SegmentHeader { version: 2, ..SegmentHeader::new(4096, ChecksumAlgorithm::Crc32, 0.0, 1) };
+        let mut bytes = header.encode().to_vec();
+        let payload = entry(1).encode_payload(EntryFormat::new(2), Lsn(1)).unwrap();
+        crate::wal::frame::encode_frame(&payload, 1, ChecksumAlgorithm::Crc32, &mut bytes);
+        std::fs::write(directory.join("wal1.log"), &bytes).unwrap();
+
+        let mut wal_manager = WALManager::builder()
+            .set_directory(directory.clone())
+            .build().expect("Cannot create WALManager");
+        wal_manager.append_log(entry(2).with_metadata("tenant", b"a")).unwrap();
+        wal_manager.checkpoint().unwrap();
+        wal_manager.append_log(entry(3).with_metadata("tenant", b"b").with_metadata("request", b"42")).unwrap();
+        wal_manager.append_log(entry(4)).unwrap();
+
+        let tenants = |entry: &WALEntry| entry.metadata.get("tenant").cloned();
+        let entries = wal_manager.recover().unwrap().collect::<Result<Vec<_>, _>>().unwrap();
+        assert_eq!(entries.iter().map(|(_, entry)| tenants(entry)).collect::<Vec<_>>(), vec![
+            None,
+            // 이전 포맷의 세그먼트에 이어 쓴 엔트리에는 남지 않는다
+            None,
+            None,
+            Some(b"b".to_vec()),
+            None,
+        ]);
+        assert_eq!(entries[3].1.metadata.get("request"), Some(&b"42".to_vec()));
+        assert_eq!(entries[4].1.data, Some(vec![4; 16]));
+
+        let mut borrowed = Vec::new();
+        wal_manager.recover().unwrap().replay_ref(|_, entry| borrowed.push(entry.metadata.len())).unwrap();
+        assert_eq!(borrowed, vec![0, 0, 0, 2, 0]);
+    }
+
+    #[test]
+    fn test_reject_concurrent_writer() {
+        let directory = temp_directory("concurrent_writer");
+        let wal_manager = WALManager::builder()
+            .set_directory(directory.clone())
+            .build().expect("Cannot create WALManager");
+
+        let second = WALManager::builder().set_directory(directory.clone()).build();
+        assert!(matches!(second, Err(WALError::AlreadyLocked(_))));
+
+        drop(wal_manager);
+        assert!(WALManager::builder().set_directory(directory).build().is_ok());
+    }
+
+    #[test]
+    fn test_open_read_only_while_writing() {
+        let directory = temp_directory("read_only");
+        let mut wal_manager = WALManager::builder()
+            .set_directory(directory.clone())
+            .build().expect("Cannot create WALManager");
+
+        for transaction_id in 0..3 {
+            wal_manager.append_log(WALEntry { transaction_id, ..insert_entry(vec![transaction_id as u8; 16]) }).unwrap();
+        }
+        wal_manager.sync().unwrap();
+
+        // 쓰는 쪽이 아직 기록 중인 꼬리
+        let active = directory.join("wal1.log");
+        let mut file = std::fs::OpenOptions::new().append(true).open(&active).unwrap();
+        std::io::Write::write_all(&mut file, &[0xAB; 5]).unwrap();
+        let length = std::fs::metadata(&active).unwrap().len();
+
+        let mut reader = WALManager::builder()
+            .set_directory(directory.clone())
+            .set_read_only(true)
+            .build().expect("Cannot open read-only WALManager");
+        assert_eq!(reader.last_lsn(), Lsn(3));
+        assert_eq!(reader.recover().unwrap().count(), 3);
+
+        let error = reader.append_log(WALEntry {
+            entry_type: EntryType::Insert,
+            data: None,
+            timestamp: WALManager::get_current_secs(),
+            transaction_id: 0,
+            metadata: Metadata::new(),
+            flags: EntryFlags::NONE,
+        }).unwrap_err();
+        let error = error.downcast::<std::io::Error>().unwrap();
+        assert_eq!(error.kind(), std::io::ErrorKind::PermissionDenied);
+        assert!(reader.checkpoint().is_err());
+
+        // 꼬리를 잘라내거나 격리하지 않는다
+        assert_eq!(std::fs::metadata(&active).unwrap().len(), length);
+        assert!(!directory.join("quarantine").exists());
+    }
+
+    #[test]
+    fn test_reject_invalid_block_size() {
+        let directory = temp_directory("invalid_block_size");
+        let zero = WALManager::builder().set_directory(directory.clone()).set_flush_block_size(0).build();
+        assert!(matches!(zero, Err(WALError::InvalidConfig(_))));
+
+        let unaligned = WALManager::builder()
+            .set_directory(directory.clone())
+            .set_direct_io(true)
+            .set_flush_block_size(1000)
+            .build();
+        assert!(matches!(unaligned, Err(WALError::InvalidConfig(_))));
+
+        let wal_manager = WALManager::builder()
+            .set_directory(directory.clone())
+            .set_flush_block_size(512)
+            .build().expect("Cannot create WALManager");
+        assert_eq!(wal_manager.writer_options.block_size, 512);
+    }
+
+    #[test]
+    fn test_preallocated_segment_keeps_size() {
+        let directory = temp_directory("preallocate");
+        let mut wal_manager = WALManager::builder()
+            .set_directory(directory.clone())
+            .set_segment_max_bytes(1024 * 1024)
+            .set_preallocate(true)
+            .build().expect("Cannot create WALManager");
+
+        let entry = insert_entry(vec![1; 10]);
+        wal_manager.append_log(entry.clone()).unwrap();
+
+        let bytes = std::fs::read(directory.join("wal1.log")).unwrap();
+        let format = EntryFormat::of(&SegmentHeader::decode(&bytes[..SEGMENT_HEADER_SIZE]).unwrap());
+        let frame_size = entry.encode_frame(1, ChecksumAlgorithm::Crc32, format, Lsn(1)).unwrap().len();
+        let length = std::fs::metadata(directory.join("wal1.log")).unwrap().len();
+        assert_eq!(length, (SEGMENT_HEADER_SIZE + frame_size) as u64);
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn test_preallocate_resumed_segment() {
+        use std::io::Write;
+        use std::os::unix::fs::MetadataExt;
+
+        let directory = temp_directory("preallocate_resume");
+        let path = directory.join("wal1.log");
+        let builder = || WALManager::builder()
+            .set_directory(directory.clone())
+            .set_segment_max_bytes(1024 * 1024)
+            .set_preallocate(true);
+        let allocated = || std::fs::metadata(&path).unwrap().blocks() * 512;
+
+        let mut wal_manager = builder().build().expect("Cannot create WALManager");
+        wal_manager.append_log(insert_entry(vec![1; 10])).unwrap();
+        drop(wal_manager);
+        if allocated() < 1024 * 1024 {
+            // fallocate 를 지원하지 않는 파일시스템
+            return;
+        }
+
+        // 끊긴 꼬리를 잘라낸 뒤에 이어 쓰는 세그먼트도 다시 미리 할당한다
+        std::fs::OpenOptions::new().append(true).open(&path).unwrap().write_all(&[0xFF; 3]).unwrap();
+        let mut wal_manager = builder().build().expect("Cannot reopen WALManager");
+        wal_manager.append_log(insert_entry(vec![2; 10])).unwrap();
+        assert!(allocated() >= 1024 * 1024);
+    }
+
+    #[test]
+    fn test_direct_io_roundtrip() {
+        let directory = temp_directory("direct_io_roundtrip");
+        let mut wal_manager = WALManager::builder()
+            .set_directory(directory.clone())
+            .set_direct_io(true)
+            .build().expect("Cannot create WALManager");
+
+        for i in 0..20 {
+            let entry = insert_entry(vec![i as u8; 300]);
+            wal_manager.append_log(entry).unwrap();
+        }
+        drop(wal_manager);
+
+        let (lsn, entry) = WALReader::open(&directory).unwrap().last().unwrap().unwrap();
+        assert_eq!(lsn, Lsn(20));
+        assert_eq!(entry.data, Some(vec![19u8; 300]));
+    }
+
+    #[test]
+    fn test_append_logs_vectored() {
+        let directory = temp_directory("append_logs");
+        let mut wal_manager = WALManager::builder()
+            .set_directory(directory.clone())
+            .set_segment_max_bytes(1024 * 1024)
+            .build().expect("Cannot create WALManager");
+
+        let entries = (0..50).map(|i| WALEntry { entry_type: EntryType::Set, ..insert_entry(vec![i as u8; 64]) }).collect::<Vec<_>>();
+        wal_manager.append_logs(entries).unwrap();
+        assert_eq!(wal_manager.unsynced_entries, 0);
+
+        let bytes = std::fs::read(directory.join("wal1.log")).unwrap();
+        let (entries, _) = WALEntry::decode_frames(FrameReader::new(&bytes[SEGMENT_HEADER_SIZE..], 0, 1), RecoveryMode::TruncateAtError).unwrap();
+        assert_eq!(entries.len(), 50);
+        assert_eq!(entries[49].data, Some(vec![49u8; 64]));
+    }
+
+    #[test]
+    fn test_recycle_sealed_segment_file() {
+        let directory = temp_directory("recycle");
+        let mut wal_manager = WALManager::builder()
+            .set_directory(directory.clone())
+            .set_segment_max_bytes(1024 * 1024)
+            .set_recycle_segments(1)
+            .build().expect("Cannot create WALManager");
+
+        let entry = insert_entry(vec![3; 200]);
+        for _ in 0..5 {
+            wal_manager.append_log(entry.clone()).unwrap();
+        }
+        wal_manager.checkpoint().unwrap();
+        assert!(directory.join("wal1.recycle").exists());
+
+        let last_lsn = wal_manager.append_log(entry).unwrap();
+        assert!(!directory.join("wal1.recycle").exists());
+        drop(wal_manager);
+
+        // 재사용한 파일에는 이전 세그먼트의 프레임이 남지 않는다
+        let bytes = std::fs::read(directory.join("wal2.log")).unwrap();
+        let (entries, valid_length) = WALEntry::decode_frames(FrameReader::new(&bytes[SEGMENT_HEADER_SIZE..], 0, 2), RecoveryMode::Strict).unwrap();
+        assert_eq!(entries.len(), 1);
+        assert_eq!(valid_length as usize, bytes.len() - SEGMENT_HEADER_SIZE);
+
+        let wal_manager = WALManager::builder()
+            .set_directory(directory)
+            .set_recovery_mode(RecoveryMode::Strict)
+            .build().expect("Cannot reopen WALManager");
+        assert_eq!(wal_manager.last_lsn(), last_lsn);
+        assert!(wal_manager.verify().unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_sync_directory_after_recycling_under_never() {
+        let mut wal_manager = WALManager::builder()
+            .set_directory(temp_directory("recycle_sync_directory"))
+            .set_sync_policy(SyncPolicy::Never)
+            .set_recycle_segments(1)
+            .build().expect("Cannot create WALManager");
+        let entry = insert_entry(vec![3; 200]);
+
+        // `Never` 에서는 새로 만든 세그먼트 파일을 위해 디렉토리를 동기화하지 않는다
+        wal_manager.append_log(entry.clone()).unwrap();
+        assert_eq!(wal_manager.metrics().directory_syncs, 0);
+        wal_manager.checkpoint().unwrap();
+        let synced = wal_manager.metrics().directory_syncs;
+
+        // 재활용한 파일의 rename 은 정책과 상관없이 동기화한다
+        wal_manager.append_log(entry).unwrap();
+        assert_eq!(wal_manager.metrics().directory_syncs, synced + 1);
+    }
+
+    #[test]
+    fn test_manifest_tracks_sealed_segments() {
+        let directory = temp_directory("manifest_tracking");
+        let mut wal_manager = WALManager::builder()
+            .set_directory(directory.clone())
+            .build().expect("Cannot create WALManager");
+
+        for _ in 0..3 {
+            let entry = insert_entry(vec![5; 10]);
+            wal_manager.append_log(entry).unwrap();
+            wal_manager.checkpoint().unwrap();
+        }
+        drop(wal_manager);
+
+        let manifest = Manifest::load(&directory).unwrap().unwrap();
+        assert_eq!(manifest.sequence, 4);
+        assert_eq!(manifest.sealed_segments, vec![1, 2, 3]);
+        assert_eq!(manifest.last_checkpoint, Some(3));
+
+        let wal_manager = WALManager::builder().set_directory(directory).build().unwrap();
+        assert_eq!(wal_manager.sequence, 4);
+    }
+
+    #[test]
+    fn test_applied_lsn_removes_segments() {
+        let directory = temp_directory("applied_lsn");
+        let mut wal_manager = WALManager::builder()
+            .set_directory(directory.clone())
+            .build().expect("Cannot create WALManager");
+
+        for _ in 0..3 {
+            let entry = insert_entry(vec![5; 10]);
+            wal_manager.append_log(entry).unwrap();
+            wal_manager.checkpoint().unwrap();
+        }
+
+        // 두 번째 세그먼트(LSN 3..=4)는 아직 다 반영되지 않았다
+        assert_eq!(wal_manager.set_applied_lsn(Lsn(3)).unwrap(), vec![1]);
+        assert_eq!(wal_manager.set_applied_lsn(Lsn(2)).unwrap(), Vec::<u64>::new());
+        assert!(!directory.join("wal1.log").exists());
+        assert!(wal_manager.set_applied_lsn(Lsn(7)).is_err());
+        drop(wal_manager);
+
+        let mut wal_manager = WALManager::builder()
+            .set_directory(directory.clone())
+            .build().expect("Cannot reopen WALManager");
+        assert_eq!(wal_manager.applied_lsn(), Lsn(3));
+        let lsns = wal_manager.recover().unwrap()
+            .map(|entry| entry.map(|(lsn, _)| lsn.0))
+            .collect::<Result<Vec<_>, _>>().unwrap();
+        assert_eq!(lsns, vec![3, 4, 5, 6]);
+
+        assert_eq!(wal_manager.set_applied_lsn(Lsn(6)).unwrap(), vec![2, 3]);
+        assert!(wal_manager.verify().unwrap().is_empty());
+        assert_eq!(wal_manager.recover().unwrap().count(), 0);
+        assert_eq!(Manifest::load(&directory).unwrap().unwrap().sealed_segments, Vec::<u64>::new());
+    }
+
+    #[test]
+    fn test_truncate_before() {
+        let directory = temp_directory("truncate_before");
+        let archive = temp_directory("truncate_before_archive");
+        let mut wal_manager = WALManager::builder()
+            .set_directory(directory.clone())
+            .set_archive_directory(archive.clone())
+            .set_retain_applied_segments(true)
+            .build().expect("Cannot create WALManager");
+
+        for _ in 0..3 {
+            let entry = insert_entry(vec![5; 10]);
+            wal_manager.append_log(entry).unwrap();
+            wal_manager.checkpoint().unwrap();
+        }
+
+        // 반영되지 않은 엔트리는 지우지 않는다
+        let error = wal_manager.truncate_before(Lsn(3)).unwrap_err();
+        assert_eq!(error.kind(), std::io::ErrorKind::InvalidInput);
+        assert!(directory.join("wal1.log").exists());
+
+        assert_eq!(wal_manager.set_applied_lsn(Lsn(4)).unwrap(), Vec::<u64>::new());
+        assert!(directory.join("wal1.log").exists());
+
+        // 두 번째 세그먼트(LSN 3..=4)에는 LSN 3 이 있으므로 남긴다
+        assert_eq!(wal_manager.truncate_before(Lsn(4)).unwrap(), vec![1]);
+        assert!(wal_manager.truncate_before(Lsn(6)).is_err());
+        assert!(!directory.join("wal1.log").exists());
+        assert_eq!(wal_manager.recover().unwrap().count(), 4);
+        let archived = WALReader::open(&archive).unwrap()
+            .map(|entry| entry.map(|(_, entry)| entry.entry_type))
+            .collect::<Result<Vec<_>, _>>().unwrap();
+        assert_eq!(archived, vec![EntryType::Insert, EntryType::Checkpoint]);
+    }
+
+    #[test]
+    fn test_close() {
+        let directory = temp_directory("close");
+        let mut wal_manager = WALManager::builder()
+            .set_directory(directory.clone())
+            .set_sync_policy(SyncPolicy::Never)
+            .build().expect("Cannot create WALManager");
+
+        let entry = insert_entry(vec![5; 10]);
+        wal_manager.append_log(entry.clone()).unwrap();
+        wal_manager.append_log(entry).unwrap();
+        assert_eq!(wal_manager.close().unwrap(), Lsn(3));
+        assert_eq!(Manifest::load(&directory).unwrap().unwrap().sealed_segments, vec![1]);
+
+        // 잠금이 풀렸으므로 바로 다시 열 수 있고, 빈 활성 세그먼트는 봉인하지 않는다
+        let wal_manager = WALManager::builder()
+            .set_directory(directory.clone())
+            .build().expect("Cannot reopen WALManager");
+        assert_eq!(wal_manager.close().unwrap(), Lsn(3));
+        assert_eq!(Manifest::load(&directory).unwrap().unwrap().sealed_segments, vec![1]);
+    }
+
+    #[test]
+    fn test_sync_on_drop() {
+        for sync_on_drop in [true, false] {
+            let mut wal_manager = WALManager::builder()
+                .set_directory(temp_directory("sync_on_drop"))
+                .set_sync_policy(SyncPolicy::Never)
+                .set_sync_on_drop(sync_on_drop)
+                .build().expect("Cannot create WALManager");
+
+            let receiver = wal_manager.subscribe();
+            wal_manager.append_log(insert_entry(vec![5; 10])).unwrap();
+            drop(wal_manager);
+
+            assert_eq!(receiver.try_recv().ok(), sync_on_drop.then_some(Lsn(1)..=Lsn(1)));
+        }
+    }
+
+    #[test]
+    fn test_pause_rejects_writes() {
+        let mut wal_manager = WALManager::builder()
+            .set_directory(temp_directory("pause"))
+            .set_sync_policy(SyncPolicy::Never)
+            .build().expect("Cannot create WALManager");
+        let entry = insert_entry(vec![5; 10]);
+
+        wal_manager.append_log(entry.clone()).unwrap();
+        assert_eq!(wal_manager.pause().unwrap(), Lsn(1));
+        assert!(wal_manager.is_paused());
+
+        let error = wal_manager.append_log(entry.clone()).unwrap_err();
+        assert!(matches!(error.downcast_ref::<WALError>(), Some(WALError::Paused)));
+        assert!(wal_manager.checkpoint().unwrap_err().downcast_ref::<WALError>().is_some());
+        let error = wal_manager.next_transaction_id().unwrap_err();
+        assert!(matches!(error.get_ref().and_then(|e| e.downcast_ref::<WALError>()), Some(WALError::Paused)));
+
+        wal_manager.resume();
+        assert_eq!(wal_manager.append_log(entry).unwrap(), Lsn(2));
+    }
+
+    #[test]
+    fn test_reuse_frame_buffer() {
+        let mut wal_manager = WALManager::builder()
+            .set_directory(temp_directory("reuse_frame_buffer"))
+            .set_sync_policy(SyncPolicy::Never)
+            .build().expect("Cannot create WALManager");
+        let entry = |value| insert_entry(vec![value; 64]).with_metadata("node", &[value]);
+
+        wal_manager.append_log(entry(0)).unwrap();
+        let frame_buffer = wal_manager.frame_buffer.as_ptr();
+        for value in 1..10 {
+            wal_manager.append_log(entry(value)).unwrap();
+            assert_eq!(wal_manager.frame_buffer.as_ptr(), frame_buffer);
+        }
+
+        // 여러 엔트리를 한 번에 기록할 때도 엔트리마다 할당하지 않고 같은 버퍼에 이어서 인코딩한다
+        wal_manager.append_logs((10..20).map(entry).collect()).unwrap();
+        let frame_buffer = wal_manager.frame_buffer.as_ptr();
+        wal_manager.append_logs((20..25).map(entry).collect()).unwrap();
+        assert_eq!(wal_manager.frame_buffer.as_ptr(), frame_buffer);
+
+        let entries = wal_manager.recover().unwrap().collect::<Result<Vec<_>, _>>().unwrap();
+        assert_eq!(entries.len(), 25);
+        assert_eq!(entries[24].1.data, Some(vec![24; 64]));
+        assert_eq!(entries[9].1.data, Some(vec![9; 64]));
+        assert_eq!(entries[9].1.metadata["node"], vec![9]);
+    }
+
+    #[test]
+    fn test_keep_frame_buffer_after_rejected_batch() {
+        let mut wal_manager = WALManager::builder()
+            .set_directory(temp_directory("keep_frame_buffer"))
+            .set_sync_policy(SyncPolicy::Never)
+            .set_segment_max_bytes(1500)
+            .set_disk_quota(DiskQuota { max_total_bytes: 2000, policy: QuotaPolicy::Reject })
+            .build().expect("Cannot create WALManager");
+        let entry = |value| insert_entry(vec![value; 64]);
+
+        wal_manager.append_logs((0..5).map(entry).collect()).unwrap();
+
+        // 거절한 배치도 인코딩하느라 키운 프레임 버퍼를 비워서 돌려둔다
+        assert!(wal_manager.append_logs((0..50).map(entry).collect()).is_err());
+        assert!(wal_manager.append_many((0..50).map(entry)).is_err());
+        assert!(wal_manager.frame_buffer.is_empty());
+        assert!(wal_manager.frame_buffer.capacity() >= 50 * 64);
+
+        let frame_buffer = wal_manager.frame_buffer.as_ptr();
+        wal_manager.append_logs((5..10).map(entry).collect()).unwrap();
+        assert_eq!(wal_manager.frame_buffer.as_ptr(), frame_buffer);
+        assert_eq!(wal_manager.recover().unwrap().count(), 10);
+    }
+
+    #[test]
+    fn test_append_many() {
+        let directory = temp_directory("append_many");
+        let mut wal_manager = WALManager::builder()
+            .set_directory(directory.clone())
+            .set_sync_policy(SyncPolicy::Never)
+            .build().expect("Cannot create WALManager");
+        assert_eq!(wal_manager.append_many(std::iter::empty()).unwrap(), Lsn(0));
+
+        let entries = (0..100).map(|i| WALEntry { entry_type: EntryType::Set, ..insert_entry(vec![i as u8; 32]) });
+        assert_eq!(wal_manager.append_many(entries).unwrap(), Lsn(100));
+        assert_eq!(wal_manager.durable_lsn(), Lsn(100));
+
+        // 위치 색인으로 배치 중간부터 읽는다
+        wal_manager.checkpoint().unwrap();
+        let entries = wal_manager.read_from(Lsn(77)).unwrap().collect::<Result<Vec<_>, _>>().unwrap();
+        assert_eq!(entries.len(), 25);
+        assert_eq!(entries[0].0, Lsn(77));
+        assert_eq!(entries[0].1.data, Some(vec![76; 32]));
+    }
+
+    #[test]
+    fn test_write_buffer() {
+        let directory = temp_directory("write_buffer");
+        let mut wal_manager = WALManager::builder()
+            .set_directory(directory.clone())
+            .set_sync_policy(SyncPolicy::Never)
+            .set_write_buffer(WriteBufferOptions { max_entries: 3, max_delay: Duration::from_secs(60), ..Default::default() })
+            .build().expect("Cannot create WALManager");
+        let entry = |value| insert_entry(vec![value; 16]);
+        let segment_length = || std::fs::metadata(directory.join("wal1.log")).unwrap().len() as usize;
+
+        wal_manager.append_log(entry(1)).unwrap();
+        wal_manager.append_log(entry(2)).unwrap();
+        assert_eq!(segment_length(), SEGMENT_HEADER_SIZE);
+        assert_eq!(wal_manager.recover().unwrap().count(), 0);
+
+        // 세 번째 엔트리에서 모아둔 엔트리를 한 번에 쓴다
+        wal_manager.append_log(entry(3)).unwrap();
+        let flushed = segment_length();
+        assert!(flushed > SEGMENT_HEADER_SIZE);
+
+        wal_manager.append_log(entry(4)).unwrap();
+        assert_eq!(segment_length(), flushed);
+        assert_eq!(wal_manager.sync().unwrap(), Lsn(4));
+        assert!(segment_length() > flushed);
+
+        wal_manager.append_log(entry(5)).unwrap();
+        wal_manager.checkpoint().unwrap();
+        let entries = wal_manager.read_from(Lsn(5)).unwrap().collect::<Result<Vec<_>, _>>().unwrap();
+        assert_eq!(entries.len(), 2);
+        assert_eq!(entries[0].1.data, Some(vec![5; 16]));
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn test_disk_full_keeps_buffered_entries() {
+        let mut wal_manager = WALManager::builder()
+            .set_directory(temp_directory("disk_full"))
+            .set_sync_policy(SyncPolicy::Never)
+            .set_write_buffer(WriteBufferOptions { max_entries: 3, max_delay: Duration::from_secs(60), ..Default::default() })
+            .build().expect("Cannot create WALManager");
+        let entry = |value| insert_entry(vec![value; 16]);
+
+        wal_manager.append_log(entry(1)).unwrap();
+        wal_manager.append_log(entry(2)).unwrap();
+
+        // `/dev/full` 에 쓰면 ENOSPC 가 난다. 실패한 엔트리만 되돌리고 모아둔 두 엔트리는 남긴다.
+        let full = std::fs::OpenOptions::new().write(true).open("/dev/full").unwrap();
+        let file = wal_manager.segment_writer().unwrap().replace_file(full);
+        let error = wal_manager.append_log(entry(3)).unwrap_err();
+        assert!(matches!(error.downcast_ref::<WALError>(), Some(WALError::DiskFull)), "{}", error);
+        assert_eq!(wal_manager.last_lsn(), Lsn(2));
+        assert_eq!(wal_manager.metrics().entries_appended, 2);
+
+        // 자리가 나면 모아둔 엔트리부터 다시 쓴다
+        wal_manager.segment_writer().unwrap().replace_file(file);
+        assert_eq!(wal_manager.append_log(entry(3)).unwrap(), Lsn(3));
+        let entries = wal_manager.recover().unwrap().collect::<Result<Vec<_>, _>>().unwrap();
+        assert_eq!(entries.iter().map(|(_, entry)| entry.data.as_ref().unwrap()[0]).collect::<Vec<_>>(), vec![1, 2, 3]);
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn test_durable_append_reports_disk_full() {
+        let mut wal_manager = WALManager::builder()
+            .set_directory(temp_directory("disk_full_durable"))
+            .set_sync_policy(SyncPolicy::Never)
+            .set_write_buffer(WriteBufferOptions { max_delay: Duration::from_secs(60), ..Default::default() })
+            .build().expect("Cannot create WALManager");
+
+        // 엔트리마다 지정한 fsync 가 실패해도 `DiskFull` 로 알린다
+        let full = std::fs::OpenOptions::new().write(true).open("/dev/full").unwrap();
+        let file = wal_manager.segment_writer().unwrap().replace_file(full);
+        let error = wal_manager.append_log_with(insert_entry(vec![1; 16]), Durability::Durable).unwrap_err();
+        assert!(matches!(error.downcast_ref::<WALError>(), Some(WALError::DiskFull)), "{}", error);
+
+        wal_manager.segment_writer().unwrap().replace_file(file);
+        assert_eq!(wal_manager.sync().unwrap(), Lsn(1));
+    }
+}
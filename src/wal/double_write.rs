@@ -0,0 +1,134 @@
+use std::fs::{File, OpenOptions};
+use std::io::{self, Read};
+use std::path::{Path, PathBuf};
+
+use super::checksum::Crc32;
+use super::segment::segment_path;
+use super::writer::write_all_at;
+
+pub const DOUBLE_WRITE_FILE_NAME: &str = "DOUBLEWRITE";
+const DOUBLE_WRITE_MAGIC: [u8; 8] = *b"RRDBDWB\0";
+const RECORD_HEADER_SIZE: usize = 8 + 8 + 8 + 4 + 4;
+
+/// 페이지를 제자리에 덮어쓰기 전에 같은 내용을 먼저 기록해두는 스크래치 파일
+///
+/// `[magic 8][sequence u64][offset u64][length u32][crc32 u32][bytes]`
+///
+/// 제자리 쓰기가 찢어지더라도(torn page) 복구 시에 이 기록으로 페이지 전체를 다시 쓸 수 있다.
+/// 스크래치 기록이 찢어졌다면 체크섬이 맞지 않고, 그 경우 제자리 쓰기는 시작되지 않은 상태이므로 버린다.
+pub(crate) struct DoubleWriteBuffer {
+    file: File,
+}
+
+impl DoubleWriteBuffer {
+    pub fn path(directory: &Path) -> PathBuf {
+        directory.join(DOUBLE_WRITE_FILE_NAME)
+    }
+
+    pub fn open(directory: &Path) -> io::Result<Self> {
+        let file = OpenOptions::new().create(true).truncate(false).read(true).write(true).open(Self::path(directory))?;
+
+        Ok(Self { file })
+    }
+
+    /// `bytes` 를 `walN.log` 의 `offset` 위치에 쓸 예정임을 기록하고 fsync 한다
+    pub fn stage(&mut self, sequence: u64, offset: u64, bytes: &[u8]) -> io::Result<()> {
+        let mut record = Vec::with_capacity(RECORD_HEADER_SIZE + bytes.len());
+        record.extend_from_slice(&DOUBLE_WRITE_MAGIC);
+        record.extend_from_slice(&sequence.to_le_bytes());
+        record.extend_from_slice(&offset.to_le_bytes());
+        record.extend_from_slice(&(bytes.len() as u32).to_le_bytes());
+        record.extend_from_slice(&record_checksum(sequence, offset, bytes).to_le_bytes());
+        record.extend_from_slice(bytes);
+
+        write_all_at(&self.file, &record, 0)?;
+        self.file.set_len(record.len() as u64)?;
+        self.file.sync_data()
+    }
+
+    /// 세그먼트가 봉인되면 이전 기록은 더 이상 필요 없다
+    pub fn clear(directory: &Path) -> io::Result<()> {
+        match OpenOptions::new().write(true).open(Self::path(directory)) {
+            Ok(file) => {
+                file.set_len(0)?;
+                file.sync_data()
+            },
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(()),
+            Err(e) => Err(e),
+        }
+    }
+
+    /// 온전한 기록이 남아있다면 대상 세그먼트에 다시 써서 찢어진 페이지를 복원한다
+    pub fn recover(directory: &Path) -> io::Result<bool> {
+        let mut bytes = Vec::new();
+        match File::open(Self::path(directory)) {
+            Ok(mut file) => file.read_to_end(&mut bytes)?,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(false),
+            Err(e) => return Err(e),
+        };
+
+        if bytes.len() < RECORD_HEADER_SIZE || bytes[0..8] != DOUBLE_WRITE_MAGIC {
+            return Ok(false);
+        }
+
+        let sequence = u64::from_le_bytes(bytes[8..16].try_into().unwrap());
+        let offset = u64::from_le_bytes(bytes[16..24].try_into().unwrap());
+        let length = u32::from_le_bytes(bytes[24..28].try_into().unwrap()) as usize;
+        let checksum = u32::from_le_bytes(bytes[28..32].try_into().unwrap());
+
+        let Some(page) = bytes.get(RECORD_HEADER_SIZE..RECORD_HEADER_SIZE + length) else {
+            return Ok(false);
+        };
+        if record_checksum(sequence, offset, page) != checksum {
+            return Ok(false);
+        }
+
+        let path = segment_path(directory, sequence as usize);
+        if !path.exists() {
+            return Ok(false);
+        }
+
+        let file = OpenOptions::new().write(true).open(path)?;
+        write_all_at(&file, page, offset)?;
+        file.sync_all()?;
+
+        Ok(true)
+    }
+}
+
+fn record_checksum(sequence: u64, offset: u64, bytes: &[u8]) -> u32 {
+    let mut hasher = Crc32::new();
+    hasher.update(&sequence.to_le_bytes());
+    hasher.update(&offset.to_le_bytes());
+    hasher.update(bytes);
+    hasher.finish()
+}
+
+#[cfg(test)]
+mod double_write_tests {
+    use super::DoubleWriteBuffer;
+    use crate::wal::test_utils::temp_directory;
+
+    #[test]
+    fn test_recover_torn_page() {
+        let directory = temp_directory("double_write");
+        let segment = directory.join("wal1.log");
+        std::fs::write(&segment, vec![1u8; 4096]).unwrap();
+
+        let mut buffer = DoubleWriteBuffer::open(&directory).unwrap();
+        buffer.stage(1, 4096, &[2u8; 4096]).unwrap();
+
+        // 제자리 쓰기가 절반만 된 상황
+        let mut torn = vec![1u8; 4096];
+        torn.extend_from_slice(&[2u8; 1000]);
+        std::fs::write(&segment, &torn).unwrap();
+
+        assert!(DoubleWriteBuffer::recover(&directory).unwrap());
+        let bytes = std::fs::read(&segment).unwrap();
+        assert_eq!(bytes.len(), 8192);
+        assert!(bytes[4096..].iter().all(|&byte| byte == 2));
+
+        DoubleWriteBuffer::clear(&directory).unwrap();
+        assert!(!DoubleWriteBuffer::recover(&directory).unwrap());
+    }
+}
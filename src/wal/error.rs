@@ -0,0 +1,51 @@
+use std::fmt;
+use std::io;
+use std::path::PathBuf;
+
+#[derive(Debug)]
+pub enum WALError {
+    Io(io::Error),
+    /// 다른 `WALManager` 가 이미 디렉토리 잠금을 가지고 있음
+    AlreadyLocked(PathBuf),
+    /// 빌더 설정이 잘못됨
+    InvalidConfig(String),
+    /// `pause` 로 쓰기가 멈춰 있음
+    Paused,
+    /// 세그먼트가 디스크 용량 상한을 넘어서 append 를 거절함 ([`DiskQuota`](super::quota::DiskQuota))
+    QuotaExceeded { total_bytes: u64, max_total_bytes: u64 },
+    /// 디스크가 가득 차서 엔트리를 쓰지 못함. 실패한 엔트리는 기록되지 않았고, 앞서 버퍼에 모아둔 엔트리는 그대로 남아서
+    /// 자리가 나면 다음 append 나 `sync()` 가 다시 쓴다.
+    DiskFull,
+}
+
+impl fmt::Display for WALError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            WALError::Io(e) => write!(f, "{}", e),
+            WALError::AlreadyLocked(directory) => {
+                write!(f, "WAL directory {} is already locked by another writer", directory.display())
+            },
+            WALError::InvalidConfig(reason) => write!(f, "invalid WAL configuration: {}", reason),
+            WALError::Paused => write!(f, "WAL writes are paused"),
+            WALError::QuotaExceeded { total_bytes, max_total_bytes } => {
+                write!(f, "WAL segments use {} bytes, over the quota of {} bytes", total_bytes, max_total_bytes)
+            },
+            WALError::DiskFull => write!(f, "no space left on device for WAL segments"),
+        }
+    }
+}
+
+impl std::error::Error for WALError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            WALError::Io(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for WALError {
+    fn from(e: io::Error) -> Self {
+        WALError::Io(e)
+    }
+}
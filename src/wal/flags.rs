@@ -0,0 +1,97 @@
+// bitcode 0.4 derive 매크로가 생성하는 코드에서 발생하는 lint
+#![allow(unused_must_use, clippy::assign_op_pattern)]
+
+use bitcode::{Decode, Encode};
+use std::ops::{BitOr, BitOrAssign};
+
+/// 엔트리 헤더에 남기는 비트 플래그. 읽는 쪽이 data 를 추측하지 않고 해석할 수 있도록 기록하는 쪽이 표시해둔다.
+///
+/// 포맷 4 부터 기록하고 이전 포맷의 엔트리는 비어 있다. WAL 은 data 를 그대로 두므로 압축이나 암호화를 풀어주지 않는다.
+/// 이름이 붙지 않은 비트도 그대로 기록하고 돌려준다.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash, Encode, Decode)]
+pub struct EntryFlags(pub u32);
+
+impl EntryFlags {
+    pub const NONE: EntryFlags = EntryFlags(0);
+    pub const COMPRESSED: EntryFlags = EntryFlags(1 << 0);
+    pub const ENCRYPTED: EntryFlags = EntryFlags(1 << 1);
+    /// data 를 다른 곳에 두고 위치만 남겼다
+    pub const OVERSIZED: EntryFlags = EntryFlags(1 << 2);
+    /// 키를 지운 엔트리 ([`WALEntry::delete`](super::core::WALEntry::delete))
+    pub const TOMBSTONE: EntryFlags = EntryFlags(1 << 3);
+    /// 컴팩션이 data 를 비운 엔트리. 읽는 쪽은 건너뛴다 ([`compact_directory`](super::compaction::compact_directory))
+    pub const COMPACTED: EntryFlags = EntryFlags(1 << 4);
+
+    pub fn contains(self, flags: EntryFlags) -> bool {
+        self.0 & flags.0 == flags.0
+    }
+
+    pub fn is_empty(self) -> bool {
+        self.0 == 0
+    }
+}
+
+impl BitOr for EntryFlags {
+    type Output = EntryFlags;
+
+    fn bitor(self, flags: EntryFlags) -> EntryFlags {
+        EntryFlags(self.0 | flags.0)
+    }
+}
+
+impl BitOrAssign for EntryFlags {
+    fn bitor_assign(&mut self, flags: EntryFlags) {
+        self.0 |= flags.0;
+    }
+}
+
+#[cfg(test)]
+mod flags_tests {
+    use super::EntryFlags;
+    use crate::wal::checksum::ChecksumAlgorithm;
+    use crate::wal::core::{EntryType, Metadata, WALEntry, WALManager};
+    use crate::wal::lsn::Lsn;
+    use crate::wal::schema::EntryFormat;
+    use crate::wal::segment::SegmentHeader;
+    use crate::wal::test_utils::temp_directory;
+
+    #[test]
+    fn test_record_entry_flags() {
+        let directory = temp_directory("entry_flags");
+        let entry = |flags| WALEntry {
+            entry_type: EntryType::Insert,
+            data: Some(vec![1; 16]),
+            timestamp: WALManager::get_current_secs(),
+            transaction_id: 0,
+            metadata: Metadata::new(),
+            flags
+        };
+
+        // 플래그를 남기기 전 포맷의 활성 세그먼트
+        let header = SegmentHeader { version: 3, ..SegmentHeader::new(4096, ChecksumAlgorithm::Crc32, 0.0, 1) };
+        let mut bytes = header.encode().to_vec();
+        let payload = entry(EntryFlags::COMPRESSED).encode_payload(EntryFormat::new(3), Lsn(1)).unwrap();
+        crate::wal::frame::encode_frame(&payload, 1, ChecksumAlgorithm::Crc32, &mut bytes);
+        std::fs::write(directory.join("wal1.log"), &bytes).unwrap();
+
+        let mut wal_manager = WALManager::builder()
+            .set_directory(directory.clone())
+            .build().expect("Cannot create WALManager");
+        wal_manager.checkpoint().unwrap();
+        wal_manager.append_log(entry(EntryFlags::COMPRESSED | EntryFlags::ENCRYPTED)).unwrap();
+        wal_manager.append_log(entry(EntryFlags(1 << 31))).unwrap();
+        wal_manager.append_log(WALEntry::delete(b"key")).unwrap();
+
+        let mut flags = Vec::new();
+        wal_manager.recover().unwrap().replay_ref(|_, entry| flags.push(entry.flags)).unwrap();
+        assert_eq!(flags, vec![
+            EntryFlags::NONE,
+            EntryFlags::NONE,
+            EntryFlags::COMPRESSED | EntryFlags::ENCRYPTED,
+            EntryFlags(1 << 31),
+            EntryFlags::TOMBSTONE,
+        ]);
+        assert!(flags[2].contains(EntryFlags::ENCRYPTED));
+        assert!(!flags[2].contains(EntryFlags::COMPRESSED | EntryFlags::OVERSIZED));
+    }
+}
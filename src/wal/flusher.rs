@@ -0,0 +1,297 @@
+use std::error::Error;
+use std::sync::mpsc::Receiver;
+use std::sync::{Arc, Condvar, Mutex};
+use std::thread::JoinHandle;
+use std::time::{Duration, Instant};
+
+use super::core::{WALEntry, WALManager};
+use super::lsn::Lsn;
+use super::stall::StallReason;
+use super::trace::OperationSpan;
+use super::watch::DurableRange;
+use super::writer::SyncHandle;
+
+/// 백그라운드 스레드가 `interval` 마다 모아둔 엔트리를 쓰고 fsync 한다
+///
+/// `append_log` 는 쓰기(버퍼링한다면 메모리에 모으기)만 하고 반환하므로 append 하는 스레드는 fsync 를 기다리지 않는다.
+/// 내구성이 필요한 호출자는 `subscribe` 나 `durable_lsn` 으로 확인한다. fsync 는 락 밖에서 하므로 그동안에도 append 할 수 있다.
+/// 버퍼 두 개를 번갈아 쓰므로, 앞의 배치를 쓰고 fsync 하는 동안에도 다음 배치의 엔트리를 인코딩해서 모은다.
+/// 백그라운드에서 실패한 fsync 는 다음 `append_log` 나 `sync` 가 돌려준다.
+pub struct BackgroundFlusher {
+    shared: Arc<Shared>,
+    thread: Option<JoinHandle<()>>,
+}
+
+struct Shared {
+    manager: Mutex<WALManager>,
+    /// 배치를 다 쓰고 나면 알린다 (`manager` 락과 함께 쓴다)
+    flushed: Condvar,
+    stopped: Mutex<bool>,
+    wake: Condvar,
+    failure: Mutex<Option<std::io::Error>>,
+}
+
+impl BackgroundFlusher {
+    pub fn new(manager: WALManager, interval: Duration) -> Self {
+        let shared = Arc::new(Shared {
+            manager: Mutex::new(manager),
+            flushed: Condvar::new(),
+            stopped: Mutex::new(false),
+            wake: Condvar::new(),
+            failure: Mutex::new(None),
+        });
+
+        let thread = {
+            let shared = shared.clone();
+            std::thread::spawn(move || shared.run(interval))
+        };
+
+        Self { shared, thread: Some(thread) }
+    }
+
+    pub fn append_log(&self, entry: WALEntry) -> Result<Lsn, Box<dyn Error>> {
+        self.take_failure()?;
+        let mut manager = self.shared.manager.lock().unwrap();
+        // 체크포인트는 파일을 직접 읽고 쓰므로, 버퍼가 가득 찼다면 디스크가 따라잡도록 쓰는 중인 배치가 끝날 때까지 기다린다
+        let (full, started) = (manager.buffer_full(), Instant::now());
+        while manager.must_wait_for_flush() {
+            manager = self.shared.flushed.wait(manager).unwrap();
+        }
+        if full {
+            manager.report_stall(StallReason::BufferFull, started.elapsed());
+        }
+        let (lsn, _) = manager.append_unsynced(entry)?;
+
+        Ok(lsn)
+    }
+
+    /// 주기를 기다리지 않고 지금 쓰고 fsync 한다 ([`WALManager::sync`])
+    pub fn sync(&self) -> Result<Lsn, std::io::Error> {
+        self.take_failure()?;
+        let mut manager = self.shared.manager.lock().unwrap();
+        while manager.is_flushing() {
+            manager = self.shared.flushed.wait(manager).unwrap();
+        }
+
+        manager.sync()
+    }
+
+    pub fn durable_lsn(&self) -> Lsn {
+        self.shared.manager.lock().unwrap().durable_lsn()
+    }
+
+    /// [`WALManager::subscribe`]
+    pub fn subscribe(&self) -> Receiver<DurableRange> {
+        self.shared.manager.lock().unwrap().subscribe()
+    }
+
+    /// 스레드를 멈추고 남은 엔트리를 fsync 한 뒤 [`WALManager`] 를 돌려준다
+    pub fn into_inner(mut self) -> Result<WALManager, std::io::Error> {
+        self.stop();
+        self.take_failure()?;
+
+        // 스레드가 끝났으므로 남은 참조는 이것 하나다
+        let shared = self.shared.clone();
+        drop(self);
+        let Ok(shared) = Arc::try_unwrap(shared) else {
+            unreachable!("flusher thread has exited");
+        };
+        let mut manager = shared.manager.into_inner().unwrap();
+        manager.sync()?;
+
+        Ok(manager)
+    }
+
+    fn take_failure(&self) -> Result<(), std::io::Error> {
+        match self.shared.failure.lock().unwrap().take() {
+            Some(e) => Err(e),
+            None => Ok(()),
+        }
+    }
+
+    fn stop(&mut self) {
+        if let Some(thread) = self.thread.take() {
+            *self.shared.stopped.lock().unwrap() = true;
+            self.shared.wake.notify_all();
+            let _ = thread.join();
+        }
+    }
+}
+
+impl Drop for BackgroundFlusher {
+    /// 스레드만 멈추고, 남은 엔트리는 `WALManager` 의 drop 에 맡긴다
+    fn drop(&mut self) {
+        self.stop();
+    }
+}
+
+impl Shared {
+    fn run(&self, interval: Duration) {
+        let mut stopped = self.stopped.lock().unwrap();
+        while !*stopped {
+            stopped = self.wake.wait_timeout(stopped, interval).unwrap().0;
+            if *stopped {
+                break;
+            }
+
+            drop(stopped);
+            if let Err(e) = self.flush() {
+                *self.failure.lock().unwrap() = Some(e);
+            }
+            stopped = self.stopped.lock().unwrap();
+        }
+    }
+
+    fn flush(&self) -> Result<(), std::io::Error> {
+        let batch = {
+            let mut manager = self.manager.lock().unwrap();
+            if manager.durable_lsn() >= manager.last_lsn() {
+                return Ok(());
+            }
+
+            manager.begin_flush()?
+        };
+
+        let result = batch.write();
+        let finished = self.manager.lock().unwrap().finish_flush(batch, result);
+        self.flushed.notify_all();
+
+        finished
+    }
+}
+
+/// [`WALManager::begin_flush`] 가 꺼내준, 락 밖에서 쓰고 fsync 할 프레임들
+pub(crate) struct FlushBatch {
+    pub(crate) handle: SyncHandle,
+    /// 세그먼트에 잡아둔 자리의 시작 위치
+    pub(crate) offset: u64,
+    pub(crate) frames: Vec<u8>,
+    /// 블록으로 묶어서 쓴다면 묶기 전의 프레임. 쓰지 못하면 이것을 버퍼에 되돌린다.
+    pub(crate) unencoded: Option<Vec<u8>>,
+    /// 배치의 첫 엔트리가 세그먼트에서 몇 번째인지와 배치에 담긴 엔트리 수
+    pub(crate) first: u64,
+    pub(crate) entries: usize,
+    /// 이 배치까지 쓰면 내구성이 보장되는 LSN
+    pub(crate) target: Lsn,
+    /// 꺼낸 시각. 쓰고 fsync 하는 데 걸린 시간을 잰다.
+    pub(crate) started: Instant,
+    /// `finish_flush` 에서 닫는 flush span. 꺼낸 스레드에서 쓰고 돌려줘야 한다.
+    pub(crate) span: OperationSpan,
+}
+
+impl FlushBatch {
+    pub(crate) fn write(&self) -> Result<(), std::io::Error> {
+        self.handle.write_and_sync_at(&self.frames, self.offset)
+    }
+}
+
+#[cfg(test)]
+mod flusher_tests {
+    use std::time::Duration;
+
+    use super::BackgroundFlusher;
+    use crate::wal::block::BlockOptions;
+    use crate::wal::core::WALManager;
+    use crate::wal::lsn::Lsn;
+    use crate::wal::sync::SyncPolicy;
+    use crate::wal::test_utils::{insert_entry, temp_directory};
+    use crate::wal::writer::WriteBufferOptions;
+
+    #[test]
+    fn test_background_flush() {
+        let wal_manager = WALManager::builder()
+            .set_directory(temp_directory("flusher"))
+            .set_sync_policy(SyncPolicy::Never)
+            .set_write_buffer(WriteBufferOptions { max_delay: Duration::from_secs(60), ..Default::default() })
+            .build().expect("Cannot create WALManager");
+        let flusher = BackgroundFlusher::new(wal_manager, Duration::from_millis(5));
+        let receiver = flusher.subscribe();
+
+        for value in 0..3 {
+            flusher.append_log(insert_entry(vec![value; 16])).unwrap();
+        }
+
+        let mut durable = Lsn(0);
+        while durable < Lsn(3) {
+            durable = *receiver.recv_timeout(Duration::from_secs(5)).expect("Background flush timed out").end();
+        }
+        assert_eq!(flusher.durable_lsn(), Lsn(3));
+
+        let wal_manager = flusher.into_inner().unwrap();
+        assert_eq!(wal_manager.recover().unwrap().count(), 3);
+    }
+
+    #[test]
+    fn test_encode_while_flushing() {
+        let mut wal_manager = WALManager::builder()
+            .set_directory(temp_directory("flusher_double_buffer"))
+            .set_sync_policy(SyncPolicy::Never)
+            .set_write_buffer(WriteBufferOptions { max_delay: Duration::from_secs(60), ..Default::default() })
+            .build().expect("Cannot create WALManager");
+        let entry = |value| insert_entry(vec![value; 16]);
+
+        wal_manager.append_log(entry(1)).unwrap();
+        wal_manager.append_log(entry(2)).unwrap();
+        let batch = wal_manager.begin_flush().unwrap();
+
+        // 앞의 배치를 쓰기 전에 다음 엔트리가 다른 버퍼에 인코딩된다
+        wal_manager.append_log(entry(3)).unwrap();
+        let error = wal_manager.checkpoint().unwrap_err();
+        assert_eq!(error.downcast_ref::<std::io::Error>().unwrap().kind(), std::io::ErrorKind::WouldBlock);
+
+        let result = batch.write();
+        wal_manager.finish_flush(batch, result).unwrap();
+        assert_eq!(wal_manager.durable_lsn(), Lsn(2));
+        assert_eq!(wal_manager.sync().unwrap(), Lsn(3));
+
+        wal_manager.checkpoint().unwrap();
+        let entries = wal_manager.read_from(Lsn(2)).unwrap().collect::<Result<Vec<_>, _>>().unwrap();
+        let data = entries.iter().map(|(_, entry)| entry.data.clone()).collect::<Vec<_>>();
+        assert_eq!(data, vec![Some(vec![2; 16]), Some(vec![3; 16]), None]);
+    }
+    #[test]
+    fn test_keep_batch_after_failed_flush() {
+        let cases = [
+            ("flusher_failed", None, true),
+            ("flusher_failed_after", None, false),
+            ("flusher_failed_blocks", Some(BlockOptions::default()), true),
+        ];
+        for (name, block_framing, append_while_flushing) in cases {
+            let directory = temp_directory(name);
+            let open = || {
+                let builder = WALManager::builder()
+                    .set_directory(directory.clone())
+                    .set_sync_policy(SyncPolicy::Never)
+                    .set_write_buffer(WriteBufferOptions { max_delay: Duration::from_secs(60), ..Default::default() });
+                match block_framing {
+                    Some(options) => builder.set_block_framing(options),
+                    None => builder,
+                }.build().expect("Cannot create WALManager")
+            };
+            let mut wal_manager = open();
+            let entry = |value| insert_entry(vec![value; 16]);
+
+            wal_manager.append_log(entry(1)).unwrap();
+            wal_manager.append_log(entry(2)).unwrap();
+            let batch = wal_manager.begin_flush().unwrap();
+            if append_while_flushing {
+                wal_manager.append_log(entry(3)).unwrap();
+            }
+
+            // 쓰지 못한 배치는 버퍼에 되돌아가고 durable_lsn 도 넘어가지 않는다
+            assert!(wal_manager.finish_flush(batch, Err(std::io::Error::from(std::io::ErrorKind::StorageFull))).is_err());
+            assert_eq!(wal_manager.durable_lsn(), Lsn(0));
+            if !append_while_flushing {
+                wal_manager.append_log(entry(3)).unwrap();
+            }
+            assert_eq!(wal_manager.sync().unwrap(), Lsn(3));
+            assert_eq!(wal_manager.recover().unwrap().count(), 3);
+            drop(wal_manager);
+
+            let wal_manager = open();
+            assert_eq!(wal_manager.last_lsn(), Lsn(3));
+            let data = wal_manager.recover().unwrap().map(|entry| entry.unwrap().1.data).collect::<Vec<_>>();
+            assert_eq!(data, vec![Some(vec![1; 16]), Some(vec![2; 16]), Some(vec![3; 16])]);
+        }
+    }
+}
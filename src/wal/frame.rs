@@ -0,0 +1,427 @@
+use std::fmt;
+use std::io::{self, Read};
+
+use super::checksum::ChecksumAlgorithm;
+use super::varint::{encode_varint, read_varint, varint_length, MAX_VARINT_LENGTH};
+
+/// `[u32 LE 길이][u32 LE CRC32]`
+///
+/// 체크섬은 `salt` (세그먼트 순번) 와 페이로드를 이어서 계산한다 ([`ChecksumAlgorithm::checksum`]).
+/// 재활용된 세그먼트 파일에 남아있는 이전 프레임은 salt 가 달라 체크섬 검증에서 걸러진다.
+/// [`FrameLayout::Varint`] 의 헤더는 이보다 짧지만, 패딩이 들어갈 자리는 레이아웃과 상관없이 이만큼 남긴다.
+pub const FRAME_HEADER_SIZE: usize = size_of::<u32>() * 2;
+
+/// varint 로 남긴 u32 길이가 차지할 수 있는 가장 긴 길이
+const MAX_LENGTH_SIZE: usize = 5;
+
+/// 프레임 헤더의 모양. 세그먼트 포맷을 따른다 ([`EntryFormat::frame_layout`](super::schema::EntryFormat::frame_layout)).
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum FrameLayout {
+    /// `[u32 LE 길이][u32 LE 체크섬]`
+    #[default]
+    Fixed,
+    /// `[varint 길이][u32 LE 체크섬]`. 작은 프레임의 헤더가 5 바이트로 줄어든다.
+    Varint,
+}
+
+impl FrameLayout {
+    /// 길이가 `length` 인 payload 를 감쌀 헤더의 크기
+    pub fn header_length(self, length: usize) -> usize {
+        match self {
+            FrameLayout::Fixed => FRAME_HEADER_SIZE,
+            FrameLayout::Varint => varint_length(length as u64) + size_of::<u32>(),
+        }
+    }
+
+    /// 프레임 경계에서 시작하는 `frames` 의 첫 프레임에서 헤더의 크기와 payload 의 길이. 헤더가 끊겼다면 `None`
+    pub fn decode_header(self, frames: &[u8]) -> Option<(usize, usize)> {
+        let (length, size) = match self {
+            FrameLayout::Fixed => (u32::from_le_bytes(*frames.first_chunk::<4>()?) as u64, 4),
+            FrameLayout::Varint => read_varint(&frames[..frames.len().min(MAX_LENGTH_SIZE)])?,
+        };
+        let header = size + size_of::<u32>();
+
+        (frames.len() >= header).then_some((header, length as usize))
+    }
+}
+
+#[derive(Debug)]
+pub enum FrameError {
+    /// 헤더나 페이로드가 중간에 끊긴 프레임 (torn write)
+    Incomplete,
+    ChecksumMismatch,
+    Io(io::Error),
+}
+
+impl fmt::Display for FrameError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            FrameError::Incomplete => write!(f, "incomplete frame"),
+            FrameError::ChecksumMismatch => write!(f, "frame checksum mismatch"),
+            FrameError::Io(e) => write!(f, "{}", e),
+        }
+    }
+}
+
+impl std::error::Error for FrameError {}
+
+impl From<io::Error> for FrameError {
+    fn from(e: io::Error) -> Self {
+        FrameError::Io(e)
+    }
+}
+
+/// [`FrameLayout::Fixed`] 프레임
+pub fn encode_frame(payload: &[u8], salt: u64, checksum: ChecksumAlgorithm, out: &mut Vec<u8>) {
+    out.reserve(FRAME_HEADER_SIZE + payload.len());
+    out.extend_from_slice(&(payload.len() as u32).to_le_bytes());
+    out.extend_from_slice(&checksum.checksum(salt, payload).to_le_bytes());
+    out.extend_from_slice(payload);
+}
+
+/// `encode_frame` 과 같지만 payload 를 따로 모으지 않고, `encode_payload` 가 `out` 끝에 바로 이어붙인 payload 를 헤더로 감싼다.
+/// 실패하면 `out` 을 부르기 전 길이로 되돌린다.
+///
+/// varint 헤더는 payload 를 다 쓴 뒤에야 길이를 알 수 있으므로 가장 긴 헤더 자리를 잡아뒀다가 payload 를 앞으로 당긴다.
+pub fn encode_frame_with<E>(
+    layout: FrameLayout,
+    salt: u64,
+    checksum: ChecksumAlgorithm,
+    out: &mut Vec<u8>,
+    encode_payload: impl FnOnce(&mut Vec<u8>) -> Result<(), E>,
+) -> Result<(), E> {
+    let start = out.len();
+    let reserved = match layout {
+        FrameLayout::Fixed => FRAME_HEADER_SIZE,
+        FrameLayout::Varint => MAX_LENGTH_SIZE + size_of::<u32>(),
+    };
+    out.extend_from_slice(&[0; MAX_LENGTH_SIZE + size_of::<u32>()][..reserved]);
+    if let Err(e) = encode_payload(out) {
+        out.truncate(start);
+        return Err(e);
+    }
+
+    let length = out.len() - start - reserved;
+    let header = layout.header_length(length);
+    if header < reserved {
+        out.copy_within(start + reserved.., start + header);
+        out.truncate(start + header + length);
+    }
+
+    let (encoded, size) = match layout {
+        FrameLayout::Fixed => {
+            let mut bytes = [0; MAX_VARINT_LENGTH];
+            bytes[..4].copy_from_slice(&(length as u32).to_le_bytes());
+            (bytes, 4)
+        },
+        FrameLayout::Varint => encode_varint(length as u64),
+    };
+    let checksum = checksum.checksum(salt, &out[start + header..]);
+    out[start..start + size].copy_from_slice(&encoded[..size]);
+    out[start + size..start + header].copy_from_slice(&checksum.to_le_bytes());
+
+    Ok(())
+}
+
+/// 프레임을 하나씩 읽어들이는 스트리밍 리더
+pub struct FrameReader<R> {
+    reader: R,
+    offset: u64,
+    salt: u64,
+    checksum: ChecksumAlgorithm,
+    layout: FrameLayout,
+    block_size: Option<u64>,
+    /// 직전에 체크섬 검증에 실패한 프레임의 크기
+    rejected: u64,
+    /// 마지막으로 온전히 읽은 프레임의 체크섬
+    last_checksum: u32,
+}
+
+impl<R: Read> FrameReader<R> {
+    /// `offset` 은 `reader` 의 현재 위치 (파일 기준)
+    pub fn new(reader: R, offset: u64, salt: u64) -> Self {
+        Self { reader, offset, salt, checksum: ChecksumAlgorithm::default(), layout: FrameLayout::default(), block_size: None, rejected: 0, last_checksum: 0 }
+    }
+
+    /// 세그먼트 포맷에 맞는 헤더 모양으로 읽는다
+    pub fn with_layout(mut self, layout: FrameLayout) -> Self {
+        self.layout = layout;
+        self
+    }
+
+    /// 세그먼트 헤더에 기록된 체크섬 알고리즘으로 검증한다
+    pub fn with_checksum(mut self, checksum: ChecksumAlgorithm) -> Self {
+        self.checksum = checksum;
+        self
+    }
+
+    /// 패딩을 만나면 데이터의 끝으로 보지 않고 다음 블록 경계로 건너뛴다
+    pub fn with_block_size(mut self, block_size: u64) -> Self {
+        self.block_size = Some(block_size);
+        self
+    }
+
+    /// 마지막으로 온전히 읽은 프레임의 끝 위치
+    pub fn offset(&self) -> u64 {
+        self.offset
+    }
+
+    /// 세그먼트 순번
+    pub fn salt(&self) -> u64 {
+        self.salt
+    }
+
+    /// 마지막으로 온전히 읽은 프레임의 체크섬. 같은 위치에 다른 프레임이 다시 쓰였는지 알아볼 때 쓴다.
+    pub fn last_checksum(&self) -> u32 {
+        self.last_checksum
+    }
+
+    /// 직전에 `ChecksumMismatch` 로 거부된 프레임을 건너뛰고 그 다음 프레임부터 읽는다
+    pub fn skip_rejected(&mut self) {
+        self.offset += self.rejected;
+        self.rejected = 0;
+    }
+
+    /// 깨끗하게 끝났다면 `Ok(None)`
+    pub fn next_frame(&mut self) -> Result<Option<Vec<u8>>, FrameError> {
+        let mut payload = Vec::new();
+
+        Ok(self.next_frame_into(&mut payload)?.then_some(payload))
+    }
+
+    /// `next_frame` 과 같지만 프레임마다 새로 할당하지 않고 `payload` 를 비우고 다시 채운다. 깨끗하게 끝났다면 `Ok(false)`
+    pub fn next_frame_into(&mut self, payload: &mut Vec<u8>) -> Result<bool, FrameError> {
+        let (header, length, checksum) = loop {
+            let Some((header, length, checksum)) = self.read_header()? else {
+                return Ok(false);
+            };
+
+            // 길이와 체크섬이 모두 0 인 프레임은 패딩이다.
+            // 블록 크기를 모르거나 다음 블록 경계까지 파일이 이어지지 않으면 데이터의 끝으로 본다.
+            if length != 0 || checksum != 0 {
+                break (header, length, checksum);
+            }
+
+            let Some(boundary) = self.padding_boundary() else {
+                return Ok(false);
+            };
+            let skip = boundary - self.offset - header as u64;
+            if io::copy(&mut (&mut self.reader).take(skip), &mut io::sink())? != skip {
+                return Ok(false);
+            }
+
+            self.offset = boundary;
+        };
+
+        payload.clear();
+        let read = (&mut self.reader).take(length as u64).read_to_end(payload)?;
+        if read != length {
+            return Err(FrameError::Incomplete);
+        }
+
+        if self.checksum.checksum(self.salt, payload) != checksum {
+            self.rejected = (header + length) as u64;
+            return Err(FrameError::ChecksumMismatch);
+        }
+
+        self.offset += (header + length) as u64;
+        self.last_checksum = checksum;
+
+        Ok(true)
+    }
+
+    /// 프레임 헤더의 크기, payload 길이, 체크섬. 아무것도 남지 않았다면 `Ok(None)`
+    fn read_header(&mut self) -> Result<Option<(usize, usize, u32)>, FrameError> {
+        let mut header = [0u8; MAX_LENGTH_SIZE + size_of::<u32>()];
+        let length = match self.layout {
+            FrameLayout::Fixed => match read_full(&mut self.reader, &mut header[..FRAME_HEADER_SIZE])? {
+                0 => return Ok(None),
+                FRAME_HEADER_SIZE => 4,
+                _ => return Err(FrameError::Incomplete),
+            },
+            // 길이의 마지막 바이트까지 한 바이트씩 읽는다. 다섯 바이트를 넘는 길이는 끊긴 프레임과 같이 다룬다.
+            FrameLayout::Varint => {
+                let mut size = 0;
+                loop {
+                    match read_full(&mut self.reader, &mut header[size..size + 1])? {
+                        0 if size == 0 => return Ok(None),
+                        0 => return Err(FrameError::Incomplete),
+                        _ => size += 1,
+                    }
+                    if header[size - 1] & 0x80 == 0 {
+                        break size;
+                    }
+                    if size == MAX_LENGTH_SIZE {
+                        return Err(FrameError::Incomplete);
+                    }
+                }
+            },
+        };
+
+        let size = length + size_of::<u32>();
+        if self.layout == FrameLayout::Varint && read_full(&mut self.reader, &mut header[length..size])? != size_of::<u32>() {
+            return Err(FrameError::Incomplete);
+        }
+        let Some((_, payload)) = self.layout.decode_header(&header[..size]) else {
+            return Err(FrameError::Incomplete);
+        };
+        let checksum = u32::from_le_bytes(header[length..size].try_into().unwrap());
+
+        Ok(Some((size, payload, checksum)))
+    }
+}
+
+impl<R> FrameReader<R> {
+    /// 현재 위치의 패딩이 끝나는 블록 경계. 패딩 헤더가 들어갈 자리는 항상 남겨두므로
+    /// 경계까지 프레임 헤더보다 적게 남았다면 그 다음 경계까지 이어진다.
+    fn padding_boundary(&self) -> Option<u64> {
+        let block_size = self.block_size?;
+
+        Some((self.offset + FRAME_HEADER_SIZE as u64).div_ceil(block_size) * block_size)
+    }
+}
+
+/// `length` 에서 시작하는 패딩을 넣었을 때 다음 데이터가 시작될 블록 경계 ([`FrameReader`] 와 같은 규칙)
+pub fn padded_length(length: u64, block_size: u64) -> u64 {
+    if length.is_multiple_of(block_size) {
+        return length;
+    }
+
+    (length + FRAME_HEADER_SIZE as u64).div_ceil(block_size) * block_size
+}
+
+fn read_full<R: Read>(reader: &mut R, buf: &mut [u8]) -> io::Result<usize> {
+    let mut filled = 0;
+
+    while filled < buf.len() {
+        match reader.read(&mut buf[filled..]) {
+            Ok(0) => break,
+            Ok(n) => filled += n,
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => {},
+            Err(e) => return Err(e),
+        }
+    }
+
+    Ok(filled)
+}
+
+#[cfg(test)]
+mod frame_tests {
+    use super::{encode_frame, encode_frame_with, padded_length, FrameError, FrameLayout, FrameReader, FRAME_HEADER_SIZE};
+    use crate::wal::checksum::ChecksumAlgorithm;
+
+    #[test]
+    fn test_read_frames_one_by_one() {
+        let mut bytes = Vec::new();
+        encode_frame(b"first", 1, ChecksumAlgorithm::Crc32, &mut bytes);
+        encode_frame(b"second", 1, ChecksumAlgorithm::Crc32, &mut bytes);
+
+        let mut reader = FrameReader::new(&bytes[..], 0, 1);
+        assert_eq!(reader.next_frame().unwrap(), Some(b"first".to_vec()));
+        assert_eq!(reader.next_frame().unwrap(), Some(b"second".to_vec()));
+        assert!(reader.next_frame().unwrap().is_none());
+        assert_eq!(reader.offset(), bytes.len() as u64);
+    }
+
+    #[test]
+    fn test_varint_frame_layout() {
+        let mut bytes = Vec::new();
+        for payload in [&b"short"[..], &[7; 300]] {
+            encode_frame_with::<()>(FrameLayout::Varint, 1, ChecksumAlgorithm::Crc32, &mut bytes, |out| {
+                out.extend_from_slice(payload);
+                Ok(())
+            }).unwrap();
+        }
+        // 길이 5 는 1 바이트, 300 은 2 바이트로 남는다
+        assert_eq!(bytes.len(), (1 + 4 + 5) + (2 + 4 + 300));
+
+        let mut reader = FrameReader::new(&bytes[..], 0, 1).with_layout(FrameLayout::Varint);
+        assert_eq!(reader.next_frame().unwrap(), Some(b"short".to_vec()));
+        assert_eq!(reader.next_frame().unwrap(), Some(vec![7; 300]));
+        assert!(reader.next_frame().unwrap().is_none());
+        assert_eq!(reader.offset(), bytes.len() as u64);
+
+        // 헤더 한가운데서 끊긴 프레임
+        let mut reader = FrameReader::new(&bytes[..12], 0, 1).with_layout(FrameLayout::Varint);
+        assert!(reader.next_frame().unwrap().is_some());
+        assert!(matches!(reader.next_frame(), Err(FrameError::Incomplete)));
+    }
+
+    #[test]
+    fn test_incomplete_frame() {
+        let mut bytes = Vec::new();
+        encode_frame(b"complete", 1, ChecksumAlgorithm::Crc32, &mut bytes);
+        let complete = bytes.len();
+        encode_frame(b"torn", 1, ChecksumAlgorithm::Crc32, &mut bytes);
+        bytes.truncate(bytes.len() - 2);
+
+        let mut reader = FrameReader::new(&bytes[..], 0, 1);
+        assert!(reader.next_frame().unwrap().is_some());
+        assert!(matches!(reader.next_frame(), Err(FrameError::Incomplete)));
+        assert_eq!(reader.offset(), complete as u64);
+    }
+
+    #[test]
+    fn test_reject_frame_with_other_salt() {
+        let mut bytes = Vec::new();
+        encode_frame(b"stale", 3, ChecksumAlgorithm::Crc32, &mut bytes);
+
+        let mut reader = FrameReader::new(&bytes[..], 0, 7);
+        assert!(matches!(reader.next_frame(), Err(FrameError::ChecksumMismatch)));
+    }
+
+    #[test]
+    fn test_skip_padding_to_block_boundary() {
+        let mut bytes = Vec::new();
+        encode_frame(b"first", 1, ChecksumAlgorithm::Crc32, &mut bytes);
+        let padded = padded_length(bytes.len() as u64, 64);
+        bytes.resize(padded as usize, 0);
+        encode_frame(b"second", 1, ChecksumAlgorithm::Crc32, &mut bytes);
+
+        let mut reader = FrameReader::new(&bytes[..], 0, 1).with_block_size(64);
+        assert_eq!(reader.next_frame().unwrap(), Some(b"first".to_vec()));
+        assert_eq!(reader.next_frame().unwrap(), Some(b"second".to_vec()));
+        assert!(reader.next_frame().unwrap().is_none());
+
+        // 블록 크기를 모르면 패딩에서 멈춘다
+        let mut reader = FrameReader::new(&bytes[..], 0, 1);
+        assert!(reader.next_frame().unwrap().is_some());
+        assert!(reader.next_frame().unwrap().is_none());
+    }
+
+    #[test]
+    fn test_padding_leaves_room_for_header() {
+        assert_eq!(padded_length(64, 64), 64);
+        assert_eq!(padded_length(50, 64), 64);
+        assert_eq!(padded_length(60, 64), 128);
+    }
+
+    #[test]
+    fn test_verify_with_recorded_algorithm() {
+        let mut bytes = Vec::new();
+        encode_frame(b"hashed", 1, ChecksumAlgorithm::XxHash64, &mut bytes);
+
+        let mut reader = FrameReader::new(&bytes[..], 0, 1).with_checksum(ChecksumAlgorithm::XxHash64);
+        assert_eq!(reader.next_frame().unwrap(), Some(b"hashed".to_vec()));
+
+        let mut reader = FrameReader::new(&bytes[..], 0, 1).with_checksum(ChecksumAlgorithm::Crc32c);
+        assert!(matches!(reader.next_frame(), Err(FrameError::ChecksumMismatch)));
+    }
+
+    #[test]
+    fn test_skip_rejected_frame() {
+        let mut bytes = Vec::new();
+        encode_frame(b"first", 1, ChecksumAlgorithm::Crc32, &mut bytes);
+        let corrupt = bytes.len() + FRAME_HEADER_SIZE;
+        encode_frame(b"corrupt", 1, ChecksumAlgorithm::Crc32, &mut bytes);
+        encode_frame(b"third", 1, ChecksumAlgorithm::Crc32, &mut bytes);
+        bytes[corrupt] ^= 0xFF;
+
+        let mut reader = FrameReader::new(&bytes[..], 0, 1);
+        assert!(reader.next_frame().unwrap().is_some());
+        assert!(matches!(reader.next_frame(), Err(FrameError::ChecksumMismatch)));
+        reader.skip_rejected();
+        assert_eq!(reader.next_frame().unwrap(), Some(b"third".to_vec()));
+        assert_eq!(reader.offset(), bytes.len() as u64);
+    }
+}
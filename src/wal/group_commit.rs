@@ -0,0 +1,243 @@
+use std::error::Error;
+use std::sync::mpsc::Receiver;
+use std::sync::{Condvar, Mutex};
+use std::time::{Duration, Instant};
+
+use super::core::{WALEntry, WALManager};
+use super::lsn::Lsn;
+use super::sync::Durability;
+use super::watch::DurableRange;
+
+#[derive(Clone, Copy, Debug)]
+pub struct GroupCommitOptions {
+    /// 그룹의 첫 엔트리가 fsync 를 기다리는 최대 시간
+    pub max_latency: Duration,
+    /// 이만큼 쌓이면 시간을 기다리지 않고 바로 fsync
+    pub max_batch_bytes: usize,
+}
+
+impl Default for GroupCommitOptions {
+    fn default() -> Self {
+        Self { max_latency: Duration::from_millis(1), max_batch_bytes: 1024 * 1024 }
+    }
+}
+
+/// 여러 스레드의 append 를 모아서 fsync 한 번으로 내구성을 확보한다
+///
+/// 각 `append_log` 호출은 자신의 엔트리가 포함된 그룹이 fsync 될 때까지 블록된다.
+/// fsync 는 대기 중인 호출자 중 하나(leader)가 락 밖에서 수행하므로 그동안 다른 스레드가 계속 쓸 수 있다.
+pub struct GroupCommitter {
+    state: Mutex<GroupState>,
+    synced: Condvar,
+    options: GroupCommitOptions,
+}
+
+struct GroupState {
+    manager: WALManager,
+    written: u64,
+    synced: u64,
+    syncing: bool,
+    pending_bytes: usize,
+    batch_started: Option<Instant>,
+    sync_count: usize,
+}
+
+impl GroupCommitter {
+    pub fn new(manager: WALManager, options: GroupCommitOptions) -> Self {
+        Self {
+            state: Mutex::new(GroupState {
+                manager,
+                written: 0,
+                synced: 0,
+                syncing: false,
+                pending_bytes: 0,
+                batch_started: None,
+                sync_count: 0,
+            }),
+            synced: Condvar::new(),
+            options,
+        }
+    }
+
+    /// 기다리는 호출자가 없으므로 그대로 [`WALManager::close`] 를 부른다
+    pub fn close(self) -> Result<Lsn, Box<dyn Error>> {
+        self.state.into_inner().unwrap().manager.close()
+    }
+
+    pub fn append_log(&self, entry: WALEntry) -> Result<Lsn, Box<dyn Error>> {
+        self.append_log_with(entry, Durability::Durable)
+    }
+
+    /// `Lazy` 엔트리는 기다리지 않고 반환하고, 다음 그룹의 fsync 에 함께 실린다
+    pub fn append_log_with(&self, entry: WALEntry, durability: Durability) -> Result<Lsn, Box<dyn Error>> {
+        let mut state = self.state.lock().unwrap();
+        while state.manager.is_paused() {
+            state = self.synced.wait(state).unwrap();
+        }
+
+        let (lsn, size) = state.manager.append_unsynced(entry)?;
+        state.written += 1;
+        state.pending_bytes += size;
+        state.batch_started.get_or_insert_with(Instant::now);
+        let ticket = state.written;
+
+        if durability == Durability::Lazy {
+            return Ok(lsn);
+        }
+
+        loop {
+            if state.synced >= ticket {
+                return Ok(lsn);
+            }
+
+            let elapsed = state.batch_started.map_or(Duration::ZERO, |started| started.elapsed());
+            let batch_ready = state.pending_bytes >= self.options.max_batch_bytes || elapsed >= self.options.max_latency;
+
+            if !state.syncing && batch_ready {
+                state.syncing = true;
+                let target = state.written;
+                let target_lsn = state.manager.last_lsn();
+                state.pending_bytes = 0;
+                state.batch_started = None;
+
+                let handle = match state.manager.sync_handle() {
+                    Ok(handle) => handle,
+                    Err(e) => {
+                        state.syncing = false;
+                        self.synced.notify_all();
+                        return Err(e.into());
+                    },
+                };
+
+                drop(state);
+                let started = Instant::now();
+                let result = handle.sync();
+                state = self.state.lock().unwrap();
+
+                state.syncing = false;
+                if result.is_ok() {
+                    state.synced = state.synced.max(target);
+                    state.manager.mark_durable(target_lsn);
+                    state.manager.record_fsync(started.elapsed());
+                    state.sync_count += 1;
+                }
+                self.synced.notify_all();
+
+                result?;
+                continue;
+            }
+
+            let timeout = self.options.max_latency.saturating_sub(elapsed).max(Duration::from_micros(50));
+            state = self.synced.wait_timeout(state, timeout).unwrap().0;
+        }
+    }
+
+    /// [`WALManager::pause`]. 그동안 `append_log` 는 오류를 돌려주지 않고 `resume` 까지 기다린다.
+    pub fn pause(&self) -> Result<Lsn, std::io::Error> {
+        self.state.lock().unwrap().manager.pause()
+    }
+
+    pub fn resume(&self) {
+        self.state.lock().unwrap().manager.resume();
+        self.synced.notify_all();
+    }
+
+    /// [`WALManager::subscribe`]
+    pub fn subscribe(&self) -> Receiver<DurableRange> {
+        self.state.lock().unwrap().manager.subscribe()
+    }
+
+    /// [`WALManager::next_transaction_id`]
+    pub fn next_transaction_id(&self) -> Result<u64, std::io::Error> {
+        self.state.lock().unwrap().manager.next_transaction_id()
+    }
+
+    pub fn into_inner(self) -> WALManager {
+        self.state.into_inner().unwrap().manager
+    }
+}
+
+#[cfg(test)]
+mod group_commit_tests {
+    use super::{GroupCommitOptions, GroupCommitter};
+    use crate::wal::core::WALManager;
+    use crate::wal::lsn::Lsn;
+    use crate::wal::sync::{Durability, SyncPolicy};
+    use crate::wal::test_utils::{insert_entry, temp_directory};
+    use std::sync::Arc;
+    use std::time::Duration;
+
+    #[test]
+    fn test_concurrent_appends_share_fsync() {
+        let wal_manager = WALManager::builder()
+            .set_directory(temp_directory("group_commit"))
+            .set_segment_max_bytes(1024 * 1024)
+            .set_sync_policy(SyncPolicy::Never)
+            .build().expect("Cannot create WALManager");
+
+        let options = GroupCommitOptions { max_latency: Duration::from_millis(5), ..Default::default() };
+        let committer = Arc::new(GroupCommitter::new(wal_manager, options));
+
+        let threads = (0..8).map(|i| {
+            let committer = committer.clone();
+            std::thread::spawn(move || {
+                for _ in 0..10 {
+                    let entry = insert_entry(vec![i as u8; 16]);
+                    committer.append_log(entry).expect("Cannot append entry");
+                }
+            })
+        }).collect::<Vec<_>>();
+
+        for thread in threads {
+            thread.join().unwrap();
+        }
+
+        let state = committer.state.lock().unwrap();
+        assert_eq!(state.synced, 80);
+        assert!(state.sync_count < 80);
+        assert_eq!(state.manager.durable_lsn(), Lsn(80));
+    }
+
+    #[test]
+    fn test_lazy_entries_ride_along() {
+        let wal_manager = WALManager::builder()
+            .set_directory(temp_directory("group_commit_lazy"))
+            .set_sync_policy(SyncPolicy::Never)
+            .build().expect("Cannot create WALManager");
+        let committer = GroupCommitter::new(wal_manager, GroupCommitOptions::default());
+
+        let entry = insert_entry(vec![1; 16]);
+        for _ in 0..5 {
+            committer.append_log_with(entry.clone(), Durability::Lazy).expect("Cannot append entry");
+        }
+        assert_eq!(committer.state.lock().unwrap().sync_count, 0);
+
+        committer.append_log(entry).expect("Cannot append entry");
+        let state = committer.state.lock().unwrap();
+        assert_eq!(state.sync_count, 1);
+        assert_eq!(state.manager.durable_lsn(), Lsn(6));
+    }
+
+    #[test]
+    fn test_pause_blocks_appends() {
+        let wal_manager = WALManager::builder()
+            .set_directory(temp_directory("group_commit_pause"))
+            .set_sync_policy(SyncPolicy::Never)
+            .build().expect("Cannot create WALManager");
+        let committer = Arc::new(GroupCommitter::new(wal_manager, GroupCommitOptions::default()));
+        assert_eq!(committer.pause().unwrap(), Lsn(0));
+
+        let writer = {
+            let committer = committer.clone();
+            std::thread::spawn(move || {
+                committer.append_log(insert_entry(vec![1; 16])).expect("Cannot append entry")
+            })
+        };
+
+        std::thread::sleep(Duration::from_millis(20));
+        assert_eq!(committer.state.lock().unwrap().manager.last_lsn(), Lsn(0));
+
+        committer.resume();
+        assert_eq!(writer.join().unwrap(), Lsn(1));
+    }
+}
@@ -0,0 +1,147 @@
+use std::fs::File;
+use std::io::{Error, ErrorKind, Write};
+use std::path::{Path, PathBuf};
+
+use super::checksum::crc32;
+
+pub const INDEX_MAGIC: [u8; 8] = *b"RRDBIDX\0";
+/// 이 간격(엔트리 수)마다 위치를 하나씩 기록한다
+pub const INDEX_INTERVAL: u64 = 64;
+
+/// 봉인된 세그먼트 옆에 남기는 sparse LSN 색인 파일
+pub fn index_path(directory: &Path, sequence: usize) -> PathBuf {
+    directory.join(format!("wal{}.idx", sequence))
+}
+
+/// 세그먼트 안에서 `position` 개의 프레임을 지난 바로 다음 프레임이 `offset` 에서 시작한다
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct IndexEntry {
+    pub position: u64,
+    pub offset: u64,
+}
+
+/// 세그먼트 안의 엔트리 순번 → 파일 위치를 [`INDEX_INTERVAL`] 마다 기록한 색인
+///
+/// `[magic 8][sequence u64][count u32][(position u64, offset u64) * count][CRC32 u32]`
+///
+/// 세그먼트를 읽는 데 꼭 필요한 정보가 아니므로 fsync 하지 않고, 없거나 깨졌다면 처음부터 읽으면 된다.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct SegmentIndex {
+    pub sequence: u64,
+    pub entries: Vec<IndexEntry>,
+}
+
+impl SegmentIndex {
+    pub fn new(sequence: u64) -> Self {
+        Self { sequence, entries: Vec::new() }
+    }
+
+    /// 간격에 맞는 위치일 때만 기록한다
+    pub fn record(&mut self, position: u64, offset: u64) {
+        self.record_block(position, 1, offset);
+    }
+
+    /// `position` 번째부터 `entries` 개의 프레임이 `offset` 에서 시작하는 블록 하나에 들어 있을 때,
+    /// 그 안에 간격에 맞는 위치가 있다면 블록의 시작을 기록한다
+    pub fn record_block(&mut self, position: u64, entries: u64, offset: u64) {
+        let recorded = self.entries.last().is_some_and(|entry| entry.position >= position);
+        if position.next_multiple_of(INDEX_INTERVAL) < position + entries && !recorded {
+            self.entries.push(IndexEntry { position, offset });
+        }
+    }
+
+    /// `position` 번째 프레임 이전에서 가장 가까운 기록
+    pub fn lookup(&self, position: u64) -> Option<IndexEntry> {
+        let index = self.entries.partition_point(|entry| entry.position <= position);
+
+        index.checked_sub(1).map(|index| self.entries[index])
+    }
+
+    pub fn encode(&self) -> Vec<u8> {
+        let mut bytes = Vec::with_capacity(20 + self.entries.len() * 16 + 4);
+        bytes.extend_from_slice(&INDEX_MAGIC);
+        bytes.extend_from_slice(&self.sequence.to_le_bytes());
+        bytes.extend_from_slice(&(self.entries.len() as u32).to_le_bytes());
+
+        for entry in &self.entries {
+            bytes.extend_from_slice(&entry.position.to_le_bytes());
+            bytes.extend_from_slice(&entry.offset.to_le_bytes());
+        }
+
+        let checksum = crc32(&bytes);
+        bytes.extend_from_slice(&checksum.to_le_bytes());
+
+        bytes
+    }
+
+    /// magic, 길이, 체크섬 중 하나라도 맞지 않으면 `None`
+    pub fn decode(bytes: &[u8]) -> Option<Self> {
+        let (body, checksum) = bytes.split_last_chunk::<4>()?;
+        if body.len() < 20 || body[0..8] != INDEX_MAGIC || crc32(body) != u32::from_le_bytes(*checksum) {
+            return None;
+        }
+
+        let count = u32::from_le_bytes(body[16..20].try_into().unwrap()) as usize;
+        let pairs = &body[20..];
+        if pairs.len() != count * 16 {
+            return None;
+        }
+
+        let entries = pairs.chunks_exact(16)
+            .map(|pair| IndexEntry {
+                position: u64::from_le_bytes(pair[0..8].try_into().unwrap()),
+                offset: u64::from_le_bytes(pair[8..16].try_into().unwrap()),
+            })
+            .collect();
+
+        Some(Self { sequence: u64::from_le_bytes(body[8..16].try_into().unwrap()), entries })
+    }
+
+    pub fn save(&self, directory: &Path) -> Result<(), Error> {
+        let mut file = File::create(index_path(directory, self.sequence as usize))?;
+        file.write_all(&self.encode())
+    }
+
+    /// 색인 파일이 없거나 다른 세그먼트의 것이거나 깨졌다면 `Ok(None)`
+    pub fn load(directory: &Path, sequence: u64) -> Result<Option<Self>, Error> {
+        let bytes = match std::fs::read(index_path(directory, sequence as usize)) {
+            Ok(bytes) => bytes,
+            Err(e) if e.kind() == ErrorKind::NotFound => return Ok(None),
+            Err(e) => return Err(e),
+        };
+
+        Ok(Self::decode(&bytes).filter(|index| index.sequence == sequence))
+    }
+}
+
+#[cfg(test)]
+mod index_tests {
+    use super::{IndexEntry, SegmentIndex, INDEX_INTERVAL};
+
+    #[test]
+    fn test_record_sparse_positions() {
+        let mut index = SegmentIndex::new(3);
+        for position in 0..200 {
+            index.record(position, 32 + position * 10);
+        }
+
+        assert_eq!(index.entries.len(), 4);
+        assert_eq!(index.lookup(0), Some(IndexEntry { position: 0, offset: 32 }));
+        assert_eq!(index.lookup(INDEX_INTERVAL * 2 + 5), Some(IndexEntry { position: 128, offset: 32 + 1280 }));
+        assert_eq!(SegmentIndex::new(3).lookup(10), None);
+    }
+
+    #[test]
+    fn test_encode_roundtrip() {
+        let mut index = SegmentIndex::new(7);
+        index.record(0, 32);
+        index.record(INDEX_INTERVAL, 4096);
+
+        let mut bytes = index.encode();
+        assert_eq!(SegmentIndex::decode(&bytes), Some(index));
+
+        bytes[24] ^= 0xff;
+        assert_eq!(SegmentIndex::decode(&bytes), None);
+        assert_eq!(SegmentIndex::decode(&bytes[..10]), None);
+    }
+}
@@ -0,0 +1,147 @@
+use super::core::{EntryType, Metadata, WALEntry, WALEntryRef, WALManager};
+use super::flags::EntryFlags;
+
+/// `Insert`/`Set`/`Delete` 엔트리의 data 에 담는 키/값
+///
+/// `[u32 LE 키 길이][키][u8 값 유무][값]` 으로 data 에 기록하므로 KV 엔진이 키를 따로 나누는 방식을 만들 필요가 없다.
+/// 값이 없는 것(`Delete`)과 빈 값은 서로 다르게 남는다.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct KeyValue {
+    pub key: Vec<u8>,
+    pub value: Option<Vec<u8>>,
+}
+
+/// 프레임 버퍼에서 빌려온 [`KeyValue`] ([`WALEntryRef::key_value_ref`])
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct KeyValueRef<'a> {
+    pub key: &'a [u8],
+    pub value: Option<&'a [u8]>,
+}
+
+impl KeyValue {
+    pub fn encode(&self) -> Vec<u8> {
+        KeyValueRef { key: &self.key, value: self.value.as_deref() }.encode()
+    }
+
+    pub fn decode(data: &[u8]) -> Option<Self> {
+        KeyValueRef::decode(data).map(KeyValueRef::into_owned)
+    }
+}
+
+impl<'a> KeyValueRef<'a> {
+    pub fn encode(&self) -> Vec<u8> {
+        let mut data = Vec::with_capacity(5 + self.key.len() + self.value.map_or(0, |value| value.len()));
+        data.extend_from_slice(&(self.key.len() as u32).to_le_bytes());
+        data.extend_from_slice(self.key);
+        data.push(self.value.is_some() as u8);
+        data.extend_from_slice(self.value.unwrap_or_default());
+
+        data
+    }
+
+    /// 키/값 형식이 아니라면 `None`
+    pub fn decode(data: &'a [u8]) -> Option<Self> {
+        let (length, rest) = data.split_first_chunk::<4>()?;
+        let length = u32::from_le_bytes(*length) as usize;
+        if rest.len() <= length {
+            return None;
+        }
+
+        let (key, rest) = rest.split_at(length);
+        let value = match rest {
+            [0] => None,
+            [1, value @ ..] => Some(value),
+            _ => return None,
+        };
+
+        Some(Self { key, value })
+    }
+
+    pub fn into_owned(self) -> KeyValue {
+        KeyValue { key: self.key.to_vec(), value: self.value.map(|value| value.to_vec()) }
+    }
+}
+
+impl WALEntry {
+    /// 트랜잭션에 속하지 않은 키/값 엔트리. 트랜잭션에 넣으려면 `transaction_id` 를 바꾼다.
+    /// 값이 없다면 [`EntryFlags::TOMBSTONE`] 을 표시한다.
+    pub fn key_value(entry_type: EntryType, key: &[u8], value: Option<&[u8]>) -> Self {
+        WALEntry {
+            entry_type,
+            data: Some(KeyValueRef { key, value }.encode()),
+            timestamp: WALManager::get_current_secs(),
+            transaction_id: 0,
+            metadata: Metadata::new(),
+            flags: match value {
+                Some(_) => EntryFlags::NONE,
+                None => EntryFlags::TOMBSTONE,
+            },
+        }
+    }
+
+    pub fn put(key: &[u8], value: &[u8]) -> Self {
+        Self::key_value(EntryType::Set, key, Some(value))
+    }
+
+    pub fn delete(key: &[u8]) -> Self {
+        Self::key_value(EntryType::Delete, key, None)
+    }
+
+    /// data 를 키/값으로 풀어낸다. data 가 없거나 키/값 형식이 아니라면 `None`
+    pub fn key_value_ref(&self) -> Option<KeyValueRef<'_>> {
+        self.data.as_deref().and_then(KeyValueRef::decode)
+    }
+}
+
+impl<'a> WALEntryRef<'a> {
+    /// [`WALEntry::key_value_ref`] 와 같지만 프레임 버퍼를 그대로 빌려준다
+    pub fn key_value_ref(&self) -> Option<KeyValueRef<'a>> {
+        self.data.and_then(KeyValueRef::decode)
+    }
+}
+
+#[cfg(test)]
+mod kv_tests {
+    use super::{KeyValue, KeyValueRef};
+    use crate::wal::core::{EntryType, WALEntry, WALManager};
+    use crate::wal::test_utils::temp_directory;
+
+    #[test]
+    fn test_key_value_round_trip() {
+        let put = KeyValue { key: b"key".to_vec(), value: Some(b"value".to_vec()) };
+        assert_eq!(KeyValue::decode(&put.encode()), Some(put));
+
+        // 빈 값과 값이 없는 것은 다르다
+        let empty = KeyValueRef { key: b"", value: Some(b"") };
+        assert_eq!(KeyValueRef::decode(&empty.encode()), Some(empty));
+        assert_eq!(KeyValueRef::decode(&KeyValueRef { key: b"", value: None }.encode()).unwrap().value, None);
+
+        assert_eq!(KeyValueRef::decode(b""), None);
+        assert_eq!(KeyValueRef::decode(&[3, 0, 0, 0, b'k', b'e', b'y']), None);
+        assert_eq!(KeyValueRef::decode(&[3, 0, 0, 0, b'k', b'e', b'y', 2]), None);
+    }
+
+    #[test]
+    fn test_replay_key_value_entries() {
+        let directory = temp_directory("kv_replay");
+        let mut wal_manager = WALManager::builder()
+            .set_directory(directory.clone())
+            .build().expect("Cannot create WALManager");
+
+        wal_manager.append_log(WALEntry::put(b"a", b"1")).unwrap();
+        wal_manager.append_log(WALEntry::key_value(EntryType::Insert, b"b", Some(b"2"))).unwrap();
+        wal_manager.append_log(WALEntry::delete(b"a")).unwrap();
+
+        let mut replayed = Vec::new();
+        wal_manager.recover().unwrap().replay_ref(|_, entry| {
+            let key_value = entry.key_value_ref().unwrap();
+            replayed.push((entry.entry_type, key_value.key.to_vec(), key_value.value.map(|value| value.to_vec())));
+        }).unwrap();
+
+        assert_eq!(replayed, vec![
+            (EntryType::Set, b"a".to_vec(), Some(b"1".to_vec())),
+            (EntryType::Insert, b"b".to_vec(), Some(b"2".to_vec())),
+            (EntryType::Delete, b"a".to_vec(), None),
+        ]);
+    }
+}
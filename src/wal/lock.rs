@@ -0,0 +1,161 @@
+use std::fs::{File, OpenOptions};
+use std::io;
+use std::path::Path;
+
+use super::error::WALError;
+
+pub const LOCK_FILE_NAME: &str = "LOCK";
+
+/// 디렉토리 단위 advisory 잠금. drop 되면 해제된다.
+#[derive(Debug)]
+pub struct DirectoryLock {
+    file: File,
+}
+
+impl DirectoryLock {
+    pub fn acquire(directory: &Path) -> Result<Self, WALError> {
+        let file = OpenOptions::new()
+            .create(true)
+            .truncate(false)
+            .read(true)
+            .write(true)
+            .open(directory.join(LOCK_FILE_NAME))?;
+
+        match sys::try_lock(&file) {
+            Ok(()) => Ok(Self { file }),
+            Err(e) if e.kind() == io::ErrorKind::WouldBlock => Err(WALError::AlreadyLocked(directory.to_path_buf())),
+            Err(e) => Err(e.into()),
+        }
+    }
+}
+
+impl Drop for DirectoryLock {
+    fn drop(&mut self) {
+        let _ = sys::unlock(&self.file);
+    }
+}
+
+#[cfg(unix)]
+mod sys {
+    use std::fs::File;
+    use std::io;
+    use std::os::raw::c_int;
+    use std::os::unix::io::AsRawFd;
+
+    const LOCK_EX: c_int = 2;
+    const LOCK_NB: c_int = 4;
+    const LOCK_UN: c_int = 8;
+
+    extern "C" {
+        fn flock(fd: c_int, operation: c_int) -> c_int;
+    }
+
+    pub fn try_lock(file: &File) -> io::Result<()> {
+        if unsafe { flock(file.as_raw_fd(), LOCK_EX | LOCK_NB) } == 0 {
+            Ok(())
+        } else {
+            Err(io::Error::last_os_error())
+        }
+    }
+
+    pub fn unlock(file: &File) -> io::Result<()> {
+        if unsafe { flock(file.as_raw_fd(), LOCK_UN) } == 0 {
+            Ok(())
+        } else {
+            Err(io::Error::last_os_error())
+        }
+    }
+}
+
+#[cfg(windows)]
+mod sys {
+    use std::ffi::c_void;
+    use std::fs::File;
+    use std::io;
+    use std::os::windows::io::AsRawHandle;
+
+    const LOCKFILE_FAIL_IMMEDIATELY: u32 = 0x1;
+    const LOCKFILE_EXCLUSIVE_LOCK: u32 = 0x2;
+    const ERROR_SHARING_VIOLATION: i32 = 32;
+    const ERROR_LOCK_VIOLATION: i32 = 33;
+
+    #[repr(C)]
+    struct Overlapped {
+        internal: usize,
+        internal_high: usize,
+        offset: u32,
+        offset_high: u32,
+        event: *mut c_void,
+    }
+
+    impl Overlapped {
+        fn zeroed() -> Self {
+            Self { internal: 0, internal_high: 0, offset: 0, offset_high: 0, event: std::ptr::null_mut() }
+        }
+    }
+
+    #[link(name = "kernel32")]
+    extern "system" {
+        fn LockFileEx(file: *mut c_void, flags: u32, reserved: u32, low: u32, high: u32, overlapped: *mut Overlapped) -> i32;
+        fn UnlockFileEx(file: *mut c_void, reserved: u32, low: u32, high: u32, overlapped: *mut Overlapped) -> i32;
+    }
+
+    pub fn try_lock(file: &File) -> io::Result<()> {
+        let mut overlapped = Overlapped::zeroed();
+        let flags = LOCKFILE_EXCLUSIVE_LOCK | LOCKFILE_FAIL_IMMEDIATELY;
+
+        if unsafe { LockFileEx(file.as_raw_handle() as *mut c_void, flags, 0, !0, !0, &mut overlapped) } != 0 {
+            return Ok(());
+        }
+
+        // 다른 프로세스가 잠근 경우 LockFileEx 는 잠금 충돌, 공유 모드 없이 연 경우 공유 위반을 돌려준다
+        let error = io::Error::last_os_error();
+        if matches!(error.raw_os_error(), Some(ERROR_LOCK_VIOLATION | ERROR_SHARING_VIOLATION)) {
+            Err(io::Error::new(io::ErrorKind::WouldBlock, error))
+        } else {
+            Err(error)
+        }
+    }
+
+    pub fn unlock(file: &File) -> io::Result<()> {
+        let mut overlapped = Overlapped::zeroed();
+
+        if unsafe { UnlockFileEx(file.as_raw_handle() as *mut c_void, 0, !0, !0, &mut overlapped) } != 0 {
+            Ok(())
+        } else {
+            Err(io::Error::last_os_error())
+        }
+    }
+}
+
+#[cfg(not(any(unix, windows)))]
+mod sys {
+    use std::fs::File;
+    use std::io;
+
+    pub fn try_lock(_file: &File) -> io::Result<()> {
+        Ok(())
+    }
+
+    pub fn unlock(_file: &File) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod lock_tests {
+    use super::DirectoryLock;
+    use crate::wal::error::WALError;
+    use crate::wal::test_utils::temp_directory;
+
+    #[test]
+    fn test_second_lock_fails() {
+        let directory = temp_directory("lock");
+
+        let lock = DirectoryLock::acquire(&directory).expect("Cannot acquire lock");
+        assert!(matches!(DirectoryLock::acquire(&directory), Err(WALError::AlreadyLocked(_))));
+
+        drop(lock);
+        assert!(DirectoryLock::acquire(&directory).is_ok());
+    }
+}
@@ -0,0 +1,66 @@
+// bitcode 0.4 derive 매크로가 생성하는 코드에서 발생하는 lint
+#![allow(unused_must_use, clippy::assign_op_pattern)]
+
+use bitcode::{Decode, Encode};
+use std::fmt;
+use std::ops::{Add, AddAssign, Sub};
+
+/// 로그 전체에서 엔트리마다 1 씩 증가하는 순번 (체크포인트 엔트리 포함).
+/// 0 은 아직 아무 엔트리도 없다는 뜻이다.
+///
+/// 파일 이름에 붙는 세그먼트 순번이나 세그먼트 안의 엔트리 개수와 섞이지 않도록 따로 감싼다.
+/// 엔트리 개수만큼 더하거나 빼고, 두 LSN 의 차이는 엔트리 개수(`u64`)가 된다. 디스크에는 `u64` 와 같은 모양으로 남는다.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, PartialOrd, Ord, Hash, Encode, Decode)]
+pub struct Lsn(pub u64);
+
+impl Lsn {
+    pub const ZERO: Lsn = Lsn(0);
+
+    pub fn next(self) -> Lsn {
+        Lsn(self.0 + 1)
+    }
+
+    pub fn saturating_sub(self, count: u64) -> Lsn {
+        Lsn(self.0.saturating_sub(count))
+    }
+}
+
+impl Add<u64> for Lsn {
+    type Output = Lsn;
+
+    fn add(self, count: u64) -> Lsn {
+        Lsn(self.0 + count)
+    }
+}
+
+impl AddAssign<u64> for Lsn {
+    fn add_assign(&mut self, count: u64) {
+        self.0 += count;
+    }
+}
+
+impl Sub for Lsn {
+    type Output = u64;
+
+    fn sub(self, other: Lsn) -> u64 {
+        self.0 - other.0
+    }
+}
+
+impl From<u64> for Lsn {
+    fn from(lsn: u64) -> Self {
+        Lsn(lsn)
+    }
+}
+
+impl From<Lsn> for u64 {
+    fn from(lsn: Lsn) -> Self {
+        lsn.0
+    }
+}
+
+impl fmt::Display for Lsn {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
@@ -0,0 +1,179 @@
+// bitcode 0.4 derive 매크로가 생성하는 코드에서 발생하는 lint
+#![allow(unused_must_use, clippy::assign_op_pattern)]
+
+use bitcode::{Decode, Encode};
+use std::fs::File;
+use std::io::{Error, ErrorKind, Write};
+use std::path::{Path, PathBuf};
+
+use super::checksum::crc32;
+use super::lsn::Lsn;
+use super::segment::{segment_path, SegmentFooter};
+use super::sync::sync_directory;
+
+pub const MANIFEST_FILE_NAME: &str = "MANIFEST";
+const MANIFEST_MAGIC: [u8; 8] = *b"RRDBMAN\0";
+
+/// 살아있는 세그먼트 목록과 현재 순번, 마지막 체크포인트를 기록하는 메타 파일
+///
+/// `[magic 8][u32 LE CRC32][bitcode 페이로드]` 형태로 저장되며, 임시 파일에 쓴 뒤 rename 해서 원자적으로 교체한다.
+#[derive(Clone, Debug, Default, PartialEq, Encode, Decode)]
+pub struct Manifest {
+    /// 현재 쓰고 있는 세그먼트의 순번
+    pub sequence: u64,
+    /// 체크포인트로 봉인된 세그먼트 순번들 (오름차순)
+    pub sealed_segments: Vec<u64>,
+    /// 마지막 체크포인트 엔트리가 기록된 세그먼트 순번
+    pub last_checkpoint: Option<u64>,
+    /// 봉인된 세그먼트들에 기록된 마지막 LSN. 활성 세그먼트의 LSN 은 이 다음부터 이어진다.
+    pub sealed_lsn: Lsn,
+    /// 이 값 미만의 트랜잭션 id 는 이미 나눠준 것으로 본다 (`WALManager::next_transaction_id`)
+    pub transaction_id_limit: u64,
+    /// 어플리케이션이 다른 곳에 내구성 있게 반영했다고 알려준 마지막 LSN (`WALManager::set_applied_lsn`)
+    pub applied_lsn: Lsn,
+}
+
+impl Manifest {
+    pub fn path(directory: &Path) -> PathBuf {
+        directory.join(MANIFEST_FILE_NAME)
+    }
+
+    /// 매니페스트가 없다면 `Ok(None)`
+    pub fn load(directory: &Path) -> Result<Option<Self>, Error> {
+        let bytes = match std::fs::read(Self::path(directory)) {
+            Ok(bytes) => bytes,
+            Err(e) if e.kind() == ErrorKind::NotFound => return Ok(None),
+            Err(e) => return Err(e),
+        };
+
+        if bytes.len() < 12 || bytes[0..8] != MANIFEST_MAGIC {
+            return Err(Error::new(ErrorKind::InvalidData, "invalid manifest file"));
+        }
+
+        let checksum = u32::from_le_bytes(bytes[8..12].try_into().unwrap());
+        if crc32(&bytes[12..]) != checksum {
+            return Err(Error::new(ErrorKind::InvalidData, "manifest checksum mismatch"));
+        }
+
+        let manifest = bitcode::decode(&bytes[12..])
+            .map_err(|e| Error::new(ErrorKind::InvalidData, e))?;
+
+        Ok(Some(manifest))
+    }
+
+    pub fn save(&self, directory: &Path) -> Result<(), Error> {
+        let payload = bitcode::encode(self)
+            .map_err(|e| Error::new(ErrorKind::InvalidData, e))?;
+
+        let mut bytes = Vec::with_capacity(12 + payload.len());
+        bytes.extend_from_slice(&MANIFEST_MAGIC);
+        bytes.extend_from_slice(&crc32(&payload).to_le_bytes());
+        bytes.extend_from_slice(&payload);
+
+        let path = Self::path(directory);
+        let temp_path = path.with_extension("tmp");
+
+        let mut file = File::create(&temp_path)?;
+        file.write_all(&bytes)?;
+        file.sync_all()?;
+        drop(file);
+
+        std::fs::rename(&temp_path, &path)?;
+        sync_directory(directory)
+    }
+
+    /// 매니페스트가 없는 디렉토리에서 `walN.log` 파일 이름을 순번으로 정렬해 다시 만든다.
+    /// 가장 큰 순번을 활성 세그먼트로 본다. 봉인된 세그먼트의 LSN 은 footer 의 엔트리 수로 다시 센다.
+    pub fn rebuild(directory: &Path) -> Result<Self, Error> {
+        let mut sequences = list_segments(directory)?;
+
+        let sequence = sequences.pop().unwrap_or(1);
+
+        let mut sealed_lsn = Lsn::ZERO;
+        for &sealed in &sequences {
+            if let Some(footer) = SegmentFooter::read(&segment_path(directory, sealed as usize), sealed)? {
+                sealed_lsn += footer.entry_count;
+            }
+        }
+
+        Ok(Self { sequence, sealed_segments: sequences, last_checkpoint: None, sealed_lsn, transaction_id_limit: 0, applied_lsn: Lsn::ZERO })
+    }
+
+    /// 봉인된 세그먼트의 엔트리 수를 빼서 첫 세그먼트의 첫 엔트리 바로 앞 LSN 을 되짚는다
+    pub fn first_lsn(&self, directory: &Path) -> Result<Lsn, Error> {
+        let mut lsn = self.sealed_lsn;
+        for &sequence in &self.sealed_segments {
+            match SegmentFooter::read(&segment_path(directory, sequence as usize), sequence) {
+                Ok(Some(footer)) => lsn = lsn.saturating_sub(footer.entry_count),
+                Ok(None) => {},
+                Err(e) if e.kind() == ErrorKind::NotFound => {},
+                Err(e) => return Err(e),
+            }
+        }
+
+        Ok(lsn)
+    }
+
+    /// 봉인된 세그먼트와 활성 세그먼트를 순서대로
+    pub fn live_segments(&self) -> impl Iterator<Item = u64> + '_ {
+        self.sealed_segments.iter().copied().chain(std::iter::once(self.sequence))
+    }
+}
+
+/// 디렉토리의 `walN.log` 순번들을 `read_dir` 순서가 아니라 숫자 순서로 정렬해서 반환
+pub fn list_segments(directory: &Path) -> Result<Vec<u64>, Error> {
+    let mut sequences = std::fs::read_dir(directory)?
+        .filter_map(|entry| entry.ok())
+        .filter_map(|entry| parse_segment_sequence(&entry.path()))
+        .collect::<Vec<_>>();
+    sequences.sort_unstable();
+
+    Ok(sequences)
+}
+
+/// `walN.log` 에서 `N` 을 꺼낸다
+pub fn parse_segment_sequence(path: &Path) -> Option<u64> {
+    path.file_name()?
+        .to_str()?
+        .strip_prefix("wal")?
+        .strip_suffix(".log")?
+        .parse()
+        .ok()
+}
+
+#[cfg(test)]
+mod manifest_tests {
+    use super::{list_segments, parse_segment_sequence, Manifest};
+    use crate::wal::lsn::Lsn;
+    use crate::wal::test_utils::temp_directory;
+    use std::path::Path;
+
+    #[test]
+    fn test_manifest_roundtrip() {
+        let directory = temp_directory("manifest");
+        assert!(Manifest::load(&directory).unwrap().is_none());
+
+        let manifest = Manifest { sequence: 4, sealed_segments: vec![1, 2, 3], last_checkpoint: Some(3), sealed_lsn: Lsn(12), transaction_id_limit: 1024, applied_lsn: Lsn(9) };
+        manifest.save(&directory).unwrap();
+
+        assert_eq!(Manifest::load(&directory).unwrap(), Some(manifest));
+    }
+
+    #[test]
+    fn test_rebuild_sorts_by_sequence() {
+        let directory = temp_directory("manifest_rebuild");
+        for sequence in [10, 2, 1] {
+            std::fs::write(directory.join(format!("wal{}.log", sequence)), b"").unwrap();
+        }
+        std::fs::write(directory.join("wal3.log.tmp"), b"").unwrap();
+
+        assert_eq!(list_segments(&directory).unwrap(), vec![1, 2, 10]);
+
+        let manifest = Manifest::rebuild(&directory).unwrap();
+        assert_eq!(manifest.sequence, 10);
+        assert_eq!(manifest.sealed_segments, vec![1, 2]);
+
+        assert_eq!(parse_segment_sequence(Path::new("wal12.log")), Some(12));
+        assert_eq!(parse_segment_sequence(Path::new("notes.log")), None);
+    }
+}
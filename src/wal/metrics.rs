@@ -0,0 +1,278 @@
+use std::time::Duration;
+
+/// 버킷 `i` 는 `2^(i-1)` 초과 `2^i` 마이크로초 이하를 센다 (버킷 0 은 1 마이크로초 이하). 마지막 버킷은 그 위를 모두 센다.
+const LATENCY_BUCKETS: usize = 32;
+
+/// [`WALManager::metrics`](super::core::WALManager::metrics) 가 돌려주는 누적 지표. 연 뒤부터 센다.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct WALMetrics {
+    /// append 한 엔트리 수 (체크포인트 엔트리는 빼고)
+    pub entries_appended: u64,
+    /// 인코딩해서 세그먼트에 쓴 프레임 크기의 합. 블록으로 묶는다면 압축하기 전의 크기다.
+    pub bytes_written: u64,
+    pub fsyncs: u64,
+    pub fsync_latency: LatencyHistogram,
+    /// 봉인하고 다음 세그먼트로 넘어간 횟수
+    pub rotations: u64,
+    /// 세그먼트 파일을 만들거나 옮기거나 지운 뒤 WAL 디렉토리를 fsync 한 횟수 (매니페스트 저장은 빼고)
+    pub directory_syncs: u64,
+    /// 열면서 매니페스트와 세그먼트를 읽는 데 걸린 시간
+    pub recovery_time: Duration,
+}
+
+/// 2 의 거듭제곱 마이크로초 단위로 나눈 지연 시간 히스토그램
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct LatencyHistogram {
+    buckets: [u64; LATENCY_BUCKETS],
+    sum: Duration,
+}
+
+impl Default for LatencyHistogram {
+    fn default() -> Self {
+        Self { buckets: [0; LATENCY_BUCKETS], sum: Duration::ZERO }
+    }
+}
+
+impl LatencyHistogram {
+    pub fn record(&mut self, latency: Duration) {
+        // 소수점 아래는 올려서 `2^i` 마이크로초를 조금이라도 넘으면 다음 버킷에 센다
+        let micros = latency.as_nanos().div_ceil(1000).min(u64::MAX as u128) as u64;
+        let bucket = (u64::BITS - micros.saturating_sub(1).leading_zeros()) as usize;
+        self.buckets[bucket.min(LATENCY_BUCKETS - 1)] += 1;
+        self.sum += latency;
+    }
+
+    pub fn count(&self) -> u64 {
+        self.buckets.iter().sum()
+    }
+
+    pub fn sum(&self) -> Duration {
+        self.sum
+    }
+
+    pub fn mean(&self) -> Duration {
+        match self.count() {
+            0 => Duration::ZERO,
+            count => self.sum / count as u32,
+        }
+    }
+
+    /// `quantile` (0.0 ~ 1.0) 번째 값이 든 버킷의 상한. 기록이 없다면 0.
+    pub fn percentile(&self, quantile: f64) -> Duration {
+        let count = self.count();
+        if count == 0 {
+            return Duration::ZERO;
+        }
+
+        let rank = ((count as f64 * quantile.clamp(0.0, 1.0)).ceil() as u64).max(1);
+        let mut seen = 0;
+        let bucket = self.buckets.iter().position(|&n| {
+            seen += n;
+            seen >= rank
+        }).unwrap_or(LATENCY_BUCKETS - 1);
+
+        Duration::from_micros(1 << bucket)
+    }
+
+    /// 버킷별 개수 (`LATENCY_BUCKETS` 개)
+    pub fn buckets(&self) -> &[u64] {
+        &self.buckets
+    }
+}
+
+#[cfg(feature = "metrics-prometheus")]
+impl WALMetrics {
+    /// Prometheus 텍스트 형식(0.0.4)으로 `out` 끝에 붙인다. `segment_count` 는 봉인된 세그먼트와 활성 세그먼트의 수다.
+    ///
+    /// 레지스트리를 거치지 않고 텍스트만 만들므로, 스크레이프 핸들러가 그대로 돌려주거나 다른 지표 뒤에 이어 붙인다.
+    pub fn encode_prometheus(&self, segment_count: u64, out: &mut String) {
+        use std::fmt::Write;
+
+        let mut metric = |name: &str, kind: &str, help: &str, value: String| {
+            let _ = writeln!(out, "# HELP {} {}\n# TYPE {} {}\n{} {}", name, help, name, kind, name, value);
+        };
+        metric("wal_appends_total", "counter", "Entries appended to the WAL.", self.entries_appended.to_string());
+        metric("wal_bytes_written_total", "counter", "Encoded frame bytes written to segments.", self.bytes_written.to_string());
+        metric("wal_rotations_total", "counter", "Segments sealed and rotated.", self.rotations.to_string());
+        metric("wal_segment_count", "gauge", "Sealed and active segments.", segment_count.to_string());
+        metric("wal_recovery_seconds", "gauge", "Time spent loading the WAL when it was opened.", self.recovery_time.as_secs_f64().to_string());
+
+        let histogram = &self.fsync_latency;
+        let _ = writeln!(out, "# HELP wal_fsync_seconds Latency of WAL fsyncs.\n# TYPE wal_fsync_seconds histogram");
+        let mut cumulative = 0;
+        for (count, bound) in histogram.buckets().iter().zip(latency_bounds()) {
+            cumulative += count;
+            let _ = writeln!(out, "wal_fsync_seconds_bucket{{le=\"{}\"}} {}", bound, cumulative);
+        }
+        let _ = writeln!(out, "wal_fsync_seconds_bucket{{le=\"+Inf\"}} {}", histogram.count());
+        let _ = writeln!(out, "wal_fsync_seconds_sum {}", histogram.sum().as_secs_f64());
+        let _ = writeln!(out, "wal_fsync_seconds_count {}", histogram.count());
+    }
+}
+
+/// 마지막 버킷을 뺀 버킷들의 상한 (초)
+#[cfg(feature = "metrics-prometheus")]
+fn latency_bounds() -> impl Iterator<Item = f64> {
+    (0..LATENCY_BUCKETS - 1).map(|bucket| Duration::from_micros(1 << bucket).as_secs_f64())
+}
+
+/// `prometheus` 레지스트리에 등록한 WAL 지표 ([`WALBuilder::set_prometheus_registry`](super::core::WALBuilder::set_prometheus_registry))
+///
+/// 매니저가 [`WALMetrics`] 를 갱신할 때 같이 갱신하므로 스크레이프할 때 매니저를 거치지 않는다.
+#[cfg(feature = "metrics-prometheus")]
+pub(crate) struct PrometheusMetrics {
+    appends: prometheus::IntCounter,
+    bytes_written: prometheus::IntCounter,
+    rotations: prometheus::IntCounter,
+    segment_count: prometheus::IntGauge,
+    fsync_seconds: prometheus::Histogram,
+}
+
+#[cfg(feature = "metrics-prometheus")]
+impl PrometheusMetrics {
+    pub(crate) fn register(registry: &prometheus::Registry) -> prometheus::Result<Self> {
+        use prometheus::{Histogram, HistogramOpts, IntCounter, IntGauge};
+
+        let metrics = Self {
+            appends: IntCounter::new("wal_appends_total", "Entries appended to the WAL.")?,
+            bytes_written: IntCounter::new("wal_bytes_written_total", "Encoded frame bytes written to segments.")?,
+            rotations: IntCounter::new("wal_rotations_total", "Segments sealed and rotated.")?,
+            segment_count: IntGauge::new("wal_segment_count", "Sealed and active segments.")?,
+            fsync_seconds: Histogram::with_opts(
+                HistogramOpts::new("wal_fsync_seconds", "Latency of WAL fsyncs.").buckets(latency_bounds().collect()),
+            )?,
+        };
+        registry.register(Box::new(metrics.appends.clone()))?;
+        registry.register(Box::new(metrics.bytes_written.clone()))?;
+        registry.register(Box::new(metrics.rotations.clone()))?;
+        registry.register(Box::new(metrics.segment_count.clone()))?;
+        registry.register(Box::new(metrics.fsync_seconds.clone()))?;
+
+        Ok(metrics)
+    }
+
+    pub(crate) fn record_appends(&self, entries: u64, bytes: u64) {
+        self.appends.inc_by(entries);
+        self.bytes_written.inc_by(bytes);
+    }
+
+    pub(crate) fn record_fsync(&self, latency: Duration) {
+        self.fsync_seconds.observe(latency.as_secs_f64());
+    }
+
+    pub(crate) fn record_rotation(&self) {
+        self.rotations.inc();
+    }
+
+    pub(crate) fn set_segment_count(&self, segment_count: u64) {
+        self.segment_count.set(segment_count as i64);
+    }
+}
+
+#[cfg(test)]
+mod metrics_tests {
+    use std::time::Duration;
+
+    use super::LatencyHistogram;
+    use crate::wal::core::WALManager;
+    use crate::wal::sync::SyncPolicy;
+    use crate::wal::test_utils::{insert_entry, temp_directory};
+
+    #[test]
+    fn test_latency_histogram() {
+        let mut histogram = LatencyHistogram::default();
+        assert_eq!(histogram.percentile(0.99), Duration::ZERO);
+
+        for micros in [0, 3, 3, 3, 100, 5000] {
+            histogram.record(Duration::from_micros(micros));
+        }
+        histogram.record(Duration::from_secs(1 << 40));
+
+        assert_eq!(histogram.count(), 7);
+        assert_eq!(histogram.buckets()[0], 1);
+        assert_eq!(histogram.buckets()[2], 3);
+        assert_eq!(histogram.percentile(0.5), Duration::from_micros(4));
+        assert_eq!(histogram.percentile(0.8), Duration::from_micros(8192));
+        assert_eq!(histogram.buckets()[31], 1);
+
+        // 상한과 같은 값은 그 버킷에 든다
+        let mut histogram = LatencyHistogram::default();
+        histogram.record(Duration::from_micros(1));
+        histogram.record(Duration::from_micros(4));
+        histogram.record(Duration::from_nanos(4001));
+        assert_eq!(&histogram.buckets()[..4], &[1, 0, 1, 1]);
+    }
+
+    #[cfg(feature = "metrics-prometheus")]
+    #[test]
+    fn test_encode_prometheus() {
+        let mut metrics = super::WALMetrics { entries_appended: 3, ..Default::default() };
+        metrics.fsync_latency.record(Duration::from_micros(3));
+        metrics.fsync_latency.record(Duration::from_micros(8));
+
+        let mut text = String::new();
+        metrics.encode_prometheus(2, &mut text);
+        assert!(text.contains("# TYPE wal_appends_total counter\nwal_appends_total 3\n"));
+        assert!(text.contains("wal_segment_count 2\n"));
+        assert!(text.contains("wal_fsync_seconds_bucket{le=\"0.000002\"} 0\n"));
+        assert!(text.contains("wal_fsync_seconds_bucket{le=\"0.000004\"} 1\n"));
+        assert!(text.contains("wal_fsync_seconds_bucket{le=\"0.000008\"} 2\n"));
+        assert!(text.contains("wal_fsync_seconds_bucket{le=\"+Inf\"} 2\nwal_fsync_seconds_sum 0.000011\nwal_fsync_seconds_count 2\n"));
+    }
+
+    #[cfg(feature = "metrics-prometheus")]
+    #[test]
+    fn test_register_prometheus_metrics() {
+        use prometheus::Registry;
+
+        let registry = Registry::new();
+        let builder = |name| WALManager::builder()
+            .set_directory(temp_directory(name))
+            .set_sync_policy(SyncPolicy::Always)
+            .set_prometheus_registry(&registry);
+        let mut wal_manager = builder("metrics_prometheus").build().expect("Cannot create WALManager");
+        wal_manager.append_log(insert_entry(vec![1; 16])).unwrap();
+        wal_manager.append_log(insert_entry(vec![2; 16])).unwrap();
+        wal_manager.checkpoint().unwrap();
+
+        let families = registry.gather();
+        let family = |name: &str| families.iter().find(|family| family.name() == name).unwrap().get_metric()[0].clone();
+        assert_eq!(family("wal_appends_total").get_counter().get_value(), 2.0);
+        assert_eq!(family("wal_bytes_written_total").get_counter().get_value(), wal_manager.metrics().bytes_written as f64);
+        assert_eq!(family("wal_rotations_total").get_counter().get_value(), 1.0);
+        assert_eq!(family("wal_segment_count").get_gauge().get_value(), 2.0);
+        assert_eq!(family("wal_fsync_seconds").get_histogram().get_sample_count(), wal_manager.metrics().fsyncs);
+
+        // 같은 레지스트리에 같은 이름을 다시 등록하지 않는다
+        assert!(matches!(builder("metrics_prometheus_duplicate").build(), Err(crate::wal::error::WALError::InvalidConfig(_))));
+    }
+
+    #[test]
+    fn test_manager_metrics() {
+        let directory = temp_directory("metrics");
+        let builder = || WALManager::builder()
+            .set_directory(directory.clone())
+            .set_sync_policy(SyncPolicy::Never);
+        let mut wal_manager = builder().build().expect("Cannot create WALManager");
+        let entry = insert_entry(vec![1; 16]);
+
+        wal_manager.append_log(entry.clone()).unwrap();
+        wal_manager.append_logs(vec![entry.clone(), entry.clone()]).unwrap();
+        wal_manager.sync().unwrap();
+        wal_manager.checkpoint().unwrap();
+        wal_manager.append_many(vec![entry; 2]).unwrap();
+
+        let metrics = wal_manager.metrics();
+        assert_eq!(metrics.entries_appended, 5);
+        assert!(metrics.bytes_written > 5 * 16);
+        assert_eq!(metrics.fsyncs, 2);
+        assert_eq!(metrics.fsync_latency.count(), 2);
+        assert_eq!(metrics.rotations, 1);
+        drop(wal_manager);
+
+        // 다시 열면 새로 센다
+        let wal_manager = builder().build().expect("Cannot reopen WALManager");
+        assert_eq!(wal_manager.metrics().entries_appended, 0);
+        assert!(wal_manager.metrics().recovery_time > Duration::ZERO);
+    }
+}
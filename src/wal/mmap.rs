@@ -0,0 +1,184 @@
+use std::fs::File;
+use std::io;
+use std::ops::Deref;
+use std::path::Path;
+
+/// 파일 전체를 읽기 전용으로 매핑한 영역
+///
+/// `read()` 로 BufReader 를 거쳐 다시 복사하지 않고 페이지 캐시에서 바로 읽는다.
+/// 매핑한 동안 파일이 잘리면 접근할 때 SIGBUS 가 나므로 더 이상 바뀌지 않는 봉인된 세그먼트에만 쓴다.
+pub struct MappedFile {
+    ptr: *const u8,
+    len: usize,
+}
+
+// 읽기 전용 매핑이므로 여러 스레드에서 함께 읽어도 된다
+unsafe impl Send for MappedFile {}
+unsafe impl Sync for MappedFile {}
+
+impl MappedFile {
+    /// 처음부터 끝까지 차례로 읽을 것이라고 커널에 알려서 미리 읽어들이도록 한다
+    pub fn open(path: &Path) -> io::Result<Self> {
+        let file = File::open(path)?;
+        let len = file.metadata()?.len() as usize;
+
+        // 길이가 0 인 매핑은 만들 수 없다
+        if len == 0 {
+            return Ok(Self { ptr: std::ptr::NonNull::dangling().as_ptr(), len });
+        }
+
+        let ptr = sys::map(&file, len)?;
+        Ok(Self { ptr, len })
+    }
+}
+
+impl Deref for MappedFile {
+    type Target = [u8];
+
+    fn deref(&self) -> &[u8] {
+        unsafe { std::slice::from_raw_parts(self.ptr, self.len) }
+    }
+}
+
+impl AsRef<[u8]> for MappedFile {
+    fn as_ref(&self) -> &[u8] {
+        self
+    }
+}
+
+impl Drop for MappedFile {
+    fn drop(&mut self) {
+        if self.len > 0 {
+            sys::unmap(self.ptr, self.len);
+        }
+    }
+}
+
+#[cfg(unix)]
+mod sys {
+    use std::ffi::c_void;
+    use std::fs::File;
+    use std::io;
+    use std::os::raw::c_int;
+    use std::os::unix::io::AsRawFd;
+
+    const PROT_READ: c_int = 1;
+    const MAP_PRIVATE: c_int = 2;
+    const MADV_SEQUENTIAL: c_int = 2;
+    const MADV_WILLNEED: c_int = 3;
+
+    extern "C" {
+        fn mmap(addr: *mut c_void, len: usize, prot: c_int, flags: c_int, fd: c_int, offset: i64) -> *mut c_void;
+        fn munmap(addr: *mut c_void, len: usize) -> c_int;
+        fn madvise(addr: *mut c_void, len: usize, advice: c_int) -> c_int;
+    }
+
+    pub fn map(file: &File, len: usize) -> io::Result<*const u8> {
+        let ptr = unsafe { mmap(std::ptr::null_mut(), len, PROT_READ, MAP_PRIVATE, file.as_raw_fd(), 0) };
+        // MAP_FAILED
+        if ptr as isize == -1 {
+            return Err(io::Error::last_os_error());
+        }
+
+        // 힌트일 뿐이므로 실패해도 읽는 데는 문제가 없다
+        unsafe {
+            madvise(ptr, len, MADV_SEQUENTIAL);
+            madvise(ptr, len, MADV_WILLNEED);
+        }
+
+        Ok(ptr as *const u8)
+    }
+
+    pub fn unmap(ptr: *const u8, len: usize) {
+        unsafe {
+            munmap(ptr as *mut c_void, len);
+        }
+    }
+}
+
+#[cfg(windows)]
+mod sys {
+    use std::ffi::c_void;
+    use std::fs::File;
+    use std::io;
+    use std::os::windows::io::AsRawHandle;
+
+    const PAGE_READONLY: u32 = 0x02;
+    const FILE_MAP_READ: u32 = 0x04;
+
+    #[link(name = "kernel32")]
+    extern "system" {
+        fn CreateFileMappingW(file: *mut c_void, attributes: *mut c_void, protect: u32, high: u32, low: u32, name: *const u16) -> *mut c_void;
+        fn MapViewOfFile(mapping: *mut c_void, access: u32, high: u32, low: u32, len: usize) -> *mut c_void;
+        fn UnmapViewOfFile(address: *const c_void) -> i32;
+        fn CloseHandle(handle: *mut c_void) -> i32;
+    }
+
+    /// Windows 에는 madvise 에 해당하는 힌트를 주지 않는다
+    pub fn map(file: &File, len: usize) -> io::Result<*const u8> {
+        let mapping = unsafe {
+            CreateFileMappingW(file.as_raw_handle() as *mut c_void, std::ptr::null_mut(), PAGE_READONLY, 0, 0, std::ptr::null())
+        };
+        if mapping.is_null() {
+            return Err(io::Error::last_os_error());
+        }
+
+        // 뷰가 매핑 객체를 붙잡고 있으므로 핸들은 바로 닫아도 된다
+        let ptr = unsafe { MapViewOfFile(mapping, FILE_MAP_READ, 0, 0, len) };
+        let error = io::Error::last_os_error();
+        unsafe {
+            CloseHandle(mapping);
+        }
+
+        if ptr.is_null() {
+            return Err(error);
+        }
+
+        Ok(ptr as *const u8)
+    }
+
+    pub fn unmap(ptr: *const u8, _len: usize) {
+        unsafe {
+            UnmapViewOfFile(ptr as *const c_void);
+        }
+    }
+}
+
+/// 매핑을 지원하지 않는 플랫폼은 파일을 한 번에 읽어들인다
+#[cfg(not(any(unix, windows)))]
+mod sys {
+    use std::fs::File;
+    use std::io::{self, Read};
+
+    pub fn map(mut file: &File, len: usize) -> io::Result<*const u8> {
+        let mut bytes = Vec::with_capacity(len);
+        file.read_to_end(&mut bytes)?;
+        bytes.resize(len, 0);
+
+        Ok(Box::into_raw(bytes.into_boxed_slice()) as *const u8)
+    }
+
+    pub fn unmap(ptr: *const u8, len: usize) {
+        unsafe {
+            drop(Box::from_raw(std::ptr::slice_from_raw_parts_mut(ptr as *mut u8, len)));
+        }
+    }
+}
+
+#[cfg(test)]
+mod mmap_tests {
+    use super::MappedFile;
+    use crate::wal::test_utils::temp_directory;
+
+    #[test]
+    fn test_map_whole_file() {
+        let directory = temp_directory("mmap");
+        let path = directory.join("wal1.log");
+
+        std::fs::write(&path, b"0123456789").unwrap();
+        assert_eq!(&*MappedFile::open(&path).unwrap(), b"0123456789");
+
+        std::fs::write(&path, b"").unwrap();
+        assert!(MappedFile::open(&path).unwrap().is_empty());
+    }
+}
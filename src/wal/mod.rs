@@ -1 +1,106 @@
-pub mod core;
\ No newline at end of file
+pub mod actor;
+pub mod block;
+pub mod block_cache;
+pub mod checkpoint;
+pub mod checksum;
+pub mod compaction;
+pub mod compression;
+pub mod core;
+pub mod double_write;
+pub mod error;
+pub mod flags;
+pub mod flusher;
+pub mod frame;
+pub mod group_commit;
+pub mod index;
+pub mod kv;
+pub mod lock;
+pub mod lsn;
+pub mod manifest;
+pub mod metrics;
+pub mod mmap;
+pub mod quarantine;
+pub mod queue;
+pub mod quota;
+pub mod rate_limit;
+pub mod reader;
+pub mod record;
+pub mod repair;
+pub mod retry;
+pub mod schema;
+pub mod segment;
+pub mod shared;
+pub mod stall;
+pub mod sync;
+pub mod tail;
+pub mod trace;
+pub mod transaction;
+pub mod uring;
+pub mod varint;
+pub mod verify;
+pub mod watch;
+pub mod writer;
+
+#[cfg(test)]
+pub(crate) mod test_utils {
+    use super::core::{EntryType, Metadata, WALEntry, WALManager};
+    use super::flags::EntryFlags;
+    use std::alloc::{GlobalAlloc, Layout, System};
+    use std::cell::Cell;
+    use std::path::PathBuf;
+
+    /// 테스트가 나란히 돌아도 서로 섞이지 않도록 스레드마다 할당 횟수를 센다 ([`count_allocations`])
+    struct CountingAllocator;
+
+    thread_local! {
+        static ALLOCATIONS: Cell<usize> = const { Cell::new(0) };
+    }
+
+    unsafe impl GlobalAlloc for CountingAllocator {
+        unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+            ALLOCATIONS.with(|allocations| allocations.set(allocations.get() + 1));
+            System.alloc(layout)
+        }
+
+        unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+            System.dealloc(ptr, layout)
+        }
+
+        unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+            ALLOCATIONS.with(|allocations| allocations.set(allocations.get() + 1));
+            System.realloc(ptr, layout, new_size)
+        }
+    }
+
+    #[global_allocator]
+    static ALLOCATOR: CountingAllocator = CountingAllocator;
+
+    /// 이 스레드에서 `f` 가 힙을 잡거나 늘린 횟수
+    pub(crate) fn count_allocations<T>(f: impl FnOnce() -> T) -> (T, usize) {
+        let before = ALLOCATIONS.with(Cell::get);
+        let result = f();
+
+        (result, ALLOCATIONS.with(Cell::get) - before)
+    }
+
+    pub(crate) fn temp_directory(name: &str) -> PathBuf {
+        let directory = std::env::temp_dir()
+            .join(format!("wal-test-{}-{}", std::process::id(), name));
+
+        let _ = std::fs::remove_dir_all(&directory);
+        std::fs::create_dir_all(&directory).expect("Cannot create test directory");
+
+        directory
+    }
+    /// 트랜잭션에 속하지 않고 메타데이터가 없는 `Insert` 엔트리
+    pub(crate) fn insert_entry(data: Vec<u8>) -> WALEntry {
+        WALEntry {
+            entry_type: EntryType::Insert,
+            data: Some(data),
+            timestamp: WALManager::get_current_secs(),
+            transaction_id: 0,
+            metadata: Metadata::new(),
+            flags: EntryFlags::NONE
+        }
+    }
+}
@@ -0,0 +1,88 @@
+use std::fs::File;
+use std::io::{self, Error, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+use super::segment::segment_path;
+use super::sync::sync_directory;
+
+pub const QUARANTINE_DIRECTORY: &str = "quarantine";
+
+pub fn quarantine_directory(directory: &Path) -> PathBuf {
+    directory.join(QUARANTINE_DIRECTORY)
+}
+
+/// 세그먼트의 `offset` 부터 끝까지를 `quarantine/walN.<offset>.bin` (이미 있다면 `walN.<offset>-<n>.bin`) 으로 옮기고,
+/// 어디서 왜 치웠는지를 `key=value` 줄로 적은 `quarantine/walN.<offset>.meta` 를 함께 남긴다.
+///
+/// `offset` 이 0 이면 세그먼트 파일을 통째로 rename 해서 옮기고, 아니면 복사만 하므로 원본을 자르는 건 호출하는 쪽의 몫이다.
+pub fn quarantine(directory: &Path, sequence: u64, offset: u64, reason: &str) -> Result<PathBuf, Error> {
+    let quarantine = quarantine_directory(directory);
+    std::fs::create_dir_all(&quarantine)?;
+
+    let path = segment_path(directory, sequence as usize);
+    let length = std::fs::metadata(&path)?.len();
+    // 같은 자리를 다시 격리하더라도 이전에 격리한 내용을 덮어쓰지 않는다
+    let target = (0..)
+        .map(|attempt| match attempt {
+            0 => quarantine.join(format!("wal{}.{}.bin", sequence, offset)),
+            _ => quarantine.join(format!("wal{}.{}-{}.bin", sequence, offset, attempt)),
+        })
+        .find(|target| !target.exists())
+        .unwrap();
+
+    if offset == 0 {
+        std::fs::rename(&path, &target)?;
+    } else {
+        let mut source = File::open(&path)?;
+        source.seek(SeekFrom::Start(offset))?;
+        let mut file = File::create(&target)?;
+        io::copy(&mut source, &mut file)?;
+        file.sync_all()?;
+    }
+
+    let quarantined_at = SystemTime::now()
+        .duration_since(SystemTime::UNIX_EPOCH)
+        .map_or(0.0, |elapsed| elapsed.as_secs_f64());
+    let mut meta = File::create(target.with_extension("meta"))?;
+    write!(
+        meta,
+        "segment={}\noffset={}\nlength={}\nreason={}\nquarantined_at={}\n",
+        sequence, offset, length - offset, reason, quarantined_at,
+    )?;
+    meta.sync_all()?;
+
+    sync_directory(&quarantine)?;
+    sync_directory(directory)?;
+
+    Ok(target)
+}
+
+#[cfg(test)]
+mod quarantine_tests {
+    use super::{quarantine, quarantine_directory};
+    use crate::wal::test_utils::temp_directory;
+
+    #[test]
+    fn test_quarantine_with_metadata() {
+        let directory = temp_directory("quarantine");
+        std::fs::write(directory.join("wal3.log"), b"0123456789").unwrap();
+
+        let target = quarantine(&directory, 3, 4, "checksum mismatch").unwrap();
+        assert_eq!(target, quarantine_directory(&directory).join("wal3.4.bin"));
+        assert_eq!(std::fs::read(&target).unwrap(), b"456789");
+        assert_eq!(std::fs::read(directory.join("wal3.log")).unwrap(), b"0123456789");
+
+        let meta = std::fs::read_to_string(target.with_extension("meta")).unwrap();
+        assert!(meta.starts_with("segment=3\noffset=4\nlength=6\nreason=checksum mismatch\n"));
+
+        // 통째로 옮기면 원본은 사라진다
+        quarantine(&directory, 3, 0, "invalid header").unwrap();
+        assert!(!directory.join("wal3.log").exists());
+        assert_eq!(std::fs::read(quarantine_directory(&directory).join("wal3.0.bin")).unwrap(), b"0123456789");
+
+        std::fs::write(directory.join("wal3.log"), b"abcdef").unwrap();
+        let target = quarantine(&directory, 3, 0, "invalid header").unwrap();
+        assert_eq!(target, quarantine_directory(&directory).join("wal3.0-1.bin"));
+    }
+}
@@ -0,0 +1,283 @@
+use std::error::Error;
+use std::ptr;
+use std::sync::atomic::{AtomicBool, AtomicPtr, AtomicUsize, Ordering};
+use std::sync::{Arc, OnceLock};
+use std::thread::{JoinHandle, Thread};
+
+use super::core::{WALEntry, WALManager};
+use super::lsn::Lsn;
+
+/// 쓰기 전용 스레드 하나가 [`WALManager`] 를 가지고, 여러 스레드가 [`WALWriterHandle`] 로 락 없이 엔트리를 넘긴다
+///
+/// 핸들은 엔트리를 lock-free 스택에 올려두기만 하고, 쓰기 스레드가 쌓인 엔트리를 한꺼번에 꺼내
+/// [`WALManager::append_logs`] 로 기록한다. 그래서 동기화 정책은 엔트리마다가 아니라 꺼낸 묶음마다 한 번 따진다.
+/// 엔트리의 순서는 스택에 올린 순서이고, 한 스레드가 넘긴 엔트리끼리는 넘긴 순서대로 기록된다.
+pub struct WALWriter {
+    shared: Arc<Shared>,
+    thread: Option<JoinHandle<WALManager>>,
+}
+
+/// [`WALWriter`] 에 엔트리를 넘기는 핸들. 복제해서 여러 스레드에 나눠준다.
+#[derive(Clone)]
+pub struct WALWriterHandle {
+    shared: Arc<Shared>,
+}
+
+struct Shared {
+    queue: Queue,
+    /// 쓰기 스레드. 핸들을 나눠주기 전에 채운다.
+    writer: OnceLock<Thread>,
+    stopped: AtomicBool,
+    /// 스택에 올리는 중인 핸들 수. 멈출 때 이것이 0 이 된 뒤에 마지막으로 비운다.
+    pushing: AtomicUsize,
+    /// 기다리지 않고 넘긴 엔트리를 쓰다가 처음 실패한 이유. 한 번 실패하면 그 뒤로는 받지 않는다.
+    failure: OnceLock<String>,
+}
+
+/// 엔트리를 넘긴 스레드가 LSN 을 기다리는 자리
+struct Completion {
+    thread: Thread,
+    result: OnceLock<Result<Lsn, String>>,
+}
+
+struct Node {
+    entry: WALEntry,
+    completion: Option<Arc<Completion>>,
+    next: *mut Node,
+}
+
+/// 여러 스레드가 CAS 로 올리고 한 스레드가 통째로 꺼내는 스택 (Treiber stack)
+///
+/// 꺼낼 때는 머리를 한 번에 바꿔치기하므로 노드 하나씩 꺼내는 스택의 ABA 문제가 생기지 않는다.
+struct Queue {
+    head: AtomicPtr<Node>,
+}
+
+// 노드는 올린 뒤로 꺼낸 스레드만 만진다
+unsafe impl Send for Queue {}
+unsafe impl Sync for Queue {}
+
+impl WALWriter {
+    pub fn new(mut manager: WALManager) -> Self {
+        let shared = Arc::new(Shared {
+            queue: Queue { head: AtomicPtr::new(ptr::null_mut()) },
+            writer: OnceLock::new(),
+            stopped: AtomicBool::new(false),
+            pushing: AtomicUsize::new(0),
+            failure: OnceLock::new(),
+        });
+
+        let thread = {
+            let shared = shared.clone();
+            std::thread::spawn(move || {
+                shared.run(&mut manager);
+                manager
+            })
+        };
+        let _ = shared.writer.set(thread.thread().clone());
+
+        Self { shared, thread: Some(thread) }
+    }
+
+    pub fn handle(&self) -> WALWriterHandle {
+        WALWriterHandle { shared: self.shared.clone() }
+    }
+
+    /// 쓰기 스레드를 멈추고 남은 엔트리까지 기록한 [`WALManager`] 를 돌려준다.
+    /// 그 뒤로 남은 핸들은 엔트리를 받지 않는다.
+    pub fn into_inner(mut self) -> WALManager {
+        self.stop().expect("writer thread is running")
+    }
+
+    fn stop(&mut self) -> Option<WALManager> {
+        let thread = self.thread.take()?;
+        self.shared.stopped.store(true, Ordering::SeqCst);
+        self.shared.wake();
+
+        Some(thread.join().expect("WAL writer thread panicked"))
+    }
+}
+
+impl Drop for WALWriter {
+    /// 스레드를 멈추고, 남은 엔트리의 fsync 는 `WALManager` 의 drop 에 맡긴다
+    fn drop(&mut self) {
+        self.stop();
+    }
+}
+
+impl WALWriterHandle {
+    /// 엔트리를 넘기고 쓰기 스레드가 기록할 때까지 기다린다. 기록한 LSN 을 반환하며,
+    /// 동기화 정책에 따라 아직 durable 하지 않을 수 있다 ([`WALManager::append_log`]).
+    pub fn append_log(&self, entry: WALEntry) -> Result<Lsn, Box<dyn Error>> {
+        let completion = Arc::new(Completion { thread: std::thread::current(), result: OnceLock::new() });
+        self.push(entry, Some(completion.clone()))?;
+
+        loop {
+            match completion.result.get() {
+                Some(result) => return result.clone().map_err(Into::into),
+                None => std::thread::park(),
+            }
+        }
+    }
+
+    /// 엔트리를 넘기기만 하고 기다리지 않는다. 기록하다 실패했다면 그 뒤의 호출이 오류를 돌려준다.
+    pub fn append_log_nowait(&self, entry: WALEntry) -> Result<(), Box<dyn Error>> {
+        if let Some(failure) = self.shared.failure.get() {
+            return Err(failure.clone().into());
+        }
+
+        self.push(entry, None)
+    }
+
+    fn push(&self, entry: WALEntry, completion: Option<Arc<Completion>>) -> Result<(), Box<dyn Error>> {
+        let shared = &self.shared;
+        // 멈추는 쪽은 `stopped` 를 켠 뒤 `pushing` 을 보므로, 둘 중 하나는 반드시 상대를 본다
+        shared.pushing.fetch_add(1, Ordering::SeqCst);
+        if shared.stopped.load(Ordering::SeqCst) {
+            shared.pushing.fetch_sub(1, Ordering::SeqCst);
+            return Err("WAL writer is stopped".into());
+        }
+
+        shared.queue.push(Node { entry, completion, next: ptr::null_mut() });
+        shared.pushing.fetch_sub(1, Ordering::SeqCst);
+        shared.wake();
+
+        Ok(())
+    }
+}
+
+impl Shared {
+    fn wake(&self) {
+        if let Some(writer) = self.writer.get() {
+            writer.unpark();
+        }
+    }
+
+    fn run(&self, manager: &mut WALManager) {
+        loop {
+            let nodes = self.queue.take();
+            if !nodes.is_empty() {
+                self.write(manager, nodes);
+                continue;
+            }
+
+            if self.stopped.load(Ordering::SeqCst) {
+                break;
+            }
+            std::thread::park();
+        }
+
+        // 멈추기 직전에 올라온 엔트리까지 쓴다
+        while self.pushing.load(Ordering::SeqCst) != 0 {
+            std::thread::yield_now();
+        }
+        let nodes = self.queue.take();
+        if !nodes.is_empty() {
+            self.write(manager, nodes);
+        }
+    }
+
+    fn write(&self, manager: &mut WALManager, nodes: Vec<Node>) {
+        let (entries, completions): (Vec<_>, Vec<_>) = nodes.into_iter().map(|node| (node.entry, node.completion)).unzip();
+        let count = entries.len() as u64;
+
+        let result = manager.append_logs(entries).map_err(|e| e.to_string());
+        if let Err(e) = &result {
+            if completions.iter().any(Option::is_none) {
+                let _ = self.failure.set(e.clone());
+            }
+        }
+
+        for (offset, completion) in (0..count).rev().zip(completions).filter_map(|(offset, completion)| Some((offset, completion?))) {
+            let _ = completion.result.set(result.clone().map(|last| Lsn(last.0 - offset)));
+            completion.thread.unpark();
+        }
+    }
+}
+
+impl Queue {
+    fn push(&self, node: Node) {
+        let node = Box::into_raw(Box::new(node));
+        let mut head = self.head.load(Ordering::Relaxed);
+        loop {
+            unsafe { (*node).next = head };
+            match self.head.compare_exchange_weak(head, node, Ordering::Release, Ordering::Relaxed) {
+                Ok(_) => return,
+                Err(current) => head = current,
+            }
+        }
+    }
+
+    /// 지금까지 올라온 노드를 모두 꺼내 올린 순서대로 돌려준다
+    fn take(&self) -> Vec<Node> {
+        let mut node = self.head.swap(ptr::null_mut(), Ordering::Acquire);
+        let mut nodes = Vec::new();
+        while !node.is_null() {
+            let boxed = unsafe { Box::from_raw(node) };
+            node = boxed.next;
+            nodes.push(*boxed);
+        }
+        nodes.reverse();
+
+        nodes
+    }
+}
+
+impl Drop for Queue {
+    fn drop(&mut self) {
+        self.take();
+    }
+}
+
+#[cfg(test)]
+mod queue_tests {
+    use std::collections::HashMap;
+
+    use super::WALWriter;
+    use crate::wal::core::{WALEntry, WALManager};
+    use crate::wal::lsn::Lsn;
+    use crate::wal::sync::SyncPolicy;
+    use crate::wal::test_utils::{insert_entry, temp_directory};
+
+    fn entry(thread: u8, value: u8) -> WALEntry {
+        insert_entry(vec![thread, value])
+    }
+
+    #[test]
+    fn test_concurrent_handles() {
+        let wal_manager = WALManager::builder()
+            .set_directory(temp_directory("writer_handle"))
+            .set_sync_policy(SyncPolicy::Never)
+            .build().expect("Cannot create WALManager");
+        let writer = WALWriter::new(wal_manager);
+
+        let threads = (0..8).map(|thread| {
+            let handle = writer.handle();
+            std::thread::spawn(move || {
+                (0..50).map(|value| match value % 2 {
+                    0 => Some(handle.append_log(entry(thread, value)).unwrap()),
+                    _ => handle.append_log_nowait(entry(thread, value)).map(|_| None).unwrap(),
+                }).collect::<Vec<_>>()
+            })
+        }).collect::<Vec<_>>();
+        let lsns = threads.into_iter().map(|thread| thread.join().unwrap()).collect::<Vec<_>>();
+
+        let handle = writer.handle();
+        let wal_manager = writer.into_inner();
+        assert_eq!(wal_manager.last_lsn(), Lsn(400));
+        assert!(handle.append_log(entry(0, 0)).is_err());
+
+        // 돌려받은 LSN 에 그 엔트리가 있고, 한 스레드의 엔트리는 넘긴 순서대로 남는다
+        let entries = wal_manager.recover().unwrap().collect::<Result<Vec<_>, _>>().unwrap();
+        let data = entries.iter().map(|(lsn, entry)| (*lsn, entry.data.clone().unwrap())).collect::<HashMap<_, _>>();
+        for (thread, lsns) in lsns.iter().enumerate() {
+            for (value, lsn) in lsns.iter().enumerate().filter_map(|(value, lsn)| Some((value, (*lsn)?))) {
+                assert_eq!(data[&lsn], vec![thread as u8, value as u8]);
+            }
+        }
+        for thread in 0..8 {
+            let values = entries.iter().filter_map(|(_, entry)| entry.data.as_ref().filter(|data| data[0] == thread).map(|data| data[1])).collect::<Vec<_>>();
+            assert_eq!(values, (0..50).collect::<Vec<_>>());
+        }
+    }
+}
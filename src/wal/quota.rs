@@ -0,0 +1,139 @@
+/// 세그먼트 파일 크기의 합이 [`DiskQuota::max_total_bytes`] 를 넘었을 때 할 일
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum QuotaPolicy {
+    /// 지우지 않고 append 를 [`WALError::QuotaExceeded`](super::error::WALError::QuotaExceeded) 로 거절한다.
+    /// `set_applied_lsn` 이나 `truncate_before` 로 자리가 나면 다시 받는다.
+    #[default]
+    Reject,
+    /// 모든 엔트리가 반영된(`applied_lsn` 이하) 봉인된 세그먼트를 오래된 것부터 지운다. `truncate_before` 와 같이
+    /// 보관 디렉토리가 있다면 그곳에 옮긴다. 반영되지 않은 세그먼트는 지우지 않으므로, 그것만으로 자리가 나지 않으면 거절한다.
+    /// [`WALBuilder::set_retain_applied_segments`](super::core::WALBuilder::set_retain_applied_segments) 로 반영된 세그먼트를 남겨둘 때 쓴다.
+    EvictOldest,
+}
+
+/// WAL 디렉토리가 쓸 디스크 용량의 상한 ([`WALBuilder::set_disk_quota`](super::core::WALBuilder::set_disk_quota))
+///
+/// append 직전에 봉인된 세그먼트와 활성 세그먼트의 크기를 합쳐서 따진다. 색인, 매니페스트, 재활용을 기다리는 파일은 세지 않는다.
+/// 배치(`append_logs`, `append_many`, `append_batch`)는 인코딩한 전체 길이로 따져서, 들어가지 않으면 하나도 쓰지 않고 거절한다.
+/// 활성 세그먼트는 지울 수 없으므로 `segment_max_bytes` 보다 커야 한다.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct DiskQuota {
+    pub max_total_bytes: u64,
+    pub policy: QuotaPolicy,
+}
+
+#[cfg(test)]
+mod quota_tests {
+    use super::{DiskQuota, QuotaPolicy};
+    use crate::wal::core::{WALEntry, WALManager};
+    use crate::wal::error::WALError;
+    use crate::wal::lsn::Lsn;
+    use crate::wal::test_utils::{insert_entry, temp_directory};
+
+    fn entry() -> WALEntry {
+        insert_entry(vec![1; 100])
+    }
+
+    #[test]
+    fn test_evict_oldest_segments() {
+        let mut wal_manager = WALManager::builder()
+            .set_directory(temp_directory("quota_evict"))
+            .set_segment_max_bytes(500)
+            .set_retain_applied_segments(true)
+            .set_disk_quota(DiskQuota { max_total_bytes: 2000, policy: QuotaPolicy::EvictOldest })
+            .build().expect("Cannot create WALManager");
+
+        for _ in 0..50 {
+            wal_manager.append_log(entry()).unwrap();
+            wal_manager.set_applied_lsn(wal_manager.last_lsn()).unwrap();
+            assert!(wal_manager.disk_usage() <= 2000);
+        }
+
+        // 오래된 세그먼트는 지워지고 마지막 엔트리들은 남는다
+        let entries = wal_manager.recover().unwrap().collect::<Result<Vec<_>, _>>().unwrap();
+        assert!(entries.len() < 50);
+        assert_eq!(entries.last().unwrap().0, wal_manager.last_lsn());
+    }
+
+    #[test]
+    fn test_evict_only_applied_segments() {
+        let mut wal_manager = WALManager::builder()
+            .set_directory(temp_directory("quota_evict_unapplied"))
+            .set_segment_max_bytes(500)
+            .set_disk_quota(DiskQuota { max_total_bytes: 2000, policy: QuotaPolicy::EvictOldest })
+            .build().expect("Cannot create WALManager");
+
+        // applied_lsn 이 0 이면 지울 세그먼트가 없으므로 거절하고, 쓴 엔트리는 모두 남는다
+        let error = loop {
+            if let Err(e) = wal_manager.append_log(entry()) {
+                break e;
+            }
+        };
+        assert!(matches!(error.downcast_ref::<WALError>(), Some(WALError::QuotaExceeded { max_total_bytes: 2000, .. })));
+
+        let entries = wal_manager.recover().unwrap().collect::<Result<Vec<_>, _>>().unwrap();
+        assert_eq!(Lsn(entries.len() as u64), wal_manager.last_lsn());
+        assert_eq!(entries.first().unwrap().0, Lsn(1));
+    }
+
+    #[test]
+    fn test_default_policy_rejects() {
+        assert_eq!(QuotaPolicy::default(), QuotaPolicy::Reject);
+    }
+
+    #[test]
+    fn test_reject_appends_over_quota() {
+        let mut wal_manager = WALManager::builder()
+            .set_directory(temp_directory("quota_reject"))
+            .set_segment_max_bytes(500)
+            .set_disk_quota(DiskQuota { max_total_bytes: 2000, policy: QuotaPolicy::Reject })
+            .build().expect("Cannot create WALManager");
+
+        let error = loop {
+            if let Err(e) = wal_manager.append_log(entry()) {
+                break e;
+            }
+        };
+        assert!(matches!(error.downcast_ref::<WALError>(), Some(WALError::QuotaExceeded { max_total_bytes: 2000, .. })));
+        let usage = wal_manager.disk_usage();
+
+        // 반영한 세그먼트를 지우면 다시 받는다
+        wal_manager.set_applied_lsn(wal_manager.last_lsn()).unwrap();
+        assert!(wal_manager.disk_usage() < usage);
+        wal_manager.append_log(entry()).unwrap();
+    }
+
+    #[test]
+    fn test_reject_batches_over_quota() {
+        let mut wal_manager = WALManager::builder()
+            .set_directory(temp_directory("quota_reject_batch"))
+            .set_segment_max_bytes(1500)
+            .set_disk_quota(DiskQuota { max_total_bytes: 2000, policy: QuotaPolicy::Reject })
+            .build().expect("Cannot create WALManager");
+        wal_manager.append_log(entry()).unwrap();
+        let usage = wal_manager.disk_usage();
+
+        // 첫 엔트리는 들어가더라도 배치 전체가 넘친다면 아무것도 쓰지 않는다
+        let error = wal_manager.append_logs(vec![entry(); 30]).unwrap_err();
+        assert!(matches!(error.downcast_ref::<WALError>(), Some(WALError::QuotaExceeded { max_total_bytes: 2000, .. })));
+        let error = wal_manager.append_many(vec![entry(); 30]).unwrap_err();
+        assert!(matches!(error.downcast_ref::<WALError>(), Some(WALError::QuotaExceeded { max_total_bytes: 2000, .. })));
+        assert_eq!(wal_manager.last_lsn(), Lsn(1));
+        assert_eq!(wal_manager.disk_usage(), usage);
+
+        assert_eq!(wal_manager.append_logs(vec![entry(); 5]).unwrap(), Lsn(6));
+        assert_eq!(wal_manager.recover().unwrap().count(), 6);
+    }
+    #[test]
+    fn test_disk_usage_without_opening_writer() {
+        let directory = temp_directory("quota_usage_read_only");
+        let mut wal_manager = WALManager::builder().set_directory(directory.clone()).build().expect("Cannot create WALManager");
+        wal_manager.append_log(entry()).unwrap();
+        let usage = wal_manager.disk_usage();
+        drop(wal_manager);
+
+        // 쓰기 핸들을 열지 않는 읽기 전용 매니저에서도 같은 크기를 센다
+        let wal_manager = WALManager::builder().set_directory(directory).set_read_only(true).build().expect("Cannot reopen WALManager");
+        assert_eq!(wal_manager.disk_usage(), usage);
+    }
+}
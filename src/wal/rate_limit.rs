@@ -0,0 +1,124 @@
+use std::time::{Duration, Instant};
+
+/// append 속도의 상한 ([`WALBuilder::set_rate_limit`](super::core::WALBuilder::set_rate_limit))
+///
+/// 넘으면 append 가 오류를 돌려주지 않고 그만큼 잠들었다가 기록한다. 체크포인트는 막지 않는다.
+/// 크기는 세그먼트에 쓰는 인코딩된 프레임의 길이로 센다.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct RateLimit {
+    pub bytes_per_sec: Option<u64>,
+    pub entries_per_sec: Option<u64>,
+    /// 쉬었다가 몰아서 쓸 수 있는 양. 초당 한도의 이 시간만큼까지 잠들지 않고 쓴다.
+    pub burst: Duration,
+}
+
+impl Default for RateLimit {
+    fn default() -> Self {
+        Self { bytes_per_sec: None, entries_per_sec: None, burst: Duration::from_secs(1) }
+    }
+}
+
+/// `RateLimit` 의 한도마다 하나씩 두는 토큰 버킷
+#[derive(Debug)]
+pub(crate) struct RateLimiter {
+    bytes: Option<TokenBucket>,
+    entries: Option<TokenBucket>,
+}
+
+impl RateLimiter {
+    pub(crate) fn new(limit: RateLimit) -> Self {
+        Self {
+            bytes: limit.bytes_per_sec.map(|rate| TokenBucket::new(rate, limit.burst)),
+            entries: limit.entries_per_sec.map(|rate| TokenBucket::new(rate, limit.burst)),
+        }
+    }
+
+    /// 한도 안에 들 때까지 잠들고 잠든 시간을 반환한다
+    pub(crate) fn throttle(&mut self, entries: usize, bytes: usize) -> Duration {
+        let now = Instant::now();
+        let wait = [(&mut self.entries, entries), (&mut self.bytes, bytes)].into_iter()
+            .filter_map(|(bucket, amount)| Some(bucket.as_mut()?.take(amount as u64, now)))
+            .max()
+            .unwrap_or_default();
+
+        if !wait.is_zero() {
+            std::thread::sleep(wait);
+        }
+
+        wait
+    }
+}
+
+#[derive(Debug)]
+struct TokenBucket {
+    rate: f64,
+    capacity: f64,
+    /// 음수면 빌려 쓴 만큼이 다시 찰 때까지 기다려야 한다
+    tokens: f64,
+    refilled: Instant,
+}
+
+impl TokenBucket {
+    fn new(rate: u64, burst: Duration) -> Self {
+        let rate = rate.max(1) as f64;
+        let capacity = rate * burst.as_secs_f64();
+
+        Self { rate, capacity, tokens: capacity, refilled: Instant::now() }
+    }
+
+    /// `amount` 를 꺼내고 그만큼이 찰 때까지 기다릴 시간을 반환한다. 한 번에 한도보다 많이 꺼내도 기다리기만 하면 된다.
+    fn take(&mut self, amount: u64, now: Instant) -> Duration {
+        let elapsed = now.saturating_duration_since(self.refilled).as_secs_f64();
+        self.tokens = (self.tokens + elapsed * self.rate).min(self.capacity);
+        self.refilled = now;
+
+        self.tokens -= amount as f64;
+        match self.tokens < 0.0 {
+            true => Duration::from_secs_f64(-self.tokens / self.rate),
+            false => Duration::ZERO,
+        }
+    }
+}
+
+#[cfg(test)]
+mod rate_limit_tests {
+    use std::time::{Duration, Instant};
+
+    use super::{RateLimit, TokenBucket};
+    use crate::wal::core::WALManager;
+    use crate::wal::sync::SyncPolicy;
+    use crate::wal::test_utils::{insert_entry, temp_directory};
+
+    #[test]
+    fn test_token_bucket() {
+        let mut bucket = TokenBucket::new(100, Duration::from_millis(100));
+        let now = Instant::now();
+
+        // 버스트만큼은 기다리지 않고, 그 뒤로는 빌려 쓴 만큼 기다린다
+        assert_eq!(bucket.take(10, now), Duration::ZERO);
+        assert!((bucket.take(5, now).as_secs_f64() - 0.05).abs() < 1e-6);
+
+        // 기다린 만큼 다시 차고, 버스트보다 많이 쌓이지는 않는다
+        assert_eq!(bucket.take(0, now + Duration::from_millis(60)), Duration::ZERO);
+        assert_eq!(bucket.take(10, now + Duration::from_secs(10)), Duration::ZERO);
+        assert!(bucket.take(1, now + Duration::from_secs(10)) > Duration::ZERO);
+    }
+
+    #[test]
+    fn test_throttle_appends() {
+        let mut wal_manager = WALManager::builder()
+            .set_directory(temp_directory("rate_limit"))
+            .set_sync_policy(SyncPolicy::Never)
+            .set_rate_limit(RateLimit { entries_per_sec: Some(200), burst: Duration::from_millis(50), ..Default::default() })
+            .build().expect("Cannot create WALManager");
+        let entry = insert_entry(vec![1; 16]);
+
+        // 버스트 10 개 뒤의 20 개는 초당 200 개로 100ms 가 걸린다
+        let started = Instant::now();
+        for _ in 0..20 {
+            wal_manager.append_log(entry.clone()).unwrap();
+        }
+        wal_manager.append_logs(vec![entry; 10]).unwrap();
+        assert!(started.elapsed() >= Duration::from_millis(90), "{:?}", started.elapsed());
+    }
+}
@@ -0,0 +1,1955 @@
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::fs::File;
+use std::io::{self, BufReader, Cursor, Error, ErrorKind, Read, Seek, SeekFrom};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::thread::JoinHandle;
+
+use super::block::{decode_block, next_frame, BlockHeader};
+use super::block_cache::BlockCache;
+use super::core::{EntryHeader, EntryType, Metadata, WALEntry, WALEntryRef};
+use super::frame::{FrameError, FrameReader};
+use super::flags::EntryFlags;
+use super::index::{IndexEntry, SegmentIndex};
+use super::lsn::Lsn;
+use super::manifest::Manifest;
+use super::mmap::MappedFile;
+use super::schema::{EntryFormat, LegacyEntry};
+use super::segment::{segment_path, SegmentFooter, SegmentHeader, FORMAT_VERSION, LEGACY_FORMAT_VERSION, SEGMENT_HEADER_SIZE};
+
+/// 복구 중에 깨진 프레임을 만났을 때의 처리 방식
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum RecoveryMode {
+    /// 끊긴 꼬리를 포함해 깨진 프레임이 하나라도 있으면 실패
+    Strict,
+    /// 첫 번째 깨진 프레임에서 멈추고 그 뒤는 버린다
+    #[default]
+    TruncateAtError,
+    /// 체크섬이 맞지 않는 프레임은 건너뛰고 계속 읽는다. 끊긴 프레임에서는 다음 세그먼트로 넘어간다.
+    SkipCorrupt,
+}
+
+/// 트랜잭션에 속한 엔트리 중 어디까지 읽는 쪽에 보여줄지 ([`WALReader::with_visibility`])
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Visibility {
+    /// 커밋 여부와 상관없이 기록된 그대로
+    #[default]
+    Raw,
+    /// 커밋된 트랜잭션의 엔트리와 트랜잭션에 속하지 않은 엔트리만. 중첩 트랜잭션은 조상까지 모두 커밋되어야 보인다.
+    ///
+    /// 순서는 기록된 순서 그대로이고, 저장점으로 되돌린 엔트리는 거르지 않는다 (필요하다면 `replay_committed`).
+    Committed,
+}
+
+/// 복구하면서 실제로 일어난 일을 운영자가 남기고 확인할 수 있도록 모은 통계
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct RecoveryReport {
+    pub segments_scanned: usize,
+    pub entries_replayed: u64,
+    /// 세그먼트 헤더와 온전히 읽은 프레임(패딩 포함)의 바이트 수
+    pub bytes_read: u64,
+    /// 활성 세그먼트 끝에서 중간에 끊긴 프레임
+    pub truncated_frames: usize,
+    /// 체크섬이 맞지 않아 멈춘 프레임
+    pub corrupt_frames: usize,
+    /// 마지막으로 돌려준 엔트리의 LSN
+    pub last_lsn: Lsn,
+    /// 마지막 체크포인트 엔트리의 LSN
+    pub last_checkpoint: Option<Lsn>,
+    /// `replay_committed` 에서 커밋되지 않아 버려진 트랜잭션 id (오름차순)
+    pub uncommitted_transactions: Vec<u64>,
+    /// `replay_committed` 에서 준비까지 마쳤지만 커밋도 중단도 기록되지 않은 트랜잭션 id (오름차순).
+    /// 버리지 않으므로 코디네이터에게 결정을 받아 남긴 뒤 다시 재생하거나 `filter_transactions` 로 읽어서 처리한다.
+    pub in_doubt_transactions: Vec<u64>,
+    /// `replay_committed` 에서 중단 엔트리를 만나 버려진 트랜잭션 id (기록된 순서)
+    pub aborted_transactions: Vec<u64>,
+    /// 버려지거나 저장점으로 되돌려진 트랜잭션 엔트리 수 (중단, 되돌리기 엔트리 포함)
+    pub discarded_entries: u64,
+}
+
+/// 복구 중에 만난 깨진 프레임의 위치
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct CorruptFrame {
+    pub segment: u64,
+    /// 깨진 프레임이 시작하는 위치
+    pub offset: u64,
+    /// 중간에 끊긴 프레임이면 `true`, 체크섬이 맞지 않는 프레임이면 `false`
+    pub truncated: bool,
+}
+
+/// 복구하면서 읽은 엔트리를 애플리케이션의 상태 기계에 넘겨주는 콜백 모음 ([`WALReader::visit`])
+///
+/// 체크포인트와 트랜잭션 커밋, 중단 엔트리는 `on_entry` 대신 각자의 콜백으로 가고, 나머지는 모두 `on_entry` 로 간다.
+pub trait WALVisitor {
+    fn on_entry(&mut self, lsn: Lsn, entry: WALEntry);
+
+    fn on_checkpoint(&mut self, _lsn: Lsn) {}
+
+    fn on_transaction_commit(&mut self, _lsn: Lsn, _transaction_id: u64) {}
+
+    fn on_transaction_abort(&mut self, _lsn: Lsn, _transaction_id: u64) {}
+
+    /// [`RecoveryMode`] 에 따라 처리하기 직전에 불린다. `Strict` 에서는 이 뒤에 재생이 실패한다.
+    fn on_corruption(&mut self, _frame: &CorruptFrame) {}
+}
+
+/// 세그먼트 파일 하나를 프레임 단위로 풀어내는 스트리밍 디코더
+///
+/// 파일 전체를 읽어 한 번에 디코딩하지 않으므로 세그먼트가 아무리 커도 메모리는 프레임 하나 크기만큼만 쓴다.
+/// 체크포인트 엔트리 뒤에 오는 footer 는 데이터의 끝으로 보고,
+/// 그 밖의 깨진 프레임은 [`RecoveryMode`] 에 따라 처리한다.
+pub struct SegmentReader<R> {
+    frames: FrameReader<R>,
+    mode: RecoveryMode,
+    checkpointed: bool,
+    finished: bool,
+    /// 건너뛴 프레임을 포함해 지금까지 지나온 프레임 수
+    position: u64,
+    damaged: bool,
+    truncated_frames: usize,
+    corrupt_frames: usize,
+    /// 아직 가져가지 않은 깨진 프레임
+    corruptions: Vec<CorruptFrame>,
+    /// 이 종류의 엔트리만 돌려준다
+    entry_types: Option<Vec<EntryType>>,
+    /// 컴팩션이 비운 엔트리도 돌려준다
+    compacted: bool,
+    /// 프레임마다 디코딩 버퍼를 새로 할당하지 않도록 재사용한다
+    buffer: bitcode::Buffer,
+    /// 엔트리 페이로드의 포맷 버전 (세그먼트 헤더)
+    format: EntryFormat,
+    /// 마지막으로 읽은 프레임의 페이로드. 프레임마다 다시 채워서 쓴다.
+    payload: Vec<u8>,
+    /// 마지막으로 읽은 엔트리의 헤더와 payload 안에서 data 가 시작하는 위치
+    current: Option<(EntryHeader, usize)>,
+    /// 포맷 1 은 data 를 따로 빌려줄 수 없어 통째로 디코딩한 엔트리를 들고 있는다
+    legacy: Option<WALEntry>,
+    /// 마지막으로 읽은 엔트리의 메타데이터. 메타데이터가 없는 엔트리는 비워둔다.
+    metadata: Metadata,
+    /// 엔트리 프레임을 블록으로 묶어서 기록한 세그먼트인지 (세그먼트 헤더)
+    blocked: bool,
+    /// 풀어둔 블록과 그 안에서 다음 프레임의 위치. 캐시에서 꺼냈다면 다른 리더와 함께 쓴다.
+    block: Arc<Vec<u8>>,
+    block_cursor: usize,
+    block_cache: Option<Arc<BlockCache>>,
+    /// 지금 읽고 있는 블록이 시작하는 곳
+    block_start: IndexEntry,
+    /// 풀기 전의 블록 프레임 payload
+    raw: Vec<u8>,
+    /// 이 위치 앞의 엔트리는 디코딩하지 않고 건너뛴다
+    skip_to: u64,
+}
+
+/// 세그먼트 파일을 읽어들이는 방식
+pub enum SegmentSource {
+    Buffered(BufReader<File>),
+    /// 봉인된 세그먼트를 매핑해서 `read()` 버퍼를 거치지 않고 읽는다 ([`MappedFile`])
+    Mapped(Cursor<MappedFile>),
+    /// 백그라운드 스레드가 미리 통째로 읽어둔 봉인된 세그먼트 ([`WALReader::with_read_ahead`])
+    Prefetched(Cursor<Vec<u8>>),
+}
+
+impl Read for SegmentSource {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        match self {
+            Self::Buffered(reader) => reader.read(buf),
+            Self::Mapped(reader) => reader.read(buf),
+            Self::Prefetched(reader) => reader.read(buf),
+        }
+    }
+}
+
+impl Seek for SegmentSource {
+    fn seek(&mut self, position: SeekFrom) -> io::Result<u64> {
+        match self {
+            Self::Buffered(reader) => reader.seek(position),
+            Self::Mapped(reader) => reader.seek(position),
+            Self::Prefetched(reader) => reader.seek(position),
+        }
+    }
+}
+
+impl SegmentReader<BufReader<File>> {
+    /// 헤더를 검증하고 첫 프레임 앞에 위치한다
+    pub fn open(path: &Path, sequence: u64) -> Result<Self, Error> {
+        Self::open_at(path, sequence, IndexEntry { position: 0, offset: SEGMENT_HEADER_SIZE as u64 })
+    }
+
+    /// 헤더를 검증하고 색인이 가리키는 프레임 앞에 위치한다
+    pub fn open_at(path: &Path, sequence: u64, at: IndexEntry) -> Result<Self, Error> {
+        Self::from_source(BufReader::new(File::open(path)?), sequence, at)
+    }
+}
+
+impl SegmentReader<SegmentSource> {
+    /// `mapped` 라면 파일을 매핑해서 읽는다. 봉인되어 더 이상 바뀌지 않는 세그먼트에만 써야 한다.
+    pub fn open_source(path: &Path, sequence: u64, at: IndexEntry, mapped: bool) -> Result<Self, Error> {
+        let source = if mapped {
+            SegmentSource::Mapped(Cursor::new(MappedFile::open(path)?))
+        } else {
+            SegmentSource::Buffered(BufReader::new(File::open(path)?))
+        };
+
+        Self::from_source(source, sequence, at)
+    }
+}
+
+impl<R: Read + Seek> SegmentReader<R> {
+    fn from_source(mut reader: R, sequence: u64, at: IndexEntry) -> Result<Self, Error> {
+        let mut header = [0u8; SEGMENT_HEADER_SIZE];
+        reader.read_exact(&mut header)?;
+        let header = SegmentHeader::decode(&header)?;
+        reader.seek(SeekFrom::Start(at.offset))?;
+
+        let frames = FrameReader::new(reader, at.offset, sequence)
+            .with_checksum(header.checksum)
+            .with_block_size(header.block_size as u64);
+
+        let mut segment = Self::new(frames).with_format(EntryFormat::of(&header));
+        segment.position = at.position;
+        segment.blocked = header.blocks;
+        segment.block_start = at;
+        Ok(segment)
+    }
+}
+
+impl<R: Read> SegmentReader<R> {
+    pub fn new(frames: FrameReader<R>) -> Self {
+        let format = EntryFormat::new(FORMAT_VERSION);
+        Self {
+            frames: frames.with_layout(format.frame_layout()),
+            mode: RecoveryMode::default(),
+            checkpointed: false,
+            finished: false,
+            position: 0,
+            damaged: false,
+            truncated_frames: 0,
+            corrupt_frames: 0,
+            corruptions: Vec::new(),
+            entry_types: None,
+            compacted: false,
+            buffer: bitcode::Buffer::new(),
+            format,
+            payload: Vec::new(),
+            current: None,
+            legacy: None,
+            metadata: Metadata::new(),
+            blocked: false,
+            block: Arc::default(),
+            block_cursor: 0,
+            block_cache: None,
+            block_start: IndexEntry { position: 0, offset: SEGMENT_HEADER_SIZE as u64 },
+            raw: Vec::new(),
+            skip_to: 0,
+        }
+    }
+
+    pub fn with_recovery_mode(mut self, mode: RecoveryMode) -> Self {
+        self.mode = mode;
+        self
+    }
+
+    /// 세그먼트 헤더의 포맷으로 프레임과 엔트리를 읽는다
+    fn with_format(self, format: EntryFormat) -> Self {
+        Self { frames: self.frames.with_layout(format.frame_layout()), format, ..self }
+    }
+
+    /// `entry_types` 에 없는 종류의 엔트리는 돌려주지 않고 건너뛴다. 건너뛴 엔트리도 LSN 은 차지한다.
+    pub fn with_entry_types(mut self, entry_types: Option<Vec<EntryType>>) -> Self {
+        self.entry_types = entry_types;
+        self
+    }
+
+    /// 블록으로 묶은 세그먼트라면 풀어둔 블록을 `block_cache` 에서 찾고, 없다면 풀어서 넣어둔다
+    pub fn with_block_cache(mut self, block_cache: Option<Arc<BlockCache>>) -> Self {
+        self.block_cache = block_cache;
+        self
+    }
+
+    /// 컴팩션이 data 를 비운 엔트리([`EntryFlags::COMPACTED`])를 건너뛰지 않고 돌려준다. 건너뛰더라도 LSN 은 차지한다.
+    pub fn with_compacted(mut self, compacted: bool) -> Self {
+        self.compacted = compacted;
+        self
+    }
+
+    /// `position` 번째 앞의 엔트리는 디코딩하지 않고 건너뛴다. 블록으로 묶은 세그먼트라면 그 앞에서 끝나는 블록은 풀지도 않는다.
+    pub fn with_skip_to(mut self, position: u64) -> Self {
+        self.skip_to = position;
+        self
+    }
+
+    /// 마지막으로 온전히 읽은(혹은 건너뛴) 프레임의 끝 위치. 블록으로 묶은 세그먼트라면 블록 프레임의 끝이다.
+    pub fn offset(&self) -> u64 {
+        self.frames.offset()
+    }
+
+    /// 다시 열어서 이어 읽을 수 있는 가장 가까운 위치. 블록을 읽는 중이라면 그 블록의 시작이다.
+    pub fn resume_point(&self) -> IndexEntry {
+        match self.block_cursor < self.block.len() {
+            true => self.block_start,
+            false => IndexEntry { position: self.position, offset: self.frames.offset() },
+        }
+    }
+
+    /// 건너뛴 프레임을 포함해 지금까지 지나온 프레임 수. 세그먼트 안에서의 LSN 이다.
+    pub fn frames_read(&self) -> u64 {
+        self.position
+    }
+
+    /// 깨진 프레임 때문에 멈췄는지
+    pub fn damaged(&self) -> bool {
+        self.damaged
+    }
+
+    pub fn truncated_frames(&self) -> usize {
+        self.truncated_frames
+    }
+
+    pub fn corrupt_frames(&self) -> usize {
+        self.corrupt_frames
+    }
+
+    /// 지난번 호출 이후 만난 깨진 프레임들을 가져간다
+    pub fn take_corruptions(&mut self) -> Vec<CorruptFrame> {
+        std::mem::take(&mut self.corruptions)
+    }
+
+    /// 데이터의 끝이면 `Ok(None)`
+    pub fn next_entry(&mut self) -> Result<Option<WALEntry>, Error> {
+        if self.advance()?.is_none() {
+            return Ok(None);
+        }
+
+        Ok(Some(self.take_entry()))
+    }
+
+    /// `next_entry` 와 같지만 data 를 복사하지 않고 빌려준다
+    pub fn next_entry_ref(&mut self) -> Result<Option<WALEntryRef<'_>>, Error> {
+        if self.advance()?.is_none() {
+            return Ok(None);
+        }
+
+        Ok(self.entry_ref())
+    }
+
+    /// 마지막으로 읽은 엔트리
+    pub(crate) fn entry_ref(&self) -> Option<WALEntryRef<'_>> {
+        let (header, data_start) = self.current?;
+        if let Some(entry) = &self.legacy {
+            return Some(entry.as_entry_ref());
+        }
+
+        Some(WALEntryRef {
+            entry_type: header.entry_type,
+            data: header.has_data.then(|| &self.payload[data_start..]),
+            timestamp: header.timestamp,
+            transaction_id: header.transaction_id,
+            metadata: &self.metadata,
+            flags: header.flags,
+        })
+    }
+
+    /// 마지막으로 읽은 엔트리를 가져간다. `advance` 가 엔트리를 돌려준 직후에만 불러야 한다.
+    pub(crate) fn take_entry(&mut self) -> WALEntry {
+        match self.legacy.take() {
+            Some(entry) => entry,
+            None => self.entry_ref().expect("no entry was read").into_owned(),
+        }
+    }
+
+    /// 다음 엔트리까지 읽고 그 헤더를 돌려준다. 엔트리 자체는 `entry_ref` 나 `take_entry` 로 가져간다.
+    pub(crate) fn advance(&mut self) -> Result<Option<EntryHeader>, Error> {
+        self.current = None;
+        self.legacy = None;
+
+        while !self.finished {
+            match self.next_frame() {
+                Ok(true) => {},
+                Ok(false) => break,
+                // 봉인된 세그먼트의 체크포인트 뒤에는 footer 가 있다
+                Err(FrameError::Incomplete | FrameError::ChecksumMismatch) if self.checkpointed => break,
+                Err(error @ (FrameError::Incomplete | FrameError::ChecksumMismatch)) => {
+                    let corrupt = matches!(error, FrameError::ChecksumMismatch);
+                    if corrupt {
+                        self.corrupt_frames += 1;
+                    } else {
+                        self.truncated_frames += 1;
+                    }
+                    self.corruptions.push(CorruptFrame {
+                        segment: self.frames.salt(),
+                        offset: self.frames.offset(),
+                        truncated: !corrupt,
+                    });
+
+                    match self.mode {
+                        RecoveryMode::Strict => {
+                            return Err(Error::new(
+                                ErrorKind::InvalidData,
+                                format!("{} in segment {} at offset {}", error, self.frames.salt(), self.frames.offset()),
+                            ));
+                        },
+                        // 건너뛴 엔트리도 LSN 하나를 차지한다. 깨진 블록은 몇 개의 엔트리였는지 알 수 없으므로 세그먼트를 넘긴다.
+                        RecoveryMode::SkipCorrupt if corrupt && !self.blocked => {
+                            self.frames.skip_rejected();
+                            self.position += 1;
+                            continue;
+                        },
+                        _ => {
+                            self.damaged = true;
+                            break;
+                        },
+                    }
+                },
+                Err(FrameError::Io(e)) => return Err(e),
+            };
+            if self.position < self.skip_to {
+                self.position += 1;
+                continue;
+            }
+
+            let (header, data_start) = if self.format.version == LEGACY_FORMAT_VERSION {
+                let entry: LegacyEntry = self.buffer.decode(&self.payload)
+                    .map_err(|e| Error::new(ErrorKind::InvalidData, e))?;
+                let entry = WALEntry::from(entry);
+                let header = EntryHeader::of(entry.as_entry_ref(), Lsn::ZERO);
+                self.legacy = Some(entry);
+                (header, 0)
+            } else {
+                let (header, metadata, data_start) = EntryHeader::decode_payload(&self.payload, self.format, &mut self.buffer)?;
+                self.metadata = match metadata.is_empty() {
+                    true => Metadata::new(),
+                    false => self.buffer.decode(&self.payload[metadata])
+                        .map_err(|e| Error::new(ErrorKind::InvalidData, e))?,
+                };
+                (header, data_start)
+            };
+            if matches!(header.entry_type, EntryType::Checkpoint) {
+                self.checkpointed = true;
+            }
+
+            self.position += 1;
+            let compacted = !self.compacted && header.flags.contains(EntryFlags::COMPACTED);
+            if compacted || self.entry_types.as_ref().is_some_and(|entry_types| !entry_types.contains(&header.entry_type)) {
+                self.legacy = None;
+                continue;
+            }
+            self.current = Some((header, data_start));
+            return Ok(Some(header));
+        }
+
+        self.finished = true;
+        Ok(None)
+    }
+
+    /// 다음 엔트리 프레임의 payload 를 `payload` 에 채운다. 블록으로 묶은 세그먼트라면 블록을 풀어서 하나씩 꺼낸다.
+    fn next_frame(&mut self) -> Result<bool, FrameError> {
+        if !self.blocked {
+            return self.frames.next_frame_into(&mut self.payload);
+        }
+
+        while self.block_cursor == self.block.len() {
+            self.block_start = IndexEntry { position: self.position, offset: self.frames.offset() };
+            if !self.frames.next_frame_into(&mut self.raw)? {
+                return Ok(false);
+            }
+
+            // 찾는 위치 앞에서 끝나는 블록은 풀지 않는다
+            let header = BlockHeader::decode(&self.raw)?;
+            if self.position + header.entries <= self.skip_to {
+                self.position += header.entries;
+                continue;
+            }
+            self.decode_block()?;
+            self.block_cursor = 0;
+        }
+
+        let payload = next_frame(&self.block, self.format.frame_layout(), &mut self.block_cursor)?;
+        self.payload.clear();
+        self.payload.extend_from_slice(payload);
+        Ok(true)
+    }
+
+    /// 방금 읽은 블록 프레임을 `block` 에 푼다. 캐시가 없다면 버퍼를 다시 쓴다.
+    fn decode_block(&mut self) -> Result<(), Error> {
+        let Some(cache) = &self.block_cache else {
+            decode_block(&self.raw, Arc::make_mut(&mut self.block))?;
+            return Ok(());
+        };
+
+        let (sequence, offset, checksum) = (self.frames.salt(), self.block_start.offset, self.frames.last_checksum());
+        self.block = match cache.get(sequence, offset, checksum) {
+            Some(block) => block,
+            None => {
+                let mut block = Vec::new();
+                decode_block(&self.raw, &mut block)?;
+                let block = Arc::new(block);
+                cache.insert(sequence, offset, checksum, block.clone());
+                block
+            },
+        };
+
+        Ok(())
+    }
+}
+
+/// 다른 스레드에서 끝까지 풀어둔 봉인된 세그먼트 ([`WALReader::with_parallelism`])
+///
+/// [`SegmentReader::advance`] 를 부를 때마다의 결과와 그 뒤의 상태를 차례로 남겨두고, 같은 순서로 돌려준다.
+struct DecodedSegment {
+    steps: VecDeque<DecodedStep>,
+    /// 마지막으로 돌려준 단계. 처음에는 세그먼트를 막 열었을 때의 상태다.
+    state: DecodedStep,
+}
+
+struct DecodedStep {
+    header: Result<Option<EntryHeader>, Error>,
+    entry: Option<WALEntry>,
+    corruptions: Vec<CorruptFrame>,
+    offset: u64,
+    frames_read: u64,
+    truncated_frames: usize,
+    corrupt_frames: usize,
+    damaged: bool,
+}
+
+impl DecodedStep {
+    fn of<R: Read>(segment: &mut SegmentReader<R>, header: Result<Option<EntryHeader>, Error>) -> Self {
+        let entry = matches!(header, Ok(Some(_))).then(|| segment.take_entry());
+        Self {
+            header,
+            entry,
+            corruptions: segment.take_corruptions(),
+            offset: segment.offset(),
+            frames_read: segment.frames_read(),
+            truncated_frames: segment.truncated_frames(),
+            corrupt_frames: segment.corrupt_frames(),
+            damaged: segment.damaged(),
+        }
+    }
+}
+
+impl DecodedSegment {
+    /// 데이터의 끝이나 오류를 만날 때까지 모두 풀어둔다
+    fn decode(mut segment: SegmentReader<SegmentSource>) -> Self {
+        let state = DecodedStep::of(&mut segment, Ok(None));
+        let mut steps = VecDeque::new();
+        loop {
+            let header = segment.advance();
+            let last = !matches!(header, Ok(Some(_)));
+            steps.push_back(DecodedStep::of(&mut segment, header));
+            if last {
+                return Self { steps, state };
+            }
+        }
+    }
+
+    fn advance(&mut self) -> Result<Option<EntryHeader>, Error> {
+        let Some(mut step) = self.steps.pop_front() else {
+            return Ok(None);
+        };
+        let header = std::mem::replace(&mut step.header, Ok(None));
+        self.state = step;
+
+        header
+    }
+}
+
+/// [`WALReader`] 가 지금 읽고 있는 세그먼트
+enum CurrentSegment {
+    Streaming(Box<SegmentReader<SegmentSource>>),
+    Decoded(DecodedSegment),
+}
+
+impl CurrentSegment {
+    fn offset(&self) -> u64 {
+        match self {
+            Self::Streaming(segment) => segment.offset(),
+            Self::Decoded(segment) => segment.state.offset,
+        }
+    }
+
+    fn frames_read(&self) -> u64 {
+        match self {
+            Self::Streaming(segment) => segment.frames_read(),
+            Self::Decoded(segment) => segment.state.frames_read,
+        }
+    }
+
+    fn damaged(&self) -> bool {
+        match self {
+            Self::Streaming(segment) => segment.damaged(),
+            Self::Decoded(segment) => segment.state.damaged,
+        }
+    }
+
+    fn truncated_frames(&self) -> usize {
+        match self {
+            Self::Streaming(segment) => segment.truncated_frames(),
+            Self::Decoded(segment) => segment.state.truncated_frames,
+        }
+    }
+
+    fn corrupt_frames(&self) -> usize {
+        match self {
+            Self::Streaming(segment) => segment.corrupt_frames(),
+            Self::Decoded(segment) => segment.state.corrupt_frames,
+        }
+    }
+
+    fn take_corruptions(&mut self) -> Vec<CorruptFrame> {
+        match self {
+            Self::Streaming(segment) => segment.take_corruptions(),
+            Self::Decoded(segment) => std::mem::take(&mut segment.state.corruptions),
+        }
+    }
+
+    fn advance(&mut self) -> Result<Option<EntryHeader>, Error> {
+        match self {
+            Self::Streaming(segment) => segment.advance(),
+            Self::Decoded(segment) => segment.advance(),
+        }
+    }
+
+    fn entry_ref(&self) -> Option<WALEntryRef<'_>> {
+        match self {
+            Self::Streaming(segment) => segment.entry_ref(),
+            Self::Decoded(segment) => segment.state.entry.as_ref().map(WALEntry::as_entry_ref),
+        }
+    }
+
+    fn take_entry(&mut self) -> WALEntry {
+        match self {
+            Self::Streaming(segment) => segment.take_entry(),
+            Self::Decoded(segment) => segment.state.entry.take().expect("no entry was read"),
+        }
+    }
+}
+
+impl<R: Read> Iterator for SegmentReader<R> {
+    type Item = io::Result<WALEntry>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.next_entry().transpose()
+    }
+}
+
+/// 디렉토리의 모든 세그먼트를 순번 순서대로 읽으면서 엔트리를 LSN 과 함께 돌려주는 복구용 리더
+///
+/// 세그먼트마다 [`SegmentReader`] 로 스트리밍하며, 깨진 프레임은 [`RecoveryMode`] 에 따라 처리한다.
+/// `TruncateAtError` 에서는 첫 번째 깨진 프레임에서 전체 재생을 멈춘다.
+/// 읽기만 하므로 디렉토리 잠금을 잡지 않고, 매니페스트가 없어도 파일을 고치지 않는다.
+pub struct WALReader {
+    directory: PathBuf,
+    segments: VecDeque<u64>,
+    /// 아직 봉인되지 않은 활성 세그먼트의 순번
+    active: u64,
+    current: Option<CurrentSegment>,
+    /// 현재 세그먼트의 첫 엔트리 바로 앞 LSN
+    segment_lsn: Lsn,
+    lsn: Lsn,
+    /// 이 LSN 보다 앞선 엔트리는 돌려주지 않는다
+    start: Lsn,
+    /// `start <= timestamp < end` 인 엔트리만 돌려준다
+    time_range: Option<(f64, f64)>,
+    /// 이 트랜잭션들에 속한 엔트리만 돌려준다
+    transactions: Option<HashSet<u64>>,
+    /// 커밋되지 않아 돌려주지 않을 트랜잭션들 ([`Visibility::Committed`])
+    hidden: Option<HashSet<u64>>,
+    entry_types: Option<Vec<EntryType>>,
+    mode: RecoveryMode,
+    /// 봉인된 세그먼트를 매핑해서 읽는다
+    mmap: bool,
+    read_ahead: bool,
+    block_cache: Option<Arc<BlockCache>>,
+    /// 백그라운드에서 읽고 있는 다음 세그먼트의 순번과 그 스레드
+    prefetching: Option<(u64, JoinHandle<io::Result<Vec<u8>>>)>,
+    /// 봉인된 세그먼트를 풀어둘 스레드 수. 1 이면 지금 스레드에서 차례로 읽는다.
+    parallelism: usize,
+    /// 다른 스레드에서 풀고 있는 다음 세그먼트들의 순번과 그 스레드 (순번 순서)
+    decoding: VecDeque<(u64, JoinHandle<io::Result<DecodedSegment>>)>,
+    report: RecoveryReport,
+    /// 직전 `next_entry` 에서 만난 깨진 프레임
+    corruptions: Vec<CorruptFrame>,
+}
+
+impl WALReader {
+    pub fn open(directory: &Path) -> Result<Self, Error> {
+        let manifest = match Manifest::load(directory)? {
+            Some(manifest) => manifest,
+            None => Manifest::rebuild(directory)?,
+        };
+
+        let first_lsn = manifest.first_lsn(directory)?;
+
+        Ok(Self {
+            directory: directory.to_path_buf(),
+            segments: manifest.live_segments().collect(),
+            active: manifest.sequence,
+            current: None,
+            segment_lsn: first_lsn,
+            lsn: first_lsn,
+            start: Lsn::ZERO,
+            time_range: None,
+            transactions: None,
+            hidden: None,
+            entry_types: None,
+            mode: RecoveryMode::default(),
+            mmap: false,
+            read_ahead: false,
+            block_cache: None,
+            prefetching: None,
+            parallelism: 1,
+            decoding: VecDeque::new(),
+            report: RecoveryReport { last_lsn: first_lsn, ..Default::default() },
+            corruptions: Vec::new(),
+        })
+    }
+
+    pub fn with_recovery_mode(mut self, mode: RecoveryMode) -> Self {
+        self.mode = mode;
+        self
+    }
+
+    /// 봉인된 세그먼트를 `read()` 대신 매핑해서 읽는다 ([`MappedFile`]). 큰 세그먼트를 복구할 때 복사를 한 번 줄인다.
+    pub fn with_mmap(mut self, mmap: bool) -> Self {
+        self.mmap = mmap;
+        self
+    }
+
+    /// 세그먼트를 읽는 동안 다음 봉인된 세그먼트를 백그라운드 스레드에서 메모리로 통째로 읽어둔다.
+    /// 캐시가 비어 있는 디스크나 네트워크 파일시스템에서 엔트리를 적용하는 시간과 읽는 시간을 겹친다.
+    /// 세그먼트 하나만큼 메모리를 더 쓰고, 매핑해서 읽는 세그먼트와 활성 세그먼트는 미리 읽지 않는다.
+    pub fn with_read_ahead(mut self, read_ahead: bool) -> Self {
+        self.read_ahead = read_ahead;
+        self
+    }
+
+    /// 블록으로 묶은 세그먼트의 풀어둔 블록을 다른 리더와 함께 기억한다 ([`BlockCache`])
+    pub fn with_block_cache(mut self, block_cache: Option<Arc<BlockCache>>) -> Self {
+        self.block_cache = block_cache;
+        self
+    }
+
+    /// 앞으로 읽을 봉인된 세그먼트를 `threads` 개까지 각자의 스레드에서 끝까지 풀어두고, 순번 순서대로 꺼내서 돌려준다.
+    /// 세그먼트가 수백 개라면 체크섬 검사와 디코딩을 여러 코어에 나눈다. 돌려주는 엔트리와 LSN, 통계는 차례로 읽을 때와 같다.
+    ///
+    /// 풀어둔 세그먼트의 엔트리를 메모리에 들고 있으므로 세그먼트 `threads` 개만큼 메모리를 더 쓴다. 활성 세그먼트는 차례로 읽는다.
+    pub fn with_parallelism(mut self, threads: usize) -> Self {
+        self.parallelism = threads.max(1);
+        self
+    }
+
+    /// LSN 이 `lsn` 이상인 첫 엔트리부터 읽도록 위치를 옮긴다. 읽기 시작 전에 불러야 한다.
+    ///
+    /// footer 의 엔트리 수로 봉인된 세그먼트를 통째로 건너뛰고, 도착한 세그먼트에 색인(`walN.idx`)이 있다면
+    /// 가장 가까운 위치로 바로 이동한다. 나머지 앞선 엔트리는 디코딩하지 않고 지나치며, 블록은 풀지 않고 건너뛴다.
+    pub fn read_from(mut self, lsn: Lsn) -> Result<Self, Error> {
+        self.start = lsn;
+
+        while let Some(&sequence) = self.segments.front() {
+            let path = segment_path(&self.directory, sequence as usize);
+            let footer = match SegmentFooter::read(&path, sequence) {
+                Ok(footer) => footer,
+                Err(e) if e.kind() == ErrorKind::NotFound => None,
+                Err(e) => return Err(e),
+            };
+
+            match footer {
+                Some(footer) if self.lsn + footer.entry_count < lsn => {
+                    self.segments.pop_front();
+                    self.lsn += footer.entry_count;
+                    self.segment_lsn = self.lsn;
+                },
+                _ => break,
+            }
+        }
+
+        let Some(&sequence) = self.segments.front() else {
+            return Ok(self);
+        };
+        let position = lsn.0.saturating_sub(self.lsn.next().0);
+        if position == 0 {
+            return Ok(self);
+        }
+
+        let start = IndexEntry { position: 0, offset: SEGMENT_HEADER_SIZE as u64 };
+        let at = SegmentIndex::load(&self.directory, sequence)?.and_then(|index| index.lookup(position)).unwrap_or(start);
+        let path = segment_path(&self.directory, sequence as usize);
+        let segment = match SegmentReader::open_source(&path, sequence, at, self.mapped(sequence)) {
+            Ok(segment) => segment,
+            Err(e) if e.kind() == ErrorKind::NotFound => return Ok(self),
+            Err(e) => return Err(e),
+        };
+        self.segments.pop_front();
+        self.enter_segment(segment.with_skip_to(position));
+
+        Ok(self)
+    }
+
+    /// 타임스탬프가 `start` 이상 `end` 미만인 엔트리만 돌려준다
+    ///
+    /// 타임스탬프는 기록하는 쪽의 시계를 따르므로 단조 증가를 가정하지 않고 모든 엔트리를 검사한다.
+    pub fn read_between(mut self, start: f64, end: f64) -> Self {
+        self.time_range = Some((start, end));
+        self
+    }
+
+    /// `transaction_id` 가 `transactions` 중 하나인 엔트리만 돌려준다 (트랜잭션 하나의 이력을 볼 때)
+    pub fn filter_transactions<I: IntoIterator<Item = u64>>(mut self, transactions: I) -> Self {
+        self.transactions = Some(transactions.into_iter().collect());
+        self
+    }
+
+    /// `Visibility::Committed` 라면 먼저 로그 전체를 한 번 훑어서 커밋된 트랜잭션을 모은 뒤, 나머지 트랜잭션의 엔트리를 거른다.
+    /// 훑을 때는 data 를 복사하지 않고 트랜잭션 id 만 모으므로 메모리는 트랜잭션 수만큼만 쓴다.
+    pub fn with_visibility(mut self, visibility: Visibility) -> Result<Self, Error> {
+        self.hidden = match visibility {
+            Visibility::Raw => None,
+            Visibility::Committed => Some(self.uncommitted_transactions()?),
+        };
+
+        Ok(self)
+    }
+
+    fn uncommitted_transactions(&self) -> Result<HashSet<u64>, Error> {
+        let mut reader = WALReader::open(&self.directory)?.with_recovery_mode(self.mode).with_mmap(self.mmap).with_read_ahead(self.read_ahead)
+            .with_block_cache(self.block_cache.clone()).with_parallelism(self.parallelism);
+        let mut parents = HashMap::new();
+        let mut committed = HashSet::new();
+
+        while let Some((_, entry)) = reader.next_entry_ref()? {
+            match entry.entry_type {
+                EntryType::TransactionBegin => {
+                    parents.insert(entry.transaction_id, entry.parent_transaction_id());
+                },
+                EntryType::TransactionCommit => {
+                    committed.insert(entry.transaction_id);
+                },
+                EntryType::TransactionAbort => {
+                    committed.remove(&entry.transaction_id);
+                },
+                _ => {},
+            }
+        }
+
+        // 부모를 따라 올라가면서 모두 커밋되었는지 본다. 깨진 기록으로 순환이 생겨도 트랜잭션 수만큼만 올라간다.
+        let visible = |mut transaction_id| {
+            for _ in 0..=parents.len() {
+                if !committed.contains(&transaction_id) {
+                    return false;
+                }
+                match parents.get(&transaction_id).copied().flatten() {
+                    Some(parent) if parents.contains_key(&parent) => transaction_id = parent,
+                    _ => return true,
+                }
+            }
+            false
+        };
+
+        Ok(parents.keys().copied().filter(|&transaction_id| !visible(transaction_id)).collect())
+    }
+
+    /// `entry_types` 에 속한 종류의 엔트리만 돌려준다. 거르는 일은 세그먼트를 풀어내는 [`SegmentReader`] 에서 한다.
+    pub fn filter_entry_types<I: IntoIterator<Item = EntryType>>(mut self, entry_types: I) -> Self {
+        self.entry_types = Some(entry_types.into_iter().collect());
+        self
+    }
+
+    /// 지금까지 읽은 만큼의 통계
+    pub fn report(&self) -> &RecoveryReport {
+        &self.report
+    }
+
+    /// 모든 엔트리를 순서대로 `apply` 에 넘기고 복구 통계를 반환
+    pub fn replay<F: FnMut(Lsn, WALEntry)>(mut self, mut apply: F) -> Result<RecoveryReport, Error> {
+        while let Some((lsn, entry)) = self.next_entry()? {
+            apply(lsn, entry);
+        }
+
+        Ok(self.report)
+    }
+
+    /// LSN 이 `applied` 이하인 엔트리는 건너뛰고 나머지를 `apply` 에 넘긴다. 소비자가 적용을 마친 LSN 을 따로 남겨두면
+    /// 소비자 쪽에서 죽은 뒤 다시 재생해도 같은 엔트리를 두 번 적용하지 않는다 (exactly-once).
+    pub fn replay_after<F: FnMut(Lsn, WALEntry)>(self, applied: Lsn, apply: F) -> Result<RecoveryReport, Error> {
+        self.read_from(applied + 1)?.replay(apply)
+    }
+
+    /// `replay` 와 같지만 엔트리마다 data 를 새로 할당하지 않고 빌려서 넘긴다. 남겨둘 엔트리만 `into_owned` 로 옮기면 된다.
+    pub fn replay_ref<F: FnMut(Lsn, WALEntryRef<'_>)>(mut self, mut apply: F) -> Result<RecoveryReport, Error> {
+        while let Some((lsn, entry)) = self.next_entry_ref()? {
+            apply(lsn, entry);
+        }
+
+        Ok(self.report)
+    }
+
+    /// 모든 엔트리를 순서대로 `visitor` 에 넘기고 복구 통계를 반환
+    pub fn visit<V: WALVisitor>(mut self, visitor: &mut V) -> Result<RecoveryReport, Error> {
+        loop {
+            let next = self.next_entry();
+            for frame in &self.corruptions {
+                visitor.on_corruption(frame);
+            }
+
+            let Some((lsn, entry)) = next? else {
+                break;
+            };
+            match entry.entry_type {
+                EntryType::Checkpoint => visitor.on_checkpoint(lsn),
+                EntryType::TransactionCommit => visitor.on_transaction_commit(lsn, entry.transaction_id),
+                EntryType::TransactionAbort => visitor.on_transaction_abort(lsn, entry.transaction_id),
+                _ => visitor.on_entry(lsn, entry),
+            }
+        }
+
+        Ok(self.report)
+    }
+
+    /// `replay` 와 같지만 `TransactionBegin` 부터 `TransactionCommit` 까지의 엔트리를 트랜잭션 id 별로 모았다가
+    /// 커밋 엔트리를 만났을 때 LSN 순서대로 한꺼번에 넘긴다. 중단 엔트리를 만났거나 끝까지 커밋되지 않은 트랜잭션은
+    /// 버리고 통계에 남긴다. 중첩 트랜잭션의 엔트리는 부모 트랜잭션이 커밋될 때 넘긴다. 저장점으로 되돌리기 엔트리를 만나면 같은 이름의 마지막 저장점 뒤로 모아둔 엔트리를 버린다.
+    ///
+    /// 열린 트랜잭션에 속하지 않는 엔트리(체크포인트 포함)는 바로 넘긴다.
+    pub fn replay_committed<F: FnMut(Lsn, WALEntry)>(mut self, mut apply: F) -> Result<RecoveryReport, Error> {
+        let mut pending: HashMap<u64, Vec<(Lsn, WALEntry)>> = HashMap::new();
+        let mut discarded_entries = 0;
+
+        while let Some((lsn, entry)) = self.next_entry()? {
+            match entry.entry_type {
+                EntryType::TransactionBegin => {
+                    pending.insert(entry.transaction_id, vec![(lsn, entry)]);
+                },
+                EntryType::TransactionCommit => {
+                    let mut committed = pending.remove(&entry.transaction_id).unwrap_or_default();
+                    committed.push((lsn, entry));
+
+                    // 중첩 트랜잭션은 부모가 커밋될 때 함께 넘기고, 부모가 중단되었다면 커밋했더라도 버린다
+                    let parent = committed[0].1.parent_transaction_id();
+                    if let Some(transaction) = parent.and_then(|parent| pending.get_mut(&parent)) {
+                        transaction.extend(committed);
+                    } else if parent.is_some_and(|parent| self.report.aborted_transactions.contains(&parent)) {
+                        discarded_entries += committed.len() as u64;
+                    } else {
+                        for (lsn, entry) in committed {
+                            apply(lsn, entry);
+                        }
+                    }
+                },
+                EntryType::RollbackToSavepoint => {
+                    if let Some(transaction) = pending.get_mut(&entry.transaction_id) {
+                        let savepoint = transaction.iter().rposition(|(_, saved)| {
+                            saved.entry_type == EntryType::Savepoint && saved.data == entry.data
+                        });
+                        if let Some(savepoint) = savepoint {
+                            discarded_entries += (transaction.len() - savepoint - 1) as u64;
+                            transaction.truncate(savepoint + 1);
+                        }
+                    }
+                    discarded_entries += 1;
+                },
+                EntryType::TransactionAbort => {
+                    discarded_entries += pending.remove(&entry.transaction_id).map_or(0, |entries| entries.len() as u64) + 1;
+                    self.report.aborted_transactions.push(entry.transaction_id);
+                },
+                _ => match pending.get_mut(&entry.transaction_id) {
+                    Some(transaction) => transaction.push((lsn, entry)),
+                    None => apply(lsn, entry),
+                },
+            }
+        }
+
+        let (mut in_doubt, uncommitted): (Vec<_>, Vec<_>) = pending.into_iter()
+            .partition(|(_, entries)| entries.iter().any(|(_, entry)| entry.entry_type == EntryType::TransactionPrepare));
+        in_doubt.sort_unstable_by_key(|(transaction_id, _)| *transaction_id);
+        self.report.in_doubt_transactions = in_doubt.into_iter().map(|(transaction_id, _)| transaction_id).collect();
+
+        let mut uncommitted_transactions = uncommitted.iter().map(|(transaction_id, _)| *transaction_id).collect::<Vec<_>>();
+        uncommitted_transactions.sort_unstable();
+        self.report.uncommitted_transactions = uncommitted_transactions;
+        self.report.discarded_entries = discarded_entries + uncommitted.iter().map(|(_, entries)| entries.len() as u64).sum::<u64>();
+
+        Ok(self.report)
+    }
+
+    /// 다음에 돌려줄 엔트리의 바로 앞 LSN
+    pub fn lsn(&self) -> Lsn {
+        self.lsn
+    }
+
+    /// 다음 세그먼트를 연다. 더 읽을 세그먼트가 없다면 `Ok(false)`
+    fn open_next_segment(&mut self) -> Result<bool, Error> {
+        self.decode_ahead();
+        while let Some(sequence) = self.segments.pop_front() {
+            match self.take_decoded(sequence) {
+                Some(Ok(segment)) => {
+                    self.enter(CurrentSegment::Decoded(segment));
+                    return Ok(true);
+                },
+                Some(Err(e)) if e.kind() == ErrorKind::NotFound => continue,
+                Some(Err(e)) => return Err(e),
+                None => {},
+            }
+
+            let path = segment_path(&self.directory, sequence as usize);
+            let start = IndexEntry { position: 0, offset: SEGMENT_HEADER_SIZE as u64 };
+            let segment = match self.take_prefetched(sequence) {
+                Some(bytes) => bytes.and_then(|bytes| SegmentReader::from_source(SegmentSource::Prefetched(Cursor::new(bytes)), sequence, start)),
+                None => SegmentReader::open_source(&path, sequence, start, self.mapped(sequence)),
+            };
+            let segment = match segment {
+                Ok(segment) => segment,
+                Err(e) if e.kind() == ErrorKind::NotFound => continue,
+                Err(e) => return Err(e),
+            };
+
+            self.enter_segment(segment);
+            return Ok(true);
+        }
+
+        Ok(false)
+    }
+
+    fn mapped(&self, sequence: u64) -> bool {
+        self.mmap && sequence != self.active
+    }
+
+    fn enter_segment(&mut self, segment: SegmentReader<SegmentSource>) {
+        let segment = segment.with_recovery_mode(self.mode).with_entry_types(self.entry_types.clone()).with_block_cache(self.block_cache.clone());
+        self.enter(CurrentSegment::Streaming(Box::new(segment)));
+    }
+
+    fn enter(&mut self, segment: CurrentSegment) {
+        self.current = Some(segment);
+        self.segment_lsn = self.lsn;
+        self.report.segments_scanned += 1;
+        self.report.bytes_read += SEGMENT_HEADER_SIZE as u64;
+        self.decode_ahead();
+        self.prefetch_next();
+    }
+
+    /// 앞으로 읽을 봉인된 세그먼트 중 `parallelism` 개까지 아직 풀고 있지 않은 것을 새 스레드에서 풀기 시작한다
+    fn decode_ahead(&mut self) {
+        if self.parallelism <= 1 {
+            return;
+        }
+
+        for &sequence in self.segments.iter().take(self.parallelism) {
+            if sequence == self.active || self.decoding.iter().any(|(decoding, _)| *decoding == sequence) {
+                continue;
+            }
+
+            let path = segment_path(&self.directory, sequence as usize);
+            let start = IndexEntry { position: 0, offset: SEGMENT_HEADER_SIZE as u64 };
+            let (mapped, mode, entry_types, block_cache) = (self.mapped(sequence), self.mode, self.entry_types.clone(), self.block_cache.clone());
+            let handle = std::thread::spawn(move || {
+                let segment = SegmentReader::open_source(&path, sequence, start, mapped)?;
+                Ok(DecodedSegment::decode(segment.with_recovery_mode(mode).with_entry_types(entry_types).with_block_cache(block_cache)))
+            });
+            self.decoding.push_back((sequence, handle));
+        }
+    }
+
+    /// `sequence` 를 다른 스레드에서 풀고 있었다면 끝날 때까지 기다렸다가 돌려준다. 그 앞의 세그먼트는 더 읽지 않으므로 버린다.
+    fn take_decoded(&mut self, sequence: u64) -> Option<io::Result<DecodedSegment>> {
+        while let Some((decoding, _)) = self.decoding.front() {
+            if *decoding > sequence {
+                return None;
+            }
+
+            let (decoding, handle) = self.decoding.pop_front()?;
+            if decoding == sequence {
+                return handle.join().ok();
+            }
+        }
+
+        None
+    }
+
+    /// 다음 세그먼트가 봉인되어 있다면 백그라운드 스레드에서 읽기 시작한다
+    fn prefetch_next(&mut self) {
+        let Some(&sequence) = self.segments.front() else {
+            return;
+        };
+        if !self.read_ahead || self.prefetching.is_some() || sequence == self.active || self.mapped(sequence)
+            || self.decoding.iter().any(|(decoding, _)| *decoding == sequence) {
+            return;
+        }
+
+        let path = segment_path(&self.directory, sequence as usize);
+        self.prefetching = Some((sequence, std::thread::spawn(move || std::fs::read(path))));
+    }
+
+    /// `sequence` 를 미리 읽고 있었다면 끝날 때까지 기다렸다가 그 내용을 돌려준다
+    fn take_prefetched(&mut self, sequence: u64) -> Option<io::Result<Vec<u8>>> {
+        let (prefetched, handle) = self.prefetching.take()?;
+        if prefetched != sequence {
+            return None;
+        }
+
+        handle.join().ok()
+    }
+
+    fn next_entry(&mut self) -> Result<Option<(Lsn, WALEntry)>, Error> {
+        let Some(lsn) = self.advance()? else {
+            return Ok(None);
+        };
+
+        Ok(Some((lsn, self.current.as_mut().unwrap().take_entry())))
+    }
+
+    /// 다음 엔트리를 data 를 복사하지 않고 빌려준다. 돌려받은 엔트리는 다음 호출 전까지만 쓸 수 있다.
+    pub fn next_entry_ref(&mut self) -> Result<Option<(Lsn, WALEntryRef<'_>)>, Error> {
+        let Some(lsn) = self.advance()? else {
+            return Ok(None);
+        };
+
+        Ok(self.current.as_ref().and_then(|segment| segment.entry_ref()).map(|entry| (lsn, entry)))
+    }
+
+    /// 걸러지지 않은 다음 엔트리까지 읽고 그 LSN 을 돌려준다
+    fn advance(&mut self) -> Result<Option<Lsn>, Error> {
+        self.corruptions.clear();
+
+        loop {
+            let Some(segment) = &mut self.current else {
+                if self.open_next_segment()? {
+                    continue;
+                }
+                return Ok(None);
+            };
+
+            let offset = segment.offset();
+            let (truncated, corrupt) = (segment.truncated_frames(), segment.corrupt_frames());
+            let header = segment.advance();
+
+            self.report.bytes_read += segment.offset() - offset;
+            self.report.truncated_frames += segment.truncated_frames() - truncated;
+            self.report.corrupt_frames += segment.corrupt_frames() - corrupt;
+            self.corruptions.extend(segment.take_corruptions());
+
+            let Some(header) = header? else {
+                // 끝에서 건너뛴 프레임도 LSN 을 차지하므로 다음 세그먼트는 그 뒤부터 센다
+                self.lsn = self.segment_lsn + segment.frames_read();
+                if segment.damaged() && self.mode == RecoveryMode::TruncateAtError {
+                    self.segments.clear();
+                }
+                self.current = None;
+                continue;
+            };
+
+            // 엔트리에 남은 LSN 이 있다면 매니페스트와 footer 로 되짚은 값 대신 그것을 따른다
+            if header.lsn != Lsn::ZERO {
+                self.segment_lsn = header.lsn.saturating_sub(segment.frames_read());
+            }
+            self.lsn = self.segment_lsn + segment.frames_read();
+            if self.lsn < self.start {
+                continue;
+            }
+            if self.time_range.is_some_and(|(start, end)| !(start..end).contains(&header.timestamp)) {
+                continue;
+            }
+            if self.transactions.as_ref().is_some_and(|transactions| !transactions.contains(&header.transaction_id)) {
+                continue;
+            }
+            if self.hidden.as_ref().is_some_and(|hidden| hidden.contains(&header.transaction_id)) {
+                continue;
+            }
+
+            if matches!(header.entry_type, EntryType::Checkpoint) {
+                self.report.last_checkpoint = Some(self.lsn);
+            }
+
+            self.report.entries_replayed += 1;
+            self.report.last_lsn = self.lsn;
+            return Ok(Some(self.lsn));
+        }
+    }
+}
+
+impl Iterator for WALReader {
+    type Item = io::Result<(Lsn, WALEntry)>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.next_entry().transpose()
+    }
+}
+
+#[cfg(test)]
+mod reader_tests {
+    use super::{CorruptFrame, RecoveryMode, RecoveryReport, SegmentReader, Visibility, WALReader, WALVisitor};
+    use crate::wal::lsn::Lsn;
+    use crate::wal::core::{EntryType, Metadata, WALEntry, WALManager};
+    use crate::wal::flags::EntryFlags;
+    use crate::wal::manifest::MANIFEST_FILE_NAME;
+    use crate::wal::segment::SEGMENT_HEADER_SIZE;
+    use crate::wal::test_utils::{insert_entry, temp_directory};
+
+    fn entry(value: u8) -> WALEntry {
+        insert_entry(vec![value; 16])
+    }
+
+    #[test]
+    fn test_replay_every_segment_in_order() {
+        let directory = temp_directory("reader_replay");
+        let mut wal_manager = WALManager::builder()
+            .set_directory(directory.clone())
+            .build().expect("Cannot create WALManager");
+
+        wal_manager.append_log(entry(1)).unwrap();
+        wal_manager.append_log(entry(2)).unwrap();
+        wal_manager.checkpoint().unwrap();
+        wal_manager.append_log(entry(3)).unwrap();
+
+        let replayed = wal_manager.recover().unwrap().collect::<Result<Vec<_>, _>>().unwrap();
+        let lsns = replayed.iter().map(|(lsn, _)| lsn.0).collect::<Vec<_>>();
+        assert_eq!(lsns, vec![1, 2, 3, 4]);
+        assert!(matches!(replayed[2].1.entry_type, EntryType::Checkpoint));
+        assert_eq!(replayed[3].1.data, Some(vec![3; 16]));
+        drop(wal_manager);
+
+        // 활성 세그먼트의 끊긴 꼬리는 건너뛴다
+        let path = directory.join("wal2.log");
+        let length = std::fs::metadata(&path).unwrap().len();
+        std::fs::OpenOptions::new().write(true).open(&path).unwrap().set_len(length - 3).unwrap();
+        assert_eq!(WALReader::open(&directory).unwrap().count(), 3);
+    }
+
+    #[test]
+    fn test_replay_borrowed_entries() {
+        let directory = temp_directory("reader_borrowed");
+        let mut wal_manager = WALManager::builder()
+            .set_directory(directory.clone())
+            .build().expect("Cannot create WALManager");
+
+        wal_manager.append_log(entry(1)).unwrap();
+        wal_manager.append_log(WALEntry { data: None, ..entry(0) }).unwrap();
+        wal_manager.checkpoint().unwrap();
+        wal_manager.append_log(entry(2)).unwrap();
+
+        let mut replayed = Vec::new();
+        let report = wal_manager.recover().unwrap().replay_ref(|lsn, entry| {
+            replayed.push((lsn.0, entry.entry_type, entry.data.map(|data| data[0])));
+        }).unwrap();
+
+        assert_eq!(report.entries_replayed, 4);
+        assert_eq!(replayed, vec![
+            (1, EntryType::Insert, Some(1)),
+            (2, EntryType::Insert, None),
+            (3, EntryType::Checkpoint, None),
+            (4, EntryType::Insert, Some(2)),
+        ]);
+
+        let mut reader = WALReader::open(&directory).unwrap().filter_entry_types([EntryType::Insert]);
+        let (lsn, entry) = reader.next_entry_ref().unwrap().unwrap();
+        assert_eq!((lsn, entry.data), (Lsn(1), Some(&[1u8; 16][..])));
+        assert_eq!(entry.into_owned().data, Some(vec![1; 16]));
+    }
+
+    #[test]
+    fn test_replay_after_applied_lsn() {
+        let directory = temp_directory("reader_replay_after");
+        let mut wal_manager = WALManager::builder()
+            .set_directory(directory.clone())
+            .build().expect("Cannot create WALManager");
+
+        wal_manager.append_log(entry(1)).unwrap();
+        wal_manager.append_log(entry(2)).unwrap();
+        wal_manager.checkpoint().unwrap();
+        wal_manager.append_log(entry(3)).unwrap();
+        wal_manager.append_log(entry(4)).unwrap();
+        wal_manager.checkpoint().unwrap();
+        wal_manager.append_log(entry(5)).unwrap();
+
+        let mut applied = Vec::new();
+        wal_manager.replay_after(Lsn(4), |lsn, _| applied.push(lsn.0)).unwrap();
+        assert_eq!(applied, vec![5, 6, 7]);
+        drop(wal_manager);
+
+        // 앞선 세그먼트와 매니페스트가 사라져도 엔트리에 남은 LSN 은 그대로다
+        std::fs::remove_file(directory.join("wal1.log")).unwrap();
+        std::fs::remove_file(directory.join(MANIFEST_FILE_NAME)).unwrap();
+        let lsns = WALReader::open(&directory).unwrap().map(|entry| entry.unwrap().0.0).collect::<Vec<_>>();
+        assert_eq!(lsns, vec![4, 5, 6, 7]);
+
+        let wal_manager = WALManager::builder()
+            .set_directory(directory.clone())
+            .build().expect("Cannot create WALManager");
+        assert_eq!(wal_manager.last_lsn(), Lsn(7));
+
+        let mut applied = Vec::new();
+        WALReader::open(&directory).unwrap().replay_after(Lsn(6), |lsn, entry| applied.push((lsn.0, entry.data))).unwrap();
+        assert_eq!(applied, vec![(7, Some(vec![5; 16]))]);
+    }
+
+    #[test]
+    fn test_replay_mapped_segments() {
+        let directory = temp_directory("reader_mmap");
+        let mut wal_manager = WALManager::builder()
+            .set_directory(directory.clone())
+            .set_mmap_reads(true)
+            .build().expect("Cannot create WALManager");
+
+        for value in 0..3 {
+            wal_manager.append_log(entry(value)).unwrap();
+            wal_manager.append_log(entry(value)).unwrap();
+            wal_manager.checkpoint().unwrap();
+        }
+        wal_manager.append_log(entry(3)).unwrap();
+
+        let mapped = wal_manager.recover().unwrap().collect::<Result<Vec<_>, _>>().unwrap();
+        let buffered = WALReader::open(&directory).unwrap().collect::<Result<Vec<_>, _>>().unwrap();
+        assert_eq!((mapped.len(), buffered.len()), (10, 10));
+        for ((mapped_lsn, mapped), (lsn, entry)) in mapped.iter().zip(&buffered) {
+            assert_eq!(mapped_lsn, lsn);
+            assert_eq!(mapped.data, entry.data);
+        }
+
+        let reader = WALReader::open(&directory).unwrap().with_mmap(true).read_from(Lsn(8)).unwrap();
+        assert_eq!(reader.map(|entry| entry.unwrap().0.0).collect::<Vec<_>>(), vec![8, 9, 10]);
+    }
+
+    #[test]
+    fn test_replay_with_read_ahead() {
+        let directory = temp_directory("reader_read_ahead");
+        let mut wal_manager = WALManager::builder()
+            .set_directory(directory.clone())
+            .set_read_ahead(true)
+            .build().expect("Cannot create WALManager");
+
+        for value in 0..3 {
+            wal_manager.append_log(entry(value)).unwrap();
+            wal_manager.append_log(entry(value)).unwrap();
+            wal_manager.checkpoint().unwrap();
+        }
+        wal_manager.append_log(entry(3)).unwrap();
+
+        let prefetched = wal_manager.recover().unwrap().collect::<Result<Vec<_>, _>>().unwrap();
+        let buffered = WALReader::open(&directory).unwrap().collect::<Result<Vec<_>, _>>().unwrap();
+        assert_eq!(prefetched.len(), 10);
+        assert_eq!(prefetched.iter().map(|(lsn, entry)| (*lsn, &entry.data)).collect::<Vec<_>>(),
+            buffered.iter().map(|(lsn, entry)| (*lsn, &entry.data)).collect::<Vec<_>>());
+
+        let reader = WALReader::open(&directory).unwrap().with_read_ahead(true).read_from(Lsn(5)).unwrap();
+        assert_eq!(reader.map(|entry| entry.unwrap().0.0).collect::<Vec<_>>(), vec![5, 6, 7, 8, 9, 10]);
+    }
+
+    #[test]
+    fn test_replay_in_parallel() {
+        let directory = temp_directory("reader_parallel");
+        let mut wal_manager = WALManager::builder()
+            .set_directory(directory.clone())
+            .set_recovery_threads(4)
+            .build().expect("Cannot create WALManager");
+
+        for value in 0..8 {
+            wal_manager.append_logs(vec![entry(value); 3]).unwrap();
+            wal_manager.checkpoint().unwrap();
+        }
+        wal_manager.append_log(entry(8)).unwrap();
+
+        // 여러 스레드에서 풀어도 순서와 LSN, 통계는 차례로 읽을 때와 같다
+        let mut parallel = wal_manager.recover().unwrap();
+        let mut sequential = WALReader::open(&directory).unwrap();
+        let decoded = parallel.by_ref().collect::<Result<Vec<_>, _>>().unwrap();
+        let streamed = sequential.by_ref().collect::<Result<Vec<_>, _>>().unwrap();
+        assert_eq!(decoded.len(), 33);
+        assert_eq!(decoded.iter().map(|(lsn, entry)| (*lsn, &entry.data)).collect::<Vec<_>>(),
+            streamed.iter().map(|(lsn, entry)| (*lsn, &entry.data)).collect::<Vec<_>>());
+        assert_eq!(parallel.report(), sequential.report());
+
+        let reader = WALReader::open(&directory).unwrap().with_parallelism(4).read_from(Lsn(30)).unwrap();
+        assert_eq!(reader.map(|entry| entry.unwrap().0.0).collect::<Vec<_>>(), vec![30, 31, 32, 33]);
+    }
+
+    #[test]
+    fn test_stream_sealed_segment() {
+        let directory = temp_directory("reader_segment");
+        let mut wal_manager = WALManager::builder()
+            .set_directory(directory.clone())
+            .build().expect("Cannot create WALManager");
+
+        for i in 0..100 {
+            wal_manager.append_log(entry(i)).unwrap();
+        }
+        wal_manager.checkpoint().unwrap();
+        drop(wal_manager);
+
+        // footer 는 엔트리로 읽지 않는다
+        let mut segment = SegmentReader::open(&directory.join("wal1.log"), 1).unwrap();
+        let entries = segment.by_ref().collect::<Result<Vec<_>, _>>().unwrap();
+        assert_eq!(entries.len(), 101);
+        assert_eq!(entries[99].data, Some(vec![99; 16]));
+        assert_eq!(segment.frames_read(), 101);
+        assert!(!segment.damaged());
+        assert_eq!(segment.corrupt_frames(), 0);
+    }
+
+    #[test]
+    fn test_read_from_lsn() {
+        let directory = temp_directory("reader_read_from");
+        let mut wal_manager = WALManager::builder()
+            .set_directory(directory.clone())
+            .build().expect("Cannot create WALManager");
+
+        for i in 0..3 {
+            wal_manager.append_log(entry(i * 2)).unwrap();
+            wal_manager.append_log(entry(i * 2 + 1)).unwrap();
+            if i < 2 {
+                wal_manager.checkpoint().unwrap();
+            }
+        }
+
+        // 첫 번째 세그먼트 (LSN 1..=3) 는 열지 않는다
+        let mut reader = wal_manager.read_from(Lsn(5)).unwrap();
+        let (lsn, first) = reader.next().unwrap().unwrap();
+        assert_eq!(lsn, Lsn(5));
+        assert_eq!(first.data, Some(vec![3; 16]));
+        assert_eq!(reader.by_ref().count(), 3);
+        assert_eq!(reader.report().segments_scanned, 2);
+        assert_eq!(reader.report().entries_replayed, 4);
+
+        assert_eq!(wal_manager.read_from(Lsn(0)).unwrap().count(), 8);
+        assert_eq!(wal_manager.read_from(Lsn(8)).unwrap().count(), 1);
+        assert_eq!(wal_manager.read_from(Lsn(9)).unwrap().count(), 0);
+    }
+
+    #[test]
+    fn test_read_between_timestamps() {
+        let directory = temp_directory("reader_between");
+        let mut wal_manager = WALManager::builder()
+            .set_directory(directory)
+            .build().expect("Cannot create WALManager");
+
+        for i in 0..10 {
+            wal_manager.append_log(WALEntry { timestamp: 100.0 + i as f64, ..entry(i) }).unwrap();
+        }
+
+        let replayed = wal_manager.read_between(103.0, 106.0).unwrap().collect::<Result<Vec<_>, _>>().unwrap();
+        let lsns = replayed.iter().map(|(lsn, _)| lsn.0).collect::<Vec<_>>();
+        assert_eq!(lsns, vec![4, 5, 6]);
+        assert_eq!(wal_manager.read_between(200.0, 300.0).unwrap().count(), 0);
+    }
+
+    #[test]
+    fn test_filter_transactions() {
+        let directory = temp_directory("reader_transactions");
+        let mut wal_manager = WALManager::builder()
+            .set_directory(directory)
+            .build().expect("Cannot create WALManager");
+
+        for i in 0..9 {
+            wal_manager.append_log(WALEntry { transaction_id: i % 3, ..entry(i as u8) }).unwrap();
+        }
+
+        let replayed = wal_manager.recover().unwrap()
+            .filter_transactions([1])
+            .collect::<Result<Vec<_>, _>>().unwrap();
+        let lsns = replayed.iter().map(|(lsn, _)| lsn.0).collect::<Vec<_>>();
+        assert_eq!(lsns, vec![2, 5, 8]);
+
+        let reader = wal_manager.recover().unwrap().filter_transactions([0, 2]);
+        assert_eq!(reader.count(), 6);
+    }
+
+    #[test]
+    fn test_filter_entry_types() {
+        let directory = temp_directory("reader_entry_types");
+        let mut wal_manager = WALManager::builder()
+            .set_directory(directory)
+            .build().expect("Cannot create WALManager");
+
+        wal_manager.append_log(WALEntry { entry_type: EntryType::TransactionBegin, ..entry(0) }).unwrap();
+        wal_manager.append_log(entry(1)).unwrap();
+        wal_manager.append_log(WALEntry { entry_type: EntryType::Delete, ..entry(2) }).unwrap();
+        wal_manager.append_log(WALEntry { entry_type: EntryType::TransactionCommit, ..entry(3) }).unwrap();
+        wal_manager.checkpoint().unwrap();
+        wal_manager.append_log(entry(4)).unwrap();
+
+        let replayed = wal_manager.recover().unwrap()
+            .filter_entry_types([EntryType::Insert, EntryType::Delete])
+            .collect::<Result<Vec<_>, _>>().unwrap();
+        let lsns = replayed.iter().map(|(lsn, _)| lsn.0).collect::<Vec<_>>();
+        assert_eq!(lsns, vec![2, 3, 6]);
+    }
+
+    #[test]
+    fn test_read_from_seeks_with_index() {
+        let directory = temp_directory("reader_index");
+        let mut wal_manager = WALManager::builder()
+            .set_directory(directory.clone())
+            .build().expect("Cannot create WALManager");
+
+        for i in 0..100 {
+            wal_manager.append_log(entry(i as u8)).unwrap();
+        }
+        drop(wal_manager);
+
+        // 다시 열 때 활성 세그먼트를 읽으면서 색인도 다시 만든다
+        let mut wal_manager = WALManager::builder()
+            .set_directory(directory.clone())
+            .build().expect("Cannot reopen WALManager");
+        for i in 100..200 {
+            wal_manager.append_log(entry(i as u8)).unwrap();
+        }
+        wal_manager.checkpoint().unwrap();
+        assert!(directory.join("wal1.idx").exists());
+
+        // 색인이 가리키는 128 번째 프레임부터 읽으므로 그 앞의 엔트리는 읽지 않는다
+        let mut indexed = wal_manager.read_from(Lsn(150)).unwrap();
+        let (lsn, first) = indexed.next().unwrap().unwrap();
+        assert_eq!(lsn, Lsn(150));
+        assert_eq!(first.data, Some(vec![149; 16]));
+
+        // 색인이 없어도 결과는 같지만 앞부분을 모두 읽는다
+        std::fs::remove_file(directory.join("wal1.idx")).unwrap();
+        let mut scanned = wal_manager.read_from(Lsn(150)).unwrap();
+        let (lsn, first) = scanned.next().unwrap().unwrap();
+        assert_eq!(lsn, Lsn(150));
+        assert_eq!(first.data, Some(vec![149; 16]));
+        assert!(indexed.report().bytes_read * 4 < scanned.report().bytes_read);
+    }
+
+    #[test]
+    fn test_report_corrupted_sealed_segment() {
+        let directory = temp_directory("reader_corrupt");
+        let mut wal_manager = WALManager::builder()
+            .set_directory(directory.clone())
+            .build().expect("Cannot create WALManager");
+
+        wal_manager.append_log(entry(1)).unwrap();
+        wal_manager.checkpoint().unwrap();
+        drop(wal_manager);
+
+        let path = directory.join("wal1.log");
+        let mut bytes = std::fs::read(&path).unwrap();
+        bytes[SEGMENT_HEADER_SIZE + 10] ^= 0xFF;
+        std::fs::write(&path, &bytes).unwrap();
+
+        let result = WALReader::open(&directory).unwrap()
+            .with_recovery_mode(RecoveryMode::Strict)
+            .collect::<Result<Vec<_>, _>>();
+        assert_eq!(result.unwrap_err().kind(), std::io::ErrorKind::InvalidData);
+    }
+
+    #[test]
+    fn test_recovery_report() {
+        let directory = temp_directory("reader_report");
+        let mut wal_manager = WALManager::builder()
+            .set_directory(directory.clone())
+            .build().expect("Cannot create WALManager");
+
+        wal_manager.append_log(entry(1)).unwrap();
+        wal_manager.checkpoint().unwrap();
+        wal_manager.append_log(entry(2)).unwrap();
+        wal_manager.append_log(entry(3)).unwrap();
+        drop(wal_manager);
+
+        let path = directory.join("wal2.log");
+        let length = std::fs::metadata(&path).unwrap().len();
+        std::fs::OpenOptions::new().write(true).open(&path).unwrap().set_len(length - 3).unwrap();
+
+        let mut replayed = Vec::new();
+        let report = WALReader::open(&directory).unwrap()
+            .replay(|lsn, _| replayed.push(lsn.0))
+            .unwrap();
+
+        assert_eq!(replayed, vec![1, 2, 3]);
+        let frame_bytes = report.bytes_read - 2 * SEGMENT_HEADER_SIZE as u64;
+        assert_eq!(report, RecoveryReport {
+            segments_scanned: 2,
+            entries_replayed: 3,
+            bytes_read: report.bytes_read,
+            truncated_frames: 1,
+            corrupt_frames: 0,
+            last_lsn: Lsn(3),
+            last_checkpoint: Some(Lsn(2)),
+            uncommitted_transactions: Vec::new(),
+            in_doubt_transactions: Vec::new(),
+            aborted_transactions: Vec::new(),
+            discarded_entries: 0,
+        });
+        assert!(frame_bytes > 0);
+    }
+
+    #[test]
+    fn test_recovery_modes() {
+        let directory = temp_directory("reader_modes");
+        let mut wal_manager = WALManager::builder()
+            .set_directory(directory.clone())
+            .build().expect("Cannot create WALManager");
+
+        let frame_size = {
+            wal_manager.append_log(entry(1)).unwrap();
+            std::fs::metadata(directory.join("wal1.log")).unwrap().len() as usize - SEGMENT_HEADER_SIZE
+        };
+        wal_manager.append_log(entry(2)).unwrap();
+        wal_manager.append_log(entry(3)).unwrap();
+        drop(wal_manager);
+
+        let path = directory.join("wal1.log");
+        let mut bytes = std::fs::read(&path).unwrap();
+        bytes[SEGMENT_HEADER_SIZE + frame_size + 10] ^= 0xFF;
+        std::fs::write(&path, &bytes).unwrap();
+
+        let replay = |mode| WALReader::open(&directory).unwrap()
+            .with_recovery_mode(mode)
+            .collect::<Result<Vec<_>, _>>();
+
+        assert!(replay(RecoveryMode::Strict).is_err());
+
+        let lsns = |entries: Vec<(Lsn, WALEntry)>| entries.into_iter().map(|(lsn, _)| lsn.0).collect::<Vec<_>>();
+        assert_eq!(lsns(replay(RecoveryMode::TruncateAtError).unwrap()), vec![1]);
+        assert_eq!(lsns(replay(RecoveryMode::SkipCorrupt).unwrap()), vec![1, 3]);
+    }
+
+    #[derive(Default)]
+    struct Recorder {
+        entries: Vec<u64>,
+        checkpoints: Vec<u64>,
+        commits: Vec<(u64, u64)>,
+        corruptions: Vec<CorruptFrame>,
+    }
+
+    impl WALVisitor for Recorder {
+        fn on_entry(&mut self, lsn: Lsn, _entry: WALEntry) {
+            self.entries.push(lsn.0);
+        }
+
+        fn on_checkpoint(&mut self, lsn: Lsn) {
+            self.checkpoints.push(lsn.0);
+        }
+
+        fn on_transaction_commit(&mut self, lsn: Lsn, transaction_id: u64) {
+            self.commits.push((lsn.0, transaction_id));
+        }
+
+        fn on_corruption(&mut self, frame: &CorruptFrame) {
+            self.corruptions.push(frame.clone());
+        }
+    }
+
+    #[test]
+    fn test_visit_entries() {
+        let directory = temp_directory("reader_visitor");
+        let mut wal_manager = WALManager::builder()
+            .set_directory(directory.clone())
+            .set_recovery_mode(RecoveryMode::SkipCorrupt)
+            .build().expect("Cannot create WALManager");
+
+        wal_manager.append_log(WALEntry { entry_type: EntryType::TransactionBegin, transaction_id: 7, ..entry(0) }).unwrap();
+        wal_manager.append_log(WALEntry { transaction_id: 7, ..entry(1) }).unwrap();
+        wal_manager.append_log(WALEntry { entry_type: EntryType::TransactionCommit, transaction_id: 7, ..entry(2) }).unwrap();
+        wal_manager.checkpoint().unwrap();
+
+        let frame_size = {
+            wal_manager.append_log(entry(3)).unwrap();
+            std::fs::metadata(directory.join("wal2.log")).unwrap().len() as usize - SEGMENT_HEADER_SIZE
+        };
+        wal_manager.append_log(entry(4)).unwrap();
+        wal_manager.append_log(entry(5)).unwrap();
+
+        let path = directory.join("wal2.log");
+        let mut bytes = std::fs::read(&path).unwrap();
+        bytes[SEGMENT_HEADER_SIZE + frame_size + 10] ^= 0xFF;
+        std::fs::write(&path, &bytes).unwrap();
+
+        let mut recorder = Recorder::default();
+        let report = wal_manager.replay(&mut recorder).unwrap();
+        assert_eq!(recorder.entries, vec![1, 2, 5, 7]);
+        assert_eq!(recorder.checkpoints, vec![4]);
+        assert_eq!(recorder.commits, vec![(3, 7)]);
+        assert_eq!(recorder.corruptions, vec![CorruptFrame {
+            segment: 2,
+            offset: (SEGMENT_HEADER_SIZE + frame_size) as u64,
+            truncated: false,
+        }]);
+        assert_eq!(report.corrupt_frames, 1);
+    }
+
+    #[test]
+    fn test_replay_orders_segments_numerically() {
+        let directory = temp_directory("reader_ordering");
+        let mut wal_manager = WALManager::builder()
+            .set_directory(directory.clone())
+            .build().expect("Cannot create WALManager");
+
+        // wal10.log, wal11.log 가 wal2.log 보다 앞에 오면 순서가 틀어진다
+        for i in 0..11 {
+            wal_manager.append_log(entry(i)).unwrap();
+            wal_manager.checkpoint().unwrap();
+        }
+        wal_manager.append_log(entry(11)).unwrap();
+        drop(wal_manager);
+        std::fs::remove_file(directory.join("MANIFEST")).unwrap();
+
+        let inserted = WALReader::open(&directory).unwrap()
+            .map(|replayed| replayed.unwrap())
+            .filter(|(_, entry)| matches!(entry.entry_type, EntryType::Insert))
+            .map(|(lsn, entry)| (lsn.0, entry.data.unwrap()[0]))
+            .collect::<Vec<_>>();
+
+        let expected = (0..12u8).map(|i| (i as u64 * 2 + 1, i)).collect::<Vec<_>>();
+        assert_eq!(inserted, expected);
+    }
+
+    #[test]
+    fn test_discard_uncommitted_transactions() {
+        let directory = temp_directory("reader_transactions");
+        let mut wal_manager = WALManager::builder()
+            .set_directory(directory.clone())
+            .build().expect("Cannot create WALManager");
+
+        let record = |entry_type, transaction_id| WALEntry {
+            entry_type,
+            data: None,
+            timestamp: WALManager::get_current_secs(),
+            transaction_id,
+            metadata: Metadata::new(),
+            flags: EntryFlags::NONE
+        };
+        wal_manager.append_log(record(EntryType::TransactionBegin, 1)).unwrap();
+        wal_manager.append_log(record(EntryType::TransactionBegin, 2)).unwrap();
+        wal_manager.append_log(record(EntryType::Insert, 1)).unwrap();
+        wal_manager.append_log(record(EntryType::Insert, 2)).unwrap();
+        wal_manager.append_log(record(EntryType::Set, 0)).unwrap();
+        wal_manager.append_log(record(EntryType::TransactionCommit, 1)).unwrap();
+        drop(wal_manager);
+
+        let mut applied = Vec::new();
+        let report = WALReader::open(&directory).unwrap()
+            .replay_committed(|lsn, _| applied.push(lsn.0))
+            .unwrap();
+
+        assert_eq!(applied, vec![5, 1, 3, 6]);
+        assert_eq!(report.uncommitted_transactions, vec![2]);
+        assert_eq!(report.discarded_entries, 2);
+    }
+
+    #[test]
+    fn test_rollback_to_savepoint() {
+        let directory = temp_directory("reader_savepoints");
+        let mut wal_manager = WALManager::builder()
+            .set_directory(directory.clone())
+            .build().expect("Cannot create WALManager");
+
+        let record = |entry_type, data: u8| WALEntry { entry_type, transaction_id: 1, ..insert_entry(vec![data]) };
+        wal_manager.append_log(record(EntryType::TransactionBegin, 0)).unwrap();
+        wal_manager.append_log(record(EntryType::Insert, 1)).unwrap();
+        wal_manager.savepoint(1, "a").unwrap();
+        wal_manager.append_log(record(EntryType::Insert, 2)).unwrap();
+        wal_manager.savepoint(1, "b").unwrap();
+        wal_manager.append_log(record(EntryType::Insert, 3)).unwrap();
+        wal_manager.rollback_to_savepoint(1, "a").unwrap();
+        wal_manager.append_log(record(EntryType::Insert, 4)).unwrap();
+        // 이미 되돌려진 저장점은 찾지 못하므로 아무것도 되돌리지 않는다
+        wal_manager.rollback_to_savepoint(1, "b").unwrap();
+        wal_manager.append_log(record(EntryType::TransactionCommit, 0)).unwrap();
+        drop(wal_manager);
+
+        let mut applied = Vec::new();
+        let report = WALReader::open(&directory).unwrap()
+            .replay_committed(|lsn, _| applied.push(lsn.0))
+            .unwrap();
+
+        assert_eq!(applied, vec![1, 2, 3, 8, 10]);
+        assert_eq!(report.discarded_entries, 5);
+    }
+
+    #[test]
+    fn test_report_in_doubt_transactions() {
+        let directory = temp_directory("reader_prepared");
+        let mut wal_manager = WALManager::builder()
+            .set_directory(directory.clone())
+            .build().expect("Cannot create WALManager");
+
+        let record = |entry_type, transaction_id| WALEntry {
+            entry_type,
+            data: None,
+            timestamp: WALManager::get_current_secs(),
+            transaction_id,
+            metadata: Metadata::new(),
+            flags: EntryFlags::NONE
+        };
+        for transaction_id in 1..=3 {
+            wal_manager.append_log(record(EntryType::TransactionBegin, transaction_id)).unwrap();
+            wal_manager.append_log(record(EntryType::Insert, transaction_id)).unwrap();
+        }
+        wal_manager.prepare_transaction(1).unwrap();
+        wal_manager.prepare_transaction(2).unwrap();
+        assert_eq!(wal_manager.durable_lsn(), Lsn(8));
+        wal_manager.append_log(record(EntryType::TransactionCommit, 2)).unwrap();
+        drop(wal_manager);
+
+        let mut applied = Vec::new();
+        let report = WALReader::open(&directory).unwrap()
+            .replay_committed(|lsn, _| applied.push(lsn.0))
+            .unwrap();
+
+        assert_eq!(applied, vec![3, 4, 8, 9]);
+        assert_eq!(report.in_doubt_transactions, vec![1]);
+        assert_eq!(report.uncommitted_transactions, vec![3]);
+        assert_eq!(report.discarded_entries, 2);
+    }
+
+    #[test]
+    fn test_replay_nested_transactions() {
+        let directory = temp_directory("reader_nested");
+        let mut wal_manager = WALManager::builder()
+            .set_directory(directory.clone())
+            .build().expect("Cannot create WALManager");
+
+        let record = |entry_type, transaction_id| WALEntry {
+            entry_type,
+            data: None,
+            timestamp: WALManager::get_current_secs(),
+            transaction_id,
+            metadata: Metadata::new(),
+            flags: EntryFlags::NONE
+        };
+        // 1 과 그 자식 2 는 커밋, 3 은 자식 4 가 커밋한 뒤 중단, 자식 5 는 부모 1 이 커밋되기 전에 중단
+        wal_manager.append_log(record(EntryType::TransactionBegin, 1)).unwrap();
+        wal_manager.begin_nested_transaction(2, 1).unwrap();
+        wal_manager.append_log(record(EntryType::Insert, 2)).unwrap();
+        wal_manager.append_log(record(EntryType::TransactionCommit, 2)).unwrap();
+        wal_manager.begin_nested_transaction(5, 1).unwrap();
+        wal_manager.append_log(record(EntryType::Insert, 5)).unwrap();
+        wal_manager.abort_transaction(5).unwrap();
+        wal_manager.append_log(record(EntryType::TransactionBegin, 3)).unwrap();
+        wal_manager.begin_nested_transaction(4, 3).unwrap();
+        wal_manager.append_log(record(EntryType::Insert, 4)).unwrap();
+        wal_manager.append_log(record(EntryType::TransactionCommit, 4)).unwrap();
+        wal_manager.append_log(record(EntryType::Insert, 1)).unwrap();
+        wal_manager.abort_transaction(3).unwrap();
+        wal_manager.append_log(record(EntryType::TransactionCommit, 1)).unwrap();
+        drop(wal_manager);
+
+        let mut applied = Vec::new();
+        let report = WALReader::open(&directory).unwrap()
+            .replay_committed(|lsn, entry| applied.push((lsn.0, entry.transaction_id)))
+            .unwrap();
+
+        assert_eq!(applied, vec![(1, 1), (2, 2), (3, 2), (4, 2), (12, 1), (14, 1)]);
+        assert_eq!(report.aborted_transactions, vec![5, 3]);
+        assert_eq!(report.discarded_entries, 8);
+    }
+
+    #[test]
+    fn test_hide_uncommitted_entries() {
+        let directory = temp_directory("reader_visibility");
+        let mut wal_manager = WALManager::builder()
+            .set_directory(directory.clone())
+            .build().expect("Cannot create WALManager");
+
+        let record = |entry_type, transaction_id| WALEntry {
+            entry_type,
+            data: None,
+            timestamp: WALManager::get_current_secs(),
+            transaction_id,
+            metadata: Metadata::new(),
+            flags: EntryFlags::NONE
+        };
+        wal_manager.append_log(record(EntryType::TransactionBegin, 1)).unwrap();
+        wal_manager.append_log(record(EntryType::TransactionBegin, 2)).unwrap();
+        wal_manager.begin_nested_transaction(3, 2).unwrap();
+        wal_manager.append_log(record(EntryType::Insert, 1)).unwrap();
+        wal_manager.append_log(record(EntryType::Insert, 2)).unwrap();
+        wal_manager.append_log(record(EntryType::Insert, 3)).unwrap();
+        wal_manager.append_log(record(EntryType::TransactionCommit, 3)).unwrap();
+        wal_manager.checkpoint().unwrap();
+        wal_manager.append_log(record(EntryType::Set, 0)).unwrap();
+        wal_manager.append_log(record(EntryType::TransactionCommit, 1)).unwrap();
+        wal_manager.abort_transaction(2).unwrap();
+        drop(wal_manager);
+
+        let lsns = |reader: WALReader| reader.map(|entry| entry.unwrap().0.0).collect::<Vec<_>>();
+        assert_eq!(lsns(WALReader::open(&directory).unwrap()), (1..=11).collect::<Vec<_>>());
+
+        // 부모 2 가 중단되었으므로 커밋한 자식 3 도 보이지 않는다
+        let reader = WALReader::open(&directory).unwrap().with_visibility(Visibility::Committed).unwrap();
+        assert_eq!(lsns(reader), vec![1, 4, 8, 9, 10]);
+
+        let reader = WALReader::open(&directory).unwrap().read_from(Lsn(5)).unwrap().with_visibility(Visibility::Committed).unwrap();
+        assert_eq!(lsns(reader), vec![8, 9, 10]);
+    }
+
+    #[test]
+    fn test_discard_aborted_transactions() {
+        let directory = temp_directory("reader_aborted");
+        let mut wal_manager = WALManager::builder()
+            .set_directory(directory.clone())
+            .build().expect("Cannot create WALManager");
+
+        let record = |entry_type, transaction_id| WALEntry {
+            entry_type,
+            data: None,
+            timestamp: WALManager::get_current_secs(),
+            transaction_id,
+            metadata: Metadata::new(),
+            flags: EntryFlags::NONE
+        };
+        wal_manager.append_log(record(EntryType::TransactionBegin, 1)).unwrap();
+        wal_manager.append_log(record(EntryType::Insert, 1)).unwrap();
+        wal_manager.append_log(record(EntryType::TransactionBegin, 2)).unwrap();
+        wal_manager.append_log(record(EntryType::Insert, 2)).unwrap();
+        wal_manager.abort_transaction(1).unwrap();
+        wal_manager.append_log(record(EntryType::TransactionCommit, 2)).unwrap();
+        wal_manager.append_log(record(EntryType::TransactionBegin, 1)).unwrap();
+        drop(wal_manager);
+
+        let mut applied = Vec::new();
+        let report = WALReader::open(&directory).unwrap()
+            .replay_committed(|lsn, _| applied.push(lsn.0))
+            .unwrap();
+
+        // 중단된 뒤 같은 id 로 다시 시작한 트랜잭션은 새 트랜잭션이다
+        assert_eq!(applied, vec![3, 4, 6]);
+        assert_eq!(report.aborted_transactions, vec![1]);
+        assert_eq!(report.uncommitted_transactions, vec![1]);
+        assert_eq!(report.discarded_entries, 4);
+    }
+
+    #[test]
+    fn test_replay_custom_entries() {
+        let directory = temp_directory("reader_custom");
+        let mut wal_manager = WALManager::builder()
+            .set_directory(directory.clone())
+            .build().expect("Cannot create WALManager");
+
+        let record = |entry_type, transaction_id| WALEntry {
+            entry_type,
+            data: None,
+            timestamp: WALManager::get_current_secs(),
+            transaction_id,
+            metadata: Metadata::new(),
+            flags: EntryFlags::NONE
+        };
+        wal_manager.append_log(record(EntryType::Custom(7), 0)).unwrap();
+        wal_manager.append_log(record(EntryType::TransactionBegin, 1)).unwrap();
+        wal_manager.append_log(record(EntryType::Custom(8), 1)).unwrap();
+        wal_manager.append_log(record(EntryType::TransactionBegin, 2)).unwrap();
+        wal_manager.append_log(record(EntryType::Custom(9), 2)).unwrap();
+        wal_manager.append_log(record(EntryType::TransactionCommit, 1)).unwrap();
+        drop(wal_manager);
+
+        let mut applied = Vec::new();
+        let report = WALReader::open(&directory).unwrap()
+            .replay_committed(|_, entry| applied.push(entry.entry_type))
+            .unwrap();
+
+        // 커밋되지 않은 트랜잭션의 어플리케이션 엔트리도 함께 버린다
+        assert_eq!(applied, vec![
+            EntryType::Custom(7),
+            EntryType::TransactionBegin,
+            EntryType::Custom(8),
+            EntryType::TransactionCommit,
+        ]);
+        assert_eq!(report.uncommitted_transactions, vec![2]);
+
+        let custom = WALReader::open(&directory).unwrap()
+            .filter_entry_types([EntryType::Custom(8), EntryType::Custom(9)])
+            .map(|entry| entry.map(|(lsn, _)| lsn.0))
+            .collect::<Result<Vec<_>, _>>().unwrap();
+        assert_eq!(custom, vec![3, 5]);
+    }
+}
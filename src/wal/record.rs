@@ -0,0 +1,116 @@
+use std::error::Error;
+use std::io;
+
+use bitcode::{Decode, Encode};
+
+use super::core::{EntryType, Metadata, WALEntry, WALEntryRef, WALManager};
+use super::flags::EntryFlags;
+use super::lsn::Lsn;
+
+/// 어플리케이션이 정의한 타입을 data 에 bitcode 로 인코딩해서 담는다
+///
+/// 포맷 2 부터 data 는 페이로드 끝에 그대로 들어가므로 레코드는 한 번만 인코딩되고, 읽을 때도 프레임 버퍼에서 바로
+/// 디코딩한다 ([`WALEntryRef::decode_record`]). 어떤 타입으로 기록했는지는 남기지 않으므로 같은 타입으로 읽어야 한다.
+impl WALEntry {
+    /// 트랜잭션에 속하지 않은 엔트리. 트랜잭션에 넣으려면 `transaction_id` 를 바꾼다.
+    pub fn record<T: Encode + ?Sized>(entry_type: EntryType, record: &T) -> Result<Self, bitcode::Error> {
+        Ok(WALEntry {
+            entry_type,
+            data: Some(bitcode::encode(record)?),
+            timestamp: WALManager::get_current_secs(),
+            transaction_id: 0,
+            metadata: Metadata::new(),
+            flags: EntryFlags::NONE,
+        })
+    }
+
+    /// data 가 없다면 `Ok(None)`
+    pub fn decode_record<T: Decode>(&self) -> Result<Option<T>, io::Error> {
+        decode_record(self.data.as_deref())
+    }
+}
+
+impl WALEntryRef<'_> {
+    /// [`WALEntry::decode_record`]
+    pub fn decode_record<T: Decode>(&self) -> Result<Option<T>, io::Error> {
+        decode_record(self.data)
+    }
+}
+
+impl WALManager {
+    /// [`WALEntry::record`] 로 만든 엔트리를 `append_log` 로 기록하는 것과 같다. 레코드는 매니저가 재사용하는 버퍼에 인코딩해서
+    /// 프레임에 바로 담으므로 엔트리마다 `Vec<u8>` 이나 [`WALEntry`] 를 만들지 않는다.
+    pub fn append_record<T: Encode + ?Sized>(&mut self, entry_type: EntryType, record: &T) -> Result<Lsn, Box<dyn Error>> {
+        self.append_encoded(entry_type, record)
+    }
+}
+
+fn decode_record<T: Decode>(data: Option<&[u8]>) -> Result<Option<T>, io::Error> {
+    data.map(|data| bitcode::decode(data).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))).transpose()
+}
+
+#[cfg(test)]
+mod record_tests {
+    // bitcode 0.4 derive 매크로가 생성하는 코드에서 발생하는 lint
+    #![allow(unused_must_use, clippy::assign_op_pattern)]
+
+    use bitcode::{Decode, Encode};
+
+    use crate::wal::core::{EntryType, WALManager};
+    use crate::wal::sync::SyncPolicy;
+    use crate::wal::test_utils::{count_allocations, temp_directory};
+
+    #[derive(Debug, PartialEq, Encode, Decode)]
+    struct Account {
+        id: u32,
+        owner: String,
+        balance: i64,
+    }
+
+    #[test]
+    fn test_replay_typed_records() {
+        let directory = temp_directory("record_replay");
+        let mut wal_manager = WALManager::builder()
+            .set_directory(directory.clone())
+            .build().expect("Cannot create WALManager");
+
+        wal_manager.append_record(EntryType::Insert, &Account { id: 1, owner: "alice".to_string(), balance: 100 }).unwrap();
+        wal_manager.append_record(EntryType::Set, &Account { id: 1, owner: "alice".to_string(), balance: -5 }).unwrap();
+        wal_manager.checkpoint().unwrap();
+
+        let mut accounts = Vec::new();
+        wal_manager.recover().unwrap().replay_ref(|_, entry| {
+            accounts.push(entry.decode_record::<Account>().unwrap());
+        }).unwrap();
+
+        assert_eq!(accounts, vec![
+            Some(Account { id: 1, owner: "alice".to_string(), balance: 100 }),
+            Some(Account { id: 1, owner: "alice".to_string(), balance: -5 }),
+            None,
+        ]);
+
+        let entries = wal_manager.recover().unwrap().collect::<Result<Vec<_>, _>>().unwrap();
+        assert!(entries[0].1.decode_record::<u8>().is_err());
+    }
+    #[test]
+    fn test_append_record_without_allocating_each() {
+        let mut wal_manager = WALManager::builder()
+            .set_directory(temp_directory("record_allocations"))
+            .set_sync_policy(SyncPolicy::Never)
+            .build().expect("Cannot create WALManager");
+        let account = Account { id: 1, owner: "alice".to_string(), balance: 100 };
+        wal_manager.append_record(EntryType::Insert, &account).unwrap();
+
+        // 레코드를 `Vec<u8>` 이나 `WALEntry` 로 옮기지 않고, 재사용하는 버퍼에 인코딩해서 프레임에 바로 담는다
+        let (_, allocations) = count_allocations(|| {
+            for _ in 1..1000 {
+                wal_manager.append_record(EntryType::Insert, &account).unwrap();
+            }
+        });
+        assert!(allocations < 64, "{allocations} allocations");
+
+        let accounts = wal_manager.recover().unwrap().map(|entry| entry.unwrap().1.decode_record::<Account>().unwrap().unwrap()).collect::<Vec<_>>();
+        assert_eq!(accounts.len(), 1000);
+        assert_eq!(accounts[999], account);
+    }
+}
@@ -0,0 +1,456 @@
+use std::fs::{File, OpenOptions};
+use std::io::{Error, ErrorKind, Read};
+use std::path::{Path, PathBuf};
+
+use super::core::{finish_interrupted_writes, WALEntry};
+use super::double_write::DoubleWriteBuffer;
+use super::index::index_path;
+use super::lsn::Lsn;
+use super::manifest::Manifest;
+use super::quarantine::quarantine;
+use super::reader::{RecoveryMode, SegmentReader};
+use super::segment::{segment_path, SegmentFooter, SegmentHeader, SEGMENT_HEADER_SIZE};
+use super::sync::sync_directory;
+use super::writer::{SegmentWriter, WriterOptions};
+
+/// `repair` 가 실제로 고친 내용
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct RepairReport {
+    /// 첫 번째 손상이 있던 세그먼트와 그 위치. 세그먼트는 이 위치에서 잘렸다.
+    pub truncated_at: Option<(u64, u64)>,
+    /// 손상 지점 뒤에 있어서 통째로 치운 세그먼트
+    pub removed_segments: Vec<u64>,
+    /// 프레임은 온전하지만 footer 의 체크섬이 맞지 않아 footer 만 떼어낸 세그먼트
+    pub stripped_footers: Vec<u64>,
+    /// 잘라내거나 치운 바이트 수
+    pub removed_bytes: u64,
+    /// 치운 바이트를 옮겨둔 `quarantine/` 안의 파일
+    pub quarantined_files: Vec<PathBuf>,
+}
+
+/// [`recover_to`] 가 되돌릴 시점. 이 시점 이후의 엔트리는 모두 잘려나간다.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum RecoveryTarget {
+    /// 이 LSN 까지 남긴다
+    Lsn(Lsn),
+    /// 타임스탬프가 이 값 이하인 엔트리까지 남긴다. 처음으로 이 값을 넘는 엔트리에서 멈춘다.
+    Timestamp(f64),
+}
+
+impl RecoveryTarget {
+    fn passed(&self, lsn: Lsn, entry: &WALEntry) -> bool {
+        match *self {
+            RecoveryTarget::Lsn(target) => lsn > target,
+            RecoveryTarget::Timestamp(target) => entry.timestamp > target,
+        }
+    }
+}
+
+enum Scan {
+    Missing,
+    InvalidHeader,
+    Damaged { offset: u64, truncated: bool },
+    Intact { frames: u64, offset: u64 },
+}
+
+/// 세그먼트들을 순서대로 읽다가 첫 번째로 깨진 프레임(혹은 읽을 수 없는 세그먼트)을 만나면
+/// 그 세그먼트를 마지막으로 온전한 엔트리 뒤에서 자르고, 그 뒤의 세그먼트는 모두 치운다.
+/// 잘린 세그먼트는 활성 세그먼트가 되고 매니페스트도 그에 맞춰 다시 쓴다.
+///
+/// `save_removed` 면 치운 바이트를 메타데이터와 함께 `quarantine/` 으로 옮기고 ([`quarantine`]), 아니면 지운다.
+/// 디렉토리 잠금은 호출하는 쪽에서 잡아야 한다 ([`WALManager::repair`](super::core::WALManager::repair)).
+pub fn repair_directory(directory: &Path, save_removed: bool) -> Result<RepairReport, Error> {
+    finish_interrupted_writes(directory)?;
+
+    let (mut manifest, mut changed) = load_manifest(directory)?;
+    let mut sealed_lsn = manifest.first_lsn(directory)?;
+    let mut report = RepairReport::default();
+    let segments = manifest.live_segments().collect::<Vec<_>>();
+    let mut cut = None;
+
+    for (index, &sequence) in segments.iter().enumerate() {
+        let path = segment_path(directory, sequence as usize);
+        let sealed = sequence != manifest.sequence;
+
+        match scan(&path, sequence)? {
+            Scan::Missing if !sealed => {},
+            Scan::Missing | Scan::InvalidHeader => {
+                cut = Some((index, 0, "invalid segment header"));
+                break;
+            },
+            Scan::Damaged { offset, truncated } => {
+                cut = Some((index, offset, if truncated { "truncated frame" } else { "checksum mismatch" }));
+                break;
+            },
+            Scan::Intact { frames, offset } if sealed => {
+                // 체크포인트 프레임까지 온전하므로 footer 를 떼어내면 footer 없는 예전 형식의 봉인된 세그먼트가 된다
+                if let Some(footer) = SegmentFooter::read(&path, sequence)? {
+                    if !footer.verify(&path)? {
+                        set_aside(directory, sequence, offset, save_removed, "footer checksum mismatch", &mut report)?;
+                        report.stripped_footers.push(sequence);
+                    }
+                }
+                sealed_lsn += frames;
+            },
+            Scan::Intact { .. } => {},
+        }
+    }
+
+    if let Some((index, offset, reason)) = cut {
+        cut_log(directory, &mut manifest, &segments[index..], offset, save_removed, reason, &mut report)?;
+        changed = true;
+    }
+
+    if changed || !report.stripped_footers.is_empty() {
+        manifest.sealed_lsn = sealed_lsn;
+        sync_directory(directory)?;
+        manifest.save(directory)?;
+    }
+
+    Ok(report)
+}
+
+/// `target` 이후의 엔트리를 모두 잘라내서 로그를 그 시점으로 되돌린다 ("잘못된 배포 직전으로 복원").
+///
+/// 잘라낸 바이트는 지우지 않고 `quarantine/` 으로 옮긴다. 자르는 방식은 [`repair_directory`] 와 같고,
+/// 목표 시점보다 앞에서 깨진 프레임을 만나면 그 자리에서 자른다. 디렉토리 잠금은 호출하는 쪽에서 잡아야 한다.
+/// 블록으로 묶은 세그먼트는 블록의 시작에서 자르고, 그 블록에서 목표 시점 앞에 있던 엔트리는 새 블록으로 다시 쓴다.
+pub fn recover_to(directory: &Path, target: RecoveryTarget) -> Result<RepairReport, Error> {
+    finish_interrupted_writes(directory)?;
+
+    let (mut manifest, changed) = load_manifest(directory)?;
+    let mut lsn = manifest.first_lsn(directory)?;
+    let mut sealed_lsn = lsn;
+    let mut report = RepairReport::default();
+    let segments = manifest.live_segments().collect::<Vec<_>>();
+    let mut cut = None;
+
+    'segments: for (index, &sequence) in segments.iter().enumerate() {
+        let path = segment_path(directory, sequence as usize);
+        if !path.exists() {
+            if sequence != manifest.sequence {
+                cut = Some((index, 0, Vec::new()));
+            }
+            break;
+        }
+
+        let mut segment = SegmentReader::open(&path, sequence)?.with_recovery_mode(RecoveryMode::TruncateAtError);
+        // 지금 읽고 있는 블록의 시작과 그 안에서 이미 지나온 엔트리
+        let (mut block, mut kept) = (segment.resume_point(), Vec::new());
+        loop {
+            let at = segment.resume_point();
+            if at.position != block.position {
+                (block, kept) = (at, Vec::new());
+            }
+            let Some(entry) = segment.next_entry()? else {
+                break;
+            };
+
+            if target.passed(lsn + segment.frames_read(), &entry) {
+                let first_lsn = lsn + block.position + 1;
+                cut = Some((index, block.offset, kept.into_iter().map(|entry| (first_lsn, entry)).collect()));
+                break 'segments;
+            }
+            kept.push(entry);
+        }
+
+        if segment.damaged() {
+            cut = Some((index, segment.offset(), Vec::new()));
+            break;
+        }
+
+        lsn += segment.frames_read();
+        if sequence != manifest.sequence {
+            sealed_lsn = lsn;
+        }
+    }
+
+    if let Some((index, offset, kept)) = &cut {
+        let reason = format!("after recovery target {:?}", target);
+        cut_log(directory, &mut manifest, &segments[*index..], *offset, true, &reason, &mut report)?;
+        if let Some(&(first_lsn, _)) = kept.first() {
+            let entries = kept.iter().map(|(_, entry)| entry);
+            append_block(directory, segments[*index], first_lsn, entries)?;
+        }
+    }
+
+    if changed || cut.is_some() {
+        manifest.sealed_lsn = sealed_lsn;
+        sync_directory(directory)?;
+        manifest.save(directory)?;
+    }
+
+    Ok(report)
+}
+
+/// 블록의 시작에서 자른 세그먼트 끝에 그 블록에서 남겨야 할 엔트리들을 다시 묶어서 붙인다
+fn append_block<'a>(directory: &Path, sequence: u64, first_lsn: Lsn, entries: impl Iterator<Item = &'a WALEntry>) -> Result<(), Error> {
+    let path = segment_path(directory, sequence as usize);
+    let mut writer = SegmentWriter::open(&path, sequence as usize, &WriterOptions::default())?;
+    let mut frames = Vec::new();
+    for (lsn, entry) in (first_lsn.0..).map(Lsn).zip(entries) {
+        let frame = entry.encode_frame(sequence as usize, writer.checksum(), writer.format(), lsn)
+            .map_err(|e| Error::new(ErrorKind::InvalidData, e))?;
+        frames.extend_from_slice(&frame);
+    }
+
+    writer.write_frames_and_sync(&frames)?;
+    Ok(())
+}
+
+fn load_manifest(directory: &Path) -> Result<(Manifest, bool), Error> {
+    match Manifest::load(directory) {
+        Ok(Some(manifest)) => Ok((manifest, false)),
+        Ok(None) => Ok((Manifest::rebuild(directory)?, true)),
+        Err(e) if e.kind() == ErrorKind::InvalidData => Ok((Manifest::rebuild(directory)?, true)),
+        Err(e) => Err(e),
+    }
+}
+
+/// `segments[0]` 을 `offset` 에서 자르고 그 뒤의 세그먼트는 모두 치운 뒤, 잘린 세그먼트를 활성 세그먼트로 삼는다.
+/// 매니페스트는 고치기만 하고 저장은 호출하는 쪽에서 `sealed_lsn` 과 함께 한다.
+fn cut_log(
+    directory: &Path,
+    manifest: &mut Manifest,
+    segments: &[u64],
+    offset: u64,
+    save: bool,
+    reason: &str,
+    report: &mut RepairReport,
+) -> Result<(), Error> {
+    let sequence = segments[0];
+    report.truncated_at = Some((sequence, offset));
+
+    let following = format!("follows segment {} ({})", sequence, reason);
+    for (position, &later) in segments.iter().enumerate() {
+        if segment_path(directory, later as usize).exists() {
+            if position == 0 {
+                set_aside(directory, later, offset, save, reason, report)?;
+            } else {
+                set_aside(directory, later, 0, save, &following, report)?;
+                report.removed_segments.push(later);
+            }
+        }
+
+        // 잘린 세그먼트의 색인은 파일 끝 너머를 가리킬 수 있다
+        match std::fs::remove_file(index_path(directory, later as usize)) {
+            Err(e) if e.kind() != ErrorKind::NotFound => return Err(e),
+            _ => {},
+        }
+    }
+
+    // 잘라낸 위치에 찢어진 페이지를 다시 쓰지 않도록 한다
+    DoubleWriteBuffer::clear(directory)?;
+
+    let kept = manifest.sealed_segments.iter().take_while(|&&sealed| sealed < sequence).count();
+    manifest.sealed_segments.truncate(kept);
+    manifest.last_checkpoint = manifest.sealed_segments.last().copied();
+    manifest.sequence = sequence;
+
+    Ok(())
+}
+
+fn scan(path: &Path, sequence: u64) -> Result<Scan, Error> {
+    let mut file = match File::open(path) {
+        Ok(file) => file,
+        Err(e) if e.kind() == ErrorKind::NotFound => return Ok(Scan::Missing),
+        Err(e) => return Err(e),
+    };
+
+    let mut header = [0u8; SEGMENT_HEADER_SIZE];
+    match file.read_exact(&mut header) {
+        Ok(()) => {},
+        Err(e) if e.kind() == ErrorKind::UnexpectedEof => return Ok(Scan::InvalidHeader),
+        Err(e) => return Err(e),
+    }
+    if !SegmentHeader::decode(&header).is_ok_and(|header| header.sequence == sequence) {
+        return Ok(Scan::InvalidHeader);
+    }
+    drop(file);
+
+    let mut segment = SegmentReader::open(path, sequence)?.with_recovery_mode(RecoveryMode::TruncateAtError);
+    while segment.next_entry()?.is_some() {}
+
+    if segment.damaged() {
+        let truncated = segment.take_corruptions().last().is_some_and(|frame| frame.truncated);
+        Ok(Scan::Damaged { offset: segment.offset(), truncated })
+    } else {
+        Ok(Scan::Intact { frames: segment.frames_read(), offset: segment.offset() })
+    }
+}
+
+/// 세그먼트의 `offset` 뒤를 잘라내고, `save` 면 잘라낸 바이트를 격리한다. `offset` 이 0 이면 파일을 통째로 치운다.
+fn set_aside(
+    directory: &Path,
+    sequence: u64,
+    offset: u64,
+    save: bool,
+    reason: &str,
+    report: &mut RepairReport,
+) -> Result<(), Error> {
+    let path = segment_path(directory, sequence as usize);
+    let length = std::fs::metadata(&path)?.len();
+    if offset >= length {
+        return Ok(());
+    }
+
+    if save {
+        report.quarantined_files.push(quarantine(directory, sequence, offset, reason)?);
+    }
+
+    if offset > 0 {
+        let file = OpenOptions::new().write(true).open(&path)?;
+        file.set_len(offset)?;
+        file.sync_all()?;
+    } else if !save {
+        std::fs::remove_file(&path)?;
+    }
+    report.removed_bytes += length - offset;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod repair_tests {
+    use super::{repair_directory, RecoveryTarget};
+    use crate::wal::core::{WALEntry, WALManager};
+    use crate::wal::lsn::Lsn;
+    use crate::wal::reader::{RecoveryMode, WALReader};
+    use crate::wal::segment::{SegmentFooter, SEGMENT_FOOTER_SIZE, SEGMENT_HEADER_SIZE};
+    use crate::wal::test_utils::{insert_entry, temp_directory};
+
+    fn entry(value: u8) -> WALEntry {
+        insert_entry(vec![value; 16])
+    }
+
+    #[test]
+    fn test_truncate_at_first_corruption() {
+        let directory = temp_directory("repair_truncate");
+        let mut wal_manager = WALManager::builder()
+            .set_directory(directory.clone())
+            .build().expect("Cannot create WALManager");
+
+        let frame_size = {
+            wal_manager.append_log(entry(1)).unwrap();
+            std::fs::metadata(directory.join("wal1.log")).unwrap().len() - SEGMENT_HEADER_SIZE as u64
+        };
+        wal_manager.append_log(entry(2)).unwrap();
+        wal_manager.checkpoint().unwrap();
+        wal_manager.append_log(entry(3)).unwrap();
+        drop(wal_manager);
+
+        // 봉인된 첫 세그먼트의 두 번째 프레임이 깨졌다
+        let path = directory.join("wal1.log");
+        let mut bytes = std::fs::read(&path).unwrap();
+        let length = bytes.len() as u64;
+        let removed_segment = std::fs::metadata(directory.join("wal2.log")).unwrap().len();
+        bytes[SEGMENT_HEADER_SIZE + frame_size as usize + 10] ^= 0xFF;
+        std::fs::write(&path, &bytes).unwrap();
+        assert!(WALManager::builder().set_directory(directory.clone()).build().is_err());
+
+        let offset = SEGMENT_HEADER_SIZE as u64 + frame_size;
+        let report = WALManager::repair(&directory, true).unwrap();
+        assert_eq!(report.truncated_at, Some((1, offset)));
+        assert_eq!(report.removed_segments, vec![2]);
+        assert_eq!(report.quarantined_files.len(), 2);
+        assert_eq!(std::fs::read(&report.quarantined_files[0]).unwrap(), &bytes[offset as usize..]);
+        let meta = std::fs::read_to_string(report.quarantined_files[0].with_extension("meta")).unwrap();
+        assert!(meta.contains("reason=checksum mismatch\n"));
+        let meta = std::fs::read_to_string(report.quarantined_files[1].with_extension("meta")).unwrap();
+        assert!(meta.starts_with("segment=2\noffset=0\n"));
+        assert_eq!(report.removed_bytes, (length - offset) + removed_segment);
+
+        // 잘린 세그먼트가 활성 세그먼트가 되고 그 뒤에 이어 쓴다
+        let mut wal_manager = WALManager::builder()
+            .set_directory(directory.clone())
+            .set_recovery_mode(RecoveryMode::Strict)
+            .build().expect("Repaired WAL should open");
+        assert_eq!(wal_manager.last_lsn(), Lsn(1));
+        wal_manager.append_log(entry(4)).unwrap();
+        drop(wal_manager);
+
+        let replayed = WALReader::open(&directory).unwrap().collect::<Result<Vec<_>, _>>().unwrap();
+        assert_eq!(replayed.iter().map(|(lsn, _)| lsn.0).collect::<Vec<_>>(), vec![1, 2]);
+        assert_eq!(replayed[1].1.data, Some(vec![4; 16]));
+
+        // 고칠 것이 없다면 아무것도 하지 않는다
+        assert_eq!(repair_directory(&directory, false).unwrap(), Default::default());
+    }
+
+    #[test]
+    fn test_recover_to_target() {
+        let directory = temp_directory("repair_recover_to");
+        let mut wal_manager = WALManager::builder()
+            .set_directory(directory.clone())
+            .build().expect("Cannot create WALManager");
+
+        for i in 0..3 {
+            wal_manager.append_log(WALEntry { timestamp: 100.0 + i as f64, ..entry(i) }).unwrap();
+        }
+        wal_manager.checkpoint().unwrap();
+        for i in 3..6 {
+            wal_manager.append_log(WALEntry { timestamp: 100.0 + i as f64, ..entry(i) }).unwrap();
+        }
+        wal_manager.checkpoint().unwrap();
+        wal_manager.append_log(WALEntry { timestamp: 106.0, ..entry(6) }).unwrap();
+        drop(wal_manager);
+
+        // 두 번째 세그먼트 (LSN 5..=8) 의 중간으로 되돌린다
+        let report = WALManager::recover_to(&directory, RecoveryTarget::Lsn(Lsn(6))).unwrap();
+        assert!(matches!(report.truncated_at, Some((2, _))));
+        assert_eq!(report.removed_segments, vec![3]);
+        assert_eq!(report.quarantined_files.len(), 2);
+
+        let mut wal_manager = WALManager::builder()
+            .set_directory(directory.clone())
+            .build().expect("Recovered WAL should open");
+        assert_eq!(wal_manager.last_lsn(), Lsn(6));
+        wal_manager.append_log(entry(9)).unwrap();
+        drop(wal_manager);
+
+        let replayed = WALReader::open(&directory).unwrap().collect::<Result<Vec<_>, _>>().unwrap();
+        assert_eq!(replayed.iter().map(|(lsn, _)| lsn.0).collect::<Vec<_>>(), (1..=7).collect::<Vec<_>>());
+        assert_eq!(replayed[6].1.data, Some(vec![9; 16]));
+
+        // 타임스탬프 101 이후는 첫 세그먼트의 세 번째 엔트리부터다
+        WALManager::recover_to(&directory, RecoveryTarget::Timestamp(101.0)).unwrap();
+        let replayed = WALReader::open(&directory).unwrap().collect::<Result<Vec<_>, _>>().unwrap();
+        assert_eq!(replayed.len(), 2);
+
+        // 끝을 넘는 목표는 아무것도 바꾸지 않는다
+        assert_eq!(WALManager::recover_to(&directory, RecoveryTarget::Lsn(Lsn(100))).unwrap(), Default::default());
+    }
+
+    #[test]
+    fn test_strip_corrupted_footer() {
+        let directory = temp_directory("repair_footer");
+        let mut wal_manager = WALManager::builder()
+            .set_directory(directory.clone())
+            .build().expect("Cannot create WALManager");
+
+        wal_manager.append_log(entry(1)).unwrap();
+        wal_manager.checkpoint().unwrap();
+        wal_manager.append_log(entry(2)).unwrap();
+        drop(wal_manager);
+
+        // 프레임은 모두 온전하지만 footer 에 기록된 body 체크섬이 맞지 않는다
+        let path = directory.join("wal1.log");
+        let mut bytes = std::fs::read(&path).unwrap();
+        let footer_at = bytes.len() - SEGMENT_FOOTER_SIZE;
+        let mut footer = SegmentFooter::decode(&bytes[footer_at..]).unwrap();
+        footer.body_checksum ^= 1;
+        bytes[footer_at..].copy_from_slice(&footer.encode());
+        std::fs::write(&path, &bytes).unwrap();
+        assert!(WALManager::builder().set_directory(directory.clone()).build().is_err());
+
+        let report = WALManager::repair(&directory, false).unwrap();
+        assert_eq!(report.truncated_at, None);
+        assert_eq!(report.stripped_footers, vec![1]);
+        assert!(report.quarantined_files.is_empty());
+
+        let wal_manager = WALManager::builder()
+            .set_directory(directory)
+            .build().expect("Repaired WAL should open");
+        assert_eq!(wal_manager.last_lsn(), Lsn(3));
+    }
+}
@@ -0,0 +1,86 @@
+use std::io;
+use std::time::Duration;
+
+/// 세그먼트에 쓰다가 일시적인 오류(디스크 가득 참, 시간 초과, 인터럽트)가 나면 다시 시도할 횟수와 간격
+/// ([`WALBuilder::set_retry_policy`](super::core::WALBuilder::set_retry_policy))
+///
+/// 실패한 쓰기는 쓰기 전의 위치로 되돌린 뒤 같은 바이트를 다시 쓴다. fsync 는 다시 시도하지 않는다.
+/// 실패한 뒤의 fsync 가 성공해도 앞서 버려진 페이지가 디스크에 닿았다고 보장하지 않기 때문이다.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct RetryPolicy {
+    /// 0 이면 다시 시도하지 않는다
+    pub max_retries: u32,
+    /// 첫 재시도 전에 기다릴 시간. 한 번 실패할 때마다 두 배로 늘린다.
+    pub initial_backoff: Duration,
+    pub max_backoff: Duration,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self { max_retries: 0, initial_backoff: Duration::from_millis(10), max_backoff: Duration::from_secs(1) }
+    }
+}
+
+impl RetryPolicy {
+    /// `operation` 이 일시적인 오류로 실패하면 기다렸다가 다시 부른다. 횟수를 넘기면 마지막 오류를 돌려준다.
+    pub(crate) fn run<T>(&self, mut operation: impl FnMut() -> io::Result<T>) -> io::Result<T> {
+        let mut backoff = self.initial_backoff;
+        let mut retries = 0;
+        loop {
+            match operation() {
+                Err(e) if retries < self.max_retries && is_transient(&e) => {
+                    std::thread::sleep(backoff);
+                    backoff = (backoff * 2).min(self.max_backoff);
+                    retries += 1;
+                },
+                result => return result,
+            }
+        }
+    }
+}
+
+/// 기다리면 풀릴 수 있는 오류
+pub(crate) fn is_transient(e: &io::Error) -> bool {
+    matches!(e.kind(), io::ErrorKind::StorageFull | io::ErrorKind::TimedOut | io::ErrorKind::Interrupted)
+}
+
+#[cfg(test)]
+mod retry_tests {
+    use std::io;
+    use std::time::Duration;
+
+    use super::RetryPolicy;
+
+    #[test]
+    fn test_retry_transient_errors() {
+        let policy = RetryPolicy { max_retries: 3, initial_backoff: Duration::from_millis(1), max_backoff: Duration::from_millis(2) };
+
+        // 일시적인 오류는 횟수 안에서 다시 시도한다
+        let mut attempts = 0;
+        let result = policy.run(|| {
+            attempts += 1;
+            match attempts {
+                1..=2 => Err(io::Error::from(io::ErrorKind::StorageFull)),
+                _ => Ok(attempts),
+            }
+        });
+        assert_eq!(result.unwrap(), 3);
+
+        // 횟수를 넘기면 마지막 오류를 돌려주고, 그 밖의 오류는 바로 돌려준다
+        let mut attempts = 0;
+        let result = policy.run(|| -> io::Result<()> {
+            attempts += 1;
+            Err(io::Error::from(io::ErrorKind::TimedOut))
+        });
+        assert_eq!(result.unwrap_err().kind(), io::ErrorKind::TimedOut);
+        assert_eq!(attempts, 4);
+
+        let mut attempts = 0;
+        let result = policy.run(|| -> io::Result<()> {
+            attempts += 1;
+            Err(io::Error::from(io::ErrorKind::PermissionDenied))
+        });
+        assert!(result.is_err());
+        assert_eq!(attempts, 1);
+    }
+}
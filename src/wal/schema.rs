@@ -0,0 +1,484 @@
+// bitcode 0.4 derive 매크로가 생성하는 코드에서 발생하는 lint
+#![allow(unused_must_use, clippy::assign_op_pattern)]
+
+use bitcode::{Decode, Encode};
+use std::io::{Error, ErrorKind};
+
+use super::core::{EntryHeader, EntryType, Metadata, WALEntry, WALEntryRef};
+use super::flags::EntryFlags;
+use super::frame::FrameLayout;
+use super::lsn::Lsn;
+use super::segment::{SegmentHeader, ENTRY_TYPE_CODE_FORMAT_VERSION, FLAGS_FORMAT_VERSION, TIMESTAMP_DELTA_FORMAT_VERSION, VARINT_FORMAT_VERSION};
+use super::varint::{read_varint, unzigzag, write_varint, zigzag};
+
+// 디스크에 남기는 엔트리의 포맷별 모양과, 예전 포맷을 지금의 `EntryHeader`/`WALEntry` 로 옮기는 변환
+//
+// bitcode 는 필드 이름을 남기지 않고 enum 의 종류 번호도 종류 수에 맞춰 줄여서 남기므로, 구조체에 필드를 더하거나
+// enum 에 종류를 더하면 예전 바이트를 다른 값으로 읽는다. 그래서 한 번 기록한 포맷의 구조체는 고치지 않고,
+// 모양을 바꿀 때는 세그먼트 포맷 버전을 올리고 새 구조체를 더한다.
+
+/// 사용자 정의 엔트리 종류 번호가 시작하는 곳. 이 아래는 WAL 이 쓰는 종류다.
+const CUSTOM_ENTRY_TYPE_CODE: u32 = 1 << 16;
+
+/// 엔트리를 기록할(혹은 기록된) 세그먼트의 포맷
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct EntryFormat {
+    pub version: u16,
+    /// 포맷 6 부터 타임스탬프를 이 시각(마이크로초)과의 차이로 남긴다. 세그먼트 헤더의 `created_at` 이다.
+    pub epoch: i64,
+}
+
+impl EntryFormat {
+    /// 기준 시각이 0 인 포맷. 세그먼트 밖에서 페이로드만 다룰 때 쓴다.
+    pub fn new(version: u16) -> Self {
+        Self { version, epoch: 0 }
+    }
+
+    pub fn of(header: &SegmentHeader) -> Self {
+        Self { version: header.version, epoch: micros(header.created_at) }
+    }
+
+    pub fn frame_layout(&self) -> FrameLayout {
+        match self.version >= VARINT_FORMAT_VERSION {
+            true => FrameLayout::Varint,
+            false => FrameLayout::Fixed,
+        }
+    }
+}
+
+impl EntryType {
+    /// 포맷 5 부터 디스크에 남기는 번호. 종류를 더해도 기존 번호는 바뀌지 않는다.
+    pub fn code(self) -> u32 {
+        match self {
+            EntryType::Insert => 0,
+            EntryType::Set => 1,
+            EntryType::Delete => 2,
+            EntryType::Checkpoint => 3,
+            EntryType::TransactionBegin => 4,
+            EntryType::TransactionCommit => 5,
+            EntryType::TransactionAbort => 6,
+            EntryType::Savepoint => 7,
+            EntryType::RollbackToSavepoint => 8,
+            EntryType::TransactionPrepare => 9,
+            EntryType::BatchBegin => 10,
+            EntryType::BatchEnd => 11,
+            EntryType::Custom(kind) => CUSTOM_ENTRY_TYPE_CODE + kind as u32,
+        }
+    }
+
+    /// 모르는 번호라면 `None`
+    pub fn from_code(code: u32) -> Option<Self> {
+        let entry_type = match code {
+            0 => EntryType::Insert,
+            1 => EntryType::Set,
+            2 => EntryType::Delete,
+            3 => EntryType::Checkpoint,
+            4 => EntryType::TransactionBegin,
+            5 => EntryType::TransactionCommit,
+            6 => EntryType::TransactionAbort,
+            7 => EntryType::Savepoint,
+            8 => EntryType::RollbackToSavepoint,
+            9 => EntryType::TransactionPrepare,
+            10 => EntryType::BatchBegin,
+            11 => EntryType::BatchEnd,
+            code => EntryType::Custom(u16::try_from(code.checked_sub(CUSTOM_ENTRY_TYPE_CODE)?).ok()?),
+        };
+
+        Some(entry_type)
+    }
+}
+
+/// 포맷 4 까지 bitcode enum 으로 남기던 [`EntryType`]. 포맷 4 를 기록하던 때의 종류로 고정한다.
+#[derive(Clone, Copy, Encode, Decode)]
+enum LegacyEntryType {
+    Insert,
+    Set,
+    Delete,
+    Checkpoint,
+    TransactionBegin,
+    TransactionCommit,
+    TransactionAbort,
+    Savepoint,
+    RollbackToSavepoint,
+    TransactionPrepare,
+    BatchBegin,
+    BatchEnd,
+    Custom(u16),
+}
+
+impl From<LegacyEntryType> for EntryType {
+    fn from(entry_type: LegacyEntryType) -> Self {
+        match entry_type {
+            LegacyEntryType::Insert => EntryType::Insert,
+            LegacyEntryType::Set => EntryType::Set,
+            LegacyEntryType::Delete => EntryType::Delete,
+            LegacyEntryType::Checkpoint => EntryType::Checkpoint,
+            LegacyEntryType::TransactionBegin => EntryType::TransactionBegin,
+            LegacyEntryType::TransactionCommit => EntryType::TransactionCommit,
+            LegacyEntryType::TransactionAbort => EntryType::TransactionAbort,
+            LegacyEntryType::Savepoint => EntryType::Savepoint,
+            LegacyEntryType::RollbackToSavepoint => EntryType::RollbackToSavepoint,
+            LegacyEntryType::TransactionPrepare => EntryType::TransactionPrepare,
+            LegacyEntryType::BatchBegin => EntryType::BatchBegin,
+            LegacyEntryType::BatchEnd => EntryType::BatchEnd,
+            LegacyEntryType::Custom(kind) => EntryType::Custom(kind),
+        }
+    }
+}
+
+/// 예전 포맷의 활성 세그먼트에 이어 쓸 때 쓴다. 나중에 더한 종류는 여기서 어떻게 남길지 정해야 컴파일된다.
+impl From<EntryType> for LegacyEntryType {
+    fn from(entry_type: EntryType) -> Self {
+        match entry_type {
+            EntryType::Insert => LegacyEntryType::Insert,
+            EntryType::Set => LegacyEntryType::Set,
+            EntryType::Delete => LegacyEntryType::Delete,
+            EntryType::Checkpoint => LegacyEntryType::Checkpoint,
+            EntryType::TransactionBegin => LegacyEntryType::TransactionBegin,
+            EntryType::TransactionCommit => LegacyEntryType::TransactionCommit,
+            EntryType::TransactionAbort => LegacyEntryType::TransactionAbort,
+            EntryType::Savepoint => LegacyEntryType::Savepoint,
+            EntryType::RollbackToSavepoint => LegacyEntryType::RollbackToSavepoint,
+            EntryType::TransactionPrepare => LegacyEntryType::TransactionPrepare,
+            EntryType::BatchBegin => LegacyEntryType::BatchBegin,
+            EntryType::BatchEnd => LegacyEntryType::BatchEnd,
+            EntryType::Custom(kind) => LegacyEntryType::Custom(kind),
+        }
+    }
+}
+
+/// 포맷 1 페이로드. 엔트리 전체를 bitcode 로 인코딩하던 때의 필드만 가진다.
+#[derive(Encode, Decode)]
+pub(crate) struct LegacyEntry {
+    entry_type: LegacyEntryType,
+    data: Option<Vec<u8>>,
+    timestamp: f64,
+    transaction_id: u64,
+}
+
+impl LegacyEntry {
+    pub(crate) fn of(entry: WALEntryRef<'_>) -> Self {
+        Self {
+            entry_type: entry.entry_type.into(),
+            data: entry.data.map(<[u8]>::to_vec),
+            timestamp: entry.timestamp,
+            transaction_id: entry.transaction_id,
+        }
+    }
+}
+
+impl From<LegacyEntry> for WALEntry {
+    fn from(entry: LegacyEntry) -> Self {
+        WALEntry {
+            entry_type: entry.entry_type.into(),
+            data: entry.data,
+            timestamp: entry.timestamp,
+            transaction_id: entry.transaction_id,
+            metadata: Metadata::new(),
+            flags: EntryFlags::NONE,
+        }
+    }
+}
+
+/// `flags` 가 생기기 전인 포맷 2, 3 의 헤더
+#[derive(Encode, Decode)]
+struct EntryHeaderV2 {
+    lsn: Lsn,
+    entry_type: LegacyEntryType,
+    has_data: bool,
+    timestamp: f64,
+    transaction_id: u64,
+}
+
+/// 포맷 4 의 헤더
+#[derive(Encode, Decode)]
+struct EntryHeaderV4 {
+    lsn: Lsn,
+    entry_type: LegacyEntryType,
+    has_data: bool,
+    timestamp: f64,
+    transaction_id: u64,
+    flags: EntryFlags,
+}
+
+/// 포맷 5 의 헤더. 엔트리 종류를 [`EntryType::code`] 로 남긴다.
+#[derive(Encode, Decode)]
+struct EntryHeaderV5 {
+    lsn: Lsn,
+    entry_type: u32,
+    has_data: bool,
+    timestamp: f64,
+    transaction_id: u64,
+    flags: EntryFlags,
+}
+
+/// 포맷 6 의 헤더. 타임스탬프는 bitcode 앞에 따로 남긴다 ([`encode_header`]).
+#[derive(Encode, Decode)]
+struct EntryHeaderV6 {
+    lsn: Lsn,
+    entry_type: u32,
+    has_data: bool,
+    transaction_id: u64,
+    flags: EntryFlags,
+}
+
+/// 포맷 7 의 헤더. 트랜잭션 id 도 bitcode 앞에 varint 로 남긴다.
+#[derive(Encode, Decode)]
+struct EntryHeaderV7 {
+    lsn: Lsn,
+    entry_type: u32,
+    has_data: bool,
+    flags: EntryFlags,
+}
+
+/// 기록할 세그먼트의 포맷에 맞춰 `out` 끝에 인코딩한다. 포맷 4 전에는 `flags` 를 남기지 않는다.
+///
+/// 포맷 6 은 `[타임스탬프][bitcode 로 인코딩한 EntryHeaderV6]` 이다. 타임스탬프는 `format.epoch` 와의 마이크로초 차이를
+/// zigzag LEB128 로 남기므로, 세그먼트가 만들어진 뒤 얼마 지나지 않은 엔트리는 f64 대신 몇 바이트만 쓴다.
+/// 포맷 7 은 `[타임스탬프][트랜잭션 id varint][bitcode 로 인코딩한 EntryHeaderV7]` 이다. 트랜잭션 밖의 엔트리(id 0)는 1 바이트만 쓴다.
+pub(crate) fn encode_header(header: &EntryHeader, format: EntryFormat, buffer: &mut bitcode::Buffer, out: &mut Vec<u8>) -> Result<(), bitcode::Error> {
+    if format.version >= VARINT_FORMAT_VERSION {
+        write_timestamp(header.timestamp, format.epoch, out);
+        write_varint(header.transaction_id, out);
+        out.extend_from_slice(buffer.encode(&EntryHeaderV7 {
+            lsn: header.lsn,
+            entry_type: header.entry_type.code(),
+            has_data: header.has_data,
+            flags: header.flags,
+        })?);
+        return Ok(());
+    }
+
+    if format.version >= TIMESTAMP_DELTA_FORMAT_VERSION {
+        write_timestamp(header.timestamp, format.epoch, out);
+        out.extend_from_slice(buffer.encode(&EntryHeaderV6 {
+            lsn: header.lsn,
+            entry_type: header.entry_type.code(),
+            has_data: header.has_data,
+            transaction_id: header.transaction_id,
+            flags: header.flags,
+        })?);
+        return Ok(());
+    }
+
+    let bytes = encode_legacy_header(header, format.version, buffer)?;
+    out.extend_from_slice(bytes);
+    Ok(())
+}
+
+fn encode_legacy_header<'a>(header: &EntryHeader, format_version: u16, buffer: &'a mut bitcode::Buffer) -> Result<&'a [u8], bitcode::Error> {
+    if format_version >= ENTRY_TYPE_CODE_FORMAT_VERSION {
+        return buffer.encode(&EntryHeaderV5 {
+            lsn: header.lsn,
+            entry_type: header.entry_type.code(),
+            has_data: header.has_data,
+            timestamp: header.timestamp,
+            transaction_id: header.transaction_id,
+            flags: header.flags,
+        });
+    }
+
+    if format_version >= FLAGS_FORMAT_VERSION {
+        return buffer.encode(&EntryHeaderV4 {
+            lsn: header.lsn,
+            entry_type: header.entry_type.into(),
+            has_data: header.has_data,
+            timestamp: header.timestamp,
+            transaction_id: header.transaction_id,
+            flags: header.flags,
+        });
+    }
+
+    buffer.encode(&EntryHeaderV2 {
+        lsn: header.lsn,
+        entry_type: header.entry_type.into(),
+        has_data: header.has_data,
+        timestamp: header.timestamp,
+        transaction_id: header.transaction_id,
+    })
+}
+
+/// 포맷 2 이상의 헤더를 지금의 [`EntryHeader`] 로 읽는다. 예전 포맷에 없던 필드는 비워둔다.
+pub(crate) fn decode_header(bytes: &[u8], format: EntryFormat, buffer: &mut bitcode::Buffer) -> Result<EntryHeader, Error> {
+    let invalid = |e| Error::new(ErrorKind::InvalidData, e);
+    let format_version = format.version;
+
+    if format_version >= VARINT_FORMAT_VERSION {
+        let truncated = || Error::new(ErrorKind::InvalidData, "truncated entry header");
+        let (timestamp, length) = read_timestamp(bytes, format.epoch).ok_or_else(truncated)?;
+        let (transaction_id, id_length) = read_varint(&bytes[length..]).ok_or_else(truncated)?;
+        let header: EntryHeaderV7 = buffer.decode(&bytes[length + id_length..]).map_err(invalid)?;
+        let entry_type = EntryType::from_code(header.entry_type)
+            .ok_or_else(|| Error::new(ErrorKind::InvalidData, format!("unknown entry type {}", header.entry_type)))?;
+
+        return Ok(EntryHeader {
+            lsn: header.lsn,
+            entry_type,
+            has_data: header.has_data,
+            timestamp,
+            transaction_id,
+            flags: header.flags,
+        });
+    }
+
+    if format_version >= TIMESTAMP_DELTA_FORMAT_VERSION {
+        let (timestamp, length) = read_timestamp(bytes, format.epoch)
+            .ok_or_else(|| Error::new(ErrorKind::InvalidData, "truncated entry timestamp"))?;
+        let header: EntryHeaderV6 = buffer.decode(&bytes[length..]).map_err(invalid)?;
+        let entry_type = EntryType::from_code(header.entry_type)
+            .ok_or_else(|| Error::new(ErrorKind::InvalidData, format!("unknown entry type {}", header.entry_type)))?;
+
+        return Ok(EntryHeader {
+            lsn: header.lsn,
+            entry_type,
+            has_data: header.has_data,
+            timestamp,
+            transaction_id: header.transaction_id,
+            flags: header.flags,
+        });
+    }
+
+    if format_version >= ENTRY_TYPE_CODE_FORMAT_VERSION {
+        let header: EntryHeaderV5 = buffer.decode(bytes).map_err(invalid)?;
+        let entry_type = EntryType::from_code(header.entry_type)
+            .ok_or_else(|| Error::new(ErrorKind::InvalidData, format!("unknown entry type {}", header.entry_type)))?;
+
+        return Ok(EntryHeader {
+            lsn: header.lsn,
+            entry_type,
+            has_data: header.has_data,
+            timestamp: header.timestamp,
+            transaction_id: header.transaction_id,
+            flags: header.flags,
+        });
+    }
+
+    if format_version >= FLAGS_FORMAT_VERSION {
+        let header: EntryHeaderV4 = buffer.decode(bytes).map_err(invalid)?;
+
+        return Ok(EntryHeader {
+            lsn: header.lsn,
+            entry_type: header.entry_type.into(),
+            has_data: header.has_data,
+            timestamp: header.timestamp,
+            transaction_id: header.transaction_id,
+            flags: header.flags,
+        });
+    }
+
+    let header: EntryHeaderV2 = buffer.decode(bytes).map_err(invalid)?;
+    Ok(EntryHeader {
+        lsn: header.lsn,
+        entry_type: header.entry_type.into(),
+        has_data: header.has_data,
+        timestamp: header.timestamp,
+        transaction_id: header.transaction_id,
+        flags: EntryFlags::NONE,
+    })
+}
+
+/// 초 단위 타임스탬프를 가장 가까운 마이크로초로
+pub(crate) fn micros(secs: f64) -> i64 {
+    (secs * 1_000_000.0).round() as i64
+}
+
+fn write_timestamp(timestamp: f64, epoch: i64, out: &mut Vec<u8>) {
+    write_varint(zigzag(micros(timestamp).wrapping_sub(epoch)), out);
+}
+
+/// 타임스탬프와 그것이 차지한 바이트 수
+fn read_timestamp(bytes: &[u8], epoch: i64) -> Option<(f64, usize)> {
+    let (delta, length) = read_varint(bytes)?;
+
+    Some((epoch.wrapping_add(unzigzag(delta)) as f64 / 1_000_000.0, length))
+}
+
+#[cfg(test)]
+mod schema_tests {
+    use crate::wal::core::{EntryHeader, EntryType, Metadata, WALEntry};
+    use crate::wal::flags::EntryFlags;
+    use crate::wal::lsn::Lsn;
+    use crate::wal::schema::{micros, EntryFormat, LegacyEntry};
+
+    /// 포맷마다 기록하는 바이트를 고정해서, 구조체를 고쳐 예전 로그를 다르게 읽게 되면 여기서 실패한다
+    #[test]
+    fn test_pin_payload_encoding() {
+        let entry = WALEntry {
+            entry_type: EntryType::Custom(3),
+            data: Some(vec![1, 2]),
+            timestamp: 1.5,
+            transaction_id: 7,
+            metadata: Metadata::from([("tenant".to_string(), vec![9])]),
+            flags: EntryFlags::TOMBSTONE
+        };
+        let golden: [&[u8]; 7] = [
+            &[26, 0, 232, 0, 1, 0, 0, 0, 0, 0, 0, 252, 159, 3, 0, 0, 0, 0, 0, 0, 0],
+            &[27, 0, 42, 0, 0, 0, 0, 0, 0, 0, 26, 0, 8, 0, 0, 0, 0, 0, 128, 255, 115, 0, 0, 0, 0, 0, 0, 0, 0, 1, 2],
+            &[27, 0, 42, 0, 0, 0, 0, 0, 0, 0, 26, 0, 8, 0, 0, 0, 0, 0, 128, 255, 115, 0, 0, 0, 0, 0, 0, 0, 0, 9, 0, 0, 0, 226, 116, 101, 110, 97, 110, 116, 74, 0, 1, 2],
+            &[31, 0, 42, 0, 0, 0, 0, 0, 0, 0, 26, 0, 8, 0, 0, 0, 0, 0, 128, 255, 115, 0, 0, 0, 0, 0, 0, 0, 128, 0, 0, 0, 0, 9, 0, 0, 0, 226, 116, 101, 110, 97, 110, 116, 74, 0, 1, 2],
+            &[33, 0, 42, 0, 0, 0, 0, 0, 0, 0, 3, 0, 1, 0, 1, 0, 0, 0, 0, 0, 240, 127, 14, 0, 0, 0, 0, 0, 0, 0, 16, 0, 0, 0, 0, 9, 0, 0, 0, 226, 116, 101, 110, 97, 110, 116, 74, 0, 1, 2],
+            &[29, 0, 192, 141, 183, 1, 42, 0, 0, 0, 0, 0, 0, 0, 3, 0, 1, 0, 15, 0, 0, 0, 0, 0, 0, 0, 16, 0, 0, 0, 0, 9, 0, 0, 0, 226, 116, 101, 110, 97, 110, 116, 74, 0, 1, 2],
+            &[22, 192, 141, 183, 1, 7, 42, 0, 0, 0, 0, 0, 0, 0, 3, 0, 1, 0, 17, 0, 0, 0, 0, 9, 226, 116, 101, 110, 97, 110, 116, 74, 0, 1, 2],
+        ];
+
+        let mut buffer = bitcode::Buffer::new();
+        for (version, golden) in (1..).zip(golden) {
+            assert_eq!(entry.encode_payload(EntryFormat::new(version), Lsn(42)).unwrap(), golden, "format {}", version);
+
+            if version == 1 {
+                let legacy: WALEntry = buffer.decode::<LegacyEntry>(golden).unwrap().into();
+                assert_eq!((legacy.entry_type, legacy.data, legacy.transaction_id), (EntryType::Custom(3), Some(vec![1, 2]), 7));
+                continue;
+            }
+
+            let (header, metadata, data_start) = EntryHeader::decode_payload(golden, EntryFormat::new(version), &mut buffer).unwrap();
+            assert_eq!((header.lsn, header.entry_type, header.timestamp, header.transaction_id), (Lsn(42), EntryType::Custom(3), 1.5, 7));
+            assert_eq!(header.flags, if version >= 4 { EntryFlags::TOMBSTONE } else { EntryFlags::NONE });
+            assert_eq!(metadata.is_empty(), version < 3);
+            assert_eq!(&golden[data_start..], &[1, 2]);
+        }
+    }
+
+    #[test]
+    fn test_timestamp_delta() {
+        let format = EntryFormat { version: 6, epoch: micros(1_700_000_000.0) };
+        let mut buffer = bitcode::Buffer::new();
+        let entry = |timestamp| WALEntry {
+            entry_type: EntryType::Insert,
+            data: None,
+            timestamp,
+            transaction_id: 0,
+            metadata: Metadata::new(),
+            flags: EntryFlags::NONE
+        };
+
+        // 세그먼트보다 앞선 타임스탬프도 음수 차이로 남는다
+        for timestamp in [1_700_000_000.000_001, 1_700_000_123.5, 1_699_999_999.25, 0.0] {
+            let payload = entry(timestamp).encode_payload(format, Lsn(1)).unwrap();
+            let (header, _, _) = EntryHeader::decode_payload(&payload, format, &mut buffer).unwrap();
+            assert_eq!(header.timestamp, timestamp);
+        }
+
+        let delta = entry(1_700_000_001.0).encode_payload(format, Lsn(1)).unwrap();
+        let absolute = entry(1_700_000_001.0).encode_payload(EntryFormat::new(5), Lsn(1)).unwrap();
+        assert!(delta.len() + 4 <= absolute.len(), "{} vs {}", delta.len(), absolute.len());
+    }
+
+    #[test]
+    fn test_entry_type_codes() {
+        let entry_types = [
+            EntryType::Insert,
+            EntryType::Checkpoint,
+            EntryType::BatchEnd,
+            EntryType::Custom(0),
+            EntryType::Custom(u16::MAX),
+        ];
+        for entry_type in entry_types {
+            assert_eq!(EntryType::from_code(entry_type.code()), Some(entry_type));
+        }
+
+        assert_eq!(EntryType::Set.code(), 1);
+        assert_eq!(EntryType::from_code(12), None);
+        assert_eq!(EntryType::from_code((1 << 16) + u16::MAX as u32 + 1), None);
+    }
+}
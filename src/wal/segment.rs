@@ -0,0 +1,296 @@
+use std::fs::File;
+use std::io::{self, Error, ErrorKind, Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+
+use super::checksum::{crc32, ChecksumAlgorithm, Crc32};
+
+pub const SEGMENT_MAGIC: [u8; 8] = *b"RRDBWAL\0";
+/// 2 부터 엔트리의 data 를 bitcode 밖에 따로 둬서 읽을 때 복사하지 않고 빌려줄 수 있다 ([`WALEntry::encode_payload`])
+pub const FORMAT_VERSION: u16 = 7;
+/// 엔트리 메타데이터를 기록하기 시작한 포맷
+pub const METADATA_FORMAT_VERSION: u16 = 3;
+/// 엔트리 헤더에 플래그를 기록하기 시작한 포맷
+pub const FLAGS_FORMAT_VERSION: u16 = 4;
+/// 엔트리 종류를 bitcode enum 대신 고정된 번호로 남기기 시작한 포맷 ([`EntryType::code`](super::core::EntryType::code))
+pub const ENTRY_TYPE_CODE_FORMAT_VERSION: u16 = 5;
+/// 엔트리 타임스탬프를 세그먼트가 만들어진 시각과의 마이크로초 차이로 남기기 시작한 포맷 ([`EntryFormat`](super::schema::EntryFormat))
+pub const TIMESTAMP_DELTA_FORMAT_VERSION: u16 = 6;
+/// 프레임 길이와 엔트리의 트랜잭션 id, 길이 필드를 varint 로 남기기 시작한 포맷 ([`FrameLayout::Varint`](super::frame::FrameLayout::Varint))
+pub const VARINT_FORMAT_VERSION: u16 = 7;
+/// 엔트리 전체를 bitcode 로 인코딩하던 포맷. 읽기와 이어 쓰기만 지원한다.
+pub const LEGACY_FORMAT_VERSION: u16 = 1;
+pub const SEGMENT_HEADER_SIZE: usize = 32;
+pub const SEGMENT_FOOTER_MAGIC: [u8; 8] = *b"RRDBEND\0";
+pub const SEGMENT_FOOTER_SIZE: usize = 40;
+
+pub fn segment_path(directory: &Path, sequence: usize) -> PathBuf {
+    directory.join(format!("wal{}.log", sequence))
+}
+
+/// 재활용을 기다리는 세그먼트 파일
+pub fn recycled_segment_path(directory: &Path, sequence: usize) -> PathBuf {
+    directory.join(format!("wal{}.recycle", sequence))
+}
+
+/// 봉인(seal) 중인 세그먼트가 rename 되기 전까지 기록되는 임시 파일
+pub fn temp_segment_path(directory: &Path, sequence: usize) -> PathBuf {
+    directory.join(format!("wal{}.log.tmp", sequence))
+}
+
+/// 세그먼트 파일의 블록을 `length` 만큼 미리 할당한다.
+///
+/// 파일 크기는 그대로 두므로(`FALLOC_FL_KEEP_SIZE`) O_APPEND 쓰기와 복구 로직에는 영향이 없다.
+/// fallocate 가 없는 플랫폼에서는 아무것도 하지 않는다.
+#[cfg(all(target_os = "linux", target_pointer_width = "64"))]
+pub fn preallocate(file: &File, length: u64) -> Result<(), Error> {
+    use std::os::raw::c_int;
+    use std::os::unix::io::AsRawFd;
+
+    const FALLOC_FL_KEEP_SIZE: c_int = 0x01;
+
+    extern "C" {
+        fn fallocate(fd: c_int, mode: c_int, offset: i64, len: i64) -> c_int;
+    }
+
+    if unsafe { fallocate(file.as_raw_fd(), FALLOC_FL_KEEP_SIZE, 0, length as i64) } == 0 {
+        return Ok(());
+    }
+
+    let error = Error::last_os_error();
+    match error.kind() {
+        // 파일시스템이 지원하지 않는 경우는 무시
+        ErrorKind::Unsupported => Ok(()),
+        _ => Err(error),
+    }
+}
+
+#[cfg(not(all(target_os = "linux", target_pointer_width = "64")))]
+pub fn preallocate(_file: &File, _length: u64) -> Result<(), Error> {
+    Ok(())
+}
+
+/// 모든 `walN.log` 파일의 맨 앞에 기록되는 고정 크기 헤더
+///
+/// `[magic 8][version u16][checksum u8][framing u8][block_size u32][created_at f64][sequence u64]`
+#[derive(Clone, Debug, PartialEq)]
+pub struct SegmentHeader {
+    pub version: u16,
+    /// 프레임 체크섬 알고리즘. 예전 파일의 reserved 자리(0 = CRC32)를 쓴다.
+    pub checksum: ChecksumAlgorithm,
+    /// 엔트리 프레임을 블록으로 묶어서 기록했는지 ([`encode_blocks`](super::block::encode_blocks)).
+    /// 체크섬 칸의 남는 바이트(예전 파일은 0)를 쓴다.
+    pub blocks: bool,
+    /// flush 블록 크기
+    pub block_size: u32,
+    pub created_at: f64,
+    /// 파일 이름의 순번. 프레임 체크섬의 salt 로도 쓰인다.
+    pub sequence: u64,
+}
+
+impl SegmentHeader {
+    pub fn new(block_size: usize, checksum: ChecksumAlgorithm, created_at: f64, sequence: usize) -> Self {
+        Self { version: FORMAT_VERSION, checksum, blocks: false, block_size: block_size as u32, created_at, sequence: sequence as u64 }
+    }
+
+    pub fn encode(&self) -> [u8; SEGMENT_HEADER_SIZE] {
+        let mut bytes = [0u8; SEGMENT_HEADER_SIZE];
+
+        bytes[0..8].copy_from_slice(&SEGMENT_MAGIC);
+        bytes[8..10].copy_from_slice(&self.version.to_le_bytes());
+        bytes[10] = self.checksum.id() as u8;
+        bytes[11] = self.blocks as u8;
+        bytes[12..16].copy_from_slice(&self.block_size.to_le_bytes());
+        bytes[16..24].copy_from_slice(&self.created_at.to_le_bytes());
+        bytes[24..32].copy_from_slice(&self.sequence.to_le_bytes());
+
+        bytes
+    }
+
+    pub fn decode(bytes: &[u8]) -> Result<Self, Error> {
+        let bytes = bytes.get(..SEGMENT_HEADER_SIZE)
+            .ok_or_else(|| Error::new(ErrorKind::InvalidData, "segment header is too short"))?;
+
+        if bytes[0..8] != SEGMENT_MAGIC {
+            return Err(Error::new(ErrorKind::InvalidData, "not a WAL segment (bad magic)"));
+        }
+
+        let version = u16::from_le_bytes(bytes[8..10].try_into().unwrap());
+        if !(LEGACY_FORMAT_VERSION..=FORMAT_VERSION).contains(&version) {
+            return Err(Error::new(ErrorKind::InvalidData, format!("unsupported segment format version {}", version)));
+        }
+
+        let checksum = ChecksumAlgorithm::from_id(bytes[10] as u16)
+            .ok_or_else(|| Error::new(ErrorKind::InvalidData, format!("unknown checksum algorithm {}", bytes[10])))?;
+        let blocks = match bytes[11] {
+            0 => false,
+            1 => true,
+            framing => return Err(Error::new(ErrorKind::InvalidData, format!("unknown frame layout {}", framing))),
+        };
+
+        Ok(Self {
+            version,
+            checksum,
+            blocks,
+            block_size: u32::from_le_bytes(bytes[12..16].try_into().unwrap()),
+            created_at: f64::from_le_bytes(bytes[16..24].try_into().unwrap()),
+            sequence: u64::from_le_bytes(bytes[24..32].try_into().unwrap()),
+        })
+    }
+}
+
+/// 봉인된 세그먼트의 맨 끝에 붙는 footer
+///
+/// `[magic 8][sequence u64][entry_count u64][body_length u64][body CRC32 u32][footer CRC32 u32]`
+///
+/// body 는 헤더 바로 뒤부터 footer 앞까지(체크포인트 프레임 포함)이고, 엔트리를 하나씩 풀지 않고도
+/// 한 번의 순차 읽기로 세그먼트 전체가 온전한지 확인할 수 있다.
+#[derive(Clone, Debug, PartialEq)]
+pub struct SegmentFooter {
+    pub sequence: u64,
+    pub entry_count: u64,
+    pub body_length: u64,
+    pub body_checksum: u32,
+}
+
+impl SegmentFooter {
+    pub fn encode(&self) -> [u8; SEGMENT_FOOTER_SIZE] {
+        let mut bytes = [0u8; SEGMENT_FOOTER_SIZE];
+
+        bytes[0..8].copy_from_slice(&SEGMENT_FOOTER_MAGIC);
+        bytes[8..16].copy_from_slice(&self.sequence.to_le_bytes());
+        bytes[16..24].copy_from_slice(&self.entry_count.to_le_bytes());
+        bytes[24..32].copy_from_slice(&self.body_length.to_le_bytes());
+        bytes[32..36].copy_from_slice(&self.body_checksum.to_le_bytes());
+        let checksum = crc32(&bytes[..36]);
+        bytes[36..40].copy_from_slice(&checksum.to_le_bytes());
+
+        bytes
+    }
+
+    /// magic 이나 footer 자체의 체크섬이 맞지 않으면 `None`
+    pub fn decode(bytes: &[u8]) -> Option<Self> {
+        let bytes = bytes.get(..SEGMENT_FOOTER_SIZE)?;
+        if bytes[0..8] != SEGMENT_FOOTER_MAGIC || crc32(&bytes[..36]).to_le_bytes() != bytes[36..40] {
+            return None;
+        }
+
+        Some(Self {
+            sequence: u64::from_le_bytes(bytes[8..16].try_into().unwrap()),
+            entry_count: u64::from_le_bytes(bytes[16..24].try_into().unwrap()),
+            body_length: u64::from_le_bytes(bytes[24..32].try_into().unwrap()),
+            body_checksum: u32::from_le_bytes(bytes[32..36].try_into().unwrap()),
+        })
+    }
+
+    /// 파일 끝에서 `sequence` 세그먼트의 footer 를 읽는다.
+    pub fn read(path: &Path, sequence: u64) -> Result<Option<Self>, Error> {
+        let mut file = File::open(path)?;
+        let length = file.metadata()?.len();
+        if length < (SEGMENT_HEADER_SIZE + SEGMENT_FOOTER_SIZE) as u64 {
+            return Ok(None);
+        }
+
+        let mut bytes = [0u8; SEGMENT_FOOTER_SIZE];
+        file.seek(SeekFrom::Start(length - SEGMENT_FOOTER_SIZE as u64))?;
+        file.read_exact(&mut bytes)?;
+
+        let footer = Self::decode(&bytes).filter(|footer| {
+            footer.sequence == sequence
+                && footer.body_length + (SEGMENT_HEADER_SIZE + SEGMENT_FOOTER_SIZE) as u64 == length
+        });
+
+        Ok(footer)
+    }
+
+    /// body 를 다시 읽어 footer 의 체크섬과 비교
+    pub fn verify(&self, path: &Path) -> Result<bool, Error> {
+        let mut file = File::open(path)?;
+        file.seek(SeekFrom::Start(SEGMENT_HEADER_SIZE as u64))?;
+
+        let mut body = ChecksumWriter::new(io::sink());
+        let copied = io::copy(&mut file.take(self.body_length), &mut body)?;
+
+        Ok(copied == self.body_length && body.checksum() == self.body_checksum)
+    }
+}
+
+/// 지나가는 바이트의 CRC32 를 계산하면서 그대로 넘겨주는 쓰기 어댑터
+pub struct ChecksumWriter<W> {
+    inner: W,
+    hasher: Crc32,
+    written: u64,
+}
+
+impl<W: Write> ChecksumWriter<W> {
+    pub fn new(inner: W) -> Self {
+        Self { inner, hasher: Crc32::new(), written: 0 }
+    }
+
+    pub fn checksum(&self) -> u32 {
+        self.hasher.finish()
+    }
+
+    pub fn written(&self) -> u64 {
+        self.written
+    }
+
+    pub fn into_inner(self) -> W {
+        self.inner
+    }
+}
+
+impl<W: Write> Write for ChecksumWriter<W> {
+    fn write(&mut self, bytes: &[u8]) -> io::Result<usize> {
+        let written = self.inner.write(bytes)?;
+        self.hasher.update(&bytes[..written]);
+        self.written += written as u64;
+
+        Ok(written)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+#[cfg(test)]
+mod segment_tests {
+    use super::{SegmentFooter, SegmentHeader, SEGMENT_FOOTER_SIZE, SEGMENT_HEADER_SIZE};
+    use crate::wal::checksum::ChecksumAlgorithm;
+
+    #[test]
+    fn test_header_roundtrip() {
+        let header = SegmentHeader::new(4096, ChecksumAlgorithm::XxHash64, 1700000000.5, 3);
+        let bytes = header.encode();
+
+        assert_eq!(SegmentHeader::decode(&bytes).unwrap(), header);
+    }
+
+    #[test]
+    fn test_reject_bad_magic() {
+        let mut bytes = SegmentHeader::new(4096, ChecksumAlgorithm::Crc32, 0.0, 1).encode();
+        bytes[0] = b'X';
+
+        assert!(SegmentHeader::decode(&bytes).is_err());
+        assert!(SegmentHeader::decode(&bytes[..SEGMENT_HEADER_SIZE - 1]).is_err());
+    }
+
+    #[test]
+    fn test_reject_unknown_checksum() {
+        let mut bytes = SegmentHeader::new(4096, ChecksumAlgorithm::Crc32, 0.0, 1).encode();
+        bytes[10] = 0xFF;
+
+        assert!(SegmentHeader::decode(&bytes).is_err());
+    }
+
+    #[test]
+    fn test_footer_roundtrip() {
+        let footer = SegmentFooter { sequence: 3, entry_count: 10, body_length: 4096, body_checksum: 0xDEAD_BEEF };
+        let mut bytes = footer.encode();
+        assert_eq!(SegmentFooter::decode(&bytes), Some(footer));
+
+        bytes[16] ^= 1;
+        assert_eq!(SegmentFooter::decode(&bytes), None);
+        assert_eq!(SegmentFooter::decode(&bytes[..SEGMENT_FOOTER_SIZE - 1]), None);
+    }
+}
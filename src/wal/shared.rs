@@ -0,0 +1,211 @@
+use std::error::Error;
+use std::sync::mpsc::Receiver;
+use std::sync::{Condvar, Mutex};
+use std::time::Instant;
+
+use super::core::{append_error, WALEntry, WALManager};
+use super::lsn::Lsn;
+use super::stall::StallReason;
+use super::sync::Durability;
+use super::watch::DurableRange;
+
+/// `Arc` 로 감싸서 여러 스레드가 `&self` 로 함께 쓰는 [`WALManager`] (`Send + Sync`)
+///
+/// 락을 두 개로 나눈다. 버퍼 락은 LSN 을 붙이고 프레임을 메모리 버퍼에 인코딩하는 동안만 잡고,
+/// 파일 락은 모아둔 프레임을 꺼내 쓰고 fsync 하는 동안 잡는다. 그래서 한 스레드가 fsync 를 기다리는 동안에도
+/// 다른 스레드는 다음 엔트리를 인코딩한다. 쓰기를 모으기 위해 [`WriteBufferOptions`](super::writer::WriteBufferOptions) 가
+/// 없던 매니저라면 기본값으로 켠다.
+///
+/// LSN 순서:
+/// - LSN 은 버퍼 락을 잡은 순서대로 붙는다. 먼저 반환된 `append_log` 보다 나중에 시작한 호출은 항상 더 큰 LSN 을 받는다.
+/// - 한 스레드가 차례로 기록한 엔트리는 그 순서대로 LSN 이 커진다. 서로 다른 스레드가 동시에 기록한 엔트리끼리의 순서는 정해져 있지 않다.
+/// - 엔트리는 LSN 순서대로 durable 해진다. `durable_lsn` 이하는 모두 fsync 되었고, 어떤 엔트리가 durable 하면 그보다 작은 LSN 도 그렇다.
+pub struct SharedWAL {
+    /// 버퍼 락
+    manager: Mutex<WALManager>,
+    /// 파일 락. 꺼낸 배치를 쓰는 스레드는 하나뿐이다.
+    file: Mutex<()>,
+    /// 배치를 다 쓰고 나면 알린다 (`manager` 락과 함께 쓴다)
+    flushed: Condvar,
+}
+
+impl SharedWAL {
+    pub fn new(mut manager: WALManager) -> Self {
+        manager.ensure_write_buffer();
+
+        Self { manager: Mutex::new(manager), file: Mutex::new(()), flushed: Condvar::new() }
+    }
+
+    pub fn append_log(&self, entry: WALEntry) -> Result<Lsn, Box<dyn Error>> {
+        self.append_log_with(entry, Durability::Durable)
+    }
+
+    /// `Durable` 이면 엔트리가 fsync 될 때까지 기다리고, `Lazy` 면 버퍼에 인코딩만 하고 반환한다
+    pub fn append_log_with(&self, entry: WALEntry, durability: Durability) -> Result<Lsn, Box<dyn Error>> {
+        let lsn = {
+            let mut manager = self.manager.lock().unwrap();
+            // 체크포인트는 파일을 직접 쓰므로, 버퍼가 가득 찼다면 디스크가 따라잡도록 쓰는 중인 배치가 끝날 때까지 기다린다
+            let (full, started) = (manager.buffer_full(), Instant::now());
+            while manager.must_wait_for_flush() {
+                manager = self.flushed.wait(manager).unwrap();
+            }
+            if full {
+                manager.report_stall(StallReason::BufferFull, started.elapsed());
+            }
+            manager.append_unsynced(entry)?.0
+        };
+
+        if durability == Durability::Durable {
+            self.sync_to(lsn).map_err(append_error)?;
+        }
+
+        Ok(lsn)
+    }
+
+    /// 지금까지 기록한 엔트리를 모두 fsync 한다
+    pub fn sync(&self) -> Result<Lsn, std::io::Error> {
+        let last = self.last_lsn();
+        self.sync_to(last)
+    }
+
+    /// `lsn` 까지 durable 해지도록 쓰고 fsync 한다. 파일 락을 기다리는 동안 다른 스레드가 먼저 써줬다면 바로 반환한다.
+    fn sync_to(&self, lsn: Lsn) -> Result<Lsn, std::io::Error> {
+        let _file = self.file.lock().unwrap();
+        let batch = {
+            let mut manager = self.manager.lock().unwrap();
+            if manager.durable_lsn() >= lsn {
+                return Ok(manager.durable_lsn());
+            }
+            manager.begin_flush()?
+        };
+
+        let result = batch.write();
+        let mut manager = self.manager.lock().unwrap();
+        let finished = manager.finish_flush(batch, result);
+        self.flushed.notify_all();
+        finished?;
+
+        Ok(manager.durable_lsn())
+    }
+
+    pub fn last_lsn(&self) -> Lsn {
+        self.manager.lock().unwrap().last_lsn()
+    }
+
+    pub fn durable_lsn(&self) -> Lsn {
+        self.manager.lock().unwrap().durable_lsn()
+    }
+
+    /// [`WALManager::subscribe`]
+    pub fn subscribe(&self) -> Receiver<DurableRange> {
+        self.manager.lock().unwrap().subscribe()
+    }
+
+    /// 남은 엔트리를 fsync 한 뒤 [`WALManager`] 를 돌려준다
+    pub fn into_inner(self) -> Result<WALManager, std::io::Error> {
+        let mut manager = self.manager.into_inner().unwrap();
+        manager.sync()?;
+
+        Ok(manager)
+    }
+}
+
+#[cfg(test)]
+mod shared_tests {
+    use std::sync::Arc;
+    use std::time::Duration;
+
+    use super::SharedWAL;
+    use crate::wal::core::{WALEntry, WALManager};
+    use crate::wal::lsn::Lsn;
+    use crate::wal::stall::StallReason;
+    use crate::wal::sync::{Durability, SyncPolicy};
+    use crate::wal::test_utils::{insert_entry, temp_directory};
+    use crate::wal::writer::WriteBufferOptions;
+
+    fn entry(thread: u8, value: u8) -> WALEntry {
+        insert_entry(vec![thread, value])
+    }
+
+    fn assert_send_sync<T: Send + Sync>() {}
+
+    #[test]
+    fn test_shared_appends() {
+        assert_send_sync::<SharedWAL>();
+
+        let wal_manager = WALManager::builder()
+            .set_directory(temp_directory("shared"))
+            .set_sync_policy(SyncPolicy::Never)
+            .build().expect("Cannot create WALManager");
+        let wal = Arc::new(SharedWAL::new(wal_manager));
+
+        let threads = (0..8u8).map(|thread| {
+            let wal = wal.clone();
+            std::thread::spawn(move || {
+                let mut last = Lsn::ZERO;
+                for value in 0..20u8 {
+                    let durability = if value % 4 == 3 { Durability::Durable } else { Durability::Lazy };
+                    let lsn = wal.append_log_with(entry(thread, value), durability).unwrap();
+
+                    // 한 스레드가 받은 LSN 은 커지기만 하고, 기다린 엔트리는 fsync 되었다
+                    assert!(lsn > last);
+                    last = lsn;
+                    if durability == Durability::Durable {
+                        assert!(wal.durable_lsn() >= lsn);
+                    }
+                }
+            })
+        }).collect::<Vec<_>>();
+        for thread in threads {
+            thread.join().unwrap();
+        }
+
+        assert_eq!(wal.durable_lsn(), Lsn(160));
+        let wal_manager = Arc::try_unwrap(wal).ok().unwrap().into_inner().unwrap();
+        let entries = wal_manager.recover().unwrap().collect::<Result<Vec<_>, _>>().unwrap();
+        assert_eq!(entries.len(), 160);
+        for thread in 0..8 {
+            let values = entries.iter().filter_map(|(_, entry)| entry.data.as_ref().filter(|data| data[0] == thread).map(|data| data[1])).collect::<Vec<_>>();
+            assert_eq!(values, (0..20).collect::<Vec<_>>());
+        }
+    }
+
+    #[test]
+    fn test_backpressure_when_buffer_is_full() {
+        let options = WriteBufferOptions { max_delay: Duration::from_secs(60), max_pending_bytes: 256, ..Default::default() };
+        let (sender, stalls) = std::sync::mpsc::channel();
+        let wal_manager = WALManager::builder()
+            .set_directory(temp_directory("shared_backpressure"))
+            .set_sync_policy(SyncPolicy::Never)
+            .set_write_buffer(options)
+            .set_stall_hook(Duration::from_millis(10), move |event| sender.send(event).unwrap())
+            .build().expect("Cannot create WALManager");
+        let wal = Arc::new(SharedWAL::new(wal_manager));
+        wal.append_log_with(entry(0, 0), Durability::Lazy).unwrap();
+
+        // 디스크가 앞의 배치를 쓰는 동안 버퍼가 가득 차면 append 가 기다린다
+        let batch = wal.manager.lock().unwrap().begin_flush().unwrap();
+        let writer = {
+            let wal = wal.clone();
+            std::thread::spawn(move || {
+                for value in 1..=20 {
+                    wal.append_log_with(entry(0, value), Durability::Lazy).unwrap();
+                }
+            })
+        };
+        std::thread::sleep(Duration::from_millis(50));
+        let blocked = wal.last_lsn();
+        assert!(blocked < Lsn(21), "{:?}", blocked);
+        assert!(wal.manager.lock().unwrap().buffer_full());
+
+        let result = batch.write();
+        wal.manager.lock().unwrap().finish_flush(batch, result).unwrap();
+        wal.flushed.notify_all();
+        writer.join().unwrap();
+        assert_eq!(wal.sync().unwrap(), Lsn(21));
+
+        // 기다린 append 는 얼마나 멈췄는지 알린다
+        let stall = stalls.try_iter().find(|event| event.reason == StallReason::BufferFull).expect("no stall reported");
+        assert!(stall.duration >= Duration::from_millis(10));
+    }
+}
@@ -0,0 +1,79 @@
+use std::io;
+use std::time::{Duration, Instant};
+
+/// append 가 멈춰 있던 이유
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum StallReason {
+    /// 앞의 배치를 쓰는 동안 버퍼가 가득 차서 기다렸다 ([`WriteBufferOptions::max_pending_bytes`](super::writer::WriteBufferOptions::max_pending_bytes))
+    BufferFull,
+    /// 쓰기나 fsync 가 임계값보다 오래 걸렸다
+    SlowDisk,
+    /// 디스크가 가득 차서 쓰지 못했다. 임계값과 상관없이 알린다.
+    DiskFull,
+    /// [`RateLimit`](super::rate_limit::RateLimit) 을 넘어서 잠들었다
+    RateLimited,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct StallEvent {
+    pub reason: StallReason,
+    pub duration: Duration,
+}
+
+/// [`WALBuilder::set_stall_hook`](super::core::WALBuilder::set_stall_hook) 로 등록하는 콜백.
+/// append 하는 스레드에서 락을 잡은 채로 부르므로 부하를 덜어내거나 알림을 보내는 정도로 짧게 끝낸다.
+pub type StallHook = Box<dyn FnMut(StallEvent) + Send>;
+
+pub(crate) struct StallMonitor {
+    /// 이보다 짧게 멈춘 것은 알리지 않는다
+    threshold: Duration,
+    hook: StallHook,
+}
+
+impl StallMonitor {
+    pub(crate) fn new(threshold: Duration, hook: StallHook) -> Self {
+        Self { threshold, hook }
+    }
+
+    pub(crate) fn report(&mut self, reason: StallReason, duration: Duration) {
+        if reason == StallReason::DiskFull || duration >= self.threshold {
+            (self.hook)(StallEvent { reason, duration });
+        }
+    }
+
+    /// `started` 부터 걸린 디스크 작업의 결과를 보고 느렸거나 디스크가 가득 찼다면 알린다
+    pub(crate) fn observe<T>(&mut self, started: Instant, result: &io::Result<T>) {
+        let reason = match result {
+            Err(e) if e.kind() == io::ErrorKind::StorageFull => StallReason::DiskFull,
+            _ => StallReason::SlowDisk,
+        };
+
+        self.report(reason, started.elapsed());
+    }
+}
+
+#[cfg(test)]
+mod stall_tests {
+    use std::io;
+    use std::sync::{Arc, Mutex};
+    use std::time::{Duration, Instant};
+
+    use super::{StallEvent, StallMonitor, StallReason};
+
+    #[test]
+    fn test_report_stalls_over_threshold() {
+        let events = Arc::new(Mutex::new(Vec::new()));
+        let mut monitor = {
+            let events = events.clone();
+            StallMonitor::new(Duration::from_millis(10), Box::new(move |event: StallEvent| events.lock().unwrap().push(event.reason)))
+        };
+
+        monitor.report(StallReason::BufferFull, Duration::from_millis(5));
+        monitor.report(StallReason::RateLimited, Duration::from_millis(20));
+        monitor.observe(Instant::now(), &Ok(()));
+        monitor.observe(Instant::now(), &Err::<(), _>(io::Error::from(io::ErrorKind::StorageFull)));
+        monitor.observe(Instant::now() - Duration::from_millis(50), &Ok(()));
+
+        assert_eq!(*events.lock().unwrap(), vec![StallReason::RateLimited, StallReason::DiskFull, StallReason::SlowDisk]);
+    }
+}
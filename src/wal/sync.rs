@@ -0,0 +1,237 @@
+use std::fs::File;
+use std::io;
+use std::path::Path;
+use std::time::{Duration, Instant};
+
+/// append 된 엔트리를 언제 디스크에 fsync 할지 결정하는 정책
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum SyncPolicy {
+    /// 매 append 마다 fsync
+    #[default]
+    Always,
+    /// n개의 엔트리가 쌓일 때마다 fsync
+    EveryNEntries(usize),
+    /// 마지막 fsync 이후 주어진 시간이 지났다면 다음 append 에서 fsync
+    Interval(Duration),
+    /// fsync 하지 않음 (OS 에 맡김)
+    Never,
+}
+
+impl SyncPolicy {
+    pub(crate) fn should_sync(&self, unsynced_entries: usize, last_synced: Instant) -> bool {
+        match *self {
+            SyncPolicy::Always => true,
+            SyncPolicy::EveryNEntries(n) => unsynced_entries >= n.max(1),
+            SyncPolicy::Interval(interval) => last_synced.elapsed() >= interval,
+            SyncPolicy::Never => false,
+        }
+    }
+}
+
+/// 엔트리 하나를 append 할 때 요구하는 내구성 수준
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Durability {
+    /// 동기화 정책과 상관없이 fsync 가 끝난 뒤에 반환 (커밋 레코드 등)
+    #[default]
+    Durable,
+    /// 쓰기만 하고 반환. 다음 fsync (정책, group commit, `sync()`) 에 함께 실린다 (벌크 로드 등)
+    Lazy,
+}
+
+/// 내구성 확보를 위해 호출할 시스템 콜
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum SyncMethod {
+    /// `fsync`: 데이터와 모든 메타데이터 (Windows 는 `FlushFileBuffers`)
+    #[default]
+    All,
+    /// `fdatasync`: 데이터와 파일 크기처럼 읽는 데 필요한 메타데이터만.
+    /// Windows 는 `NtFlushBuffersFileEx` 가 있으면 쓰고 없으면 `FlushFileBuffers` 로 대신한다.
+    Data,
+    /// Linux `sync_file_range`: 더티 페이지를 기록하고 완료를 기다린다.
+    /// 메타데이터와 디바이스 쓰기 캐시는 비우지 않으므로 전원 장애에 대한 보장은 없다.
+    /// Linux 가 아니면 `Data` 와 같이 동작한다.
+    FileRange,
+}
+
+impl SyncMethod {
+    pub fn sync(&self, file: &File) -> io::Result<()> {
+        match self {
+            SyncMethod::All => file.sync_all(),
+            SyncMethod::Data => sync_data(file),
+            SyncMethod::FileRange => sync_file_range(file),
+        }
+    }
+}
+
+#[cfg(target_os = "linux")]
+fn sync_file_range(file: &File) -> io::Result<()> {
+    use std::os::raw::{c_int, c_uint};
+    use std::os::unix::io::AsRawFd;
+
+    const SYNC_FILE_RANGE_WAIT_BEFORE: c_uint = 1;
+    const SYNC_FILE_RANGE_WRITE: c_uint = 2;
+    const SYNC_FILE_RANGE_WAIT_AFTER: c_uint = 4;
+
+    extern "C" {
+        #[link_name = "sync_file_range"]
+        fn sys_sync_file_range(fd: c_int, offset: i64, nbytes: i64, flags: c_uint) -> c_int;
+    }
+
+    let flags = SYNC_FILE_RANGE_WAIT_BEFORE | SYNC_FILE_RANGE_WRITE | SYNC_FILE_RANGE_WAIT_AFTER;
+
+    // nbytes 가 0 이면 offset 부터 파일 끝까지
+    if unsafe { sys_sync_file_range(file.as_raw_fd(), 0, 0, flags) } == 0 {
+        Ok(())
+    } else {
+        Err(io::Error::last_os_error())
+    }
+}
+
+#[cfg(not(target_os = "linux"))]
+fn sync_file_range(file: &File) -> io::Result<()> {
+    sync_data(file)
+}
+
+#[cfg(not(windows))]
+fn sync_data(file: &File) -> io::Result<()> {
+    file.sync_data()
+}
+
+#[cfg(windows)]
+fn sync_data(file: &File) -> io::Result<()> {
+    windows::sync_data(file)
+}
+
+/// 디렉토리 엔트리(생성/rename)를 디스크에 반영
+#[cfg(unix)]
+pub fn sync_directory(directory: &Path) -> io::Result<()> {
+    File::open(directory)?.sync_all()
+}
+
+/// 디렉토리 핸들을 `FILE_FLAG_BACKUP_SEMANTICS` 로 열어 `FlushFileBuffers` 한다.
+/// 디렉토리 flush 를 지원하지 않는 파일시스템이나 권한이 없는 경우에는 NTFS 의 메타데이터 저널에 맡긴다.
+#[cfg(windows)]
+pub fn sync_directory(directory: &Path) -> io::Result<()> {
+    use std::fs::OpenOptions;
+    use std::os::windows::fs::OpenOptionsExt;
+
+    const FILE_FLAG_BACKUP_SEMANTICS: u32 = 0x0200_0000;
+    const ERROR_INVALID_FUNCTION: i32 = 1;
+    const ERROR_ACCESS_DENIED: i32 = 5;
+
+    let result = OpenOptions::new()
+        .write(true)
+        .custom_flags(FILE_FLAG_BACKUP_SEMANTICS)
+        .open(directory)
+        .and_then(|directory| directory.sync_all());
+
+    match result {
+        Err(e) if matches!(e.raw_os_error(), Some(ERROR_INVALID_FUNCTION | ERROR_ACCESS_DENIED)) => Ok(()),
+        result => result,
+    }
+}
+
+#[cfg(not(any(unix, windows)))]
+pub fn sync_directory(_directory: &Path) -> io::Result<()> {
+    Ok(())
+}
+
+#[cfg(windows)]
+mod windows {
+    use std::ffi::c_void;
+    use std::fs::File;
+    use std::io;
+    use std::os::windows::io::AsRawHandle;
+    use std::sync::OnceLock;
+
+    const FLUSH_FLAGS_FILE_DATA_SYNC_ONLY: u32 = 0x4;
+
+    #[repr(C)]
+    struct IoStatusBlock {
+        status: usize,
+        information: usize,
+    }
+
+    type NtFlushBuffersFileEx = unsafe extern "system" fn(
+        file: *mut c_void,
+        flags: u32,
+        parameters: *mut c_void,
+        parameters_size: u32,
+        status: *mut IoStatusBlock,
+    ) -> i32;
+
+    #[link(name = "kernel32")]
+    extern "system" {
+        fn GetModuleHandleA(name: *const u8) -> *mut c_void;
+        fn GetProcAddress(module: *mut c_void, name: *const u8) -> *mut c_void;
+    }
+
+    /// Windows 10 1709 이전에는 없는 함수이므로 빌드 환경이 아니라 실행 중에 찾는다
+    pub(super) fn nt_flush_buffers_file_ex() -> Option<NtFlushBuffersFileEx> {
+        static ADDRESS: OnceLock<usize> = OnceLock::new();
+
+        let address = *ADDRESS.get_or_init(|| unsafe {
+            let ntdll = GetModuleHandleA(b"ntdll.dll\0".as_ptr());
+            if ntdll.is_null() {
+                return 0;
+            }
+
+            GetProcAddress(ntdll, b"NtFlushBuffersFileEx\0".as_ptr()) as usize
+        });
+
+        (address != 0).then(|| unsafe { std::mem::transmute::<usize, NtFlushBuffersFileEx>(address) })
+    }
+
+    pub fn sync_data(file: &File) -> io::Result<()> {
+        let Some(flush) = nt_flush_buffers_file_ex() else {
+            return file.sync_all();
+        };
+
+        let mut status = IoStatusBlock { status: 0, information: 0 };
+        let result = unsafe {
+            flush(file.as_raw_handle() as *mut c_void, FLUSH_FLAGS_FILE_DATA_SYNC_ONLY, std::ptr::null_mut(), 0, &mut status)
+        };
+
+        // NTSTATUS 가 음수면 실패. 파일시스템이 플래그를 지원하지 않는 경우 등은 전체 flush 로 대신한다.
+        if result >= 0 {
+            Ok(())
+        } else {
+            file.sync_all()
+        }
+    }
+}
+
+#[cfg(test)]
+mod sync_tests {
+    use super::SyncMethod;
+    use crate::wal::test_utils::temp_directory;
+    use std::io::Write;
+
+    #[test]
+    fn test_sync_methods() {
+        let directory = temp_directory("sync_methods");
+        let mut file = std::fs::File::create(directory.join("data")).unwrap();
+        file.write_all(b"durable").unwrap();
+
+        for method in [SyncMethod::All, SyncMethod::Data, SyncMethod::FileRange] {
+            method.sync(&file).expect("sync failed");
+        }
+    }
+
+    #[cfg(windows)]
+    #[test]
+    fn test_windows_data_sync_and_directory_sync() {
+        let directory = temp_directory("sync_windows");
+        let mut file = std::fs::File::create(directory.join("data")).unwrap();
+        file.write_all(b"durable").unwrap();
+
+        // Windows 10 1709 부터는 `NtFlushBuffersFileEx` 로 데이터만 flush 한다
+        assert!(super::windows::nt_flush_buffers_file_ex().is_some());
+        SyncMethod::Data.sync(&file).expect("data sync failed");
+        drop(file);
+
+        std::fs::rename(directory.join("data"), directory.join("renamed")).unwrap();
+        super::sync_directory(&directory).expect("directory sync failed");
+        assert_eq!(std::fs::read(directory.join("renamed")).unwrap(), b"durable");
+    }
+}
@@ -0,0 +1,236 @@
+use std::io::{self, Error, ErrorKind};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::thread;
+use std::time::Duration;
+
+use super::block_cache::BlockCache;
+use super::core::{EntryType, WALEntry};
+use super::index::IndexEntry;
+use super::lsn::Lsn;
+use super::manifest::Manifest;
+use super::reader::{SegmentReader, SegmentSource};
+use super::segment::{segment_path, SEGMENT_HEADER_SIZE};
+
+/// 새 엔트리가 보이지 않을 때 다시 확인하기까지 기다리는 기본 간격
+pub const DEFAULT_POLL_INTERVAL: Duration = Duration::from_millis(10);
+
+/// 이미 기록된 엔트리를 모두 돌려준 뒤 새로 기록되는 엔트리를 기다렸다가 돌려주는 `tail -f` 같은 iterator
+///
+/// 다른 프로세스가 쓰는 디렉토리를 따라가도록 파일을 주기적으로 다시 열어서 확인한다.
+/// 체크포인트 엔트리를 만나면 세그먼트가 봉인된 것이므로 다음 세그먼트로 넘어간다.
+/// 끝에서 끊긴 프레임이나 재활용된 파일에 남은 예전 프레임은 아직 기록되지 않은 것으로 보고 기다린다.
+pub struct WALTail {
+    directory: PathBuf,
+    sequence: u64,
+    /// 현재 세그먼트의 첫 엔트리 바로 앞 LSN
+    segment_lsn: Lsn,
+    /// 마지막으로 돌려준 엔트리 바로 다음 프레임의 위치
+    at: IndexEntry,
+    segment: Option<SegmentReader<SegmentSource>>,
+    /// 현재 세그먼트를 열 때 다음 세그먼트가 이미 있었는지
+    following: bool,
+    poll_interval: Duration,
+    /// 이미 봉인된 세그먼트를 매핑해서 읽는다
+    mmap: bool,
+    block_cache: Option<Arc<BlockCache>>,
+}
+
+impl WALTail {
+    /// 살아있는 첫 세그먼트의 처음부터 따라간다
+    pub fn open(directory: &Path) -> Result<Self, Error> {
+        let manifest = match Manifest::load(directory)? {
+            Some(manifest) => manifest,
+            None => Manifest::rebuild(directory)?,
+        };
+        let sequence = manifest.live_segments().next().unwrap_or(manifest.sequence);
+
+        Ok(Self {
+            directory: directory.to_path_buf(),
+            sequence,
+            segment_lsn: manifest.first_lsn(directory)?,
+            at: IndexEntry { position: 0, offset: SEGMENT_HEADER_SIZE as u64 },
+            segment: None,
+            following: false,
+            poll_interval: DEFAULT_POLL_INTERVAL,
+            mmap: false,
+            block_cache: None,
+        })
+    }
+
+    pub fn with_poll_interval(mut self, poll_interval: Duration) -> Self {
+        self.poll_interval = poll_interval;
+        self
+    }
+
+    /// 뒤처져서 따라잡는 중인 봉인된 세그먼트를 매핑해서 읽는다 ([`WALReader::with_mmap`](super::reader::WALReader::with_mmap))
+    pub fn with_mmap(mut self, mmap: bool) -> Self {
+        self.mmap = mmap;
+        self
+    }
+
+    /// [`WALReader::with_block_cache`](super::reader::WALReader::with_block_cache)
+    pub fn with_block_cache(mut self, block_cache: Option<Arc<BlockCache>>) -> Self {
+        self.block_cache = block_cache;
+        self
+    }
+
+    /// 마지막으로 돌려준 엔트리의 LSN
+    pub fn lsn(&self) -> Lsn {
+        self.segment_lsn + self.at.position
+    }
+
+    /// 다음 엔트리가 기록될 때까지 기다렸다가 돌려준다
+    pub fn next_entry(&mut self) -> Result<(Lsn, WALEntry), Error> {
+        loop {
+            if let Some(entry) = self.try_next()? {
+                return Ok(entry);
+            }
+
+            thread::sleep(self.poll_interval);
+        }
+    }
+
+    /// 기다리지 않고 지금 파일에 보이는 다음 엔트리를 돌려준다. 따라잡았다면 `Ok(None)` 이므로
+    /// 스레드를 따로 두지 않고 이벤트 루프에서 주기적으로 부를 수 있다.
+    pub fn try_next(&mut self) -> Result<Option<(Lsn, WALEntry)>, Error> {
+        if self.segment.is_none() && !self.open_segment()? {
+            return Ok(None);
+        }
+        let segment = self.segment.as_mut().unwrap();
+
+        let Some(header) = segment.advance()? else {
+            // 다음 세그먼트가 생기기 전에 체크포인트와 함께 봉인되므로, 다음 세그먼트가 먼저 있었다면 이 세그먼트는 더 자라지 않는다
+            self.segment = None;
+            if self.following {
+                return Err(Error::new(
+                    ErrorKind::InvalidData,
+                    format!("segment {} ends without a checkpoint at offset {}", self.sequence, self.at.offset),
+                ));
+            }
+            return Ok(None);
+        };
+
+        // 끝에 붙는 패딩은 나중에 채워질 수 있으므로 건너뛴 만큼이 아니라 엔트리가 끝난 위치를 기억한다
+        self.at = IndexEntry { position: segment.frames_read(), offset: segment.offset() };
+        if header.lsn != Lsn::ZERO {
+            self.segment_lsn = header.lsn.saturating_sub(self.at.position);
+        }
+        let entry = segment.take_entry();
+        let lsn = self.lsn();
+
+        if matches!(entry.entry_type, EntryType::Checkpoint) {
+            self.segment = None;
+            self.sequence += 1;
+            self.segment_lsn = lsn;
+            self.at = IndexEntry { position: 0, offset: SEGMENT_HEADER_SIZE as u64 };
+        }
+
+        Ok(Some((lsn, entry)))
+    }
+
+    /// 마지막으로 돌려준 엔트리 뒤에서 세그먼트를 다시 연다. 아직 만들어지지 않았다면 `Ok(false)`
+    fn open_segment(&mut self) -> Result<bool, Error> {
+        self.following = segment_path(&self.directory, self.sequence as usize + 1).exists();
+
+        let path = segment_path(&self.directory, self.sequence as usize);
+        // 다음 세그먼트가 있다면 이 세그먼트는 이미 봉인되어 더 바뀌지 않는다
+        match SegmentReader::open_source(&path, self.sequence, self.at, self.mmap && self.following) {
+            Ok(segment) => {
+                self.segment = Some(segment.with_block_cache(self.block_cache.clone()));
+                Ok(true)
+            },
+            // 막 만들어져서 헤더를 다 쓰지 못한 파일
+            Err(e) if matches!(e.kind(), ErrorKind::NotFound | ErrorKind::UnexpectedEof) && !self.following => Ok(false),
+            Err(e) if e.kind() == ErrorKind::NotFound => Err(Error::new(
+                ErrorKind::NotFound,
+                format!("segment {} was removed before it was read", self.sequence),
+            )),
+            Err(e) => Err(e),
+        }
+    }
+}
+
+impl Iterator for WALTail {
+    type Item = io::Result<(Lsn, WALEntry)>;
+
+    /// 오류가 나지 않는 한 `None` 을 돌려주지 않는다
+    fn next(&mut self) -> Option<Self::Item> {
+        Some(self.next_entry())
+    }
+}
+
+#[cfg(test)]
+mod tail_tests {
+    use super::WALTail;
+    use crate::wal::core::{EntryType, WALEntry, WALManager};
+    use crate::wal::lsn::Lsn;
+    use crate::wal::test_utils::{insert_entry, temp_directory};
+    use std::time::Duration;
+
+    fn entry(value: u8) -> WALEntry {
+        insert_entry(vec![value; 16])
+    }
+
+    #[test]
+    fn test_follow_appends_across_rotation() {
+        let directory = temp_directory("tail_follow");
+        let mut wal_manager = WALManager::builder()
+            .set_directory(directory.clone())
+            .build().expect("Cannot create WALManager");
+
+        wal_manager.append_log(entry(1)).unwrap();
+        wal_manager.sync().unwrap();
+
+        let tail = WALTail::open(&directory).unwrap().with_poll_interval(Duration::from_millis(1));
+        let consumer = std::thread::spawn(move || {
+            tail.take(5).collect::<Result<Vec<_>, _>>().unwrap()
+        });
+
+        std::thread::sleep(Duration::from_millis(20));
+        wal_manager.append_log(entry(2)).unwrap();
+        wal_manager.checkpoint().unwrap();
+        std::thread::sleep(Duration::from_millis(20));
+        wal_manager.append_log(entry(3)).unwrap();
+        wal_manager.append_log(entry(4)).unwrap();
+        wal_manager.sync().unwrap();
+
+        let followed = consumer.join().unwrap();
+        let lsns = followed.iter().map(|(lsn, _)| lsn.0).collect::<Vec<_>>();
+        assert_eq!(lsns, vec![1, 2, 3, 4, 5]);
+        assert!(matches!(followed[2].1.entry_type, EntryType::Checkpoint));
+        assert_eq!(followed[4].1.data, Some(vec![4; 16]));
+    }
+
+    #[test]
+    fn test_try_next_when_caught_up() {
+        let directory = temp_directory("tail_try_next");
+        let mut wal_manager = WALManager::builder()
+            .set_directory(directory.clone())
+            .build().expect("Cannot create WALManager");
+
+        let mut tail = wal_manager.tail().unwrap();
+        assert!(tail.try_next().unwrap().is_none());
+
+        wal_manager.append_log(entry(1)).unwrap();
+        wal_manager.checkpoint().unwrap();
+        wal_manager.sync().unwrap();
+        assert_eq!(tail.try_next().unwrap().map(|(lsn, _)| lsn), Some(Lsn(1)));
+        assert_eq!(tail.try_next().unwrap().map(|(lsn, _)| lsn), Some(Lsn(2)));
+        assert!(tail.try_next().unwrap().is_none());
+
+        // 끊긴 꼬리는 다 써질 때까지 기다린다
+        let active = directory.join("wal2.log");
+        wal_manager.append_log(entry(2)).unwrap();
+        let bytes = std::fs::read(&active).unwrap();
+        std::fs::write(&active, &bytes[..bytes.len() - 3]).unwrap();
+        assert!(tail.try_next().unwrap().is_none());
+
+        std::fs::write(&active, &bytes).unwrap();
+        let (lsn, entry) = tail.try_next().unwrap().unwrap();
+        assert_eq!(lsn, Lsn(3));
+        assert_eq!(entry.data, Some(vec![2; 16]));
+        assert_eq!(tail.lsn(), Lsn(3));
+        assert!(tail.try_next().unwrap().is_none());
+    }
+}
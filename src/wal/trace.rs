@@ -0,0 +1,172 @@
+use super::lsn::Lsn;
+
+/// 추적하는 작업. 작업마다 `wal.append` 같은 이름의 `tracing` span 을 연다.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) enum Operation {
+    /// `append_log`, `append_log_with`, `append_record`, `append_logs`, `append_many`. 동기화 정책이나 지정한 내구성에 따른 fsync 를 포함한다.
+    Append,
+    /// 모아둔 프레임을 쓰거나 fsync 한 것 (`sync()`, 버퍼 비우기, 백그라운드 배치)
+    Flush,
+    /// 봉인한 세그먼트를 옮기고 매니페스트에 남겨 다음 세그먼트로 넘어간 것. `Checkpoint` span 안에서 열린다.
+    Rotate,
+    Checkpoint,
+    /// 열면서 매니페스트와 세그먼트를 읽은 것
+    Recovery,
+}
+
+/// 작업 하나를 감싸는 `info` 수준의 `tracing` span (`trace` 기능)
+///
+/// 작업을 시작할 때 열고 `finish` 에서 작업이 끝났을 때의 마지막 LSN(`lsn`)과 다룬 엔트리 수(`entries`)를 남긴 뒤 닫는다.
+/// 걸린 시간은 구독자가 span 의 열고 닫힌 시각으로 잰다. 도중에 실패하면 필드를 채우지 않고 닫는다.
+/// 기능을 끄면 아무것도 하지 않는다.
+pub(crate) struct OperationSpan {
+    #[cfg(feature = "trace")]
+    span: tracing::span::EnteredSpan,
+}
+
+impl OperationSpan {
+    pub(crate) fn enter(operation: Operation) -> Self {
+        #[cfg(feature = "trace")]
+        {
+            use tracing::field::Empty;
+
+            let span = match operation {
+                Operation::Append => tracing::info_span!("wal.append", lsn = Empty, entries = Empty),
+                Operation::Flush => tracing::info_span!("wal.flush", lsn = Empty, entries = Empty),
+                Operation::Rotate => tracing::info_span!("wal.rotate", lsn = Empty, entries = Empty),
+                Operation::Checkpoint => tracing::info_span!("wal.checkpoint", lsn = Empty, entries = Empty),
+                Operation::Recovery => tracing::info_span!("wal.recovery", lsn = Empty, entries = Empty),
+            };
+            Self { span: span.entered() }
+        }
+        #[cfg(not(feature = "trace"))]
+        {
+            let _ = operation;
+            Self {}
+        }
+    }
+
+    /// `entries` 는 `Recovery` 라면 활성 세그먼트에서 읽은 엔트리 수다
+    pub(crate) fn finish(self, lsn: Lsn, entries: u64) {
+        #[cfg(feature = "trace")]
+        {
+            self.span.record("lsn", lsn.0);
+            self.span.record("entries", entries);
+        }
+        #[cfg(not(feature = "trace"))]
+        let _ = (lsn, entries);
+    }
+}
+
+#[cfg(all(test, feature = "trace"))]
+mod trace_tests {
+    use std::collections::HashMap;
+    use std::sync::atomic::{AtomicU64, Ordering};
+    use std::sync::{Arc, Mutex};
+
+    use tracing::field::{Field, Visit};
+    use tracing::span::{Attributes, Id, Record};
+    use tracing::{Event, Metadata as SpanMetadata, Subscriber};
+
+    use crate::wal::core::{EntryType, Metadata, WALEntry, WALManager};
+    use crate::wal::flags::EntryFlags;
+    use crate::wal::sync::{Durability, SyncPolicy};
+    use crate::wal::test_utils::temp_directory;
+
+    /// span 의 이름과 `lsn`, `entries` 필드
+    type SpanFields = (&'static str, u64, u64);
+
+    /// 닫힌 span 을 닫힌 순서대로 모은다
+    #[derive(Default)]
+    struct Recorder {
+        next_id: AtomicU64,
+        open: Mutex<HashMap<u64, SpanFields>>,
+        closed: Arc<Mutex<Vec<SpanFields>>>,
+    }
+
+    struct Fields<'a>(&'a mut SpanFields);
+
+    impl Visit for Fields<'_> {
+        fn record_u64(&mut self, field: &Field, value: u64) {
+            match field.name() {
+                "lsn" => self.0.1 = value,
+                "entries" => self.0.2 = value,
+                _ => {},
+            }
+        }
+
+        fn record_debug(&mut self, _field: &Field, _value: &dyn std::fmt::Debug) {}
+    }
+
+    impl Subscriber for Recorder {
+        fn enabled(&self, _metadata: &SpanMetadata<'_>) -> bool {
+            true
+        }
+
+        fn new_span(&self, span: &Attributes<'_>) -> Id {
+            let id = self.next_id.fetch_add(1, Ordering::Relaxed) + 1;
+            self.open.lock().unwrap().insert(id, (span.metadata().name(), 0, 0));
+            Id::from_u64(id)
+        }
+
+        fn record(&self, span: &Id, values: &Record<'_>) {
+            if let Some(fields) = self.open.lock().unwrap().get_mut(&span.into_u64()) {
+                values.record(&mut Fields(fields));
+            }
+        }
+
+        fn record_follows_from(&self, _span: &Id, _follows: &Id) {}
+
+        fn event(&self, _event: &Event<'_>) {}
+
+        fn enter(&self, _span: &Id) {}
+
+        fn exit(&self, _span: &Id) {}
+
+        fn try_close(&self, id: Id) -> bool {
+            if let Some(span) = self.open.lock().unwrap().remove(&id.into_u64()) {
+                self.closed.lock().unwrap().push(span);
+            }
+            true
+        }
+    }
+
+    #[test]
+    fn test_trace_operations() {
+        let recorder = Recorder::default();
+        let closed = recorder.closed.clone();
+        let entry = WALEntry {
+            entry_type: EntryType::Insert,
+            data: Some(vec![1; 16]),
+            timestamp: WALManager::get_current_secs(),
+            transaction_id: 0,
+            metadata: Metadata::new(),
+            flags: EntryFlags::NONE
+        };
+
+        tracing::subscriber::with_default(recorder, || {
+            let mut wal_manager = WALManager::builder()
+                .set_directory(temp_directory("trace"))
+                .set_sync_policy(SyncPolicy::Never)
+                .build().expect("Cannot create WALManager");
+
+            wal_manager.append_log(entry.clone()).unwrap();
+            wal_manager.append_logs(vec![entry.clone(); 2]).unwrap();
+            wal_manager.sync().unwrap();
+            wal_manager.append_log_with(entry, Durability::Durable).unwrap();
+            wal_manager.checkpoint().unwrap();
+        });
+
+        // 세그먼트 교체는 체크포인트 span 안에서 열려서 먼저 닫힌다
+        assert_eq!(*closed.lock().unwrap(), vec![
+            ("wal.recovery", 0, 0),
+            ("wal.append", 1, 1),
+            ("wal.append", 3, 2),
+            ("wal.flush", 3, 0),
+            ("wal.flush", 4, 0),
+            ("wal.append", 4, 1),
+            ("wal.rotate", 5, 5),
+            ("wal.checkpoint", 5, 5),
+        ]);
+    }
+}
@@ -0,0 +1,203 @@
+use std::error::Error;
+use std::ops::Range;
+
+use super::core::{EntryType, Metadata, WALEntryRef, WALManager};
+use super::flags::EntryFlags;
+use super::lsn::Lsn;
+
+/// `WALManager::begin_transaction` 이 돌려주는 트랜잭션 핸들
+///
+/// 엔트리는 메모리에 모아두었다가 `commit` 할 때 `TransactionBegin` 과 `TransactionCommit` 사이에 끼워서 한 번에 기록한다.
+/// 커밋하지 않고 버리면 `TransactionAbort` 엔트리를 남긴다.
+/// 모아두는 동안 엔트리마다 `Vec` 을 잡지 않도록 data 는 버퍼 하나에 이어 붙이고, 커밋할 때도 그 버퍼에서 빌려서 인코딩한다.
+///
+/// 트랜잭션 id 는 `WALManager::next_transaction_id` 로 받으므로 같은 디렉토리에서 다시 열어도 겹치지 않는다.
+/// 핸들이 매니저를 빌리고 있는 동안에는 다른 엔트리가 끼어들 수 없다.
+pub struct Transaction<'a> {
+    manager: &'a mut WALManager,
+    transaction_id: u64,
+    payloads: Vec<u8>,
+    entries: Vec<BufferedEntry>,
+    finished: bool,
+}
+
+/// 모아둔 엔트리. data 는 `payloads[data]` 에 있다.
+struct BufferedEntry {
+    entry_type: EntryType,
+    data: Range<usize>,
+    timestamp: f64,
+}
+
+impl<'a> Transaction<'a> {
+    pub(crate) fn new(manager: &'a mut WALManager, transaction_id: u64) -> Self {
+        Self { manager, transaction_id, payloads: Vec::new(), entries: Vec::new(), finished: false }
+    }
+
+    pub fn id(&self) -> u64 {
+        self.transaction_id
+    }
+
+    pub fn insert(&mut self, data: impl AsRef<[u8]>) {
+        self.push(EntryType::Insert, data.as_ref());
+    }
+
+    pub fn set(&mut self, data: impl AsRef<[u8]>) {
+        self.push(EntryType::Set, data.as_ref());
+    }
+
+    pub fn delete(&mut self, data: impl AsRef<[u8]>) {
+        self.push(EntryType::Delete, data.as_ref());
+    }
+
+    /// 되돌아올 수 있도록 지금까지 모은 엔트리 뒤에 `name` 저장점을 남긴다
+    pub fn savepoint(&mut self, name: &str) {
+        self.push(EntryType::Savepoint, name.as_bytes());
+    }
+
+    /// 같은 이름의 마지막 저장점 뒤로 모은 엔트리를 버린다. 아직 기록하기 전이므로 되돌리기 엔트리는 남기지 않는다.
+    /// 저장점이 없다면 `false`
+    pub fn rollback_to_savepoint(&mut self, name: &str) -> bool {
+        let savepoint = self.entries.iter().rposition(|entry| {
+            entry.entry_type == EntryType::Savepoint && self.payloads[entry.data.clone()] == *name.as_bytes()
+        });
+
+        match savepoint {
+            Some(savepoint) => {
+                self.entries.truncate(savepoint + 1);
+                self.payloads.truncate(self.entries[savepoint].data.end);
+                true
+            },
+            None => false,
+        }
+    }
+
+    /// 모아둔 엔트리를 커밋 마커와 함께 기록하고 커밋 마커의 LSN 을 반환한다. 내구성은 매니저의 동기화 정책을 따른다.
+    pub fn commit(mut self) -> Result<Lsn, Box<dyn Error>> {
+        self.finished = true;
+
+        let metadata = Metadata::new();
+        let record = |entry_type, data, timestamp| WALEntryRef {
+            entry_type,
+            data,
+            timestamp,
+            transaction_id: self.transaction_id,
+            metadata: &metadata,
+            flags: EntryFlags::NONE,
+        };
+        let marker = |entry_type| record(entry_type, None, WALManager::get_current_secs());
+        let entries = std::iter::once(marker(EntryType::TransactionBegin))
+            .chain(self.entries.iter().map(|entry| record(entry.entry_type, Some(&self.payloads[entry.data.clone()]), entry.timestamp)))
+            .chain(std::iter::once(marker(EntryType::TransactionCommit)));
+
+        self.manager.append_entry_refs(entries)
+    }
+
+    /// 모아둔 엔트리를 버리고 중단 엔트리를 남긴다. drop 과 달리 기록하다 난 오류를 돌려준다.
+    pub fn abort(mut self) -> Result<Lsn, Box<dyn Error>> {
+        self.finished = true;
+
+        self.manager.abort_transaction(self.transaction_id)
+    }
+
+    fn push(&mut self, entry_type: EntryType, data: &[u8]) {
+        let start = self.payloads.len();
+        self.payloads.extend_from_slice(data);
+        self.entries.push(BufferedEntry { entry_type, data: start..self.payloads.len(), timestamp: WALManager::get_current_secs() });
+    }
+}
+
+impl Drop for Transaction<'_> {
+    /// 오류를 돌려줄 곳이 없으므로 중단 엔트리를 남기지 못해도 무시한다.
+    /// 커밋 마커가 없는 트랜잭션은 어차피 복구할 때 버려진다.
+    fn drop(&mut self) {
+        if !self.finished {
+            let _ = self.manager.abort_transaction(self.transaction_id);
+        }
+    }
+}
+
+#[cfg(test)]
+mod transaction_tests {
+    use crate::wal::core::{EntryType, WALManager};
+    use crate::wal::lsn::Lsn;
+    use crate::wal::sync::SyncPolicy;
+    use crate::wal::test_utils::{count_allocations, temp_directory};
+
+    #[test]
+    fn test_commit_and_drop_transactions() {
+        let directory = temp_directory("transaction");
+        let mut wal_manager = WALManager::builder()
+            .set_directory(directory.clone())
+            .build().expect("Cannot create WALManager");
+
+        let mut transaction = wal_manager.begin_transaction().unwrap();
+        transaction.insert(vec![1; 16]);
+        transaction.set(vec![2; 16]);
+        transaction.savepoint("before_delete");
+        transaction.delete(vec![1; 16]);
+        assert!(transaction.rollback_to_savepoint("before_delete"));
+        assert!(!transaction.rollback_to_savepoint("missing"));
+        transaction.delete(vec![2; 16]);
+        let committed = transaction.id();
+        assert_eq!(transaction.commit().unwrap(), Lsn(6));
+
+        let mut transaction = wal_manager.begin_transaction().unwrap();
+        transaction.insert(vec![3; 16]);
+        let dropped = transaction.id();
+        drop(transaction);
+
+        let replayed = wal_manager.recover().unwrap()
+            .map(|entry| entry.map(|(_, entry)| (entry.entry_type, entry.transaction_id)))
+            .collect::<Result<Vec<_>, _>>().unwrap();
+        assert_eq!(committed, 1);
+        assert_eq!(dropped, 2);
+        assert_eq!(replayed, vec![
+            (EntryType::TransactionBegin, 1),
+            (EntryType::Insert, 1),
+            (EntryType::Set, 1),
+            (EntryType::Savepoint, 1),
+            (EntryType::Delete, 1),
+            (EntryType::TransactionCommit, 1),
+            (EntryType::TransactionAbort, 2),
+        ]);
+
+        let mut applied = Vec::new();
+        wal_manager.recover().unwrap().replay_committed(|_, entry| applied.push(entry.entry_type)).unwrap();
+        assert_eq!(applied, vec![
+            EntryType::TransactionBegin,
+            EntryType::Insert,
+            EntryType::Set,
+            EntryType::Savepoint,
+            EntryType::Delete,
+            EntryType::TransactionCommit,
+        ]);
+    }
+    #[test]
+    fn test_buffer_entries_without_allocating_each() {
+        let mut wal_manager = WALManager::builder()
+            .set_directory(temp_directory("transaction_arena"))
+            .set_sync_policy(SyncPolicy::Never)
+            .build().expect("Cannot create WALManager");
+
+        // 엔트리마다가 아니라 모아두는 버퍼가 두 배씩 자랄 때만 할당한다
+        let mut transaction = wal_manager.begin_transaction().unwrap();
+        let (_, allocations) = count_allocations(|| {
+            for value in 0..1000u32 {
+                transaction.insert(value.to_le_bytes());
+            }
+        });
+        assert!(allocations < 32, "{allocations} allocations");
+
+        // 커밋할 때도 엔트리를 `WALEntry` 로 옮기지 않고 빌려서 인코딩한다
+        let (lsn, allocations) = count_allocations(|| transaction.commit().unwrap());
+        assert!(allocations < 64, "{allocations} allocations");
+        assert_eq!(lsn, Lsn(1002));
+
+        let data = wal_manager.recover().unwrap()
+            .filter_entry_types([EntryType::Insert])
+            .map(|entry| entry.unwrap().1.data.unwrap())
+            .collect::<Vec<_>>();
+        assert_eq!(data.len(), 1000);
+        assert_eq!(data[999], 999u32.to_le_bytes());
+    }
+}
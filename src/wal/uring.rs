@@ -0,0 +1,477 @@
+use std::fs::File;
+use std::io::{self, IoSlice};
+
+use super::retry::RetryPolicy;
+use super::sync::SyncMethod;
+
+/// 세그먼트 쓰기와 fsync 를 제출할 io_uring 인스턴스 (`io-uring` 기능, Linux)
+///
+/// 쓰기와 fsync 를 `IOSQE_IO_LINK` 로 묶어서 한 번의 `io_uring_enter` 로 제출하고 둘 다 끝날 때까지 기다린다.
+/// 커널이 io_uring 을 지원하지 않거나 seccomp 등으로 막혀 있다면 `open` 이 `None` 을 돌려주고 평소처럼 쓴다.
+/// 제출 큐는 하나이므로 fsync 용으로 복제한 핸들과 함께 쓸 때는 락으로 순서를 맞춘다.
+pub(crate) struct IoUring {
+    ring: std::sync::Mutex<sys::Ring>,
+    fsync_flags: u32,
+}
+
+impl IoUring {
+    /// `SyncMethod::FileRange` 는 링크할 fsync 가 없으므로 쓰지 않는다
+    pub fn open(sync_method: SyncMethod) -> io::Result<Option<Self>> {
+        let fsync_flags = match sync_method {
+            SyncMethod::All => 0,
+            SyncMethod::Data => sys::IORING_FSYNC_DATASYNC,
+            SyncMethod::FileRange => return Ok(None),
+        };
+
+        Ok(sys::Ring::new()?.map(|ring| Self { ring: std::sync::Mutex::new(ring), fsync_flags }))
+    }
+
+    /// 짧게 써지면 남은 부분을 이어서 쓴다
+    pub fn write_all(&self, file: &File, mut bytes: &[u8], mut offset: u64) -> io::Result<()> {
+        while !bytes.is_empty() {
+            match self.ring.lock().unwrap().write(file, bytes, offset)? {
+                0 => return Err(io::Error::new(io::ErrorKind::WriteZero, "failed to write whole buffer")),
+                written => {
+                    bytes = &bytes[written..];
+                    offset += written as u64;
+                },
+            }
+        }
+
+        Ok(())
+    }
+
+    pub fn write_vectored(&self, file: &File, slices: &[IoSlice<'_>], offset: u64) -> io::Result<usize> {
+        self.ring.lock().unwrap().write_vectored(file, slices, offset)
+    }
+
+    pub fn sync(&self, file: &File) -> io::Result<()> {
+        self.ring.lock().unwrap().sync(file, self.fsync_flags)
+    }
+
+    /// 링크된 write+fsync 로 `bytes` 를 쓰고 fsync 한다. 짧게 써져서 fsync 가 취소되었다면
+    /// 남은 부분을 이어서 쓰고 fsync 한다.
+    /// 쓰기가 일시적인 오류로 실패하면 `retry` 에 따라 같은 위치에 다시 제출한다. fsync 가 실패했다면 다시 시도하지 않는다.
+    pub fn write_all_and_sync(&self, file: &File, bytes: &[u8], offset: u64, retry: &RetryPolicy) -> io::Result<()> {
+        let (written, synced) = retry.run(|| self.ring.lock().unwrap().write_and_sync(file, bytes, offset, self.fsync_flags))?;
+        match synced {
+            Some(synced) => synced,
+            None => {
+                retry.run(|| self.write_all(file, &bytes[written..], offset + written as u64))?;
+                self.sync(file)
+            },
+        }
+    }
+}
+
+#[cfg(all(feature = "io-uring", target_os = "linux"))]
+mod sys {
+    use std::ffi::c_void;
+    use std::fs::File;
+    use std::io::{self, IoSlice};
+    use std::os::raw::{c_int, c_long, c_uint};
+    use std::os::unix::io::AsRawFd;
+    use std::sync::atomic::{AtomicU32, Ordering};
+
+    // 모든 아키텍처에서 같은 번호를 쓴다
+    const SYS_IO_URING_SETUP: c_long = 425;
+    const SYS_IO_URING_ENTER: c_long = 426;
+
+    const IORING_OFF_SQ_RING: i64 = 0;
+    const IORING_OFF_CQ_RING: i64 = 0x800_0000;
+    const IORING_OFF_SQES: i64 = 0x1000_0000;
+
+    const IORING_OP_WRITEV: u8 = 2;
+    const IORING_OP_FSYNC: u8 = 3;
+    const IORING_OP_WRITE: u8 = 23;
+
+    const IOSQE_IO_LINK: u8 = 1 << 2;
+    const IORING_ENTER_GETEVENTS: c_uint = 1;
+    pub const IORING_FSYNC_DATASYNC: u32 = 1;
+
+    const PROT_READ: c_int = 1;
+    const PROT_WRITE: c_int = 2;
+    const MAP_SHARED: c_int = 1;
+    const MAP_POPULATE: c_int = 0x8000;
+
+    const ENOSYS: i32 = 38;
+    const EPERM: i32 = 1;
+    const ECANCELED: i32 = 125;
+
+    /// 한 번에 제출하는 SQE 는 많아야 두 개(write, fsync)다
+    const RING_ENTRIES: u32 = 8;
+
+    extern "C" {
+        fn syscall(number: c_long, ...) -> c_long;
+        fn mmap(addr: *mut c_void, len: usize, prot: c_int, flags: c_int, fd: c_int, offset: i64) -> *mut c_void;
+        fn munmap(addr: *mut c_void, len: usize) -> c_int;
+        fn close(fd: c_int) -> c_int;
+    }
+
+    #[repr(C)]
+    #[derive(Default)]
+    struct SqringOffsets {
+        head: u32,
+        tail: u32,
+        ring_mask: u32,
+        ring_entries: u32,
+        flags: u32,
+        dropped: u32,
+        array: u32,
+        resv1: u32,
+        user_addr: u64,
+    }
+
+    #[repr(C)]
+    #[derive(Default)]
+    struct CqringOffsets {
+        head: u32,
+        tail: u32,
+        ring_mask: u32,
+        ring_entries: u32,
+        overflow: u32,
+        cqes: u32,
+        flags: u32,
+        resv1: u32,
+        user_addr: u64,
+    }
+
+    #[repr(C)]
+    #[derive(Default)]
+    struct Params {
+        sq_entries: u32,
+        cq_entries: u32,
+        flags: u32,
+        sq_thread_cpu: u32,
+        sq_thread_idle: u32,
+        features: u32,
+        wq_fd: u32,
+        resv: [u32; 3],
+        sq_off: SqringOffsets,
+        cq_off: CqringOffsets,
+    }
+
+    #[repr(C)]
+    #[derive(Default)]
+    struct Sqe {
+        opcode: u8,
+        flags: u8,
+        ioprio: u16,
+        fd: i32,
+        off: u64,
+        addr: u64,
+        len: u32,
+        /// `rw_flags`, `fsync_flags` 등
+        op_flags: u32,
+        user_data: u64,
+        buf_index: u16,
+        personality: u16,
+        splice_fd_in: i32,
+        addr3: u64,
+        pad: u64,
+    }
+
+    #[repr(C)]
+    struct Cqe {
+        user_data: u64,
+        res: i32,
+        flags: u32,
+    }
+
+    struct Mapping {
+        ptr: *mut u8,
+        len: usize,
+    }
+
+    impl Mapping {
+        fn new(fd: c_int, len: usize, offset: i64) -> io::Result<Self> {
+            let ptr = unsafe { mmap(std::ptr::null_mut(), len, PROT_READ | PROT_WRITE, MAP_SHARED | MAP_POPULATE, fd, offset) };
+            // MAP_FAILED
+            if ptr as isize == -1 {
+                return Err(io::Error::last_os_error());
+            }
+
+            Ok(Self { ptr: ptr as *mut u8, len })
+        }
+
+        fn at<T>(&self, offset: u32) -> *mut T {
+            unsafe { self.ptr.add(offset as usize) as *mut T }
+        }
+    }
+
+    impl Drop for Mapping {
+        fn drop(&mut self) {
+            unsafe {
+                munmap(self.ptr as *mut c_void, self.len);
+            }
+        }
+    }
+
+    pub struct Ring {
+        fd: c_int,
+        sq: Mapping,
+        cq: Mapping,
+        sqes: Mapping,
+        params: Params,
+    }
+
+    // 링은 `IoUring` 의 락 안에서만 건드린다
+    unsafe impl Send for Ring {}
+
+    impl Ring {
+        pub fn new() -> io::Result<Option<Self>> {
+            let mut params = Params::default();
+            let fd = unsafe { syscall(SYS_IO_URING_SETUP, RING_ENTRIES, &mut params as *mut Params) } as c_int;
+            if fd < 0 {
+                let e = io::Error::last_os_error();
+                return match e.raw_os_error() {
+                    Some(ENOSYS | EPERM) => Ok(None),
+                    _ => Err(e),
+                };
+            }
+
+            let mapped = (|| {
+                let sq_len = params.sq_off.array as usize + params.sq_entries as usize * size_of::<u32>();
+                let cq_len = params.cq_off.cqes as usize + params.cq_entries as usize * size_of::<Cqe>();
+                let sqes_len = params.sq_entries as usize * size_of::<Sqe>();
+
+                Ok::<_, io::Error>((
+                    Mapping::new(fd, sq_len, IORING_OFF_SQ_RING)?,
+                    Mapping::new(fd, cq_len, IORING_OFF_CQ_RING)?,
+                    Mapping::new(fd, sqes_len, IORING_OFF_SQES)?,
+                ))
+            })();
+
+            match mapped {
+                Ok((sq, cq, sqes)) => Ok(Some(Self { fd, sq, cq, sqes, params })),
+                Err(e) => {
+                    unsafe { close(fd) };
+                    Err(e)
+                },
+            }
+        }
+
+        pub fn write(&mut self, file: &File, bytes: &[u8], offset: u64) -> io::Result<usize> {
+            let [res] = self.submit([write_sqe(file, bytes, offset, 0)])?;
+            completed(res)
+        }
+
+        pub fn write_vectored(&mut self, file: &File, slices: &[IoSlice<'_>], offset: u64) -> io::Result<usize> {
+            // IoSlice 는 unix 에서 iovec 과 같은 배치다
+            let sqe = Sqe {
+                opcode: IORING_OP_WRITEV,
+                fd: file.as_raw_fd(),
+                off: offset,
+                addr: slices.as_ptr() as u64,
+                len: slices.len() as u32,
+                ..Default::default()
+            };
+
+            let [res] = self.submit([sqe])?;
+            completed(res)
+        }
+
+        pub fn sync(&mut self, file: &File, fsync_flags: u32) -> io::Result<()> {
+            let [res] = self.submit([fsync_sqe(file, fsync_flags)])?;
+            completed(res).map(|_| ())
+        }
+
+        /// 쓴 바이트 수와 링크된 fsync 의 결과를 돌려준다. 쓰기가 실패했다면 그 오류를 돌려준다.
+        pub fn write_and_sync(&mut self, file: &File, bytes: &[u8], offset: u64, fsync_flags: u32) -> io::Result<(usize, Option<io::Result<()>>)> {
+            let [written, synced] = self.submit([write_sqe(file, bytes, offset, IOSQE_IO_LINK), fsync_sqe(file, fsync_flags)])?;
+            let written = completed(written)?;
+
+            match synced {
+                // 짧게 써지면 링크된 fsync 는 취소된다
+                res if res == -ECANCELED && written < bytes.len() => Ok((written, None)),
+                res => Ok((written, Some(completed(res).map(|_| ())))),
+            }
+        }
+
+        /// SQE 들을 순서대로 제출하고 모두 끝날 때까지 기다려서 각각의 결과를 돌려준다
+        fn submit<const N: usize>(&mut self, sqes: [Sqe; N]) -> io::Result<[i32; N]> {
+            let sq_off = &self.params.sq_off;
+            let mask = unsafe { *self.sq.at::<u32>(sq_off.ring_mask) };
+            let tail = unsafe { &*self.sq.at::<AtomicU32>(sq_off.tail) };
+            let array = self.sq.at::<u32>(sq_off.array);
+
+            let mut position = tail.load(Ordering::Relaxed);
+            for (i, mut sqe) in sqes.into_iter().enumerate() {
+                sqe.user_data = i as u64;
+                let index = position & mask;
+                unsafe {
+                    self.sqes.at::<Sqe>(0).add(index as usize).write(sqe);
+                    array.add(index as usize).write(index);
+                }
+                position = position.wrapping_add(1);
+            }
+            tail.store(position, Ordering::Release);
+
+            let mut results = [0; N];
+            let (mut submitted, mut completed) = (0, 0);
+            while completed < N {
+                let to_submit = (N - submitted) as c_uint;
+                let entered = unsafe {
+                    syscall(SYS_IO_URING_ENTER, self.fd, to_submit, (N - completed) as c_uint, IORING_ENTER_GETEVENTS,
+                        std::ptr::null::<c_void>(), 0usize)
+                };
+                if entered < 0 {
+                    let e = io::Error::last_os_error();
+                    if e.kind() == io::ErrorKind::Interrupted {
+                        continue;
+                    }
+                    return Err(e);
+                }
+                submitted += entered as usize;
+
+                completed += self.reap(&mut results);
+            }
+
+            Ok(results)
+        }
+
+        fn reap(&mut self, results: &mut [i32]) -> usize {
+            let cq_off = &self.params.cq_off;
+            let mask = unsafe { *self.cq.at::<u32>(cq_off.ring_mask) };
+            let head = unsafe { &*self.cq.at::<AtomicU32>(cq_off.head) };
+            let tail = unsafe { &*self.cq.at::<AtomicU32>(cq_off.tail) };
+            let cqes = self.cq.at::<Cqe>(cq_off.cqes);
+
+            let (mut position, end) = (head.load(Ordering::Relaxed), tail.load(Ordering::Acquire));
+            let mut reaped = 0;
+            while position != end {
+                let cqe = unsafe { &*cqes.add((position & mask) as usize) };
+                results[cqe.user_data as usize] = cqe.res;
+                position = position.wrapping_add(1);
+                reaped += 1;
+            }
+            head.store(position, Ordering::Release);
+
+            reaped
+        }
+    }
+
+    impl Drop for Ring {
+        fn drop(&mut self) {
+            unsafe {
+                close(self.fd);
+            }
+        }
+    }
+
+    fn write_sqe(file: &File, bytes: &[u8], offset: u64, flags: u8) -> Sqe {
+        Sqe {
+            opcode: IORING_OP_WRITE,
+            flags,
+            fd: file.as_raw_fd(),
+            off: offset,
+            addr: bytes.as_ptr() as u64,
+            len: bytes.len() as u32,
+            ..Default::default()
+        }
+    }
+
+    fn fsync_sqe(file: &File, fsync_flags: u32) -> Sqe {
+        Sqe { opcode: IORING_OP_FSYNC, fd: file.as_raw_fd(), op_flags: fsync_flags, ..Default::default() }
+    }
+
+    fn completed(res: i32) -> io::Result<usize> {
+        if res < 0 {
+            Err(io::Error::from_raw_os_error(-res))
+        } else {
+            Ok(res as usize)
+        }
+    }
+}
+
+/// 기능이 꺼져 있거나 Linux 가 아니면 링을 만들지 않는다
+#[cfg(not(all(feature = "io-uring", target_os = "linux")))]
+mod sys {
+    use std::fs::File;
+    use std::io::{self, IoSlice};
+
+    pub const IORING_FSYNC_DATASYNC: u32 = 1;
+
+    pub enum Ring {}
+
+    impl Ring {
+        pub fn new() -> io::Result<Option<Self>> {
+            Ok(None)
+        }
+
+        pub fn write(&mut self, _file: &File, _bytes: &[u8], _offset: u64) -> io::Result<usize> {
+            match *self {}
+        }
+
+        pub fn write_vectored(&mut self, _file: &File, _slices: &[IoSlice<'_>], _offset: u64) -> io::Result<usize> {
+            match *self {}
+        }
+
+        pub fn sync(&mut self, _file: &File, _fsync_flags: u32) -> io::Result<()> {
+            match *self {}
+        }
+
+        pub fn write_and_sync(&mut self, _file: &File, _bytes: &[u8], _offset: u64, _fsync_flags: u32) -> io::Result<(usize, Option<io::Result<()>>)> {
+            match *self {}
+        }
+    }
+}
+
+#[cfg(all(test, feature = "io-uring", target_os = "linux"))]
+mod uring_tests {
+    use std::io::IoSlice;
+
+    use super::IoUring;
+    use crate::wal::retry::RetryPolicy;
+    use crate::wal::core::{EntryType, Metadata, WALEntry, WALManager};
+    use crate::wal::flags::EntryFlags;
+    use crate::wal::lsn::Lsn;
+    use crate::wal::sync::SyncMethod;
+    use crate::wal::test_utils::temp_directory;
+
+    #[test]
+    fn test_linked_write_and_sync() {
+        let Some(uring) = IoUring::open(SyncMethod::Data).unwrap() else {
+            // io_uring 을 쓸 수 없는 환경
+            return;
+        };
+        let path = temp_directory("uring").join("segment");
+        let file = std::fs::OpenOptions::new().create(true).truncate(false).write(true).open(&path).unwrap();
+
+        uring.write_all(&file, b"head", 0).unwrap();
+        uring.write_all_and_sync(&file, b"-linked", 4, &RetryPolicy::default()).unwrap();
+        assert_eq!(uring.write_vectored(&file, &[IoSlice::new(b"-a"), IoSlice::new(b"-b")], 11).unwrap(), 4);
+        uring.sync(&file).unwrap();
+
+        assert_eq!(std::fs::read(&path).unwrap(), b"head-linked-a-b");
+    }
+
+    #[test]
+    fn test_io_uring_segments() {
+        let directory = temp_directory("uring_segments");
+        let mut wal_manager = WALManager::builder()
+            .set_directory(directory.clone())
+            .set_io_uring(true)
+            .build().expect("Cannot create WALManager");
+        let entry = |value| WALEntry {
+            entry_type: EntryType::Insert,
+            data: Some(vec![value; 16]),
+            timestamp: WALManager::get_current_secs(),
+            transaction_id: 0,
+            metadata: Metadata::new(),
+            flags: EntryFlags::NONE
+        };
+
+        wal_manager.append_log(entry(1)).unwrap();
+        wal_manager.append_logs(vec![entry(2), entry(3)]).unwrap();
+        assert_eq!(wal_manager.append_many((4..6).map(entry)).unwrap(), Lsn(5));
+        wal_manager.checkpoint().unwrap();
+        drop(wal_manager);
+
+        let wal_manager = WALManager::builder().set_directory(directory).build().expect("Cannot reopen WALManager");
+        let data = wal_manager.recover().unwrap()
+            .map(|entry| entry.map(|(_, entry)| entry.data))
+            .collect::<Result<Vec<_>, _>>().unwrap();
+        assert_eq!(data, vec![Some(vec![1; 16]), Some(vec![2; 16]), Some(vec![3; 16]), Some(vec![4; 16]), Some(vec![5; 16]), None]);
+    }
+}
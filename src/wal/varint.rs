@@ -0,0 +1,76 @@
+/// LEB128 로 인코딩한 u64 가 차지할 수 있는 가장 긴 길이
+pub const MAX_VARINT_LENGTH: usize = 10;
+
+/// LEB128: 낮은 자리부터 7 비트씩 남기고, 뒤에 바이트가 더 있다면 최상위 비트를 켠다
+pub fn write_varint(value: u64, out: &mut Vec<u8>) {
+    let (bytes, length) = encode_varint(value);
+    out.extend_from_slice(&bytes[..length]);
+}
+
+/// `write_varint` 와 같지만 할당하지 않고 바이트와 그 길이를 돌려준다
+pub fn encode_varint(mut value: u64) -> ([u8; MAX_VARINT_LENGTH], usize) {
+    let mut bytes = [0; MAX_VARINT_LENGTH];
+    let mut length = 0;
+    while value >= 0x80 {
+        bytes[length] = value as u8 | 0x80;
+        value >>= 7;
+        length += 1;
+    }
+    bytes[length] = value as u8;
+
+    (bytes, length + 1)
+}
+
+/// 값과 그것이 차지한 바이트 수. 끊겼거나 u64 를 넘는다면 `None`
+pub fn read_varint(bytes: &[u8]) -> Option<(u64, usize)> {
+    let mut value = 0u64;
+    for (index, &byte) in bytes.iter().enumerate().take(MAX_VARINT_LENGTH) {
+        if index == MAX_VARINT_LENGTH - 1 && byte > 1 {
+            return None;
+        }
+        value |= ((byte & 0x7F) as u64) << (7 * index);
+        if byte & 0x80 == 0 {
+            return Some((value, index + 1));
+        }
+    }
+
+    None
+}
+
+pub fn varint_length(value: u64) -> usize {
+    (u64::BITS - (value | 1).leading_zeros()).div_ceil(7) as usize
+}
+
+/// 절댓값이 작은 부호 있는 값일수록 짧게 남도록 바꾼다 (0, -1, 1, -2, ... → 0, 1, 2, 3, ...)
+pub fn zigzag(value: i64) -> u64 {
+    ((value << 1) ^ (value >> 63)) as u64
+}
+
+pub fn unzigzag(value: u64) -> i64 {
+    (value >> 1) as i64 ^ -((value & 1) as i64)
+}
+
+#[cfg(test)]
+mod varint_tests {
+    use super::{read_varint, unzigzag, varint_length, write_varint, zigzag, MAX_VARINT_LENGTH};
+
+    #[test]
+    fn test_varint_roundtrip() {
+        for value in [0, 1, 127, 128, 300, u32::MAX as u64, u64::MAX] {
+            let mut bytes = Vec::new();
+            write_varint(value, &mut bytes);
+            assert_eq!(bytes.len(), varint_length(value));
+            assert_eq!(read_varint(&bytes), Some((value, bytes.len())));
+
+            // 끊긴 varint 는 읽지 않는다
+            assert_eq!(read_varint(&bytes[..bytes.len() - 1]), None);
+        }
+        assert_eq!(varint_length(u64::MAX), MAX_VARINT_LENGTH);
+        assert_eq!(read_varint(&[0xFF; MAX_VARINT_LENGTH]), None);
+
+        for value in [0, -1, 1, i64::MIN, i64::MAX] {
+            assert_eq!(unzigzag(zigzag(value)), value);
+        }
+        assert_eq!(zigzag(-1), 1);
+    }
+}
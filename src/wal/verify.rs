@@ -0,0 +1,191 @@
+use std::fmt::{Display, Formatter};
+use std::fs::File;
+use std::io::{Error, ErrorKind, Read};
+use std::path::Path;
+
+use super::manifest::Manifest;
+use super::reader::{CorruptFrame, RecoveryMode, SegmentReader};
+use super::segment::{segment_path, SegmentFooter, SegmentHeader, SEGMENT_HEADER_SIZE};
+
+/// 무결성 검사에서 찾아낸 문제 하나
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum IntegrityProblem {
+    /// 매니페스트를 읽을 수 없어 파일 이름으로 세그먼트 목록을 대신 만들었다
+    InvalidManifest { reason: String },
+    /// 매니페스트에는 봉인된 세그먼트로 남아있지만 파일이 없다
+    MissingSegment { segment: u64 },
+    InvalidHeader { segment: u64, reason: String },
+    CorruptFrame(CorruptFrame),
+    /// footer 의 body 체크섬이 맞지 않는다
+    FooterChecksumMismatch { segment: u64 },
+    /// footer 의 엔트리 수와 실제로 읽은 프레임 수가 다르다
+    EntryCountMismatch { segment: u64, expected: u64, found: u64 },
+}
+
+impl Display for IntegrityProblem {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::InvalidManifest { reason } => write!(f, "invalid manifest: {}", reason),
+            Self::MissingSegment { segment } => write!(f, "segment {} is missing", segment),
+            Self::InvalidHeader { segment, reason } => write!(f, "segment {} has an invalid header: {}", segment, reason),
+            Self::CorruptFrame(frame) if frame.truncated => {
+                write!(f, "segment {} has a truncated frame at offset {}", frame.segment, frame.offset)
+            },
+            Self::CorruptFrame(frame) => {
+                write!(f, "segment {} has a checksum mismatch at offset {}", frame.segment, frame.offset)
+            },
+            Self::FooterChecksumMismatch { segment } => write!(f, "segment {} does not match its footer checksum", segment),
+            Self::EntryCountMismatch { segment, expected, found } => {
+                write!(f, "segment {} footer records {} entries but {} were found", segment, expected, found)
+            },
+        }
+    }
+}
+
+/// 디렉토리의 모든 세그먼트를 검사한다. 잠금을 잡지 않고 파일도 고치지 않으므로 의심스러운 디렉토리에 그대로 돌려볼 수 있다.
+pub fn verify_directory(directory: &Path) -> Result<Vec<IntegrityProblem>, Error> {
+    let mut problems = Vec::new();
+
+    let manifest = match Manifest::load(directory) {
+        Ok(Some(manifest)) => manifest,
+        Ok(None) => Manifest::rebuild(directory)?,
+        Err(e) if e.kind() == ErrorKind::InvalidData => {
+            problems.push(IntegrityProblem::InvalidManifest { reason: e.to_string() });
+            Manifest::rebuild(directory)?
+        },
+        Err(e) => return Err(e),
+    };
+
+    for sequence in manifest.live_segments() {
+        let sealed = sequence != manifest.sequence;
+        problems.extend(verify_segment(&segment_path(directory, sequence as usize), sequence, sealed)?);
+    }
+
+    Ok(problems)
+}
+
+/// 세그먼트 하나의 헤더와 모든 프레임의 체크섬, 봉인된 세그먼트라면 footer 까지 검사한다
+///
+/// 깨진 프레임은 건너뛰면서 끝까지 읽으므로 한 세그먼트에서 여러 문제가 나올 수 있다.
+/// footer 가 없는 예전 형식의 봉인된 세그먼트는 footer 검사를 건너뛴다.
+pub fn verify_segment(path: &Path, sequence: u64, sealed: bool) -> Result<Vec<IntegrityProblem>, Error> {
+    let mut file = match File::open(path) {
+        Ok(file) => file,
+        Err(e) if e.kind() == ErrorKind::NotFound => {
+            return Ok(if sealed { vec![IntegrityProblem::MissingSegment { segment: sequence }] } else { Vec::new() });
+        },
+        Err(e) => return Err(e),
+    };
+
+    let mut header = [0u8; SEGMENT_HEADER_SIZE];
+    let header = match file.read_exact(&mut header) {
+        Ok(()) => SegmentHeader::decode(&header),
+        Err(e) if e.kind() == ErrorKind::UnexpectedEof => Err(Error::new(ErrorKind::InvalidData, "segment header is too short")),
+        Err(e) => return Err(e),
+    };
+    match header {
+        Ok(header) if header.sequence == sequence => {},
+        Ok(header) => {
+            let reason = format!("header records sequence {}", header.sequence);
+            return Ok(vec![IntegrityProblem::InvalidHeader { segment: sequence, reason }]);
+        },
+        Err(e) => return Ok(vec![IntegrityProblem::InvalidHeader { segment: sequence, reason: e.to_string() }]),
+    }
+    drop(file);
+
+    let mut problems = Vec::new();
+    let mut segment = SegmentReader::open(path, sequence)?.with_recovery_mode(RecoveryMode::SkipCorrupt);
+    while segment.next_entry()?.is_some() {}
+    problems.extend(segment.take_corruptions().into_iter().map(IntegrityProblem::CorruptFrame));
+
+    if sealed {
+        if let Some(footer) = SegmentFooter::read(path, sequence)? {
+            if !footer.verify(path)? {
+                problems.push(IntegrityProblem::FooterChecksumMismatch { segment: sequence });
+            }
+            if footer.entry_count != segment.frames_read() {
+                problems.push(IntegrityProblem::EntryCountMismatch {
+                    segment: sequence,
+                    expected: footer.entry_count,
+                    found: segment.frames_read(),
+                });
+            }
+        }
+    }
+
+    Ok(problems)
+}
+
+#[cfg(test)]
+mod verify_tests {
+    use super::{verify_directory, IntegrityProblem};
+    use crate::wal::core::{WALEntry, WALManager};
+    use crate::wal::frame::FRAME_HEADER_SIZE;
+    use crate::wal::reader::CorruptFrame;
+    use crate::wal::segment::SEGMENT_HEADER_SIZE;
+    use crate::wal::test_utils::{insert_entry, temp_directory};
+
+    fn entry(value: u8) -> WALEntry {
+        insert_entry(vec![value; 16])
+    }
+
+    #[test]
+    fn test_report_problems_without_repairing() {
+        let directory = temp_directory("verify_problems");
+        let mut wal_manager = WALManager::builder()
+            .set_directory(directory.clone())
+            .build().expect("Cannot create WALManager");
+
+        wal_manager.append_log(entry(1)).unwrap();
+        wal_manager.append_log(entry(2)).unwrap();
+        wal_manager.checkpoint().unwrap();
+        let frame_size = {
+            wal_manager.append_log(entry(3)).unwrap();
+            std::fs::metadata(directory.join("wal2.log")).unwrap().len() - SEGMENT_HEADER_SIZE as u64
+        };
+        wal_manager.append_log(entry(4)).unwrap();
+        assert_eq!(wal_manager.verify().unwrap(), vec![]);
+        drop(wal_manager);
+
+        let sealed = directory.join("wal1.log");
+        let mut bytes = std::fs::read(&sealed).unwrap();
+        bytes[SEGMENT_HEADER_SIZE + FRAME_HEADER_SIZE] ^= 0xFF;
+        std::fs::write(&sealed, &bytes).unwrap();
+
+        let active = directory.join("wal2.log");
+        let length = std::fs::metadata(&active).unwrap().len();
+        std::fs::OpenOptions::new().write(true).open(&active).unwrap().set_len(length - 3).unwrap();
+
+        let problems = verify_directory(&directory).unwrap();
+        assert_eq!(problems, vec![
+            IntegrityProblem::CorruptFrame(CorruptFrame { segment: 1, offset: SEGMENT_HEADER_SIZE as u64, truncated: false }),
+            IntegrityProblem::FooterChecksumMismatch { segment: 1 },
+            IntegrityProblem::CorruptFrame(CorruptFrame { segment: 2, offset: SEGMENT_HEADER_SIZE as u64 + frame_size, truncated: true }),
+        ]);
+
+        // 파일은 그대로 남아있다
+        assert_eq!(std::fs::metadata(&active).unwrap().len(), length - 3);
+    }
+
+    #[test]
+    fn test_report_missing_segment_and_bad_header() {
+        let directory = temp_directory("verify_missing");
+        let mut wal_manager = WALManager::builder()
+            .set_directory(directory.clone())
+            .build().expect("Cannot create WALManager");
+
+        for _ in 0..2 {
+            wal_manager.append_log(entry(1)).unwrap();
+            wal_manager.checkpoint().unwrap();
+        }
+        drop(wal_manager);
+
+        std::fs::remove_file(directory.join("wal1.log")).unwrap();
+        std::fs::write(directory.join("wal2.log"), b"garbage").unwrap();
+
+        let problems = verify_directory(&directory).unwrap();
+        assert_eq!(problems[0], IntegrityProblem::MissingSegment { segment: 1 });
+        assert!(matches!(problems[1], IntegrityProblem::InvalidHeader { segment: 2, .. }));
+        assert_eq!(problems.len(), 2);
+    }
+}
@@ -0,0 +1,72 @@
+use std::ops::RangeInclusive;
+use std::sync::mpsc::{channel, Receiver, Sender};
+
+use super::lsn::Lsn;
+
+/// 새로 내구성이 보장된 엔트리들의 LSN 범위
+pub type DurableRange = RangeInclusive<Lsn>;
+
+/// `durable_lsn` 이 올라갈 때마다 그동안 fsync 된 LSN 범위를 구독자들의 채널로 보낸다
+///
+/// 보내기만 하고 기다리지 않으므로 느린 구독자 때문에 쓰는 쪽이 막히지 않는다. 받는 쪽이 사라진 구독은 다음 알림에서 정리한다.
+#[derive(Debug, Default)]
+pub(crate) struct Watchers {
+    senders: Vec<Sender<DurableRange>>,
+}
+
+impl Watchers {
+    pub(crate) fn subscribe(&mut self) -> Receiver<DurableRange> {
+        let (sender, receiver) = channel();
+        self.senders.push(sender);
+        receiver
+    }
+
+    pub(crate) fn notify(&mut self, range: DurableRange) {
+        if range.is_empty() {
+            return;
+        }
+
+        self.senders.retain(|sender| sender.send(range.clone()).is_ok());
+    }
+}
+
+#[cfg(test)]
+mod watch_tests {
+    use crate::wal::core::{WALEntry, WALManager};
+    use crate::wal::lsn::Lsn;
+    use crate::wal::sync::SyncPolicy;
+    use crate::wal::test_utils::{insert_entry, temp_directory};
+
+    fn entry(value: u8) -> WALEntry {
+        insert_entry(vec![value; 16])
+    }
+
+    #[test]
+    fn test_notify_durable_ranges() {
+        let mut wal_manager = WALManager::builder()
+            .set_directory(temp_directory("watch"))
+            .set_sync_policy(SyncPolicy::Never)
+            .build().expect("Cannot create WALManager");
+
+        let receiver = wal_manager.subscribe();
+        for value in 0..3 {
+            wal_manager.append_log(entry(value)).unwrap();
+        }
+        assert!(receiver.try_recv().is_err());
+
+        wal_manager.sync().unwrap();
+        assert_eq!(receiver.try_recv().unwrap(), Lsn(1)..=Lsn(3));
+
+        // 이미 동기화된 상태에서는 알리지 않는다
+        wal_manager.sync().unwrap();
+        assert!(receiver.try_recv().is_err());
+
+        wal_manager.append_log(entry(4)).unwrap();
+        wal_manager.checkpoint().unwrap();
+        assert_eq!(receiver.try_recv().unwrap(), Lsn(4)..=Lsn(5));
+
+        drop(receiver);
+        wal_manager.append_log(entry(5)).unwrap();
+        wal_manager.sync().unwrap();
+    }
+}
@@ -0,0 +1,717 @@
+use std::alloc::{self, Layout};
+use std::fs::{File, OpenOptions};
+use std::io::{self, IoSlice, Read, Seek, SeekFrom, Write};
+use std::path::Path;
+use std::sync::Arc;
+use std::time::Duration;
+
+use super::block::{encode_blocks, Block, BlockOptions};
+use super::checksum::ChecksumAlgorithm;
+use super::double_write::DoubleWriteBuffer;
+use super::frame::padded_length;
+use super::retry::RetryPolicy;
+use super::schema::EntryFormat;
+use super::segment::{preallocate, SegmentHeader, FORMAT_VERSION, SEGMENT_HEADER_SIZE};
+use super::sync::SyncMethod;
+use super::uring::IoUring;
+
+/// O_DIRECT 쓰기 버퍼의 메모리 정렬 단위이자 기본 flush 블록 크기.
+/// 대부분의 디바이스 논리 블록 크기(512/4096)를 포함한다.
+pub const DIRECT_IO_ALIGNMENT: usize = 4096;
+
+/// O_DIRECT 에서 허용되는 가장 작은 블록 크기
+pub const MIN_DIRECT_IO_BLOCK_SIZE: usize = 512;
+
+/// 엔트리를 바로 쓰지 않고 메모리에 모았다가 한 번에 쓸 조건. 하나라도 넘으면 모아둔 엔트리를 쓴다.
+///
+/// 시간은 다음 append 에서 따진다. `sync()`, 체크포인트, `close()` 도 먼저 쓰고 진행한다.
+/// 쓰기 전에 죽으면 모아둔 엔트리는 사라지고, 그동안은 `recover()` 나 다른 프로세스에서 보이지 않는다.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct WriteBufferOptions {
+    pub max_bytes: usize,
+    pub max_entries: usize,
+    pub max_delay: Duration,
+    /// 백그라운드에서 앞의 배치를 쓰는 동안에는 버퍼를 비울 수 없으므로 엔트리가 계속 쌓인다.
+    /// 이만큼 쌓이면 [`BackgroundFlusher`](super::flusher::BackgroundFlusher) 와 [`SharedWAL`](super::shared::SharedWAL) 의
+    /// append 는 디스크가 따라잡을 때까지 기다린다.
+    pub max_pending_bytes: usize,
+}
+
+impl Default for WriteBufferOptions {
+    fn default() -> Self {
+        Self { max_bytes: 1024 * 1024, max_entries: 1024, max_delay: Duration::from_millis(10), max_pending_bytes: 8 * 1024 * 1024 }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub(crate) struct WriterOptions {
+    /// 정렬 쓰기, double write 의 페이지 단위
+    pub block_size: usize,
+    /// 미리 할당할 세그먼트 크기
+    pub segment_max_bytes: usize,
+    pub preallocate: bool,
+    pub direct_io: bool,
+    pub double_write: bool,
+    /// fsync 전에 블록 경계까지 패딩
+    pub pad_blocks: bool,
+    /// 새 세그먼트에 쓸 체크섬 알고리즘
+    pub checksum: ChecksumAlgorithm,
+    pub sync_method: SyncMethod,
+    /// 일반 모드의 쓰기와 fsync 를 io_uring 으로 제출 (`io-uring` 기능)
+    pub io_uring: bool,
+    /// 새 세그먼트의 엔트리 프레임을 블록으로 묶어서 기록
+    pub blocks: Option<BlockOptions>,
+    pub retry: RetryPolicy,
+}
+
+impl Default for WriterOptions {
+    fn default() -> Self {
+        Self {
+            block_size: DIRECT_IO_ALIGNMENT,
+            segment_max_bytes: 64 * 1024 * 1024,
+            preallocate: false,
+            direct_io: false,
+            double_write: false,
+            pad_blocks: false,
+            checksum: ChecksumAlgorithm::default(),
+            sync_method: SyncMethod::default(),
+            io_uring: false,
+            blocks: None,
+            retry: RetryPolicy::default(),
+        }
+    }
+}
+
+/// 활성 세그먼트 하나에 대한 쓰기 핸들
+///
+/// 일반 모드에서는 유효한 데이터의 끝부터 순차적으로 이어 쓰고, direct I/O 모드에서는
+/// 마지막 부분 블록을 정렬된 버퍼에 들고 있다가 0 으로 패딩해서 같은 위치에 다시 쓴다.
+/// 재활용된 파일은 기존 내용을 무시하고 처음부터 덮어쓴다.
+/// double write 가 켜져 있으면 매 쓰기마다 대상 페이지 전체를 먼저 스크래치 파일에 기록한다.
+/// io_uring 을 쓴다면 파일 위치 대신 `length` 를 오프셋으로 지정해서 제출한다.
+pub(crate) struct SegmentWriter {
+    file: File,
+    sequence: u64,
+    length: u64,
+    block_size: usize,
+    pad_blocks: bool,
+    checksum: ChecksumAlgorithm,
+    /// 엔트리 페이로드를 인코딩할 포맷 버전
+    format: EntryFormat,
+    sync_method: SyncMethod,
+    direct: Option<DirectState>,
+    double_write: Option<DoubleWriteState>,
+    /// 일반 모드에서만 쓴다. fsync 용으로 복제한 핸들과 함께 쓴다.
+    uring: Option<Arc<IoUring>>,
+    /// 블록 단위로 기록하는 세그먼트라면 그 조건. 이어 쓰는 세그먼트는 헤더를 따른다.
+    blocks: Option<BlockOptions>,
+    /// 블록으로 묶은 프레임을 쓰기 전에 모아두는 버퍼. 쓸 때마다 새로 할당하지 않도록 재사용한다.
+    block_buffer: Vec<u8>,
+    retry: RetryPolicy,
+}
+
+struct DirectState {
+    block_offset: u64,
+    tail: AlignedBuffer,
+}
+
+struct DoubleWriteState {
+    buffer: DoubleWriteBuffer,
+    /// 일반 모드에서 마지막 부분 페이지의 내용 (direct I/O 모드는 `DirectState::tail` 을 쓴다)
+    tail_page: Vec<u8>,
+}
+
+impl SegmentWriter {
+    /// 파일이 이미 있다면 그 끝에서부터 이어 쓴다
+    pub fn open(path: &Path, sequence: usize, options: &WriterOptions) -> io::Result<Self> {
+        Self::open_with(path, sequence, options, false)
+    }
+
+    /// 재활용된 파일을 열어 처음부터 덮어쓴다
+    pub fn open_recycled(path: &Path, sequence: usize, options: &WriterOptions) -> io::Result<Self> {
+        Self::open_with(path, sequence, options, true)
+    }
+
+    fn open_with(path: &Path, sequence: usize, options: &WriterOptions, recycled: bool) -> io::Result<Self> {
+        let block_size = options.block_size as u64;
+        let (file, length, direct) = if options.direct_io {
+            let (file, length, state) = Self::open_direct(path, block_size, recycled)?;
+            (file, length, Some(state))
+        } else {
+            // O_APPEND 대신 직접 위치를 잡아서 쓴다. Windows 에는 같은 의미의 플래그가 없고,
+            // fsync 용으로 복제한 핸들이 쓰기 위치에 영향을 주지 않도록 쓰기는 이 핸들 하나로만 한다.
+            // 재활용된 파일은 비워서 예전 프레임이 쓰기 위치 뒤에 남지 않게 한다
+            let mut file = OpenOptions::new().create(true).truncate(recycled).write(true).open(path)?;
+            let length = file.metadata()?.len();
+            file.seek(SeekFrom::Start(length))?;
+
+            (file, length, None)
+        };
+
+        let double_write = match (options.double_write, path.parent()) {
+            (true, Some(directory)) => {
+                let tail_page = if direct.is_none() {
+                    read_from(path, length - length % block_size)?
+                } else {
+                    Vec::new()
+                };
+
+                Some(DoubleWriteState { buffer: DoubleWriteBuffer::open(directory)?, tail_page })
+            },
+            _ => None,
+        };
+
+        let uring = match options.io_uring && direct.is_none() && double_write.is_none() {
+            true => IoUring::open(options.sync_method)?.map(Arc::new),
+            false => None,
+        };
+
+        let mut writer = Self {
+            file,
+            sequence: sequence as u64,
+            length,
+            block_size: options.block_size,
+            pad_blocks: options.pad_blocks,
+            checksum: options.checksum,
+            format: EntryFormat::new(FORMAT_VERSION),
+            sync_method: options.sync_method,
+            direct,
+            double_write,
+            uring,
+            blocks: options.blocks,
+            block_buffer: Vec::new(),
+            retry: options.retry,
+        };
+
+        // 이어 쓰는 세그먼트도 다시 할당한다. 열 때 끊긴 꼬리를 잘라내면서 미리 잡아둔 블록이 함께 풀린다.
+        if options.preallocate {
+            preallocate(&writer.file, (SEGMENT_HEADER_SIZE + options.segment_max_bytes) as u64)?;
+        }
+
+        if writer.length == 0 {
+            let header = SegmentHeader {
+                blocks: options.blocks.is_some(),
+                ..SegmentHeader::new(options.block_size, options.checksum, super::core::WALManager::get_current_secs(), sequence)
+            };
+            writer.format = EntryFormat::of(&header);
+            writer.write(&header.encode())?;
+        } else {
+            // 이어 쓰는 세그먼트는 처음 만들 때의 알고리즘과 포맷을 그대로 따른다
+            let mut header = [0u8; SEGMENT_HEADER_SIZE];
+            File::open(path)?.read_exact(&mut header)?;
+            let header = SegmentHeader::decode(&header)?;
+            writer.checksum = header.checksum;
+            writer.format = EntryFormat::of(&header);
+            writer.blocks = header.blocks.then(|| options.blocks.unwrap_or_default());
+        }
+
+        Ok(writer)
+    }
+
+    fn open_direct(path: &Path, block_size: u64, recycled: bool) -> io::Result<(File, u64, DirectState)> {
+        let file = open_direct_file(path)?;
+        if recycled {
+            file.set_len(0)?;
+        }
+        let length = file.metadata()?.len();
+
+        // 마지막 부분 블록은 다음 쓰기 때 다시 써야 하므로 버퍼로 읽어둔다
+        let block_offset = length - length % block_size;
+        let mut tail = AlignedBuffer::new();
+        if length > block_offset {
+            tail.extend_from_slice(&read_from(path, block_offset)?);
+        }
+
+        Ok((file, length, DirectState { block_offset, tail }))
+    }
+
+    /// 엔트리 프레임을 블록으로 묶어서 쓰는 세그먼트인지
+    pub fn blocks(&self) -> bool {
+        self.blocks.is_some()
+    }
+
+    /// 이 세그먼트의 프레임에 써야 하는 체크섬 알고리즘
+    pub fn checksum(&self) -> ChecksumAlgorithm {
+        self.checksum
+    }
+
+    /// 이 세그먼트의 엔트리 페이로드를 인코딩할 포맷 (버전과 타임스탬프의 기준 시각)
+    pub fn format(&self) -> EntryFormat {
+        self.format
+    }
+
+    /// 실제 데이터가 기록된 길이 (direct I/O 패딩 제외)
+    pub fn length(&self) -> u64 {
+        self.length
+    }
+
+    /// 일시적인 오류로 실패하면 [`RetryPolicy`] 에 따라 다시 쓴다
+    pub fn write(&mut self, bytes: &[u8]) -> io::Result<()> {
+        let retry = self.retry;
+        retry.run(|| self.write_once(bytes))
+    }
+
+    /// 실패하면 쓰기 전의 상태로 되돌리므로 같은 바이트를 다시 써도 된다.
+    /// 짧게 써진 부분은 파일에 남지만 `length` 뒤이므로 다시 쓸 때 덮어쓰고, 그 전에 죽으면 찢어진 꼬리로 잘려나간다.
+    fn write_once(&mut self, bytes: &[u8]) -> io::Result<()> {
+        match &mut self.direct {
+            None => {
+                let tail_page = self.double_write.as_ref().map(|double_write| double_write.tail_page.len());
+                let written = self.write_buffered(bytes);
+                if let Err(e) = written {
+                    if let (Some(double_write), Some(length)) = (&mut self.double_write, tail_page) {
+                        double_write.tail_page.truncate(length);
+                    }
+                    self.file.seek(SeekFrom::Start(self.length))?;
+                    return Err(e);
+                }
+
+                if let Some(double_write) = &mut self.double_write {
+                    let full_pages = double_write.tail_page.len() / self.block_size * self.block_size;
+                    double_write.tail_page.drain(..full_pages);
+                }
+            },
+            Some(state) => {
+                let tail = state.tail.len();
+                state.tail.extend_from_slice(bytes);
+
+                let padded = state.tail.len().div_ceil(self.block_size) * self.block_size;
+                let block = state.tail.padded(padded);
+                let written = match &mut self.double_write {
+                    Some(double_write) => double_write.buffer.stage(self.sequence, state.block_offset, block),
+                    None => Ok(()),
+                }.and_then(|_| write_all_at(&self.file, block, state.block_offset));
+                if let Err(e) = written {
+                    state.tail.truncate(tail);
+                    return Err(e);
+                }
+
+                let full_blocks = state.tail.len() / self.block_size * self.block_size;
+                if full_blocks > 0 {
+                    state.tail.drain_front(full_blocks);
+                    state.block_offset += full_blocks as u64;
+                }
+            },
+        }
+
+        self.length += bytes.len() as u64;
+
+        Ok(())
+    }
+
+    /// 일반 모드의 쓰기. double write 라면 마지막 부분 페이지에 붙여서 스크래치 파일에 먼저 남긴다.
+    fn write_buffered(&mut self, bytes: &[u8]) -> io::Result<()> {
+        if let Some(double_write) = &mut self.double_write {
+            let page_offset = self.length - double_write.tail_page.len() as u64;
+            double_write.tail_page.extend_from_slice(bytes);
+            double_write.buffer.stage(self.sequence, page_offset, &double_write.tail_page)?;
+        }
+
+        match &self.uring {
+            Some(uring) => uring.write_all(&self.file, bytes, self.length),
+            None => self.file.write_all(bytes),
+        }
+    }
+
+    /// 이어붙인 엔트리 프레임들을 쓴다. 블록 단위로 기록하는 세그먼트라면 블록으로 묶어서 쓰고, 쓴 블록들의 위치를 돌려준다.
+    pub fn write_frames(&mut self, frames: &[u8]) -> io::Result<Vec<Block>> {
+        self.write_frames_with(frames, false)
+    }
+
+    /// `write_frames` 뒤에 바로 fsync 한다 ([`write_and_sync`](Self::write_and_sync))
+    pub fn write_frames_and_sync(&mut self, frames: &[u8]) -> io::Result<Vec<Block>> {
+        self.write_frames_with(frames, true)
+    }
+
+    fn write_frames_with(&mut self, frames: &[u8], sync: bool) -> io::Result<Vec<Block>> {
+        let mut bytes = std::mem::take(&mut self.block_buffer);
+        bytes.clear();
+        let blocks = self.encode_frames(frames, &mut bytes);
+        let bytes_to_write = if blocks.is_some() { &bytes } else { frames };
+
+        let written = match sync {
+            true => self.write_and_sync(bytes_to_write),
+            false => self.write(bytes_to_write),
+        };
+        self.block_buffer = bytes;
+        written?;
+
+        Ok(blocks.unwrap_or_default())
+    }
+
+    /// 블록 단위로 기록하는 세그먼트라면 프레임들을 블록으로 묶어 `out` 에 붙이고, 지금 세그먼트 끝에 쓴다고 했을 때의
+    /// 블록 위치들을 돌려준다. 아니라면 `None`
+    pub fn encode_frames(&self, frames: &[u8], out: &mut Vec<u8>) -> Option<Vec<Block>> {
+        let options = self.blocks?;
+        let mut blocks = encode_blocks(frames, self.format.frame_layout(), self.sequence, self.checksum, &options, out);
+        for block in &mut blocks {
+            block.offset += self.length;
+        }
+
+        Some(blocks)
+    }
+
+    /// 프레임마다 iovec 하나씩 `writev` 로 기록한다. 중간에 짧게 써지면 남은 부분을 이어서 쓴다.
+    /// 일시적인 오류로 실패하면 처음 위치로 돌아가서 배치 전체를 다시 쓴다 ([`RetryPolicy`]).
+    pub fn write_vectored(&mut self, frames: &[&[u8]]) -> io::Result<()> {
+        if self.direct.is_some() || self.double_write.is_some() {
+            // 정렬 버퍼나 스크래치 기록으로 어차피 복사해야 하므로 한 번에 붙여서 쓴다
+            return self.write(&frames.concat());
+        }
+
+        let retry = self.retry;
+        retry.run(|| {
+            let written = self.write_vectored_once(frames);
+            if written.is_err() {
+                self.file.seek(SeekFrom::Start(self.length))?;
+            }
+
+            written
+        })
+    }
+
+    fn write_vectored_once(&mut self, frames: &[&[u8]]) -> io::Result<()> {
+
+        let total = frames.iter().map(|frame| frame.len()).sum::<usize>();
+        let mut slices = frames.iter().map(|frame| IoSlice::new(frame)).collect::<Vec<_>>();
+        let mut slices = &mut slices[..];
+
+        let mut offset = self.length;
+        while !slices.is_empty() {
+            let written = match &self.uring {
+                Some(uring) => uring.write_vectored(&self.file, slices, offset),
+                None => self.file.write_vectored(slices),
+            };
+            match written {
+                Ok(0) => return Err(io::Error::new(io::ErrorKind::WriteZero, "failed to write whole batch")),
+                Ok(written) => {
+                    IoSlice::advance_slices(&mut slices, written);
+                    offset += written as u64;
+                },
+                Err(e) if e.kind() == io::ErrorKind::Interrupted => {},
+                Err(e) => return Err(e),
+            }
+        }
+
+        self.length += total as u64;
+
+        Ok(())
+    }
+
+    /// 패딩 프레임(길이와 체크섬이 0)으로 다음 블록 경계까지 채워서 fsync 단위가 블록에 맞도록 한다
+    fn pad_to_block(&mut self) -> io::Result<()> {
+        if !self.pad_blocks {
+            return Ok(());
+        }
+
+        let padded = padded_length(self.length, self.block_size as u64);
+        if padded > self.length {
+            self.write(&vec![0; (padded - self.length) as usize])?;
+        }
+
+        Ok(())
+    }
+
+    pub fn sync(&mut self) -> io::Result<()> {
+        self.pad_to_block()?;
+        match &self.uring {
+            Some(uring) => uring.sync(&self.file),
+            None => self.sync_method.sync(&self.file),
+        }
+    }
+
+    /// 쓰고 바로 fsync 한다. io_uring 이라면 링크된 write+fsync 한 번으로 제출한다.
+    pub fn write_and_sync(&mut self, bytes: &[u8]) -> io::Result<()> {
+        match self.uring.clone() {
+            Some(uring) if !self.pad_blocks => {
+                uring.write_all_and_sync(&self.file, bytes, self.length, &self.retry)?;
+                self.length += bytes.len() as u64;
+
+                Ok(())
+            },
+            _ => {
+                self.write(bytes)?;
+                self.sync()
+            },
+        }
+    }
+
+    /// `length` 바이트를 나중에 [`SyncHandle::write_at`] 으로 채울 자리로 잡아두고 그 시작 위치를 반환한다.
+    /// 이어지는 쓰기는 그 뒤부터 기록된다. 정렬 버퍼나 스크래치 기록, 패딩처럼 쓰는 위치를 직접 따라가야 하는 모드와
+    /// 복제한 핸들이 파일 위치를 함께 쓰는 Windows 에서는 `None`.
+    pub fn reserve(&mut self, length: u64) -> io::Result<Option<u64>> {
+        if cfg!(not(unix)) || self.direct.is_some() || self.double_write.is_some() || self.pad_blocks {
+            return Ok(None);
+        }
+
+        let offset = self.length;
+        self.length += length;
+        self.file.seek(SeekFrom::Start(self.length))?;
+
+        Ok(Some(offset))
+    }
+
+    /// 채우지 못한 자리를 `offset` 부터 놓는다. `reserve` 뒤로 다른 쓰기가 없었어야 하며, 이어지는 쓰기는 `offset` 부터 기록된다.
+    pub fn release(&mut self, offset: u64) -> io::Result<()> {
+        self.length = offset;
+        self.file.seek(SeekFrom::Start(offset))?;
+
+        Ok(())
+    }
+
+    /// 쓰기 실패를 흉내내도록 파일 핸들을 바꾸고 원래 핸들을 돌려준다
+    #[cfg(test)]
+    pub(crate) fn replace_file(&mut self, file: File) -> File {
+        std::mem::replace(&mut self.file, file)
+    }
+
+    /// 반환된 핸들로 fsync 할 것이므로 패딩은 여기서 미리 기록한다
+    pub fn sync_handle(&mut self) -> io::Result<SyncHandle> {
+        self.pad_to_block()?;
+        Ok(SyncHandle { file: self.file.try_clone()?, sync_method: self.sync_method, uring: self.uring.clone(), retry: self.retry })
+    }
+}
+
+/// 세그먼트 파일을 복제한 핸들. 쓰기 핸들과 별개로 fsync 할 때 쓴다.
+pub(crate) struct SyncHandle {
+    file: File,
+    sync_method: SyncMethod,
+    uring: Option<Arc<IoUring>>,
+    retry: RetryPolicy,
+}
+
+impl SyncHandle {
+    pub fn sync(&self) -> io::Result<()> {
+        match &self.uring {
+            Some(uring) => uring.sync(&self.file),
+            None => self.sync_method.sync(&self.file),
+        }
+    }
+
+    /// [`SegmentWriter::reserve`] 로 잡아둔 자리를 채우고 fsync 한다. 쓰기는 같은 자리에 다시 시도한다 ([`RetryPolicy`]).
+    pub fn write_and_sync_at(&self, bytes: &[u8], offset: u64) -> io::Result<()> {
+        match &self.uring {
+            Some(uring) if !bytes.is_empty() => uring.write_all_and_sync(&self.file, bytes, offset, &self.retry),
+            _ => {
+                if !bytes.is_empty() {
+                    self.retry.run(|| write_all_at(&self.file, bytes, offset))?;
+                }
+                self.sync()
+            },
+        }
+    }
+}
+
+fn read_from(path: &Path, offset: u64) -> io::Result<Vec<u8>> {
+    let mut bytes = Vec::new();
+    let mut reader = File::open(path)?;
+    reader.seek(SeekFrom::Start(offset))?;
+    reader.read_to_end(&mut bytes)?;
+
+    Ok(bytes)
+}
+
+#[cfg(unix)]
+pub(crate) fn write_all_at(file: &File, bytes: &[u8], offset: u64) -> io::Result<()> {
+    use std::os::unix::fs::FileExt;
+
+    file.write_all_at(bytes, offset)
+}
+
+#[cfg(windows)]
+pub(crate) fn write_all_at(file: &File, mut bytes: &[u8], mut offset: u64) -> io::Result<()> {
+    use std::os::windows::fs::FileExt;
+
+    while !bytes.is_empty() {
+        let written = file.seek_write(bytes, offset)?;
+        if written == 0 {
+            return Err(io::Error::new(io::ErrorKind::WriteZero, "failed to write whole buffer"));
+        }
+        bytes = &bytes[written..];
+        offset += written as u64;
+    }
+
+    Ok(())
+}
+
+#[cfg(target_os = "linux")]
+fn open_direct_file(path: &Path) -> io::Result<File> {
+    use std::os::unix::fs::OpenOptionsExt;
+
+    #[cfg(any(target_arch = "aarch64", target_arch = "arm"))]
+    const O_DIRECT: i32 = 0o200000;
+    #[cfg(not(any(target_arch = "aarch64", target_arch = "arm")))]
+    const O_DIRECT: i32 = 0o40000;
+
+    match OpenOptions::new().create(true).truncate(false).write(true).custom_flags(O_DIRECT).open(path) {
+        // tmpfs 처럼 O_DIRECT 를 지원하지 않는 파일시스템은 정렬 쓰기만 유지한다
+        Err(e) if e.kind() == io::ErrorKind::InvalidInput => {
+            OpenOptions::new().create(true).truncate(false).write(true).open(path)
+        },
+        result => result,
+    }
+}
+
+#[cfg(not(target_os = "linux"))]
+fn open_direct_file(path: &Path) -> io::Result<File> {
+    OpenOptions::new().create(true).truncate(false).write(true).open(path)
+}
+
+/// `DIRECT_IO_ALIGNMENT` 로 정렬된 힙 버퍼
+struct AlignedBuffer {
+    ptr: *mut u8,
+    capacity: usize,
+    len: usize,
+}
+
+impl AlignedBuffer {
+    fn new() -> Self {
+        Self { ptr: std::ptr::null_mut(), capacity: 0, len: 0 }
+    }
+
+    fn len(&self) -> usize {
+        self.len
+    }
+
+    fn reserve(&mut self, capacity: usize) {
+        if capacity <= self.capacity {
+            return;
+        }
+
+        let capacity = capacity.div_ceil(DIRECT_IO_ALIGNMENT) * DIRECT_IO_ALIGNMENT;
+        let layout = Layout::from_size_align(capacity, DIRECT_IO_ALIGNMENT).expect("Invalid buffer layout");
+
+        unsafe {
+            let ptr = alloc::alloc_zeroed(layout);
+            if ptr.is_null() {
+                alloc::handle_alloc_error(layout);
+            }
+
+            if !self.ptr.is_null() {
+                std::ptr::copy_nonoverlapping(self.ptr, ptr, self.len);
+                alloc::dealloc(self.ptr, Layout::from_size_align_unchecked(self.capacity, DIRECT_IO_ALIGNMENT));
+            }
+
+            self.ptr = ptr;
+        }
+        self.capacity = capacity;
+    }
+
+    fn extend_from_slice(&mut self, bytes: &[u8]) {
+        self.reserve(self.len + bytes.len());
+
+        unsafe { std::ptr::copy_nonoverlapping(bytes.as_ptr(), self.ptr.add(self.len), bytes.len()) };
+        self.len += bytes.len();
+    }
+
+    /// 데이터 뒤를 0 으로 채워 `padded_len` 길이의 슬라이스로 반환
+    fn padded(&mut self, padded_len: usize) -> &[u8] {
+        self.reserve(padded_len);
+
+        unsafe {
+            std::ptr::write_bytes(self.ptr.add(self.len), 0, padded_len - self.len);
+            std::slice::from_raw_parts(self.ptr, padded_len)
+        }
+    }
+
+    fn truncate(&mut self, len: usize) {
+        self.len = self.len.min(len);
+    }
+
+    fn drain_front(&mut self, count: usize) {
+        unsafe { std::ptr::copy(self.ptr.add(count), self.ptr, self.len - count) };
+        self.len -= count;
+    }
+}
+
+// 버퍼를 소유한 쪽만 포인터에 접근한다
+unsafe impl Send for AlignedBuffer {}
+
+impl Drop for AlignedBuffer {
+    fn drop(&mut self) {
+        if !self.ptr.is_null() {
+            unsafe { alloc::dealloc(self.ptr, Layout::from_size_align_unchecked(self.capacity, DIRECT_IO_ALIGNMENT)) };
+        }
+    }
+}
+
+#[cfg(test)]
+mod writer_tests {
+    use super::{SegmentWriter, WriterOptions, DIRECT_IO_ALIGNMENT};
+    use crate::wal::double_write::DoubleWriteBuffer;
+    use crate::wal::segment::SEGMENT_HEADER_SIZE;
+    use crate::wal::test_utils::temp_directory;
+
+    #[test]
+    fn test_direct_writes_are_block_aligned() {
+        let directory = temp_directory("direct_io");
+        let path = directory.join("wal1.log");
+        let options = WriterOptions { direct_io: true, ..Default::default() };
+
+        let mut writer = SegmentWriter::open(&path, 1, &options).unwrap();
+        writer.write(&[7u8; 100]).unwrap();
+        writer.write(&[8u8; DIRECT_IO_ALIGNMENT]).unwrap();
+        assert_eq!(writer.length(), (SEGMENT_HEADER_SIZE + 100 + DIRECT_IO_ALIGNMENT) as u64);
+        drop(writer);
+
+        let bytes = std::fs::read(&path).unwrap();
+        assert_eq!(bytes.len() % DIRECT_IO_ALIGNMENT, 0);
+        assert_eq!(bytes[SEGMENT_HEADER_SIZE..SEGMENT_HEADER_SIZE + 100], [7u8; 100]);
+        assert_eq!(bytes[SEGMENT_HEADER_SIZE + 100 + DIRECT_IO_ALIGNMENT], 0);
+    }
+
+    #[test]
+    fn test_recycled_file_is_overwritten_from_start() {
+        let directory = temp_directory("recycled_writer");
+        let path = directory.join("wal2.log");
+        std::fs::write(&path, vec![0xAB; 1000]).unwrap();
+
+        let mut writer = SegmentWriter::open_recycled(&path, 2, &WriterOptions::default()).unwrap();
+        writer.write(b"fresh").unwrap();
+        drop(writer);
+
+        // 예전 내용은 남지 않는다
+        let bytes = std::fs::read(&path).unwrap();
+        assert_eq!(bytes.len(), SEGMENT_HEADER_SIZE + 5);
+        assert_eq!(&bytes[SEGMENT_HEADER_SIZE..], b"fresh");
+    }
+
+    #[test]
+    fn test_double_write_stages_tail_page() {
+        let directory = temp_directory("double_write_writer");
+        let path = directory.join("wal1.log");
+        let options = WriterOptions { double_write: true, ..Default::default() };
+
+        let mut writer = SegmentWriter::open(&path, 1, &options).unwrap();
+        writer.write(b"first").unwrap();
+        writer.write(b"second").unwrap();
+        drop(writer);
+
+        // 마지막 쓰기가 찢어져서 페이지가 통째로 날아간 상황
+        let expected = std::fs::read(&path).unwrap();
+        std::fs::write(&path, &expected[..SEGMENT_HEADER_SIZE + 2]).unwrap();
+
+        assert!(DoubleWriteBuffer::recover(&directory).unwrap());
+        assert_eq!(std::fs::read(&path).unwrap(), expected);
+    }
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn test_retry_reserved_write() {
+        use std::time::{Duration, Instant};
+
+        use crate::wal::retry::RetryPolicy;
+
+        let path = temp_directory("retry_reserved_write").join("wal1.log");
+        let retry = RetryPolicy { max_retries: 2, initial_backoff: Duration::from_millis(20), max_backoff: Duration::from_secs(1) };
+        let mut writer = SegmentWriter::open(&path, 1, &WriterOptions { retry, ..Default::default() }).unwrap();
+        let offset = writer.reserve(5).unwrap().unwrap();
+
+        // `/dev/full` 에 쓰면 ENOSPC 가 난다. 락 밖에서 쓰는 자리도 정책만큼 기다렸다가 다시 쓴다.
+        writer.replace_file(std::fs::OpenOptions::new().write(true).open("/dev/full").unwrap());
+        let handle = writer.sync_handle().unwrap();
+        let started = Instant::now();
+        let error = handle.write_and_sync_at(b"fresh", offset).unwrap_err();
+        assert_eq!(error.kind(), std::io::ErrorKind::StorageFull);
+        assert!(started.elapsed() >= Duration::from_millis(60));
+    }
+}